@@ -0,0 +1,514 @@
+//! A hook for running custom analyses on every bubble of a graph.
+//!
+//! An analysis is a function that receives a bubble, with the
+//! traversal of each path through it, and the segment sequences, and
+//! returns any value that implements `serde::Serialize`, or `None`
+//! to skip the bubble. The analyses are run on the bubbles in
+//! parallel, and the results written as newline-delimited JSON:
+//!
+//! ```ignore
+//! let mut analyses = BubbleAnalyses::default();
+//! analyses.register("traversals", |bubble: &Bubble, _: &_| {
+//!     Some(bubble.distinct_traversals())
+//! });
+//! analyses.run(&path_data, &ultrabubbles, std::io::stdout())?;
+//! ```
+
+use std::{
+    cmp::Ordering,
+    fmt,
+    io::{self, Write},
+};
+
+use bstr::{BStr, BString, ByteSlice};
+use fnv::{FnvHashMap, FnvHashSet};
+use rayon::prelude::*;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use crate::{
+    util::NodeSet,
+    variants::{self, PathData, PathStep},
+};
+
+/// The traversal of a bubble by one path
+#[derive(Debug, Clone, Copy)]
+pub struct SubPath<'a> {
+    pub path_ix: usize,
+    pub name: &'a BStr,
+    /// The steps of the path from one bubble endpoint to the other,
+    /// inclusive, in the order they appear on the path
+    pub steps: &'a [PathStep],
+    /// Whether the path enters the bubble from the `to` endpoint
+    pub reversed: bool,
+}
+
+impl<'a> SubPath<'a> {
+    /// The oriented nodes of the traversal, in the direction from
+    /// `from` to `to`
+    pub fn nodes(&self) -> Vec<(usize, bool)> {
+        let nodes = self
+            .steps
+            .iter()
+            .map(|&(node, _, o)| (node, o.is_reverse()));
+        if self.reversed {
+            nodes.rev().map(|(node, rev)| (node, !rev)).collect()
+        } else {
+            nodes.collect()
+        }
+    }
+
+    /// The sequence spelled by the traversal, in the orientation of
+    /// the path, including the endpoint nodes
+    pub fn sequence(&self, segments: &FnvHashMap<usize, BString>) -> BString {
+        let mut seq = Vec::new();
+        for &(node, _, orient) in self.steps {
+            let node_seq = segments[&node].as_slice();
+            if orient.is_reverse() {
+                seq.extend(handlegraph::util::dna::rev_comp_iter(node_seq));
+            } else {
+                seq.extend_from_slice(node_seq);
+            }
+        }
+        seq.into()
+    }
+}
+
+/// A bubble, given by its endpoint nodes, and the paths that pass
+/// through both endpoints
+#[derive(Debug, Clone)]
+pub struct Bubble<'a> {
+    pub from: u64,
+    pub to: u64,
+    pub sub_paths: Vec<SubPath<'a>>,
+}
+
+impl<'a> Bubble<'a> {
+    /// The number of distinct node traversals among the sub-paths
+    pub fn distinct_traversals(&self) -> usize {
+        self.sub_paths
+            .iter()
+            .map(|sub_path| sub_path.nodes())
+            .collect::<FnvHashSet<_>>()
+            .len()
+    }
+}
+
+type Segments = FnvHashMap<usize, BString>;
+
+type AnalysisFn = dyn Fn(&Bubble<'_>, &Segments) -> Option<Value> + Send + Sync;
+
+/// A set of named per-bubble analyses
+#[derive(Default)]
+pub struct BubbleAnalyses {
+    analyses: Vec<(String, Box<AnalysisFn>)>,
+}
+
+impl BubbleAnalyses {
+    /// Register an analysis under `name`, which is included in each
+    /// of its output lines. Results that fail to serialize are
+    /// reported and skipped.
+    pub fn register<F, R>(&mut self, name: &str, analysis: F)
+    where
+        F: Fn(&Bubble<'_>, &FnvHashMap<usize, BString>) -> Option<R>
+            + Send
+            + Sync
+            + 'static,
+        R: Serialize,
+    {
+        let analysis_name = name.to_string();
+        let analysis =
+            move |bubble: &Bubble<'_>,
+                  segments: &FnvHashMap<usize, BString>| {
+                let result = analysis(bubble, segments)?;
+                match serde_json::to_value(result) {
+                    Ok(value) => Some(value),
+                    Err(err) => {
+                        warn!(
+                            "Error serializing {} result for bubble {}-{}: {}",
+                            analysis_name, bubble.from, bubble.to, err
+                        );
+                        None
+                    }
+                }
+            };
+        self.analyses.push((name.to_string(), Box::new(analysis)));
+    }
+
+    pub fn len(&self) -> usize {
+        self.analyses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.analyses.is_empty()
+    }
+
+    /// Run every analysis on each of the bubbles in parallel, and
+    /// write one JSON object per result to `out`, with the fields
+    /// `from`, `to`, `analysis`, and `result`. The lines are in the
+    /// order of the bubbles, and then of the analyses. Bubbles that
+    /// no path passes through are skipped. Returns the number of
+    /// lines written.
+    pub fn run<W: Write>(
+        &self,
+        path_data: &PathData,
+        bubbles: &[(u64, u64)],
+        mut out: W,
+    ) -> io::Result<usize> {
+        let nodes = bubbles
+            .iter()
+            .flat_map(|&(a, b)| std::iter::once(a).chain(std::iter::once(b)))
+            .collect::<NodeSet>();
+        let path_indices =
+            variants::bubble_path_indices(&path_data.paths, &nodes);
+
+        info!(
+            "Running {} analyses on {} bubbles",
+            self.analyses.len(),
+            bubbles.len()
+        );
+
+        let lines: Vec<Vec<String>> = bubbles
+            .par_iter()
+            .map(|&(from, to)| {
+                let bubble = match bubble(path_data, &path_indices, from, to) {
+                    Some(bubble) if !bubble.sub_paths.is_empty() => bubble,
+                    _ => return Vec::new(),
+                };
+                self.analyses
+                    .iter()
+                    .filter_map(|(name, analysis)| {
+                        let result = analysis(&bubble, &path_data.segment_map)?;
+                        let line = json!({
+                            "from": from,
+                            "to": to,
+                            "analysis": name,
+                            "result": result,
+                        });
+                        Some(line.to_string())
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut written = 0;
+        for line in lines.iter().flatten() {
+            writeln!(out, "{}", line)?;
+            written += 1;
+        }
+        out.flush()?;
+
+        Ok(written)
+    }
+}
+
+/// Collect the sub-paths of the bubble between `from` and `to`
+pub fn bubble<'a>(
+    path_data: &'a PathData,
+    path_indices: &variants::PathIndices,
+    from: u64,
+    to: u64,
+) -> Option<Bubble<'a>> {
+    let ranges =
+        variants::path_data_sub_path_ranges(path_data, path_indices, from, to)?;
+
+    let sub_paths = ranges
+        .into_iter()
+        .map(|(path_ix, range)| SubPath {
+            path_ix,
+            name: path_data.path_names[path_ix].as_bstr(),
+            steps: variants::sub_path_slice(&path_data.paths[path_ix], range),
+            reversed: range.0 > range.1,
+        })
+        .collect();
+
+    Some(Bubble {
+        from,
+        to,
+        sub_paths,
+    })
+}
+
+/// The kind of an alternate allele, by its length compared to that
+/// of the reference allele
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlleleKind {
+    Insertion,
+    Deletion,
+    Balanced,
+}
+
+impl AlleleKind {
+    pub const ALL: [AlleleKind; 3] = [
+        AlleleKind::Insertion,
+        AlleleKind::Deletion,
+        AlleleKind::Balanced,
+    ];
+}
+
+impl fmt::Display for AlleleKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlleleKind::Insertion => write!(f, "insertion"),
+            AlleleKind::Deletion => write!(f, "deletion"),
+            AlleleKind::Balanced => write!(f, "balanced"),
+        }
+    }
+}
+
+/// The kind and size of each distinct traversal of the bubble that
+/// differs from the reference path's. The size of an insertion or
+/// deletion is the difference between the lengths of the alleles,
+/// and that of a balanced allele is its length, excluding the
+/// endpoints. Returns `None` if the reference path doesn't traverse
+/// the bubble.
+pub fn alt_allele_sizes(
+    bubble: &Bubble<'_>,
+    segments: &FnvHashMap<usize, BString>,
+    ref_ix: usize,
+) -> Option<Vec<(AlleleKind, usize)>> {
+    let interior_length = |sub_path: &SubPath<'_>| -> usize {
+        let len = sub_path.steps.len();
+        sub_path.steps[1..len.max(2) - 1]
+            .iter()
+            .map(|(node, _, _)| segments.get(node).map_or(0, |seq| seq.len()))
+            .sum()
+    };
+
+    let ref_path = bubble
+        .sub_paths
+        .iter()
+        .find(|sub_path| sub_path.path_ix == ref_ix)?;
+    let ref_nodes = ref_path.nodes();
+    let ref_len = interior_length(ref_path);
+
+    let mut seen: FnvHashSet<Vec<(usize, bool)>> = FnvHashSet::default();
+    seen.insert(ref_nodes);
+
+    let mut alleles = Vec::new();
+    for sub_path in bubble.sub_paths.iter() {
+        if !seen.insert(sub_path.nodes()) {
+            continue;
+        }
+        let alt_len = interior_length(sub_path);
+        let allele = match alt_len.cmp(&ref_len) {
+            Ordering::Greater => (AlleleKind::Insertion, alt_len - ref_len),
+            Ordering::Less => (AlleleKind::Deletion, ref_len - alt_len),
+            Ordering::Equal => (AlleleKind::Balanced, alt_len),
+        };
+        alleles.push(allele);
+    }
+
+    Some(alleles)
+}
+
+/// The lower bounds of the bins of the allele size histogram; the
+/// last bin has no upper bound. 50bp is the usual threshold between
+/// small variants and SVs.
+pub const ALLELE_SIZE_BINS: [usize; 8] = [0, 1, 2, 10, 50, 100, 1_000, 10_000];
+
+/// The number of alternate alleles of each kind in each size bin,
+/// across a set of bubbles
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AlleleSizeHistogram {
+    /// The number of bubbles the reference path traverses
+    pub bubbles: usize,
+    counts: [[usize; ALLELE_SIZE_BINS.len()]; 3],
+}
+
+impl AlleleSizeHistogram {
+    fn bin(size: usize) -> usize {
+        ALLELE_SIZE_BINS
+            .iter()
+            .rposition(|&lower| size >= lower)
+            .unwrap_or(0)
+    }
+
+    pub fn add(&mut self, kind: AlleleKind, size: usize) {
+        self.counts[kind as usize][Self::bin(size)] += 1;
+    }
+
+    /// The number of alleles of the kind in the bin at `bin` in
+    /// `ALLELE_SIZE_BINS`
+    pub fn count(&self, kind: AlleleKind, bin: usize) -> usize {
+        self.counts[kind as usize][bin]
+    }
+
+    pub fn total(&self, kind: AlleleKind) -> usize {
+        self.counts[kind as usize].iter().sum()
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        self.bubbles += other.bubbles;
+        for (ours, theirs) in self.counts.iter_mut().zip(other.counts.iter()) {
+            for (a, b) in ours.iter_mut().zip(theirs.iter()) {
+                *a += b;
+            }
+        }
+        self
+    }
+}
+
+/// Build the histogram of the sizes of the alternate alleles of the
+/// bubbles, compared to the reference path, in parallel
+pub fn allele_size_histogram(
+    path_data: &PathData,
+    bubbles: &[(u64, u64)],
+    ref_ix: usize,
+) -> AlleleSizeHistogram {
+    let nodes = bubbles
+        .iter()
+        .flat_map(|&(a, b)| std::iter::once(a).chain(std::iter::once(b)))
+        .collect::<NodeSet>();
+    let path_indices = variants::bubble_path_indices(&path_data.paths, &nodes);
+
+    bubbles
+        .par_iter()
+        .filter_map(|&(from, to)| {
+            let bubble = bubble(path_data, &path_indices, from, to)?;
+            let alleles =
+                alt_allele_sizes(&bubble, &path_data.segment_map, ref_ix)?;
+            let mut histogram = AlleleSizeHistogram {
+                bubbles: 1,
+                ..AlleleSizeHistogram::default()
+            };
+            for (kind, size) in alleles {
+                histogram.add(kind, size);
+            }
+            Some(histogram)
+        })
+        .reduce(AlleleSizeHistogram::default, AlleleSizeHistogram::merge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gfa::gfa::Orientation::*;
+
+    #[test]
+    fn analyses_write_ndjson() {
+        let segment_map: FnvHashMap<usize, BString> = vec![
+            (1, "A".into()),
+            (2, "C".into()),
+            (3, "G".into()),
+            (4, "T".into()),
+        ]
+        .into_iter()
+        .collect();
+        let path_data = PathData {
+            segment_map,
+            path_names: vec!["a".into(), "b".into(), "c".into()],
+            paths: vec![
+                vec![(1, 1, Forward), (2, 2, Forward), (4, 3, Forward)],
+                vec![(1, 1, Forward), (3, 2, Forward), (4, 3, Forward)],
+                vec![(4, 1, Backward), (2, 2, Backward), (1, 3, Backward)],
+            ],
+        };
+
+        let mut analyses = BubbleAnalyses::default();
+        analyses.register("traversals", |bubble: &Bubble<'_>, _: &_| {
+            Some(bubble.distinct_traversals())
+        });
+        analyses.register("sequences", |bubble: &Bubble<'_>, segments: &_| {
+            let seqs: Vec<String> = bubble
+                .sub_paths
+                .iter()
+                .map(|sub_path| sub_path.sequence(segments).to_string())
+                .collect();
+            Some(seqs)
+        });
+        analyses.register("never", |_: &Bubble<'_>, _: &_| None::<usize>);
+
+        let mut out = Vec::new();
+        let written = analyses
+            .run(&path_data, &[(1, 4), (5, 6)], &mut out)
+            .unwrap();
+        assert_eq!(written, 2);
+
+        let lines: Vec<Value> = out
+            .lines()
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                json!({
+                    "from": 1,
+                    "to": 4,
+                    "analysis": "traversals",
+                    "result": 2,
+                }),
+                json!({
+                    "from": 1,
+                    "to": 4,
+                    "analysis": "sequences",
+                    "result": ["ACT", "AGT", "AGT"],
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn alt_allele_size_histogram() {
+        let segment_map: FnvHashMap<usize, BString> = vec![
+            (1, "A".into()),
+            (2, "C".into()),
+            (3, "GGGG".into()),
+            (4, "T".into()),
+        ]
+        .into_iter()
+        .collect();
+        let path_data = PathData {
+            segment_map,
+            path_names: vec![
+                "ref".into(),
+                "same".into(),
+                "ins".into(),
+                "del".into(),
+                "ins2".into(),
+            ],
+            paths: vec![
+                vec![(1, 1, Forward), (2, 2, Forward), (4, 3, Forward)],
+                vec![(1, 1, Forward), (2, 2, Forward), (4, 3, Forward)],
+                vec![(1, 1, Forward), (3, 2, Forward), (4, 6, Forward)],
+                vec![(1, 1, Forward), (4, 2, Forward)],
+                vec![(4, 1, Backward), (3, 2, Backward), (1, 6, Backward)],
+            ],
+        };
+
+        let bubble = bubble(
+            &path_data,
+            &variants::bubble_path_indices(
+                &path_data.paths,
+                &vec![1, 4].into_iter().collect::<NodeSet>(),
+            ),
+            1,
+            4,
+        )
+        .unwrap();
+        // The reverse traversal of 3 is the same allele as the
+        // forward one, and the ref's traversal isn't an alt allele
+        assert_eq!(
+            alt_allele_sizes(&bubble, &path_data.segment_map, 0),
+            Some(vec![(AlleleKind::Insertion, 3), (AlleleKind::Deletion, 1)])
+        );
+
+        let histogram = allele_size_histogram(&path_data, &[(1, 4)], 0);
+        assert_eq!(histogram.bubbles, 1);
+        assert_eq!(histogram.count(AlleleKind::Insertion, 2), 1);
+        assert_eq!(histogram.count(AlleleKind::Deletion, 1), 1);
+        assert_eq!(histogram.total(AlleleKind::Balanced), 0);
+
+        let histogram = allele_size_histogram(&path_data, &[(1, 4)], 5);
+        assert_eq!(histogram, AlleleSizeHistogram::default());
+
+        assert_eq!(
+            serde_json::to_value(AlleleKind::Deletion).unwrap(),
+            json!("deletion")
+        );
+    }
+}
@@ -0,0 +1,529 @@
+use std::fmt;
+
+use bstr::{BString, ByteSlice};
+use clap::arg_enum;
+use fnv::{FnvHashMap, FnvHashSet};
+
+use gfa::{
+    gfa::{Link, Orientation, Path, Segment, GFA},
+    optfields::OptFields,
+};
+
+use crate::{
+    stats::{ambiguous_runs, is_acgt},
+    util::split_path,
+};
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum MaskAmbiguous {
+        Hard,
+        Split,
+    }
+}
+
+/// Counts of the records that were removed or changed by `clean_gfa`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CleanSummary {
+    pub zero_length_segments: usize,
+    pub missing_segment_links: usize,
+    pub duplicate_links: usize,
+    pub missing_segment_containments: usize,
+    pub missing_segment_steps: usize,
+    pub split_paths: usize,
+    pub removed_paths: usize,
+}
+
+/// An edge and its reverse complement (e.g. `1+ -> 2+` and
+/// `2- -> 1-`) are the same, and map to the same key
+pub(crate) fn canonical_edge<'a>(
+    from: &'a [u8],
+    from_rev: bool,
+    to: &'a [u8],
+    to_rev: bool,
+) -> (&'a [u8], bool, &'a [u8], bool) {
+    let fwd = (from, from_rev, to, to_rev);
+    let rev = (to, !to_rev, from, !from_rev);
+    fwd.min(rev)
+}
+
+fn canonical_link_key<T: OptFields>(
+    link: &Link<Vec<u8>, T>,
+) -> (&[u8], bool, &[u8], bool) {
+    canonical_edge(
+        link.from_segment.as_ref(),
+        link.from_orient.is_reverse(),
+        link.to_segment.as_ref(),
+        link.to_orient.is_reverse(),
+    )
+}
+
+/// Remove zero-length segments, links and containments that refer to
+/// segments that don't exist, and duplicate links. Paths that step
+/// on missing segments are split into the fragments that remain.
+pub fn clean_gfa<T: OptFields + Clone>(
+    gfa: &GFA<Vec<u8>, T>,
+) -> (GFA<Vec<u8>, T>, CleanSummary) {
+    let mut summary = CleanSummary::default();
+
+    let segments: Vec<_> = gfa
+        .segments
+        .iter()
+        .filter(|s| !s.sequence.is_empty())
+        .cloned()
+        .collect();
+    summary.zero_length_segments = gfa.segments.len() - segments.len();
+
+    let segment_names: FnvHashSet<&[u8]> =
+        segments.iter().map(|s| s.name.as_slice()).collect();
+
+    let mut seen_links = FnvHashSet::default();
+    let mut links = Vec::with_capacity(gfa.links.len());
+
+    for link in gfa.links.iter() {
+        if !segment_names.contains(link.from_segment.as_slice())
+            || !segment_names.contains(link.to_segment.as_slice())
+        {
+            summary.missing_segment_links += 1;
+        } else if !seen_links.insert(canonical_link_key(link)) {
+            summary.duplicate_links += 1;
+        } else {
+            links.push(link.clone());
+        }
+    }
+
+    let containments: Vec<_> = gfa
+        .containments
+        .iter()
+        .filter(|c| {
+            segment_names.contains(c.container_name.as_slice())
+                && segment_names.contains(c.contained_name.as_slice())
+        })
+        .cloned()
+        .collect();
+    summary.missing_segment_containments =
+        gfa.containments.len() - containments.len();
+
+    let mut paths = Vec::with_capacity(gfa.paths.len());
+
+    for path in gfa.paths.iter() {
+        let missing = path
+            .iter()
+            .filter(|(seg, _)| {
+                let seg: &[u8] = seg.as_ref();
+                !segment_names.contains(seg)
+            })
+            .count();
+
+        if missing == 0 {
+            paths.push(path.clone());
+            continue;
+        }
+
+        summary.missing_segment_steps += missing;
+
+        let fragments =
+            split_path(path, |seg| segment_names.contains(seg.as_slice()));
+        if fragments.is_empty() {
+            summary.removed_paths += 1;
+        } else {
+            summary.split_paths += 1;
+        }
+        paths.extend(fragments);
+    }
+
+    let new_gfa = GFA {
+        header: gfa.header.clone(),
+        segments,
+        links,
+        paths,
+        containments,
+    };
+
+    (new_gfa, summary)
+}
+
+/// Replace every base that isn't A, C, G, T, or N, e.g. IUPAC codes
+/// such as R or Y, with N. Returns the number of bases replaced.
+pub fn hard_mask_ambiguous<T: OptFields>(gfa: &mut GFA<Vec<u8>, T>) -> usize {
+    let mut masked = 0;
+    for seg in gfa.segments.iter_mut() {
+        for base in seg.sequence.iter_mut() {
+            if !is_acgt(*base) && base.to_ascii_uppercase() != b'N' {
+                *base = b'N';
+                masked += 1;
+            }
+        }
+    }
+    masked
+}
+
+/// A piece of a segment split by `split_ambiguous` would have the
+/// name of a segment that's already in the graph
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PieceNameCollision {
+    pub segment: BString,
+    pub piece: BString,
+}
+
+impl fmt::Display for PieceNameCollision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Can't split segment {}, the graph already has a segment \
+             named {}",
+            self.segment, self.piece
+        )
+    }
+}
+
+impl std::error::Error for PieceNameCollision {}
+
+/// Split each segment that has bases other than A, C, G, and T at the
+/// ends of its runs of such bases, so that every run is a segment of
+/// its own, and the rest of the segment is clean DNA. The pieces of a
+/// segment are named `segment:1`, `segment:2`, and so on, get a copy
+/// of the segment's tags, and are chained by links. Links and paths
+/// are moved onto the pieces; the paths that step on them lose their
+/// overlaps, and containments of split segments are removed. Returns
+/// the new GFA and the number of segments that were split, or an
+/// error if a piece's name is already taken by another segment.
+pub fn split_ambiguous<T: OptFields + Clone + Default>(
+    gfa: &GFA<Vec<u8>, T>,
+) -> Result<(GFA<Vec<u8>, T>, usize), PieceNameCollision> {
+    let existing: FnvHashSet<&[u8]> =
+        gfa.segments.iter().map(|s| s.name.as_slice()).collect();
+
+    let mut pieces: FnvHashMap<&[u8], Vec<Vec<u8>>> = FnvHashMap::default();
+    let mut segments = Vec::with_capacity(gfa.segments.len());
+    let mut links = Vec::with_capacity(gfa.links.len());
+
+    for seg in gfa.segments.iter() {
+        let len = seg.sequence.len();
+        let mut cuts = vec![0];
+        for (start, end) in ambiguous_runs(&seg.sequence) {
+            cuts.extend([start, end].iter().filter(|&&c| c > 0 && c < len));
+        }
+        cuts.push(len);
+
+        if cuts.len() == 2 {
+            segments.push(seg.clone());
+            continue;
+        }
+
+        let mut names = Vec::with_capacity(cuts.len() - 1);
+        for (ix, range) in cuts.windows(2).enumerate() {
+            let mut name = seg.name.clone();
+            name.extend_from_slice(format!(":{}", ix + 1).as_bytes());
+            if existing.contains(name.as_slice()) {
+                return Err(PieceNameCollision {
+                    segment: seg.name.as_bstr().to_owned(),
+                    piece: name.into(),
+                });
+            }
+            segments.push(Segment {
+                name: name.clone(),
+                sequence: BString::from(&seg.sequence[range[0]..range[1]]),
+                optional: seg.optional.clone(),
+            });
+            names.push(name);
+        }
+        for pair in names.windows(2) {
+            links.push(Link {
+                from_segment: pair[0].clone(),
+                from_orient: Orientation::Forward,
+                to_segment: pair[1].clone(),
+                to_orient: Orientation::Forward,
+                overlap: BString::from("0M"),
+                optional: T::default(),
+            });
+        }
+        pieces.insert(seg.name.as_slice(), names);
+    }
+
+    // The piece at the start or end of a segment, or the segment
+    // itself if it wasn't split
+    let end_piece = |name: &[u8], last: bool| -> Vec<u8> {
+        match pieces.get(name) {
+            Some(names) if last => names[names.len() - 1].clone(),
+            Some(names) => names[0].clone(),
+            None => name.to_vec(),
+        }
+    };
+
+    for link in gfa.links.iter() {
+        let mut link = link.clone();
+        link.from_segment =
+            end_piece(&link.from_segment, !link.from_orient.is_reverse());
+        link.to_segment =
+            end_piece(&link.to_segment, link.to_orient.is_reverse());
+        links.push(link);
+    }
+
+    let paths = gfa
+        .paths
+        .iter()
+        .map(|path| {
+            let steps: Vec<(&[u8], Orientation)> = path
+                .iter()
+                .map(|(seg, orient)| {
+                    let seg: &[u8] = seg.as_ref();
+                    (seg, orient)
+                })
+                .collect();
+
+            if !steps.iter().any(|(seg, _)| pieces.contains_key(seg)) {
+                return path.clone();
+            }
+
+            let mut segment_names: Vec<u8> = Vec::new();
+            let mut push_step = |name: &[u8], orient: Orientation| {
+                if !segment_names.is_empty() {
+                    segment_names.push(b',');
+                }
+                segment_names.extend_from_slice(name);
+                segment_names.push(if orient.is_reverse() {
+                    b'-'
+                } else {
+                    b'+'
+                });
+            };
+
+            for &(seg, orient) in steps.iter() {
+                match pieces.get(seg) {
+                    Some(names) if orient.is_reverse() => names
+                        .iter()
+                        .rev()
+                        .for_each(|name| push_step(name, orient)),
+                    Some(names) => {
+                        names.iter().for_each(|name| push_step(name, orient))
+                    }
+                    None => push_step(seg, orient),
+                }
+            }
+
+            Path::new(
+                path.path_name.clone(),
+                segment_names.into(),
+                Vec::new(),
+                path.optional.clone(),
+            )
+        })
+        .collect();
+
+    let containments = gfa
+        .containments
+        .iter()
+        .filter(|c| {
+            !pieces.contains_key(c.container_name.as_slice())
+                && !pieces.contains_key(c.contained_name.as_slice())
+        })
+        .cloned()
+        .collect();
+
+    let split = pieces.len();
+    let new_gfa = GFA {
+        header: gfa.header.clone(),
+        segments,
+        links,
+        paths,
+        containments,
+    };
+
+    Ok((new_gfa, split))
+}
+
+/// The segments and links of a GFA that no path traverses
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Uncovered {
+    /// The indices of the segments that no path steps on
+    pub segments: Vec<usize>,
+    /// The indices of the links that no path follows, in either
+    /// direction
+    pub links: Vec<usize>,
+}
+
+/// Find the segments and links that aren't covered by any path. A
+/// link is covered if two consecutive steps of a path follow it, as
+/// given or as its reverse complement.
+pub fn uncovered_by_paths<T: OptFields>(gfa: &GFA<Vec<u8>, T>) -> Uncovered {
+    let mut steps: FnvHashSet<&[u8]> = FnvHashSet::default();
+    let mut edges = FnvHashSet::default();
+
+    for path in gfa.paths.iter() {
+        let path_steps: Vec<(&[u8], bool)> = path
+            .iter()
+            .map(|(seg, orient)| {
+                let seg: &[u8] = seg.as_ref();
+                (seg, orient.is_reverse())
+            })
+            .collect();
+
+        steps.extend(path_steps.iter().map(|&(seg, _)| seg));
+        for pair in path_steps.windows(2) {
+            let (from, from_rev) = pair[0];
+            let (to, to_rev) = pair[1];
+            edges.insert(canonical_edge(from, from_rev, to, to_rev));
+        }
+    }
+
+    let segments = gfa
+        .segments
+        .iter()
+        .enumerate()
+        .filter(|(_, seg)| !steps.contains(seg.name.as_slice()))
+        .map(|(ix, _)| ix)
+        .collect();
+
+    let links = gfa
+        .links
+        .iter()
+        .enumerate()
+        .filter(|(_, link)| !edges.contains(&canonical_link_key(link)))
+        .map(|(ix, _)| ix)
+        .collect();
+
+    Uncovered { segments, links }
+}
+
+/// Remove the uncovered segments and links, and the containments that
+/// refer to the removed segments. Since every path step is on a
+/// covered segment, the paths are kept as they are.
+pub fn remove_uncovered<T: OptFields + Clone>(
+    gfa: &GFA<Vec<u8>, T>,
+    uncovered: &Uncovered,
+) -> GFA<Vec<u8>, T> {
+    let removed_segments: FnvHashSet<usize> =
+        uncovered.segments.iter().copied().collect();
+    let removed_links: FnvHashSet<usize> =
+        uncovered.links.iter().copied().collect();
+
+    let segments: Vec<_> = gfa
+        .segments
+        .iter()
+        .enumerate()
+        .filter(|(ix, _)| !removed_segments.contains(ix))
+        .map(|(_, seg)| seg.clone())
+        .collect();
+
+    let segment_names: FnvHashSet<&[u8]> =
+        segments.iter().map(|s| s.name.as_slice()).collect();
+
+    let links = gfa
+        .links
+        .iter()
+        .enumerate()
+        .filter(|(ix, _)| !removed_links.contains(ix))
+        .map(|(_, link)| link.clone())
+        .collect();
+
+    let containments = gfa
+        .containments
+        .iter()
+        .filter(|c| {
+            segment_names.contains(c.container_name.as_slice())
+                && segment_names.contains(c.contained_name.as_slice())
+        })
+        .cloned()
+        .collect();
+
+    GFA {
+        header: gfa.header.clone(),
+        segments,
+        links,
+        paths: gfa.paths.clone(),
+        containments,
+    }
+}
+
+/// A pair of consecutive path steps that isn't backed by a link with
+/// compatible orientations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingLink {
+    pub path_name: BString,
+    /// Index of the first of the two steps in the path
+    pub step: usize,
+    pub from: BString,
+    pub from_orient: Orientation,
+    pub to: BString,
+    pub to_orient: Orientation,
+}
+
+/// Find the consecutive steps in every path that aren't connected by
+/// a link, either as given, or as its reverse complement
+pub fn missing_path_links<T: OptFields>(
+    gfa: &GFA<Vec<u8>, T>,
+) -> Vec<MissingLink> {
+    let links: FnvHashSet<_> =
+        gfa.links.iter().map(canonical_link_key).collect();
+
+    let mut missing = Vec::new();
+
+    for path in gfa.paths.iter() {
+        let steps: Vec<(&[u8], Orientation)> = path
+            .iter()
+            .map(|(seg, orient)| {
+                let seg: &[u8] = seg.as_ref();
+                (seg, orient)
+            })
+            .collect();
+
+        for (ix, pair) in steps.windows(2).enumerate() {
+            let (from, from_orient) = pair[0];
+            let (to, to_orient) = pair[1];
+            let key = canonical_edge(
+                from,
+                from_orient.is_reverse(),
+                to,
+                to_orient.is_reverse(),
+            );
+            if !links.contains(&key) {
+                missing.push(MissingLink {
+                    path_name: path.path_name.clone().into(),
+                    step: ix,
+                    from: from.into(),
+                    from_orient,
+                    to: to.into(),
+                    to_orient,
+                });
+            }
+        }
+    }
+
+    missing
+}
+
+/// Add a link for every edge that's implied by the paths but missing
+/// from the GFA, with an unknown overlap. Returns the number of links
+/// that were added.
+pub fn add_missing_links<T: OptFields + Default>(
+    gfa: &mut GFA<Vec<u8>, T>,
+) -> usize {
+    let missing = missing_path_links(gfa);
+
+    let mut added = FnvHashSet::default();
+    let mut new_links = Vec::new();
+
+    for link in missing.iter() {
+        let key = canonical_edge(
+            &link.from,
+            link.from_orient.is_reverse(),
+            &link.to,
+            link.to_orient.is_reverse(),
+        );
+        if added.insert(key) {
+            new_links.push(Link {
+                from_segment: link.from.to_vec(),
+                from_orient: link.from_orient,
+                to_segment: link.to.to_vec(),
+                to_orient: link.to_orient,
+                overlap: BString::from("*"),
+                optional: T::default(),
+            });
+        }
+    }
+
+    let count = new_links.len();
+    gfa.links.extend(new_links);
+    count
+}
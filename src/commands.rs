@@ -1,14 +1,50 @@
+pub mod allele_lengths;
+pub mod ambiguous;
+pub mod check_links;
+pub mod check_paths;
+pub mod clean;
+pub mod compare;
+pub mod complexity;
+pub mod components;
 pub mod convert_names;
+pub mod core_accessory;
+pub mod cycles;
+pub mod divergence;
+pub mod edge_count;
+pub mod gaf2bed;
+pub mod gaf2gfa_paths;
 pub mod gaf2paf;
+pub mod gaf2sam;
+pub mod gafdedup;
+pub mod gafdepth;
+pub mod gafsplit;
+pub mod gafstats;
+pub mod gc;
+pub mod get_seq;
+pub mod gfa2graphml;
 pub mod gfa2vcf;
+pub mod node_coverage;
+pub mod node_map;
+pub mod paf2gaf;
+pub mod paf_lift;
+pub mod path_align;
+pub mod path_checksums;
+pub mod path_dedup;
+pub mod paths;
+pub mod report;
 pub mod saboten;
 pub mod snps;
 pub mod stats;
 pub mod subgraph;
+pub mod uncovered;
+pub mod vcf2traversal;
 
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
+
+use bstr::{io::*, ByteSlice};
+use serde::Serialize;
+use structopt::StructOpt;
 
-use bstr::io::*;
 use gfa::{
     gfa::{SegmentId, GFA},
     optfields::OptFields,
@@ -22,8 +58,26 @@ pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
 pub fn byte_lines_iter<'a, R: Read + 'a>(
     reader: R,
-) -> Box<dyn Iterator<Item = Vec<u8>> + 'a> {
-    Box::new(BufReader::new(reader).byte_lines().map(|l| l.unwrap()))
+) -> Box<dyn Iterator<Item = std::io::Result<Vec<u8>>> + 'a> {
+    Box::new(BufReader::new(reader).byte_lines())
+}
+
+/// The `--json` flag shared by the analysis commands, to output their
+/// results as JSON instead of TSV, so pipelines don't have to parse
+/// the TSV rows
+#[derive(StructOpt, Debug, Clone, Copy)]
+pub struct JsonOpt {
+    /// Output the results as JSON instead of TSV.
+    #[structopt(name = "json", long = "json")]
+    pub json: bool,
+}
+
+/// Write a command's results as pretty-printed JSON, followed by a
+/// newline
+pub fn write_json<W: Write, T: Serialize>(mut out: W, value: &T) -> Result<()> {
+    serde_json::to_writer_pretty(&mut out, value)?;
+    writeln!(out)?;
+    Ok(())
 }
 
 pub fn load_gfa<N, T, P>(path: P) -> Result<GFA<N, T>>
@@ -35,5 +89,192 @@ where
     let parser = GFAParser::new();
     info!("Parsing GFA from {}", path.as_ref().display());
     let gfa = parser.parse_file(path.as_ref())?;
+    crate::metrics::GRAPHS_LOADED
+        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    Ok(gfa)
+}
+
+/// Read the path data file `cache`, if it's given and exists, after
+/// checking that it was built from the GFA at `gfa_path` as it is
+/// now, by the GFA's size and modification time
+fn read_path_data_cache(
+    gfa_path: &std::path::Path,
+    cache: Option<&std::path::PathBuf>,
+) -> Result<Option<crate::variants::binary::PathDataFile>> {
+    use crate::variants::binary::{self, GfaFingerprint};
+
+    let cache = match cache.filter(|cache| cache.exists()) {
+        Some(cache) => cache,
+        None => return Ok(None),
+    };
+
+    info!("Loading path data from {}", cache.display());
+    let cached = binary::load_path_data(cache)?;
+    if cached.gfa != GfaFingerprint::of_file(gfa_path)? {
+        return Err(format!(
+            "{} was built from a different version of {}; \
+             delete it to rebuild it",
+            cache.display(),
+            gfa_path.display()
+        )
+        .into());
+    }
+    Ok(Some(cached))
+}
+
+/// Load the path data for the variant commands. If `cache` is given
+/// and exists, the path data is read from it instead of from the GFA,
+/// unless the GFA has changed since it was written, which is an
+/// error; if it doesn't exist, the path data is built from the GFA
+/// and saved there, so later runs on the same graph can skip parsing
+/// it.
+pub fn load_path_data<P: AsRef<std::path::Path>>(
+    gfa_path: P,
+    cache: Option<&std::path::PathBuf>,
+) -> Result<crate::variants::PathData> {
+    use crate::variants::binary::{self, GfaFingerprint};
+
+    let gfa_path = gfa_path.as_ref();
+    if let Some(cached) = read_path_data_cache(gfa_path, cache)? {
+        return Ok(cached.path_data);
+    }
+
+    let fingerprint = GfaFingerprint::of_file(gfa_path)?;
+    let gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+    let path_data = crate::variants::gfa_path_data(gfa);
+
+    if let Some(cache) = cache {
+        info!("Saving path data to {}", cache.display());
+        binary::save_path_data(fingerprint, &path_data, None, cache)?;
+    }
+
+    Ok(path_data)
+}
+
+/// Like `load_path_data`, but also find the graph's ultrabubbles. The
+/// ultrabubbles are saved to the cache along with the path data, so
+/// that later runs don't have to parse the GFA at all.
+pub fn load_path_data_and_ultrabubbles<P: AsRef<std::path::Path>>(
+    gfa_path: P,
+    cache: Option<&std::path::PathBuf>,
+) -> Result<(crate::variants::PathData, Vec<(u64, u64)>)> {
+    use crate::variants::binary::{self, GfaFingerprint};
+
+    let gfa_path = gfa_path.as_ref();
+    let (fingerprint, path_data) = match read_path_data_cache(gfa_path, cache)?
+    {
+        Some(cached) => {
+            if let Some(ultrabubbles) = cached.ultrabubbles {
+                info!("Loaded the ultrabubbles from the path data file");
+                return Ok((cached.path_data, ultrabubbles));
+            }
+            (cached.gfa, cached.path_data)
+        }
+        None => {
+            let fingerprint = GfaFingerprint::of_file(gfa_path)?;
+            let gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+            (fingerprint, crate::variants::gfa_path_data(gfa))
+        }
+    };
+
+    let ultrabubbles = saboten::find_ultrabubbles(&gfa_path.to_path_buf())?;
+
+    if let Some(cache) = cache {
+        info!("Saving path data and ultrabubbles to {}", cache.display());
+        binary::save_path_data(
+            fingerprint,
+            &path_data,
+            Some(&ultrabubbles),
+            cache,
+        )?;
+    }
+
+    Ok((path_data, ultrabubbles))
+}
+
+/// Load a GFA whose segment names are parsed as integer IDs, like
+/// `load_gfa`, but fail with the offending name and line if a name
+/// isn't a valid ID, instead of silently dropping the line it's on.
+/// Commands that parse the segment names as integers opt in to the
+/// check by loading the graph with this.
+pub fn load_numeric_gfa<T, P>(path: P) -> Result<GFA<usize, T>>
+where
+    T: OptFields,
+    P: AsRef<std::path::Path>,
+{
+    info!("Parsing GFA from {}", path.as_ref().display());
+    parse_numeric_gfa(&GFAParser::new(), path, u64::MAX)
+}
+
+/// Parse a GFA with integer segment IDs using `parser`, checking
+/// that every segment name in the S, L, C, and P lines is a valid ID
+/// no larger than `max_id` as the lines are read, so that the file is
+/// still only read once.
+pub fn parse_numeric_gfa<T, P>(
+    parser: &GFAParser<usize, T>,
+    path: P,
+    max_id: u64,
+) -> Result<GFA<usize, T>>
+where
+    T: OptFields,
+    P: AsRef<std::path::Path>,
+{
+    let file = std::fs::File::open(path.as_ref())?;
+
+    let mut error = None;
+    let lines =
+        byte_lines_iter(file)
+            .enumerate()
+            .scan((), |_, (line_ix, line)| {
+                let checked: Result<Vec<u8>> =
+                    line.map_err(Into::into).and_then(|line| {
+                        check_segment_ids(&line, line_ix + 1, max_id)?;
+                        Ok(line)
+                    });
+                checked.map_err(|err| error = Some(err)).ok()
+            });
+    let gfa = parser.parse_lines(lines)?;
+    if let Some(err) = error {
+        return Err(err);
+    }
+
+    crate::metrics::GRAPHS_LOADED
+        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
     Ok(gfa)
 }
+
+/// Check that every segment name in an S, L, C, or P line, numbered
+/// from 1, is a valid numeric ID no larger than `max_id`
+fn check_segment_ids(line: &[u8], line_no: usize, max_id: u64) -> Result<()> {
+    let fields: Vec<&[u8]> = line.split_str("\t").collect();
+
+    let names: Vec<&[u8]> = match fields.first().copied() {
+        Some(b"S") if fields.len() > 1 => vec![fields[1]],
+        Some(b"L") | Some(b"C") if fields.len() > 3 => {
+            vec![fields[1], fields[3]]
+        }
+        Some(b"P") if fields.len() > 2 => fields[2]
+            .split_str(",")
+            .map(|step| {
+                step.strip_suffix(b"+")
+                    .or_else(|| step.strip_suffix(b"-"))
+                    .unwrap_or(step)
+            })
+            .collect(),
+        _ => return Ok(()),
+    };
+
+    for name in names {
+        if let Err(err) = crate::util::parse_segment_id(name, max_id) {
+            return Err(format!(
+                "Invalid segment ID {} on line {}: {}",
+                name.as_bstr(),
+                line_no,
+                err
+            )
+            .into());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use bstr::BString;
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::gfa::GFA;
+
+use crate::{
+    bubble_analysis::{self, AlleleKind, ALLELE_SIZE_BINS},
+    util::bytes_from_os_str,
+    variants,
+};
+
+use super::{load_numeric_gfa, write_json, JsonOpt, Result};
+
+/// Summarize the lengths of the alternate alleles of the
+/// ultrabubbles, compared to a reference path.
+///
+/// Each distinct traversal of a bubble that differs from the
+/// reference's is an alternate allele, and is an insertion or a
+/// deletion if it's longer or shorter than the reference allele, or
+/// balanced otherwise. This shows how many variants a given SV length
+/// threshold would include, before running gfa2vcf. The output is
+/// TSV, with the kind of each row in the first column: SN rows with
+/// the number of bubbles on the reference and of each kind of allele,
+/// followed by an AL row for each kind and size bin, with the kind,
+/// the smallest and largest size in the bin, * if unbounded, and the
+/// number of alleles. The size of an insertion or deletion is the
+/// difference in length, and that of a balanced allele its length.
+/// With --json, the same is written as a JSON object.
+#[derive(StructOpt, Debug)]
+pub struct AlleleLengthsArgs {
+    /// The name of the path to compare the alleles against.
+    #[structopt(
+        name = "reference path",
+        long = "ref",
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    ref_path: BString,
+    /// Load ultrabubbles from a file instead of calculating them.
+    #[structopt(
+        name = "ultrabubbles file",
+        long = "ultrabubbles",
+        parse(from_os_str)
+    )]
+    ultrabubbles_file: Option<PathBuf>,
+    /// Write the output to this file instead of stdout.
+    #[structopt(
+        name = "output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    #[structopt(flatten)]
+    json: JsonOpt,
+}
+
+#[derive(Serialize)]
+struct SizeBin {
+    kind: AlleleKind,
+    min: usize,
+    max: Option<usize>,
+    alleles: usize,
+}
+
+#[derive(Serialize)]
+struct AlleleLengthReport {
+    ref_bubbles: usize,
+    insertions: usize,
+    deletions: usize,
+    balanced: usize,
+    bins: Vec<SizeBin>,
+}
+
+pub fn allele_lengths(
+    gfa_path: &PathBuf,
+    args: &AlleleLengthsArgs,
+) -> Result<()> {
+    let ultrabubbles = if let Some(path) = &args.ultrabubbles_file {
+        super::saboten::load_ultrabubbles(path)
+    } else {
+        super::saboten::find_ultrabubbles(gfa_path)
+    }?;
+    info!("Using {} ultrabubbles", ultrabubbles.len());
+
+    let gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+    let path_data = variants::gfa_path_data(gfa);
+
+    let ref_ix = path_data
+        .path_names
+        .iter()
+        .position(|name| *name == args.ref_path)
+        .ok_or_else(|| {
+            format!("Reference path does not exist in graph: {}", args.ref_path)
+        })?;
+
+    let histogram = bubble_analysis::allele_size_histogram(
+        &path_data,
+        &ultrabubbles,
+        ref_ix,
+    );
+
+    if histogram.bubbles < ultrabubbles.len() {
+        info!(
+            "{} ultrabubbles aren't traversed by {}, and were skipped",
+            ultrabubbles.len() - histogram.bubbles,
+            args.ref_path
+        );
+    }
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    let mut bins = Vec::new();
+    for &kind in AlleleKind::ALL.iter() {
+        for (bin, &min) in ALLELE_SIZE_BINS.iter().enumerate() {
+            bins.push(SizeBin {
+                kind,
+                min,
+                max: ALLELE_SIZE_BINS.get(bin + 1).map(|next| next - 1),
+                alleles: histogram.count(kind, bin),
+            });
+        }
+    }
+    let report = AlleleLengthReport {
+        ref_bubbles: histogram.bubbles,
+        insertions: histogram.total(AlleleKind::Insertion),
+        deletions: histogram.total(AlleleKind::Deletion),
+        balanced: histogram.total(AlleleKind::Balanced),
+        bins,
+    };
+
+    if args.json.json {
+        write_json(&mut out, &report)?;
+    } else {
+        writeln!(out, "SN\tref_bubbles\t{}", report.ref_bubbles)?;
+        writeln!(out, "SN\tinsertions\t{}", report.insertions)?;
+        writeln!(out, "SN\tdeletions\t{}", report.deletions)?;
+        writeln!(out, "SN\tbalanced\t{}", report.balanced)?;
+
+        for bin in report.bins.iter() {
+            let max = bin
+                .max
+                .map_or_else(|| "*".to_string(), |max| max.to_string());
+            writeln!(
+                out,
+                "AL\t{}\t{}\t{}\t{}",
+                bin.kind, bin.min, max, bin.alleles
+            )?;
+        }
+    }
+    out.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use bstr::ByteSlice;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::gfa::GFA;
+
+use crate::stats::AmbiguousBases;
+
+use super::{load_gfa, Result};
+
+/// Report the segments with bases other than A, C, G, and T.
+///
+/// The variant commands assume that segment sequences are clean DNA.
+/// The output is TSV, with the kind of each row in the first column:
+/// SN rows with the number of segments with ambiguous bases, and the
+/// number of N and other ambiguous bases, e.g. IUPAC codes, in the
+/// graph, followed by an AS row for each of those segments, with its
+/// name, length, number of N and other ambiguous bases, and the
+/// fraction of its bases that are ambiguous. Use `clean
+/// --mask-ambiguous` to mask or split them.
+#[derive(StructOpt, Debug)]
+pub struct AmbiguousArgs {
+    /// Write the report to this file instead of stdout.
+    #[structopt(
+        name = "output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+}
+
+pub fn ambiguous(gfa_path: &PathBuf, args: &AmbiguousArgs) -> Result<()> {
+    let gfa: GFA<Vec<u8>, ()> = load_gfa(gfa_path)?;
+
+    let ambiguous: Vec<(usize, AmbiguousBases)> = gfa
+        .segments
+        .iter()
+        .enumerate()
+        .map(|(ix, seg)| (ix, AmbiguousBases::from_sequence(&seg.sequence)))
+        .filter(|(_, bases)| bases.total() > 0)
+        .collect();
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    let n: usize = ambiguous.iter().map(|(_, bases)| bases.n).sum();
+    let other: usize = ambiguous.iter().map(|(_, bases)| bases.other).sum();
+    writeln!(out, "SN\tambiguous_segments\t{}", ambiguous.len())?;
+    writeln!(out, "SN\tn_bp\t{}", n)?;
+    writeln!(out, "SN\tother_ambiguous_bp\t{}", other)?;
+
+    for (ix, bases) in ambiguous.iter() {
+        let seg = &gfa.segments[*ix];
+        let len = seg.sequence.len();
+        writeln!(
+            out,
+            "AS\t{}\t{}\t{}\t{}\t{:.4}",
+            seg.name.as_bstr(),
+            len,
+            bases.n,
+            bases.other,
+            bases.total() as f64 / len as f64
+        )?;
+    }
+    out.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use serde::Serialize;
+use std::{fs::File, io::Write, path::PathBuf};
+use structopt::StructOpt;
+
+use gfa::{
+    gfa::{Orientation, GFA},
+    optfields::OptionalFields,
+};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use crate::{clean, util::write_gfa_bytes};
+
+use super::{load_gfa, write_json, JsonOpt, Result};
+
+/// Check that every pair of consecutive steps in every path is
+/// connected by a link with compatible orientations.
+///
+/// By default, the steps that lack a link are printed as TSV, or with
+/// --json, as an array with one object per step. With
+/// `--add-missing`, the missing links are added instead, and the
+/// fixed GFA is output.
+#[derive(StructOpt, Debug)]
+pub struct CheckLinksArgs {
+    /// Add the missing links to the graph, and output the result.
+    #[structopt(
+        name = "add missing links",
+        long = "add-missing",
+        conflicts_with = "json"
+    )]
+    add_missing: bool,
+    /// Write the output to this file instead of stdout.
+    #[structopt(name = "output file", short = "o", long = "out")]
+    out: Option<PathBuf>,
+    #[structopt(flatten)]
+    json: JsonOpt,
+}
+
+#[derive(Serialize)]
+struct MissingLinkRow {
+    path: String,
+    step: usize,
+    from: String,
+    from_orient: char,
+    to: String,
+    to_orient: char,
+}
+
+fn orient_char(orient: Orientation) -> char {
+    if orient.is_reverse() {
+        '-'
+    } else {
+        '+'
+    }
+}
+
+pub fn check_links(gfa_path: &PathBuf, args: &CheckLinksArgs) -> Result<()> {
+    let mut gfa: GFA<Vec<u8>, OptionalFields> = load_gfa(gfa_path)?;
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(File::create(out_path)?)
+    } else {
+        Box::new(std::io::stdout())
+    };
+
+    if args.add_missing {
+        let added = clean::add_missing_links(&mut gfa);
+        eprintln!("links added\t{}", added);
+        write_gfa_bytes(&gfa, &mut out)?;
+        return Ok(());
+    }
+
+    let missing = clean::missing_path_links(&gfa);
+    eprintln!("path steps without links\t{}", missing.len());
+
+    let rows: Vec<MissingLinkRow> = missing
+        .iter()
+        .map(|link| MissingLinkRow {
+            path: link.path_name.to_string(),
+            step: link.step,
+            from: link.from.to_string(),
+            from_orient: orient_char(link.from_orient),
+            to: link.to.to_string(),
+            to_orient: orient_char(link.to_orient),
+        })
+        .collect();
+
+    if args.json.json {
+        write_json(&mut out, &rows)?;
+        return Ok(());
+    }
+
+    writeln!(out, "path\tstep\tfrom\tfrom_orient\tto\tto_orient")?;
+    for row in rows {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            row.path,
+            row.step,
+            row.from,
+            row.from_orient,
+            row.to,
+            row.to_orient,
+        )?;
+    }
+
+    Ok(())
+}
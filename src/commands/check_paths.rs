@@ -0,0 +1,211 @@
+use bstr::BString;
+use fnv::{FnvHashMap, FnvHashSet};
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::gfa::{Orientation, GFA};
+
+use crate::{
+    compression,
+    fasta::{self, FastaReader},
+    node_seq,
+    util::bytes_from_os_str,
+};
+
+use super::{
+    gfa2vcf::load_paths_file, load_numeric_gfa, write_json, JsonOpt, Result,
+};
+
+/// Check that the paths spell the same sequences as the FASTA records
+/// of the same name.
+///
+/// The coordinates gfa2vcf reports are only as good as the paths, so
+/// this verifies that the graph embeds the assemblies it was built
+/// from. Case is ignored, so soft-masking doesn't matter. The output
+/// is TSV, with the name, path and record lengths, and status of each
+/// path: `match`, `mismatch`, or `missing` if there's no record with
+/// its name. Mismatches also get the 0-based position of the first
+/// difference, and the bases of the path and the record there, with *
+/// past the end of the shorter sequence. With --json, the output is
+/// an array with one object per path, with null instead of *.
+#[derive(StructOpt, Debug)]
+pub struct CheckPathsArgs {
+    /// The FASTA file to check the paths against, which may be gzip
+    /// or zstd compressed.
+    #[structopt(name = "FASTA file", long = "fasta", parse(from_os_str))]
+    fasta: PathBuf,
+    /// A file with the names of the paths to check, one per line.
+    /// If neither this nor --paths is given, all paths are checked.
+    #[structopt(
+        name = "file containing paths to check",
+        long = "paths-file",
+        parse(from_os_str)
+    )]
+    paths_file: Option<PathBuf>,
+    #[structopt(
+        name = "list of paths to check",
+        long = "paths",
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    paths_vec: Option<Vec<BString>>,
+    /// Write the output to this file instead of stdout.
+    #[structopt(
+        name = "output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    #[structopt(flatten)]
+    json: JsonOpt,
+}
+
+/// The result of comparing a path against its FASTA record, with
+/// `None` for the fields that don't apply
+#[derive(Serialize)]
+struct PathCheck {
+    path: String,
+    path_length: Option<usize>,
+    fasta_length: Option<usize>,
+    status: &'static str,
+    first_mismatch: Option<usize>,
+    path_base: Option<char>,
+    fasta_base: Option<char>,
+}
+
+fn or_star<T: std::fmt::Display>(value: Option<T>) -> String {
+    value.map_or_else(|| "*".to_string(), |value| value.to_string())
+}
+
+pub fn check_paths(gfa_path: &PathBuf, args: &CheckPathsArgs) -> Result<()> {
+    let mut selected: FnvHashSet<BString> = args
+        .paths_vec
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+
+    if let Some(paths_file) = &args.paths_file {
+        selected.extend(load_paths_file(paths_file.clone())?);
+    }
+
+    let mut gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+
+    for path in selected.iter() {
+        if !gfa.paths.iter().any(|p| p.path_name == *path) {
+            return Err(
+                format!("Path does not exist in graph: {}", path).into()
+            );
+        }
+    }
+
+    let mut paths = std::mem::take(&mut gfa.paths);
+    if !selected.is_empty() {
+        paths.retain(|path| selected.contains(&path.path_name));
+    }
+    let segment_map = node_seq::segment_sequences(gfa);
+
+    let path_ix: FnvHashMap<BString, usize> = paths
+        .iter()
+        .enumerate()
+        .map(|(ix, path)| (path.path_name.clone(), ix))
+        .collect();
+
+    let mut checks: Vec<PathCheck> = paths
+        .iter()
+        .map(|path| PathCheck {
+            path: path.path_name.to_string(),
+            path_length: None,
+            fasta_length: None,
+            status: "missing",
+            first_mismatch: None,
+            path_base: None,
+            fasta_base: None,
+        })
+        .collect();
+
+    let input = compression::open_input(&args.fasta)?;
+    for record in FastaReader::new(BufReader::new(input)) {
+        let record = record?;
+        let ix = match path_ix.get(&record.name) {
+            Some(&ix) => ix,
+            None => continue,
+        };
+        let path = &paths[ix];
+
+        let steps: Vec<(usize, Orientation)> = path.iter().collect();
+        let seq = node_seq::traversal_sequence(&segment_map, &steps).map_err(
+            |err| format!("Error in path {}: {}", path.path_name, err),
+        )?;
+
+        let check = &mut checks[ix];
+        check.path_length = Some(seq.len());
+        check.fasta_length = Some(record.sequence.len());
+        if let Some(pos) = fasta::first_mismatch(&seq, &record.sequence) {
+            check.status = "mismatch";
+            check.first_mismatch = Some(pos);
+            check.path_base = seq.get(pos).map(|&b| char::from(b));
+            check.fasta_base = record.sequence.get(pos).map(|&b| char::from(b));
+        } else {
+            check.status = "match";
+        }
+    }
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    if args.json.json {
+        write_json(&mut out, &checks)?;
+    } else {
+        writeln!(
+            out,
+            "path\tpath_length\tfasta_length\tstatus\t\
+             first_mismatch\tpath_base\tfasta_base"
+        )?;
+        for check in checks.iter() {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                check.path,
+                or_star(check.path_length),
+                or_star(check.fasta_length),
+                check.status,
+                or_star(check.first_mismatch),
+                or_star(check.path_base),
+                or_star(check.fasta_base)
+            )?;
+        }
+    }
+    out.flush()?;
+
+    let count = |status: &str| {
+        checks.iter().filter(|check| check.status == status).count()
+    };
+    let mismatches = count("mismatch");
+    info!(
+        "Checked {} paths: {} match, {} mismatch, {} missing",
+        paths.len(),
+        count("match"),
+        mismatches,
+        count("missing")
+    );
+    if mismatches > 0 {
+        warn!(
+            "{} paths don't spell the same sequence as their FASTA records",
+            mismatches
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+use gfa::{gfa::GFA, optfields::OptionalFields};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use crate::{
+    clean::{self, MaskAmbiguous},
+    subgraph,
+    util::write_gfa_bytes,
+};
+
+use super::{load_gfa, Result};
+
+/// Remove zero-length segments, duplicate links, and links, path
+/// steps, and containments that refer to missing segments.
+///
+/// Paths that step on missing segments are split into the fragments
+/// that remain. A summary of the removed records is printed to
+/// stderr.
+///
+/// With --mask-ambiguous, bases other than A, C, G, and T are also
+/// either replaced by N (hard), or split into segments of their own
+/// (split). With --renumber, the segments, including the pieces of
+/// split segments, are then renamed to integer IDs, as by id-convert
+/// --to-int.
+#[derive(StructOpt, Debug)]
+pub struct CleanArgs {
+    /// Write the cleaned GFA to this file instead of stdout.
+    #[structopt(
+        name = "GFA output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    /// Replace the bases other than A, C, G, T, and N with N (hard),
+    /// or split segments at the ends of their runs of bases other
+    /// than A, C, G, and T (split).
+    #[structopt(
+        name = "hard|split",
+        long = "mask-ambiguous",
+        possible_values = &MaskAmbiguous::variants(),
+        case_insensitive = true
+    )]
+    mask_ambiguous: Option<MaskAmbiguous>,
+    /// Rename the segments of the cleaned GFA to integer IDs, in
+    /// order, and write the name map to the --renumber-map file.
+    #[structopt(
+        name = "renumber",
+        long = "renumber",
+        requires = "renumber map"
+    )]
+    renumber: bool,
+    /// Where to write the name map of --renumber, in the JSON format
+    /// of id-convert, so that id-convert --namemap can restore the
+    /// names.
+    #[structopt(
+        name = "renumber map",
+        long = "renumber-map",
+        requires = "renumber",
+        parse(from_os_str)
+    )]
+    renumber_map: Option<PathBuf>,
+}
+
+pub fn clean(gfa_path: &PathBuf, args: &CleanArgs) -> Result<()> {
+    let gfa: GFA<Vec<u8>, OptionalFields> = load_gfa(gfa_path)?;
+
+    let (mut new_gfa, summary) = clean::clean_gfa(&gfa);
+
+    eprintln!(
+        "zero-length segments removed\t{}",
+        summary.zero_length_segments
+    );
+    eprintln!(
+        "links to missing segments removed\t{}",
+        summary.missing_segment_links
+    );
+    eprintln!("duplicate links removed\t{}", summary.duplicate_links);
+    eprintln!(
+        "containments of missing segments removed\t{}",
+        summary.missing_segment_containments
+    );
+    eprintln!(
+        "path steps on missing segments removed\t{}",
+        summary.missing_segment_steps
+    );
+    eprintln!("paths split into fragments\t{}", summary.split_paths);
+    eprintln!("paths removed\t{}", summary.removed_paths);
+
+    match args.mask_ambiguous {
+        Some(MaskAmbiguous::Hard) => {
+            let masked = clean::hard_mask_ambiguous(&mut new_gfa);
+            eprintln!("ambiguous bases masked\t{}", masked);
+        }
+        Some(MaskAmbiguous::Split) => {
+            let (split_gfa, split) = clean::split_ambiguous(&new_gfa)?;
+            eprintln!("segments split at ambiguous bases\t{}", split);
+            new_gfa = split_gfa;
+        }
+        None => (),
+    }
+
+    if let (true, Some(map_path)) = (args.renumber, &args.renumber_map) {
+        subgraph::renumber_segments(&mut new_gfa).save_json(map_path)?;
+        info!("Renumbered {} segments", new_gfa.segments.len());
+    }
+
+    if let Some(out_path) = &args.out {
+        let mut out_file = BufWriter::new(File::create(&out_path)?);
+        write_gfa_bytes(&new_gfa, &mut out_file)?;
+        out_file.flush()?;
+    } else {
+        let stdout = std::io::stdout();
+        let mut out = BufWriter::new(stdout.lock());
+        write_gfa_bytes(&new_gfa, &mut out)?;
+        out.flush()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,211 @@
+use bstr::BString;
+use fnv::FnvHashSet;
+use rayon::prelude::*;
+use structopt::StructOpt;
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::gfa::GFA;
+
+use crate::{
+    util::{bytes_from_os_str, NodeSet},
+    variants::{
+        self,
+        compare::{self, Allele},
+        vcf::VCFRecord,
+        VariantConfig,
+    },
+};
+
+use super::{load_numeric_gfa, Result};
+
+/// Call variants against a shared reference path in two graphs, and
+/// compare the resulting alleles.
+///
+/// Each allele is reported as shared, unique to one of the graphs,
+/// or as a representation difference, if it's unique to one graph
+/// but overlaps an allele that's unique to the other.
+#[derive(StructOpt, Debug)]
+pub struct CompareArgs {
+    /// The second graph, which is compared to the input GFA.
+    #[structopt(name = "other GFA", long = "other", parse(from_os_str))]
+    other_gfa: PathBuf,
+    /// The reference paths to call variants against. They must exist
+    /// in both graphs.
+    #[structopt(
+        name = "reference paths",
+        long = "refs",
+        required = true,
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    ref_paths: Vec<BString>,
+    /// Load the ultrabubbles of the input GFA from a file.
+    #[structopt(
+        name = "ultrabubbles file",
+        long = "ultrabubbles",
+        short = "u",
+        parse(from_os_str)
+    )]
+    ultrabubbles_file: Option<PathBuf>,
+    /// Load the ultrabubbles of the other GFA from a file.
+    #[structopt(
+        name = "other ultrabubbles file",
+        long = "other-ultrabubbles",
+        parse(from_os_str)
+    )]
+    other_ultrabubbles_file: Option<PathBuf>,
+    /// Split equal-length MNPs into SNVs in both graphs, which makes
+    /// graphs with different node boundaries easier to compare.
+    #[structopt(name = "decompose MNPs", long = "decompose-mnp")]
+    decompose_mnp: bool,
+    #[structopt(name = "TSV output file", short = "o", long = "out")]
+    out: Option<PathBuf>,
+}
+
+fn graph_vcf_records(
+    gfa_path: &PathBuf,
+    ultrabubbles_file: Option<&PathBuf>,
+    ref_paths: &FnvHashSet<BString>,
+    var_config: &VariantConfig,
+) -> Result<Vec<VCFRecord>> {
+    let path_data = {
+        let gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+
+        for path in ref_paths.iter() {
+            if !gfa.paths.iter().any(|p| p.path_name == *path) {
+                return Err(format!(
+                    "Reference path {} does not exist in {}",
+                    path,
+                    gfa_path.display()
+                )
+                .into());
+            }
+        }
+
+        variants::gfa_path_data(gfa)
+    };
+
+    let ultrabubbles = if let Some(path) = ultrabubbles_file {
+        super::saboten::load_ultrabubbles(path)
+    } else {
+        super::saboten::find_ultrabubbles(gfa_path)
+    }?;
+
+    let ultrabubble_nodes = ultrabubbles
+        .iter()
+        .flat_map(|&(a, b)| {
+            use std::iter::once;
+            once(a).chain(once(b))
+        })
+        .collect::<NodeSet>();
+
+    let path_indices =
+        variants::bubble_path_indices(&path_data.paths, &ultrabubble_nodes);
+
+    info!(
+        "Identifying variants in {} ultrabubbles of {}",
+        ultrabubbles.len(),
+        gfa_path.display()
+    );
+
+    let records = ultrabubbles
+        .par_iter()
+        .filter_map(|&(from, to)| {
+            let vars = variants::detect_variants_in_sub_paths(
+                var_config,
+                &path_data,
+                Some(ref_paths),
+                &path_indices,
+                from,
+                to,
+            )?;
+            let bubble_paths =
+                variants::bubble_path_count(&path_indices, from, to);
+            Some(variants::variant_vcf_record(
+                var_config,
+                bubble_paths,
+                &vars,
+                None,
+            ))
+        })
+        .flatten()
+        .collect();
+
+    Ok(records)
+}
+
+fn write_alleles<W: Write>(
+    out: &mut W,
+    status: &str,
+    alleles: &[Allele],
+) -> Result<()> {
+    for allele in alleles {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}",
+            status,
+            allele.chromosome,
+            allele.position,
+            allele.reference,
+            allele.alternate
+        )?;
+    }
+    Ok(())
+}
+
+pub fn compare_variants(gfa_path: &PathBuf, args: &CompareArgs) -> Result<()> {
+    let ref_paths: FnvHashSet<BString> =
+        args.ref_paths.iter().cloned().collect();
+
+    let var_config = VariantConfig {
+        decompose_mnp: args.decompose_mnp,
+        ..VariantConfig::default()
+    };
+
+    let records_a = graph_vcf_records(
+        gfa_path,
+        args.ultrabubbles_file.as_ref(),
+        &ref_paths,
+        &var_config,
+    )?;
+    let records_b = graph_vcf_records(
+        &args.other_gfa,
+        args.other_ultrabubbles_file.as_ref(),
+        &ref_paths,
+        &var_config,
+    )?;
+
+    let comparison = compare::compare_alleles(
+        &compare::record_alleles(records_a.iter()),
+        &compare::record_alleles(records_b.iter()),
+    );
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    writeln!(out, "status\tchrom\tpos\tref\talt")?;
+    write_alleles(&mut out, "shared", &comparison.shared)?;
+    write_alleles(&mut out, "only_a", &comparison.only_a)?;
+    write_alleles(&mut out, "only_b", &comparison.only_b)?;
+    write_alleles(&mut out, "repr_a", &comparison.repr_a)?;
+    write_alleles(&mut out, "repr_b", &comparison.repr_b)?;
+    out.flush()?;
+
+    eprintln!("shared\t{}", comparison.shared.len());
+    eprintln!("only_a\t{}", comparison.only_a.len());
+    eprintln!("only_b\t{}", comparison.only_b.len());
+    eprintln!("repr_a\t{}", comparison.repr_a.len());
+    eprintln!("repr_b\t{}", comparison.repr_b.len());
+
+    Ok(())
+}
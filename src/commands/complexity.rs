@@ -0,0 +1,141 @@
+use bstr::BString;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::gfa::GFA;
+
+use handlegraph::hashgraph::HashGraph;
+
+use crate::{complexity, edges, util::bytes_from_os_str, variants};
+
+use super::{load_numeric_gfa, Result};
+
+/// Slide a window along a reference path and report the complexity
+/// of the graph in each window, as BED.
+///
+/// Each line has the reference path, the window's start and end, the
+/// number of ultrabubbles inside the window, the number of reference
+/// segments in it with more than one inbound or outbound link, and the
+/// total length of the non-reference segments whose paths leave the
+/// reference in the window. Each non-reference segment is only counted
+/// once, even if many paths visit it.
+#[derive(StructOpt, Debug)]
+pub struct ComplexityArgs {
+    /// The name of the path to slide the window along.
+    #[structopt(
+        name = "reference path",
+        long = "ref",
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    ref_path: BString,
+    /// The size of each window, in bp.
+    #[structopt(
+        name = "window size",
+        long = "window",
+        default_value = "10000"
+    )]
+    window: usize,
+    /// The distance between the starts of consecutive windows, in bp.
+    /// Defaults to the window size, i.e. non-overlapping windows.
+    #[structopt(name = "step size", long = "step")]
+    step: Option<usize>,
+    /// Load ultrabubbles from a file instead of calculating them.
+    #[structopt(
+        name = "ultrabubbles file",
+        long = "ultrabubbles",
+        parse(from_os_str)
+    )]
+    ultrabubbles_file: Option<PathBuf>,
+    /// Write the BED to this file instead of stdout.
+    #[structopt(
+        name = "BED output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+}
+
+pub fn complexity(gfa_path: &PathBuf, args: &ComplexityArgs) -> Result<()> {
+    let step = args.step.unwrap_or(args.window);
+    if args.window == 0 || step == 0 {
+        return Err("The window and step sizes must be greater than 0".into());
+    }
+
+    let ultrabubbles = if let Some(path) = &args.ultrabubbles_file {
+        super::saboten::load_ultrabubbles(path)
+    } else {
+        super::saboten::find_ultrabubbles(gfa_path)
+    }?;
+    info!("Using {} ultrabubbles", ultrabubbles.len());
+
+    let gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+
+    let branching = {
+        let hashgraph = HashGraph::from_gfa(&gfa);
+        let edge_counts = edges::graph_edge_count(&hashgraph);
+        edges::branching_nodes(&edge_counts)
+    };
+
+    let path_data = variants::gfa_path_data(gfa);
+
+    let ref_ix = path_data
+        .path_names
+        .iter()
+        .position(|name| *name == args.ref_path)
+        .ok_or_else(|| {
+            format!("Reference path does not exist in graph: {}", args.ref_path)
+        })?;
+
+    let track = complexity::complexity_track(
+        &path_data,
+        ref_ix,
+        &ultrabubbles,
+        &branching,
+        args.window,
+        step,
+    );
+
+    if track.unplaced_bubbles > 0 {
+        warn!(
+            "{} ultrabubbles don't have both ends on {}, and were skipped",
+            track.unplaced_bubbles, args.ref_path
+        );
+    }
+    if track.unplaced_segments > 0 {
+        warn!(
+            "{} non-reference segments are on paths that never reach {}, \
+             and were skipped",
+            track.unplaced_segments, args.ref_path
+        );
+    }
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    for window in track.windows.iter() {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            args.ref_path,
+            window.start,
+            window.end,
+            window.bubbles,
+            window.branching_nodes,
+            window.nonref_bp
+        )?;
+    }
+    out.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use std::{fs, path::PathBuf};
+use structopt::StructOpt;
+
+use bstr::ByteSlice;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::{gfa::GFA, optfields::OptionalFields};
+
+use crate::{
+    components,
+    compression::{self, Compression},
+    output_pool::MANIFEST_NAME,
+    util::write_gfa_bytes,
+};
+
+use super::{load_gfa, subgraph::output_extension, Result};
+
+/// Split a GFA into its weakly connected components, each written to
+/// its own GFA file.
+///
+/// The components are written to component_1.gfa, component_2.gfa,
+/// etc. in the output directory, from the largest to the smallest by
+/// total sequence length, and listed in a manifest.tsv with their
+/// numbers of segments, links, and paths, their length, and the name
+/// of their first path. The files only get their final names, and the
+/// manifest is written, once every component has been written. Paths
+/// that step between components, which only happens if links are
+/// missing, are split into the fragments in each component.
+#[derive(StructOpt, Debug)]
+pub struct ComponentsArgs {
+    /// The directory to write the component GFA files to.
+    #[structopt(
+        name = "output directory",
+        short = "o",
+        long = "out-dir",
+        parse(from_os_str)
+    )]
+    out_dir: PathBuf,
+    /// Compress the output with gzip or zstd. Zstd requires `zstd` in
+    /// PATH.
+    #[structopt(
+        name = "none|gzip|zstd",
+        long = "compress",
+        default_value = "none",
+        possible_values = &Compression::variants(),
+        case_insensitive = true
+    )]
+    compress: Compression,
+}
+
+pub fn components(gfa_path: &PathBuf, args: &ComponentsArgs) -> Result<()> {
+    let gfa: GFA<Vec<u8>, OptionalFields> = load_gfa(gfa_path)?;
+
+    let graphs = components::split_components(&gfa);
+    info!("Found {} components", graphs.len());
+
+    fs::create_dir_all(&args.out_dir)?;
+    let extension = output_extension(args.compress);
+
+    // A manifest left by an earlier run would list files that are
+    // about to be replaced
+    let manifest_path = args.out_dir.join(MANIFEST_NAME);
+    if manifest_path.exists() {
+        fs::remove_file(&manifest_path)?;
+    }
+
+    // The files are written with a .tmp suffix, and only renamed once
+    // every component has been written, like `OutputPool` does, so a
+    // failed run leaves no manifest and no complete-looking files
+    let mut manifest =
+        String::from("file\tsegments\tlinks\tpaths\tbp\tfirst_path\n");
+    let mut tmp_paths = Vec::with_capacity(graphs.len());
+
+    for (ix, graph) in graphs.iter().enumerate() {
+        let file_name = format!("component_{}.{}", ix + 1, extension);
+        let tmp_path = args.out_dir.join(format!("{}.tmp", file_name));
+        let mut out =
+            compression::create_output(Some(&tmp_path), args.compress)?;
+        write_gfa_bytes(graph, &mut out)?;
+        out.finish()?;
+        tmp_paths.push((tmp_path, args.out_dir.join(&file_name)));
+
+        let first_path = components::first_path_name(graph)
+            .map(|name| name.to_str_lossy().into_owned())
+            .unwrap_or_else(|| "*".to_string());
+        manifest.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\n",
+            file_name,
+            graph.segments.len(),
+            graph.links.len(),
+            graph.paths.len(),
+            components::total_length(graph),
+            first_path
+        ));
+    }
+
+    for (tmp_path, path) in tmp_paths {
+        fs::rename(tmp_path, path)?;
+    }
+
+    let manifest_tmp = args.out_dir.join(format!("{}.tmp", MANIFEST_NAME));
+    fs::write(&manifest_tmp, manifest)?;
+    fs::rename(manifest_tmp, &manifest_path)?;
+    info!("Wrote the manifest to {}", manifest_path.display());
+
+    Ok(())
+}
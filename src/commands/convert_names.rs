@@ -1,14 +1,25 @@
 use structopt::StructOpt;
 
-use std::{fs::File, io::Write, path::PathBuf};
+use fnv::FnvHashMap;
+
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
 
 use gfa::{
     gfa::{name_conversion::NameMap, GFA},
     optfields::OptionalFields,
-    writer::write_gfa,
 };
 
-use super::{load_gfa, Result};
+use crate::{
+    provenance::{self, Provenance},
+    util::{add_program_tag, program_record, write_gfa_bytes},
+};
+
+use super::{load_gfa, load_numeric_gfa, Result};
 
 #[derive(StructOpt, Debug)]
 /// Convert a GFA with string names to one with integer names, and
@@ -30,30 +41,60 @@ pub struct GfaIdConvertArgs {
 
     #[structopt(name = "check result hash", long = "hash")]
     check_hash: bool,
+
+    /// When converting to integer names, record the original name of
+    /// each segment in an `OI:Z:<name>:0` tag, so that node-map and
+    /// other commands can report coordinates on the original graph.
+    #[structopt(name = "record provenance", long = "provenance")]
+    provenance: bool,
 }
 
-fn gfa_to_name_map_path(path: &PathBuf) -> PathBuf {
+/// Replace the extension of the file name with `suffix`, without
+/// requiring the file name to be valid UTF-8
+fn with_stem_suffix(path: &PathBuf, suffix: &str) -> PathBuf {
+    let mut new_name: OsString = path
+        .file_stem()
+        .map(|s| s.to_os_string())
+        .unwrap_or_default();
+    new_name.push(suffix);
     let mut new_path: PathBuf = path.clone();
-    let old_name = new_path.file_stem().and_then(|p| p.to_str()).unwrap();
-    let new_name = format!("{}.name_map.json", old_name);
     new_path.set_file_name(&new_name);
     new_path
 }
 
+fn gfa_to_name_map_path(path: &PathBuf) -> PathBuf {
+    with_stem_suffix(path, ".name_map.json")
+}
+
 fn converted_gfa_path(path: &PathBuf) -> PathBuf {
-    let mut new_path: PathBuf = path.clone();
-    let old_name = new_path.file_stem().and_then(|p| p.to_str()).unwrap();
-    let new_name = format!("{}.uint_ids.gfa", old_name);
-    new_path.set_file_name(&new_name);
-    new_path
+    with_stem_suffix(path, ".uint_ids.gfa")
 }
 
 fn restored_gfa_path(path: &PathBuf) -> PathBuf {
-    let mut new_path: PathBuf = path.clone();
-    let old_name = new_path.file_stem().and_then(|p| p.to_str()).unwrap();
-    let new_name = format!("{}.str_ids.gfa", old_name);
-    new_path.set_file_name(&new_name);
-    new_path
+    with_stem_suffix(path, ".str_ids.gfa")
+}
+
+fn add_provenance_tags(
+    name_map: &NameMap,
+    gfa: &GFA<Vec<u8>, OptionalFields>,
+    new_gfa: &mut GFA<usize, OptionalFields>,
+) {
+    let orig_names: FnvHashMap<usize, &[u8]> = gfa
+        .segments
+        .iter()
+        .filter_map(|seg| {
+            let id = name_map.map_name(&seg.name)?;
+            Some((id, seg.name.as_slice()))
+        })
+        .collect();
+
+    for seg in new_gfa.segments.iter_mut() {
+        if let Some(name) = orig_names.get(&seg.name) {
+            seg.optional
+                .retain(|field| &field.tag != provenance::PROVENANCE_TAG);
+            seg.optional.push(Provenance::new(*name, 0).to_opt_field());
+        }
+    }
 }
 
 fn segment_id_to_usize(
@@ -67,14 +108,23 @@ fn segment_id_to_usize(
         NameMap::build_from_gfa(gfa)
     };
 
-    if let Some(new_gfa) =
+    if let Some(mut new_gfa) =
         name_map.gfa_bytestring_to_usize(&gfa, args.check_hash)
     {
+        if args.provenance {
+            add_provenance_tags(&name_map, gfa, &mut new_gfa);
+        }
+
+        add_program_tag(
+            &mut new_gfa.header,
+            &program_record(std::env::args_os()),
+        );
+
         let new_gfa_path = converted_gfa_path(&gfa_path);
-        let mut new_gfa_file = File::create(new_gfa_path.clone())?;
-        let mut gfa_str = String::new();
-        write_gfa(&new_gfa, &mut gfa_str);
-        writeln!(new_gfa_file, "{}", gfa_str)?;
+        let mut new_gfa_file =
+            BufWriter::new(File::create(new_gfa_path.clone())?);
+        write_gfa_bytes(&new_gfa, &mut new_gfa_file)?;
+        new_gfa_file.flush()?;
         println!("Saved converted GFA to {}", new_gfa_path.display());
 
         if args.name_map_path.is_none() {
@@ -100,15 +150,16 @@ fn segment_id_to_bstring(
         .expect("Need name map to convert back");
     let name_map = NameMap::load_json(&name_map_path)?;
 
-    let new_gfa: GFA<Vec<u8>, OptionalFields> = name_map
+    let mut new_gfa: GFA<Vec<u8>, OptionalFields> = name_map
         .gfa_usize_to_bytestring(&gfa)
         .expect("Error during conversion -- is it the right name map?");
 
+    add_program_tag(&mut new_gfa.header, &program_record(std::env::args_os()));
+
     let new_gfa_path = restored_gfa_path(gfa_path);
-    let mut new_gfa_file = File::create(new_gfa_path.clone())?;
-    let mut gfa_str = String::new();
-    write_gfa(&new_gfa, &mut gfa_str);
-    writeln!(new_gfa_file, "{}", gfa_str)?;
+    let mut new_gfa_file = BufWriter::new(File::create(new_gfa_path.clone())?);
+    write_gfa_bytes(&new_gfa, &mut new_gfa_file)?;
+    new_gfa_file.flush()?;
     println!("Saved restored GFA to {}", new_gfa_path.display());
 
     Ok(())
@@ -127,7 +178,7 @@ pub fn convert_segment_ids(
         segment_id_to_usize(&gfa_path, &gfa, args)
     } else {
         // Converting from integer to string names
-        let gfa: GFA<usize, OptionalFields> = load_gfa(&gfa_path)?;
+        let gfa: GFA<usize, OptionalFields> = load_numeric_gfa(&gfa_path)?;
         segment_id_to_bstring(&gfa_path, &gfa, args)
     }
 }
@@ -153,4 +204,17 @@ mod tests {
         let new_path = converted_gfa_path(&gfa_path);
         assert_eq!(Some("some_gfa_file.uint_ids.gfa"), new_path.to_str());
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_file_stems() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let gfa_path = PathBuf::from(OsStr::from_bytes(b"dir/gr\xffph.gfa"));
+        let new_path = restored_gfa_path(&gfa_path);
+        assert_eq!(
+            new_path.as_os_str().as_bytes(),
+            &b"dir/gr\xffph.str_ids.gfa"[..]
+        );
+    }
 }
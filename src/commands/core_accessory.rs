@@ -0,0 +1,168 @@
+use bstr::{BString, ByteSlice};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::gfa::GFA;
+
+use crate::{
+    node_coverage::{self, SegmentClass},
+    util::bytes_from_os_str,
+};
+
+use super::{load_gfa, Result};
+
+/// Classify the segments as core, shell, or cloud, by the number of
+/// samples whose paths visit them.
+///
+/// Samples are taken from PanSN path names, sample#haplotype#contig,
+/// as in node-coverage. The output is a TSV with the number of
+/// segments and bases in each class, including the segments that no
+/// path visits. The class of each segment, and the classes along a
+/// reference path as BED, can also be written to files.
+#[derive(StructOpt, Debug)]
+pub struct CoreAccessoryArgs {
+    /// Write the class totals to this file instead of stdout.
+    #[structopt(
+        name = "output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    /// The fraction of the samples a segment must be in to be core.
+    #[structopt(
+        name = "core fraction",
+        long = "core-fraction",
+        default_value = "1.0"
+    )]
+    core_fraction: f64,
+    /// Segments in at most this many samples are cloud, and those
+    /// between cloud and core are shell.
+    #[structopt(
+        name = "cloud max samples",
+        long = "cloud-max-samples",
+        default_value = "1"
+    )]
+    cloud_max: usize,
+    /// Write the name, number of samples, and class of each segment
+    /// to this file, as TSV.
+    #[structopt(
+        name = "segment classes file",
+        long = "segments",
+        parse(from_os_str)
+    )]
+    segments: Option<PathBuf>,
+    /// Write the classes along the reference path given by --ref to
+    /// this file, as BED, with the class as the name.
+    #[structopt(
+        name = "BED output file",
+        long = "bed",
+        requires = "reference path",
+        parse(from_os_str)
+    )]
+    bed: Option<PathBuf>,
+    /// The name of the path to project the classes onto.
+    #[structopt(
+        name = "reference path",
+        long = "ref",
+        requires = "BED output file",
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    ref_path: Option<BString>,
+}
+
+fn create(path: &PathBuf) -> Result<BufWriter<File>> {
+    Ok(BufWriter::new(File::create(path)?))
+}
+
+pub fn core_accessory(
+    gfa_path: &PathBuf,
+    args: &CoreAccessoryArgs,
+) -> Result<()> {
+    if !(args.core_fraction > 0.0 && args.core_fraction <= 1.0) {
+        return Err("The core fraction must be in (0, 1]".into());
+    }
+
+    let gfa: GFA<Vec<u8>, ()> = load_gfa(gfa_path)?;
+
+    let ref_path = match &args.ref_path {
+        Some(name) => Some(
+            gfa.paths
+                .iter()
+                .find(|path| path.path_name == name.as_slice())
+                .ok_or_else(|| {
+                    format!("Reference path does not exist in graph: {}", name)
+                })?,
+        ),
+        None => None,
+    };
+
+    let coverage = node_coverage::path_coverage(&gfa);
+    info!(
+        "Found {} samples in {} paths",
+        coverage.samples.len(),
+        gfa.paths.len()
+    );
+
+    let classes = coverage.segment_classes(args.core_fraction, args.cloud_max);
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(create(out_path)?)
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    writeln!(out, "class\tsegments\tbp")?;
+    for &class in SegmentClass::ALL.iter() {
+        let (segments, bp) = gfa
+            .segments
+            .iter()
+            .zip(classes.iter())
+            .filter(|(_, &seg_class)| seg_class == class)
+            .fold((0, 0), |(count, bp), (seg, _)| {
+                (count + 1, bp + seg.sequence.len())
+            });
+        writeln!(out, "{}\t{}\t{}", class, segments, bp)?;
+    }
+    out.flush()?;
+
+    if let Some(segments_path) = &args.segments {
+        let mut seg_out = create(segments_path)?;
+        writeln!(seg_out, "segment\tsamples\tclass")?;
+        for ((seg, seg_cov), class) in gfa
+            .segments
+            .iter()
+            .zip(coverage.segments.iter())
+            .zip(classes.iter())
+        {
+            seg_out.write_all(&seg.name)?;
+            writeln!(seg_out, "\t{}\t{}", seg_cov.samples.len(), class)?;
+        }
+        seg_out.flush()?;
+    }
+
+    if let (Some(bed_path), Some(path)) = (&args.bed, ref_path) {
+        let mut bed = create(bed_path)?;
+        let intervals = node_coverage::class_intervals(&gfa, path, &classes);
+        for (start, end, class) in intervals {
+            writeln!(
+                bed,
+                "{}\t{}\t{}\t{}",
+                path.path_name.as_bstr(),
+                start,
+                end,
+                class
+            )?;
+        }
+        bed.flush()?;
+    }
+
+    Ok(())
+}
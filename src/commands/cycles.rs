@@ -0,0 +1,118 @@
+use structopt::StructOpt;
+
+use std::{io::Write, path::PathBuf};
+
+use bstr::ByteSlice;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::gfa::{Link, GFA};
+
+use crate::cycles::{self, OrientedSegment};
+
+use super::{load_gfa, Result};
+
+/// Report whether the bidirected graph contains cycles.
+///
+/// The output is TSV, with the kind of each row in the first column:
+/// SN rows summarize whether the graph is acyclic, and the number of
+/// self-loops, reversing links, and segments in cycles. They're
+/// followed by a CC row for each weakly connected component with
+/// self-loops or reversing links, with its ID, first segment, number
+/// of segments, and the number of each kind of link in it, an SL row
+/// for each link from a segment to itself, an RL row for each link
+/// that joins strands of different orientations, e.g. A+ to B-, and a
+/// CY row with the length and oriented segments of each of the
+/// shortest cycles.
+///
+/// The ultrabubble and variant commands assume that the graph has no
+/// such cycles and reversals, at least within the bubbles.
+#[derive(StructOpt, Debug)]
+pub struct CyclesArgs {
+    /// The number of shortest cycles to list.
+    #[structopt(
+        name = "max cycles",
+        long = "max-cycles",
+        default_value = "10"
+    )]
+    max_cycles: usize,
+    /// Only look for cycles of at most this many oriented segments.
+    /// Longer cycles still count towards the segments in cycles.
+    #[structopt(
+        name = "max cycle length",
+        long = "max-cycle-length",
+        default_value = "20"
+    )]
+    max_cycle_length: usize,
+}
+
+fn oriented_name(
+    gfa: &GFA<Vec<u8>, ()>,
+    (seg, reverse): OrientedSegment,
+) -> String {
+    let orient = if reverse { '-' } else { '+' };
+    format!("{}{}", gfa.segments[seg].name.as_bstr(), orient)
+}
+
+fn link_row(kind: &str, link: &Link<Vec<u8>, ()>) -> String {
+    format!(
+        "{}\t{}{}\t{}{}",
+        kind,
+        link.from_segment.as_bstr(),
+        link.from_orient,
+        link.to_segment.as_bstr(),
+        link.to_orient
+    )
+}
+
+pub fn cycles(gfa_path: &PathBuf, args: &CyclesArgs) -> Result<()> {
+    let gfa: GFA<Vec<u8>, ()> = load_gfa(gfa_path)?;
+
+    let report =
+        cycles::find_cycles(&gfa, args.max_cycles, args.max_cycle_length);
+    let components = cycles::links_per_component(&gfa, &report);
+
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+
+    writeln!(out, "SN\tacyclic\t{}", report.is_acyclic())?;
+    writeln!(out, "SN\tself_loops\t{}", report.self_loops.len())?;
+    writeln!(out, "SN\treversing_links\t{}", report.reversals.len())?;
+    writeln!(out, "SN\tcyclic_segments\t{}", report.cyclic_segments)?;
+    writeln!(out, "SN\taffected_components\t{}", components.len())?;
+
+    for comp in components.iter() {
+        writeln!(
+            out,
+            "CC\t{}\t{}\t{}\t{}\t{}",
+            comp.component,
+            gfa.segments[comp.first_segment].name.as_bstr(),
+            comp.segments,
+            comp.self_loops,
+            comp.reversals
+        )?;
+    }
+
+    for &ix in report.self_loops.iter() {
+        writeln!(out, "{}", link_row("SL", &gfa.links[ix]))?;
+    }
+    for &ix in report.reversals.iter() {
+        writeln!(out, "{}", link_row("RL", &gfa.links[ix]))?;
+    }
+    for cycle in report.cycles.iter() {
+        let names: Vec<String> =
+            cycle.iter().map(|&seg| oriented_name(&gfa, seg)).collect();
+        writeln!(out, "CY\t{}\t{}", cycle.len(), names.join(","))?;
+    }
+
+    if !report.is_acyclic() {
+        warn!(
+            "Found {} segments in cycles; bubble detection may not work \
+             on those regions",
+            report.cyclic_segments
+        );
+    }
+
+    Ok(())
+}
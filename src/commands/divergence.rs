@@ -0,0 +1,161 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::gfa::GFA;
+
+use crate::{
+    util::NodeSet,
+    variants::{self, PairDifferences},
+};
+
+use super::{load_numeric_gfa, Result};
+
+/// Estimate the divergence between every pair of paths, from the
+/// differences between them in the graph's ultrabubbles.
+///
+/// In each bubble both paths traverse, the mismatching bases between
+/// nodes of equal length are counted as SNVs, the nodes only one of
+/// the paths visits as indels, and mismatching nodes of different
+/// lengths as complex differences. The divergence is the total number
+/// of differences per Mb of the mean length of the two paths.
+///
+/// The output is a TSV with one line per pair of paths that share a
+/// bubble, or, with --matrix, a square matrix of the divergences,
+/// with * for the pairs that don't share any bubble.
+#[derive(StructOpt, Debug)]
+pub struct DivergenceArgs {
+    /// Load ultrabubbles from a file instead of calculating them.
+    #[structopt(
+        name = "ultrabubbles file",
+        long = "ultrabubbles",
+        parse(from_os_str)
+    )]
+    ultrabubbles_file: Option<PathBuf>,
+    /// Output a matrix of divergences, with the path names as the
+    /// first row and column.
+    #[structopt(name = "matrix", long = "matrix")]
+    matrix: bool,
+    /// Write the output to this file instead of stdout.
+    #[structopt(
+        name = "output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+}
+
+/// The differences per Mb of the mean length of the two paths
+fn divergence_per_mb(
+    differences: &PairDifferences,
+    lengths: (usize, usize),
+) -> f64 {
+    let mean_length = (lengths.0 + lengths.1) as f64 / 2.0;
+    if mean_length == 0.0 {
+        0.0
+    } else {
+        differences.total() as f64 * 1_000_000.0 / mean_length
+    }
+}
+
+pub fn divergence(gfa_path: &PathBuf, args: &DivergenceArgs) -> Result<()> {
+    let mut ultrabubbles = if let Some(path) = &args.ultrabubbles_file {
+        super::saboten::load_ultrabubbles(path)
+    } else {
+        super::saboten::find_ultrabubbles(gfa_path)
+    }?;
+    ultrabubbles.sort();
+    info!("Using {} ultrabubbles", ultrabubbles.len());
+
+    let gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+    let path_data = variants::gfa_path_data(gfa);
+
+    let ultrabubble_nodes = ultrabubbles
+        .iter()
+        .flat_map(|&(a, b)| {
+            use std::iter::once;
+            once(a).chain(once(b))
+        })
+        .collect::<NodeSet>();
+
+    let path_indices =
+        variants::bubble_path_indices(&path_data.paths, &ultrabubble_nodes);
+
+    info!(
+        "Comparing {} paths in {} ultrabubbles",
+        path_data.paths.len(),
+        ultrabubbles.len()
+    );
+    let pairs = variants::pairwise_differences(
+        &path_data,
+        &path_indices,
+        &ultrabubbles,
+    );
+
+    let lengths: Vec<usize> = (0..path_data.paths.len())
+        .map(|ix| path_data.path_length(ix))
+        .collect();
+    let names = &path_data.path_names;
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    if args.matrix {
+        write!(out, "path")?;
+        for name in names.iter() {
+            write!(out, "\t{}", name)?;
+        }
+        writeln!(out)?;
+
+        for (a, name) in names.iter().enumerate() {
+            write!(out, "{}", name)?;
+            for (b, &b_length) in lengths.iter().enumerate() {
+                let pair = (a.min(b), a.max(b));
+                if a == b {
+                    write!(out, "\t{:.2}", 0.0)?;
+                } else if let Some(differences) = pairs.get(&pair) {
+                    let divergence =
+                        divergence_per_mb(differences, (lengths[a], b_length));
+                    write!(out, "\t{:.2}", divergence)?;
+                } else {
+                    write!(out, "\t*")?;
+                }
+            }
+            writeln!(out)?;
+        }
+    } else {
+        let mut pairs: Vec<_> = pairs.into_iter().collect();
+        pairs.sort_by_key(|&(pair, _)| pair);
+
+        writeln!(
+            out,
+            "path_a\tpath_b\tbubbles\tsnvs\tindels\tcomplex\tdivergence_per_mb"
+        )?;
+        for ((a, b), differences) in pairs {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{:.2}",
+                names[a],
+                names[b],
+                differences.bubbles,
+                differences.snvs,
+                differences.indels,
+                differences.complex,
+                divergence_per_mb(&differences, (lengths[a], lengths[b]))
+            )?;
+        }
+    }
+    out.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use bstr::ByteSlice;
+use clap::arg_enum;
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::gfa::GFA;
+
+use crate::edges;
+
+use super::{load_gfa, write_json, JsonOpt, Result};
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum EdgeCountFormat {
+        Csv,
+        Tsv,
+        Json,
+    }
+}
+
+/// Report the number of inbound and outbound links of each segment.
+///
+/// Links attached to the start of a segment's forward strand are
+/// inbound, and those attached to its end are outbound, so a link
+/// from A- counts as an inbound link of A. Segments are listed in the
+/// order of the GFA, with their names as they are.
+#[derive(StructOpt, Debug)]
+pub struct EdgeCountArgs {
+    /// Write the counts to this file instead of stdout.
+    #[structopt(
+        name = "output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    /// Output CSV or TSV with a header line, or a JSON array with one
+    /// object per segment, as with --json. Defaults to CSV.
+    #[structopt(
+        name = "csv|tsv|json",
+        long = "format",
+        conflicts_with = "json",
+        possible_values = &EdgeCountFormat::variants(),
+        case_insensitive = true
+    )]
+    format: Option<EdgeCountFormat>,
+    #[structopt(flatten)]
+    json: JsonOpt,
+}
+
+#[derive(Serialize)]
+struct SegmentEdgeCount {
+    nodeid: String,
+    inbound: usize,
+    outbound: usize,
+    total: usize,
+}
+
+pub fn edge_count(gfa_path: &PathBuf, args: &EdgeCountArgs) -> Result<()> {
+    let gfa: GFA<Vec<u8>, ()> = load_gfa(gfa_path)?;
+    let edge_counts = edges::gfa_edge_count(&gfa);
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    let rows = gfa.segments.iter().zip(edge_counts.iter());
+
+    let format = if args.json.json {
+        EdgeCountFormat::Json
+    } else {
+        args.format.unwrap_or(EdgeCountFormat::Csv)
+    };
+
+    match format {
+        EdgeCountFormat::Csv | EdgeCountFormat::Tsv => {
+            let sep = if format == EdgeCountFormat::Csv {
+                ","
+            } else {
+                "\t"
+            };
+            writeln!(
+                out,
+                "{}",
+                ["nodeid", "inbound", "outbound", "total"].join(sep)
+            )?;
+            for (seg, &(i, o, t)) in rows {
+                out.write_all(&seg.name)?;
+                writeln!(out, "{}{}{}{}{}{}", sep, i, sep, o, sep, t)?;
+            }
+        }
+        EdgeCountFormat::Json => {
+            let counts: Vec<SegmentEdgeCount> = rows
+                .map(|(seg, &(inbound, outbound, total))| SegmentEdgeCount {
+                    nodeid: seg.name.to_str_lossy().into_owned(),
+                    inbound,
+                    outbound,
+                    total,
+                })
+                .collect();
+            write_json(&mut out, &counts)?;
+        }
+    }
+    out.flush()?;
+
+    Ok(())
+}
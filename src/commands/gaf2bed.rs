@@ -0,0 +1,115 @@
+use bstr::BString;
+use std::{io::Write, path::PathBuf};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::gfa::GFA;
+
+use crate::{
+    compression::{self, Compression},
+    gaf_convert, surject,
+    util::bytes_from_os_str,
+    variants,
+};
+
+use super::{load_numeric_gfa, Result};
+
+/// Project GAF alignments onto a reference path of the GFA, and
+/// output the reference intervals they cover as BED.
+///
+/// Each line has the reference path, start, end, read name, identity
+/// (residue matches divided by the block length), and the read's
+/// strand relative to the reference. An alignment that leaves the
+/// reference path, or skips part of it, is split into one interval
+/// per covered stretch. Records that don't touch the reference are
+/// skipped. The output is in the order of the GAF; pipe it through
+/// `sort -k1,1 -k2,2n` for tools that need sorted BED. The GAF may
+/// be gzip or zstd compressed.
+#[derive(StructOpt, Debug)]
+pub struct GAF2BEDArgs {
+    #[structopt(name = "path to GAF file", long = "gaf", parse(from_os_str))]
+    gaf: PathBuf,
+    /// The name of the path to project the alignments onto.
+    #[structopt(
+        name = "reference path",
+        long = "ref",
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    ref_path: BString,
+    /// Write the output to this file instead of stdout.
+    #[structopt(
+        name = "BED output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    /// Compress the BED output with gzip or zstd. Zstd requires
+    /// `zstd` in PATH.
+    #[structopt(
+        name = "none|gzip|zstd",
+        long = "compress",
+        default_value = "none",
+        possible_values = &Compression::variants(),
+        case_insensitive = true
+    )]
+    compress: Compression,
+}
+
+pub fn gaf2bed(gfa_path: &PathBuf, args: &GAF2BEDArgs) -> Result<()> {
+    let gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+    let path_data = variants::gfa_path_data(gfa);
+
+    let ref_ix = path_data
+        .path_names
+        .iter()
+        .position(|name| *name == args.ref_path)
+        .ok_or_else(|| {
+            format!("Reference path does not exist in graph: {}", args.ref_path)
+        })?;
+
+    let index = surject::PathPositionIndex::new(&path_data, ref_ix);
+    if index.repeated_nodes > 0 {
+        warn!(
+            "{} steps on {} revisit a node, only the first visit of each \
+             node is used",
+            index.repeated_nodes, index.name
+        );
+    }
+
+    let mut out = compression::create_output(args.out.as_ref(), args.compress)?;
+
+    let gaf_file = compression::open_input(&args.gaf)?;
+    let mut projected = 0;
+    let mut skipped = 0;
+    let mut intervals = 0;
+
+    for gaf in gaf_convert::gaf_records(gaf_file) {
+        let gaf = gaf?;
+        let bed = surject::project_gaf_intervals(
+            &index,
+            &path_data.segment_map,
+            &gaf,
+        );
+        if bed.is_empty() {
+            skipped += 1;
+            continue;
+        }
+        projected += 1;
+        intervals += bed.len();
+        for interval in bed {
+            writeln!(out, "{}", interval)?;
+        }
+    }
+
+    out.finish()?;
+
+    info!(
+        "Projected {} alignments onto {} as {} intervals, {} skipped",
+        projected, index.name, intervals, skipped
+    );
+
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use bstr::{BString, ByteSlice};
+use fnv::{FnvHashMap, FnvHashSet};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::{gfa::GFA, optfields::OptionalFields};
+
+use crate::{compression, gaf_convert, util::bytes_from_os_str};
+
+use super::{gfa2vcf::load_paths_file, load_gfa, Result};
+
+/// Add GAF alignments to the GFA as paths, so that e.g. aligned reads
+/// or assemblies can be used as paths by gfa2vcf.
+///
+/// The input GFA is written unchanged, followed by one P-line, or
+/// W-line with --walks, per selected GAF record, following the
+/// segments in the record's path. Paths are named after the query;
+/// when a query has several alignments, the second and later ones
+/// get a _2, _3, etc. suffix. Records whose names clash with paths
+/// already in the GFA are skipped. The GAF may be gzip or zstd
+/// compressed.
+#[derive(StructOpt, Debug)]
+pub struct GAF2GFAPathsArgs {
+    #[structopt(name = "path to GAF file", long = "gaf", parse(from_os_str))]
+    gaf: PathBuf,
+    /// Only add the alignments of these queries.
+    #[structopt(
+        name = "query names",
+        long = "reads",
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    reads: Vec<BString>,
+    /// Only add the alignments of the queries listed in this file,
+    /// one per line.
+    #[structopt(
+        name = "query names file",
+        long = "reads-file",
+        parse(from_os_str)
+    )]
+    reads_file: Option<PathBuf>,
+    /// Skip alignments with a lower identity, i.e. residue matches
+    /// divided by block length.
+    #[structopt(
+        name = "minimum identity",
+        long = "min-identity",
+        default_value = "0"
+    )]
+    min_identity: f64,
+    /// Only add alignments that cover the whole query.
+    #[structopt(name = "full length only", long = "full-length")]
+    full_length: bool,
+    /// Write W-lines instead of P-lines. W-lines are part of GFA 1.1.
+    #[structopt(name = "walks", long = "walks")]
+    walks: bool,
+    /// Write the GFA to this file instead of stdout.
+    #[structopt(
+        name = "GFA output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+}
+
+pub fn gaf2gfa_paths(
+    gfa_path: &PathBuf,
+    args: &GAF2GFAPathsArgs,
+) -> Result<()> {
+    let mut selected: FnvHashSet<BString> =
+        args.reads.iter().cloned().collect();
+    if let Some(reads_file) = &args.reads_file {
+        selected.extend(load_paths_file(reads_file.clone())?);
+    }
+
+    let gfa: GFA<Vec<u8>, OptionalFields> = load_gfa(gfa_path)?;
+    let mut path_names: FnvHashSet<BString> =
+        gfa.paths.iter().map(|p| p.path_name.clone()).collect();
+    let index = gaf_convert::SegmentIndex::new(&gfa);
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    let gfa_bytes = std::fs::read(gfa_path)?;
+    out.write_all(&gfa_bytes)?;
+    if !gfa_bytes.is_empty() && !gfa_bytes.ends_with(b"\n") {
+        out.write_all(b"\n")?;
+    }
+
+    let gaf_file = compression::open_input(&args.gaf)?;
+    let mut alignments: FnvHashMap<BString, usize> = FnvHashMap::default();
+    let mut added = 0;
+    let mut filtered = 0;
+    let mut skipped = 0;
+
+    for gaf in gaf_convert::gaf_records(gaf_file) {
+        let gaf = gaf?;
+        if !selected.is_empty() && !selected.contains(&gaf.seq_name) {
+            continue;
+        }
+
+        let identity = if gaf.block_length == 0 {
+            0.0
+        } else {
+            gaf.residue_matches as f64 / gaf.block_length as f64
+        };
+        let is_full_length = gaf.seq_range == (0, gaf.seq_len);
+        if identity < args.min_identity || (args.full_length && !is_full_length)
+        {
+            filtered += 1;
+            continue;
+        }
+
+        let count = alignments.entry(gaf.seq_name.clone()).or_default();
+        *count += 1;
+        let name: BString = if *count == 1 {
+            gaf.seq_name.clone()
+        } else {
+            format!("{}_{}", gaf.seq_name, count).into()
+        };
+
+        if !path_names.insert(name.clone()) {
+            warn!("Skipping {}, a path with that name already exists", name);
+            skipped += 1;
+            continue;
+        }
+
+        match gaf_convert::gaf_path_line(&index, &gaf, &name, args.walks) {
+            Ok(line) => {
+                out.write_all(&line)?;
+                out.write_all(b"\n")?;
+                added += 1;
+            }
+            Err(err) => {
+                warn!("Skipping {}: {}", name.as_bstr(), err);
+                skipped += 1;
+            }
+        }
+    }
+
+    out.flush()?;
+
+    info!(
+        "Added {} paths, {} alignments filtered, {} skipped",
+        added, filtered, skipped
+    );
+
+    Ok(())
+}
@@ -1,38 +1,208 @@
-use std::{fs::File, io::Write, path::PathBuf};
+use fnv::FnvHashSet;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
 use structopt::StructOpt;
 
-use gfa::{gfa::GFA, optfields::OptionalFields};
+#[allow(unused_imports)]
+use log::{debug, info, warn};
 
-use crate::gaf_convert;
+use gfa::{
+    gfa::GFA,
+    optfields::{OptFields, OptionalFields},
+};
+
+use crate::{
+    compression::{self, Compression},
+    gaf_convert::{self, GafConvertError},
+    gaf_reader::GafInput,
+};
 
 use super::{load_gfa, Result};
 
-/// Convert a file of GAF records into PAF records.
+/// Convert one or more files of GAF records into PAF records.
 ///
 /// The provided GFA file should be the same as the one used to create the GAF.
+/// The GAF may be gzip or zstd compressed.
+/// With several GAF files, the GFA is only loaded once, and the PAF
+/// records of all of them are written to one output, in the order the
+/// files were given, or with --out-dir, to one file per GAF.
+/// The GAF is streamed, so only the graph's segments are kept in memory.
+/// Lines are converted in parallel, in chunks, using the number of
+/// threads given by --threads, and written in their input order.
+/// Uncompressed GAF files are memory-mapped, and split into chunks
+/// of a few megabytes.
 #[derive(StructOpt, Debug)]
 pub struct GAF2PAFArgs {
-    #[structopt(name = "path to GAF file", long = "gaf", parse(from_os_str))]
-    gaf: PathBuf,
+    #[structopt(
+        name = "path to GAF file",
+        long = "gaf",
+        required = true,
+        parse(from_os_str)
+    )]
+    gafs: Vec<PathBuf>,
     #[structopt(name = "PAF output paf", short = "o", long = "paf")]
     out: Option<PathBuf>,
+    /// Write the PAF records of each GAF file to a separate file in
+    /// this directory, named after the GAF file, with the .gaf
+    /// extension replaced by .paf.
+    #[structopt(
+        name = "PAF output directory",
+        long = "out-dir",
+        parse(from_os_str),
+        conflicts_with = "PAF output paf"
+    )]
+    out_dir: Option<PathBuf>,
+    /// Fail on GAF records without a cg:Z: CIGAR, instead of
+    /// splitting their coordinates proportionally across the
+    /// segments in their path.
+    #[structopt(name = "require CIGAR", long = "require-cigar")]
+    require_cigar: bool,
+    /// Report and skip GAF lines that can't be parsed, instead of
+    /// failing on the first one.
+    #[structopt(name = "skip invalid lines", long = "skip-invalid")]
+    skip_invalid: bool,
+    /// The number of GAF lines to read and convert in parallel at a
+    /// time, when the GAF is compressed or read from a pipe.
+    #[structopt(
+        name = "lines per chunk",
+        long = "chunk-size",
+        default_value = "10000"
+    )]
+    chunk_size: usize,
+    /// Compress the output with gzip or zstd. Zstd requires `zstd`
+    /// in PATH.
+    #[structopt(
+        name = "none|gzip|zstd",
+        long = "compress",
+        default_value = "none",
+        possible_values = &Compression::variants(),
+        case_insensitive = true
+    )]
+    compress: Compression,
 }
 
 pub fn gaf2paf(gfa_path: &PathBuf, args: &GAF2PAFArgs) -> Result<()> {
     let gfa: GFA<Vec<u8>, OptionalFields> = load_gfa(gfa_path)?;
+    let index = gaf_convert::SegmentIndex::new(&gfa);
 
-    let paf_lines = gaf_convert::gaf_to_paf(gfa, &args.gaf);
+    let mut invalid_lines = 0;
 
-    if let Some(out_path) = &args.out {
-        let mut out_file =
-            File::create(&out_path).expect("Error creating PAF output file");
+    if let Some(out_dir) = &args.out_dir {
+        std::fs::create_dir_all(out_dir)?;
+        let out_paths = output_paths(&args.gafs, out_dir, args.compress)?;
 
-        paf_lines.iter().for_each(|p| {
-            writeln!(out_file, "{}", p).unwrap();
-        });
+        for (gaf_path, out_path) in args.gafs.iter().zip(out_paths) {
+            info!(
+                "Converting {} to {}",
+                gaf_path.display(),
+                out_path.display()
+            );
+            let mut out =
+                compression::create_output(Some(&out_path), args.compress)?;
+            convert_gaf(&index, gaf_path, &mut out, args, &mut invalid_lines)?;
+            out.finish()?;
+        }
     } else {
-        paf_lines.iter().for_each(|p| println!("{}", p));
+        let mut out =
+            compression::create_output(args.out.as_ref(), args.compress)?;
+        for gaf_path in args.gafs.iter() {
+            if args.gafs.len() > 1 {
+                info!("Converting {}", gaf_path.display());
+            }
+            convert_gaf(&index, gaf_path, &mut out, args, &mut invalid_lines)?;
+        }
+        out.finish()?;
+    }
+
+    if invalid_lines > 0 {
+        warn!("Skipped {} invalid GAF lines", invalid_lines);
     }
 
     Ok(())
 }
+
+/// The per-GAF output files in `out_dir`, named after the GAF files,
+/// without the .gaf extension and any compression extension. Fails
+/// if two GAF files would be written to the same output file.
+fn output_paths(
+    gafs: &[PathBuf],
+    out_dir: &Path,
+    compress: Compression,
+) -> Result<Vec<PathBuf>> {
+    let extension = match compress {
+        Compression::None => "paf",
+        Compression::Gzip => "paf.gz",
+        Compression::Zstd => "paf.zst",
+    };
+
+    let mut seen = FnvHashSet::default();
+    let mut paths = Vec::with_capacity(gafs.len());
+
+    for gaf in gafs {
+        let mut name = gaf
+            .file_name()
+            .ok_or_else(|| format!("Not a file: {}", gaf.display()))?
+            .to_string_lossy()
+            .into_owned();
+        for suffix in &[".gz", ".zst", ".gaf"] {
+            if name.len() > suffix.len() && name.ends_with(suffix) {
+                name.truncate(name.len() - suffix.len());
+            }
+        }
+        let path = out_dir.join(format!("{}.{}", name, extension));
+        if !seen.insert(path.clone()) {
+            return Err(format!(
+                "Several GAF files would be written to {}",
+                path.display()
+            )
+            .into());
+        }
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+/// Convert the records of one GAF file, and write them to `out`
+fn convert_gaf<T: OptFields + Sync>(
+    index: &gaf_convert::SegmentIndex<'_, T>,
+    gaf_path: &Path,
+    out: &mut dyn Write,
+    args: &GAF2PAFArgs,
+    invalid_lines: &mut usize,
+) -> Result<()> {
+    let input = GafInput::open(gaf_path)?;
+
+    let converted = input.for_each_line(
+        args.chunk_size,
+        |i, line| {
+            gaf_convert::gaf_line_to_paf_results(
+                index,
+                i,
+                line,
+                args.require_cigar,
+            )
+        },
+        |pafs| -> Result<()> {
+            for paf in pafs {
+                match paf {
+                    Ok(paf) => writeln!(out, "{}", paf)?,
+                    Err(GafConvertError::Parse(err)) if args.skip_invalid => {
+                        warn!("Skipping {}: {}", gaf_path.display(), err);
+                        *invalid_lines += 1;
+                    }
+                    Err(err) => {
+                        return Err(
+                            format!("{}: {}", gaf_path.display(), err).into()
+                        )
+                    }
+                }
+            }
+            Ok(())
+        },
+    );
+    out.flush()?;
+    converted
+}
@@ -0,0 +1,206 @@
+use bstr::BString;
+use std::{
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::gfa::GFA;
+
+use crate::{
+    compression::{self, Compression},
+    gaf_convert, surject,
+    util::bytes_from_os_str,
+    variants,
+};
+
+use super::{load_numeric_gfa, Result};
+
+/// Project GAF alignments onto a reference path of the GFA, and
+/// output them as SAM, or as sorted BAM if samtools is available.
+///
+/// Alignments to nodes that aren't on the reference become
+/// insertions, and skipped reference nodes become deletions. Records
+/// that don't touch the reference are output as unmapped. The GAF
+/// has no read sequences, so SEQ and QUAL are `*`. The GAF may be
+/// gzip or zstd compressed.
+///
+/// The SAM output is in the order of the GAF. Genome browsers such as
+/// IGV need sorted and indexed BAM, which --bam writes sorted, to be
+/// indexed with `samtools index`.
+#[derive(StructOpt, Debug)]
+pub struct GAF2SAMArgs {
+    #[structopt(name = "path to GAF file", long = "gaf", parse(from_os_str))]
+    gaf: PathBuf,
+    /// The name of the path to project the alignments onto.
+    #[structopt(
+        name = "reference path",
+        long = "ref",
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    ref_path: BString,
+    /// Write the output to this file instead of stdout.
+    #[structopt(
+        name = "SAM output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    /// Write BAM sorted by coordinate, by piping the SAM through
+    /// `samtools sort`, which must be available in PATH. Requires
+    /// `-o`. Index the BAM with `samtools index`.
+    #[structopt(name = "bam", long = "bam", requires = "SAM output file")]
+    bam: bool,
+    /// Compress the SAM output with gzip or zstd. Zstd requires
+    /// `zstd` in PATH.
+    #[structopt(
+        name = "none|gzip|zstd",
+        long = "compress",
+        default_value = "none",
+        possible_values = &Compression::variants(),
+        case_insensitive = true,
+        conflicts_with = "bam"
+    )]
+    compress: Compression,
+}
+
+/// Writes SAM to the input of a `samtools sort` process, which writes
+/// the sorted BAM. samtools is waited for when the writer is finished
+/// or dropped, so it doesn't outlive the command on an error.
+struct SamtoolsSort {
+    child: Child,
+    stdin: Option<BufWriter<ChildStdin>>,
+}
+
+impl SamtoolsSort {
+    fn spawn(out_path: &Path) -> io::Result<Self> {
+        let mut child = Command::new("samtools")
+            .args(&["sort", "-O", "bam", "-o"])
+            .arg(out_path)
+            .arg("-")
+            .stdin(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().map(BufWriter::new);
+        Ok(Self { child, stdin })
+    }
+
+    /// Close samtools' input and wait for it to write the BAM,
+    /// returning an error if it failed
+    fn finish(&mut self) -> io::Result<()> {
+        match self.stdin.take() {
+            Some(mut stdin) => {
+                let flushed = stdin.flush();
+                drop(stdin);
+                let status = self.child.wait()?;
+                flushed?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("samtools exited with {}", status),
+                    ))
+                }
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl Write for SamtoolsSort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.stdin.as_mut() {
+            Some(stdin) => stdin.write(buf),
+            None => Err(io::Error::new(io::ErrorKind::BrokenPipe, "samtools")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.stdin.as_mut() {
+            Some(stdin) => stdin.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for SamtoolsSort {
+    fn drop(&mut self) {
+        // Only reached with the pipe open on an error, in which case
+        // the BAM is incomplete
+        if self.stdin.is_some() {
+            if let Err(err) = self.finish() {
+                warn!("{}", err);
+            }
+        }
+    }
+}
+
+pub fn gaf2sam(gfa_path: &PathBuf, args: &GAF2SAMArgs) -> Result<()> {
+    let gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+    let path_data = variants::gfa_path_data(gfa);
+
+    let ref_ix = path_data
+        .path_names
+        .iter()
+        .position(|name| *name == args.ref_path)
+        .ok_or_else(|| {
+            format!("Reference path does not exist in graph: {}", args.ref_path)
+        })?;
+
+    let index = surject::PathPositionIndex::new(&path_data, ref_ix);
+    if index.repeated_nodes > 0 {
+        warn!(
+            "{} steps on {} revisit a node, only the first visit of each \
+             node is used",
+            index.repeated_nodes, index.name
+        );
+    }
+
+    let mut bam_out = None;
+    let mut sam_out = None;
+    let out: &mut dyn Write = match &args.out {
+        Some(out_path) if args.bam => {
+            bam_out.get_or_insert(SamtoolsSort::spawn(out_path)?)
+        }
+        out_path => sam_out.get_or_insert(compression::create_output(
+            out_path.as_ref(),
+            args.compress,
+        )?),
+    };
+
+    writeln!(out, "{}", surject::sam_header(&index))?;
+
+    let gaf_file = compression::open_input(&args.gaf)?;
+    let mut mapped = 0;
+    let mut unmapped = 0;
+
+    for gaf in gaf_convert::gaf_records(gaf_file) {
+        let gaf = gaf?;
+        let record = surject::surject_gaf(&index, &path_data.segment_map, &gaf);
+        if record.is_mapped() {
+            mapped += 1;
+        } else {
+            unmapped += 1;
+        }
+        writeln!(out, "{}", record)?;
+    }
+
+    if let Some(out) = sam_out {
+        out.finish()?;
+    }
+    if let Some(mut out) = bam_out {
+        out.finish()?;
+    }
+
+    info!(
+        "Projected {} alignments onto {}, {} unmapped",
+        mapped, index.name, unmapped
+    );
+
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use bstr::{io::*, ByteSlice};
+use fnv::FnvHashSet;
+use std::{
+    io::{BufReader, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use crate::{
+    compression::{self, Compression},
+    gaf_convert::diagnose_gaf_line,
+    gaf_dedup::{best_alignments, BestBy, GafAlignment},
+};
+
+use super::Result;
+
+/// Remove duplicate alignments from a GAF file, and optionally keep
+/// only the best alignment of each read.
+///
+/// Alignments are duplicates if their 12 mandatory columns are
+/// identical; only the first of them is kept. With --best, only the
+/// alignment of each read with the most matches, or the highest
+/// identity, i.e. matches divided by block length, is kept, and
+/// the other is used to break ties, after which the first is picked.
+/// The kept lines are written unchanged, in their input order. Lines
+/// that can't be parsed are reported and skipped. The GAF may be gzip
+/// or zstd compressed; with --best, it's read twice.
+#[derive(StructOpt, Debug)]
+pub struct GAFDedupArgs {
+    #[structopt(name = "path to GAF file", long = "gaf", parse(from_os_str))]
+    gaf: PathBuf,
+    /// Write the GAF to this file instead of stdout.
+    #[structopt(
+        name = "GAF output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    /// Keep only the best alignment of each read, by number of
+    /// matches or by identity.
+    #[structopt(
+        name = "matches|identity",
+        long = "best",
+        possible_values = &BestBy::variants(),
+        case_insensitive = true
+    )]
+    best: Option<BestBy>,
+    /// Compress the output with gzip or zstd. Zstd requires `zstd` in
+    /// PATH.
+    #[structopt(
+        name = "none|gzip|zstd",
+        long = "compress",
+        default_value = "none",
+        possible_values = &Compression::variants(),
+        case_insensitive = true
+    )]
+    compress: Compression,
+}
+
+pub fn gafdedup(args: &GAFDedupArgs) -> Result<()> {
+    let best = match args.best {
+        Some(by) => {
+            info!("Finding the best alignment of each read by {}", by);
+            let gaf_file = compression::open_input(&args.gaf)?;
+            Some(best_alignments(gaf_file, by)?)
+        }
+        None => None,
+    };
+
+    let mut out = compression::create_output(args.out.as_ref(), args.compress)?;
+
+    let gaf_file = compression::open_input(&args.gaf)?;
+    let mut seen: FnvHashSet<Vec<u8>> = FnvHashSet::default();
+    let mut kept = 0;
+    let mut duplicates = 0;
+    let mut not_best = 0;
+    let mut invalid_lines = 0;
+
+    for (i, line) in BufReader::new(gaf_file).byte_lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let alignment = match GafAlignment::parse(&line) {
+            Some(alignment) => alignment,
+            None => {
+                warn!("Skipping {}", diagnose_gaf_line(i + 1, &line));
+                invalid_lines += 1;
+                continue;
+            }
+        };
+
+        if let Some(best) = &best {
+            if best.get(alignment.read) != Some(&i) {
+                not_best += 1;
+                continue;
+            }
+        } else if !seen.insert(alignment.key.to_vec()) {
+            duplicates += 1;
+            continue;
+        }
+
+        out.write_all(&line)?;
+        out.write_all(b"\n")?;
+        kept += 1;
+    }
+    out.finish()?;
+
+    info!(
+        "Kept {} alignments, removed {} duplicates and {} that weren't \
+         the best of their read",
+        kept, duplicates, not_best
+    );
+    if invalid_lines > 0 {
+        warn!("Skipped {} invalid GAF lines", invalid_lines);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use bstr::BString;
+use clap::arg_enum;
+use std::{io::Write, path::PathBuf};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::gfa::GFA;
+
+use crate::{
+    compression::{self, Compression},
+    gaf_convert,
+    gaf_reader::{GafInput, DEFAULT_CHUNK_LINES},
+    surject,
+    util::bytes_from_os_str,
+    variants,
+};
+
+use super::{load_numeric_gfa, Result};
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum DepthFormat {
+        BedGraph,
+        Wig,
+    }
+}
+
+/// Compute the per-base depth of GAF alignments along a reference
+/// path of the GFA, and output it as a bedGraph or wiggle track.
+///
+/// Each alignment is projected onto the path like in `gaf2bed`, and
+/// adds one to the depth of every path base it covers. The GAF is
+/// streamed, so only the depth along the path has to fit in memory,
+/// and its lines are parsed and projected in parallel. The bedGraph
+/// has one line per run of bases with the same, nonzero, depth; the
+/// wiggle track is a fixedStep track with the depth of every base.
+/// The GAF may be gzip or zstd compressed; uncompressed GAF files are
+/// memory-mapped.
+#[derive(StructOpt, Debug)]
+pub struct GAFDepthArgs {
+    #[structopt(name = "path to GAF file", long = "gaf", parse(from_os_str))]
+    gaf: PathBuf,
+    /// The name of the path to compute the depth along.
+    #[structopt(
+        name = "reference path",
+        long = "ref",
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    ref_path: BString,
+    /// Write the output to this file instead of stdout.
+    #[structopt(
+        name = "depth output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    #[structopt(
+        name = "bedgraph|wig",
+        long = "format",
+        default_value = "bedgraph",
+        possible_values = &DepthFormat::variants(),
+        case_insensitive = true
+    )]
+    format: DepthFormat,
+    /// Compress the output with gzip or zstd. Zstd requires `zstd` in
+    /// PATH.
+    #[structopt(
+        name = "none|gzip|zstd",
+        long = "compress",
+        default_value = "none",
+        possible_values = &Compression::variants(),
+        case_insensitive = true
+    )]
+    compress: Compression,
+}
+
+pub fn gafdepth(gfa_path: &PathBuf, args: &GAFDepthArgs) -> Result<()> {
+    let gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+    let path_data = variants::gfa_path_data(gfa);
+
+    let ref_ix = path_data
+        .path_names
+        .iter()
+        .position(|name| *name == args.ref_path)
+        .ok_or_else(|| {
+            format!("Reference path does not exist in graph: {}", args.ref_path)
+        })?;
+
+    let index = surject::PathPositionIndex::new(&path_data, ref_ix);
+    if index.repeated_nodes > 0 {
+        warn!(
+            "{} steps on {} revisit a node, only the first visit of each \
+             node is used",
+            index.repeated_nodes, index.name
+        );
+    }
+
+    let mut depth = surject::PathDepth::new(&index);
+
+    let input = GafInput::open(&args.gaf)?;
+    let mut counted = 0;
+    let mut skipped = 0;
+    let mut invalid_lines = 0;
+
+    input.for_each_line(
+        DEFAULT_CHUNK_LINES,
+        |i, line| {
+            gaf_convert::parse_gaf_line(i, line).map(|gaf| {
+                surject::project_gaf_intervals(
+                    &index,
+                    &path_data.segment_map,
+                    &gaf,
+                )
+            })
+        },
+        |intervals| -> Result<()> {
+            match intervals {
+                Ok(intervals) if intervals.is_empty() => skipped += 1,
+                Ok(intervals) => {
+                    counted += 1;
+                    for interval in intervals.iter() {
+                        depth.add(interval);
+                    }
+                }
+                Err(err) => {
+                    warn!("Skipping {}", err);
+                    invalid_lines += 1;
+                }
+            }
+            Ok(())
+        },
+    )?;
+
+    info!(
+        "Computed depth of {} alignments along {}, {} skipped",
+        counted, index.name, skipped
+    );
+    if invalid_lines > 0 {
+        warn!("Skipped {} invalid GAF lines", invalid_lines);
+    }
+
+    let mut out = compression::create_output(args.out.as_ref(), args.compress)?;
+    match args.format {
+        DepthFormat::BedGraph => depth.write_bedgraph(&mut out)?,
+        DepthFormat::Wig => depth.write_wig(&mut out)?,
+    }
+    out.finish()?;
+
+    Ok(())
+}
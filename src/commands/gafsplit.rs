@@ -0,0 +1,118 @@
+use bstr::{io::*, ByteSlice};
+use std::{fs::File, io::BufReader, path::PathBuf};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use regex::bytes::Regex;
+
+use crate::{
+    compression,
+    gaf_split::{gaf_read_name, SampleAssigner},
+    output_pool::{file_name_for, OutputPool},
+};
+
+use super::Result;
+
+/// Split a GAF file into one GAF per sample, with the sample of each
+/// record found from its read name.
+///
+/// The sample is either taken from the read name with a regex, as
+/// the first capture group, or the whole match if the regex has no
+/// groups, or looked up in a TSV file of read names and samples.
+/// Records are written to <sample>.gaf in the output directory,
+/// unchanged and in their input order, and the files are listed in
+/// a manifest.tsv once all records are written. Records without a
+/// sample are skipped, or written to --unassigned. The GAF is
+/// streamed, and may be gzip or zstd compressed.
+#[derive(StructOpt, Debug)]
+pub struct GAFSplitArgs {
+    #[structopt(name = "path to GAF file", long = "gaf", parse(from_os_str))]
+    gaf: PathBuf,
+    /// The directory to write the per-sample GAF files to.
+    #[structopt(
+        name = "output directory",
+        short = "o",
+        long = "out-dir",
+        parse(from_os_str)
+    )]
+    out_dir: PathBuf,
+    /// Find the sample in each read name with this regex.
+    #[structopt(
+        name = "sample regex",
+        long = "regex",
+        required_unless = "sample map TSV"
+    )]
+    regex: Option<String>,
+    /// Look up the sample of each read in this TSV file, with the
+    /// columns read name and sample.
+    #[structopt(
+        name = "sample map TSV",
+        long = "sample-map",
+        parse(from_os_str),
+        conflicts_with = "sample regex"
+    )]
+    sample_map: Option<PathBuf>,
+    /// Write the records without a sample to a file with this name,
+    /// instead of skipping them.
+    #[structopt(name = "unassigned file name", long = "unassigned")]
+    unassigned: Option<String>,
+    /// The maximum number of files to keep open at a time.
+    #[structopt(
+        name = "max open files",
+        long = "max-open-files",
+        default_value = "64"
+    )]
+    max_open_files: usize,
+}
+
+pub fn gafsplit(args: &GAFSplitArgs) -> Result<()> {
+    let assigner = if let Some(map_path) = &args.sample_map {
+        SampleAssigner::from_tsv(File::open(map_path)?)?
+    } else if let Some(regex) = &args.regex {
+        SampleAssigner::Regex(Regex::new(regex)?)
+    } else {
+        return Err("Either --regex or --sample-map is required".into());
+    };
+
+    let mut pool = OutputPool::new(&args.out_dir, args.max_open_files, None)?;
+
+    let gaf_file = compression::open_input(&args.gaf)?;
+    let mut assigned = 0;
+    let mut unassigned = 0;
+
+    for line in BufReader::new(gaf_file).byte_lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let read_name = gaf_read_name(&line);
+        match assigner.sample(read_name) {
+            Some(sample) => {
+                let file_name =
+                    format!("{}.gaf", file_name_for(&sample.to_str_lossy()));
+                pool.write_line(&file_name, &line)?;
+                assigned += 1;
+            }
+            None => {
+                debug!("No sample for read {}", read_name.as_bstr());
+                if let Some(file_name) = &args.unassigned {
+                    pool.write_line(file_name, &line)?;
+                }
+                unassigned += 1;
+            }
+        }
+    }
+
+    let files = pool.finish()?;
+
+    info!(
+        "Split {} records into {} files, {} records without a sample",
+        assigned,
+        files.len(),
+        unassigned
+    );
+
+    Ok(())
+}
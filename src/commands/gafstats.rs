@@ -0,0 +1,102 @@
+use bstr::BString;
+use clap::arg_enum;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::{gfa::GFA, optfields::OptionalFields};
+
+use crate::{
+    gaf_convert::SegmentIndex, gaf_reader::GafInput, gaf_stats::GafStats,
+};
+
+use super::{load_gfa, write_json, Result};
+
+arg_enum! {
+    #[derive(Debug, PartialEq)]
+    pub enum StatsFormat {
+        Tsv,
+        Json,
+    }
+}
+
+/// Summarize the alignments in one or more GAF files against the
+/// graph.
+///
+/// Reports the number of primary and secondary records, the mapping
+/// rate, the mean identity, histograms of read lengths, identities,
+/// and mapping qualities, and the number of records and aligned
+/// bases on each node. Records with a tp:A:S tag are secondary, and
+/// lines with * as the path are unmapped reads. The GAF files may be
+/// gzip or zstd compressed; uncompressed files are memory-mapped, and
+/// their lines parsed in parallel.
+#[derive(StructOpt, Debug)]
+pub struct GAFStatsArgs {
+    #[structopt(
+        name = "path to GAF file",
+        long = "gaf",
+        required = true,
+        parse(from_os_str)
+    )]
+    gafs: Vec<PathBuf>,
+    /// Write the statistics to this file instead of stdout.
+    #[structopt(
+        name = "stats output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    /// Output TSV with the kind of each row in the first column (SN,
+    /// RL, ID, MQ, or NC), or a single JSON object.
+    #[structopt(
+        name = "tsv|json",
+        long = "format",
+        default_value = "tsv",
+        possible_values = &StatsFormat::variants(),
+        case_insensitive = true
+    )]
+    format: StatsFormat,
+}
+
+pub fn gafstats(gfa_path: &PathBuf, args: &GAFStatsArgs) -> Result<()> {
+    let gfa: GFA<Vec<u8>, OptionalFields> = load_gfa(gfa_path)?;
+
+    let nodes: Vec<(BString, usize)> = gfa
+        .segments
+        .iter()
+        .map(|seg| (seg.name.clone().into(), seg.sequence.len()))
+        .collect();
+
+    let index = SegmentIndex::new(&gfa);
+
+    let mut stats = GafStats::default();
+    for gaf_path in args.gafs.iter() {
+        info!("Reading GAF records from {}", gaf_path.display());
+        stats.add_input(&index, GafInput::open(gaf_path)?)?;
+    }
+
+    if stats.invalid_lines > 0 {
+        warn!("Skipped {} invalid GAF lines", stats.invalid_lines);
+    }
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    match args.format {
+        StatsFormat::Tsv => write!(out, "{}", stats.to_tsv(&nodes))?,
+        StatsFormat::Json => write_json(&mut out, &stats.report(&nodes))?,
+    }
+    out.flush()?;
+
+    Ok(())
+}
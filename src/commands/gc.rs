@@ -0,0 +1,113 @@
+use bstr::BString;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::gfa::{Orientation, GFA};
+
+use crate::{node_seq, stats, util::bytes_from_os_str};
+
+use super::{load_numeric_gfa, Result};
+
+/// Report the GC content of the sequences spelled by the paths.
+///
+/// By default, the output is a TSV with one line per path, with its
+/// name, length, GC fraction, and N fraction. With --ref, the path is
+/// instead split into windows, and each window is written as a BED
+/// line with its GC and N fractions. The GC fraction ignores N and
+/// other ambiguous bases.
+#[derive(StructOpt, Debug)]
+pub struct GCArgs {
+    /// Report the GC content in windows along this path, as BED.
+    #[structopt(
+        name = "reference path",
+        long = "ref",
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    ref_path: Option<BString>,
+    /// The size of each window, in bp, with --ref.
+    #[structopt(name = "window size", long = "window", default_value = "1000")]
+    window: usize,
+    /// The distance between the starts of consecutive windows, in bp.
+    /// Defaults to the window size, i.e. non-overlapping windows.
+    #[structopt(
+        name = "step size",
+        long = "step",
+        requires = "reference path"
+    )]
+    step: Option<usize>,
+    /// Write the output to this file instead of stdout.
+    #[structopt(
+        name = "output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+}
+
+pub fn gc(gfa_path: &PathBuf, args: &GCArgs) -> Result<()> {
+    let step = args.step.unwrap_or(args.window);
+    if args.window == 0 || step == 0 {
+        return Err("The window and step sizes must be greater than 0".into());
+    }
+
+    let mut gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    if let Some(ref_name) = &args.ref_path {
+        let ref_ix = gfa
+            .paths
+            .iter()
+            .position(|path| path.path_name == *ref_name)
+            .ok_or_else(|| {
+                format!("Reference path does not exist in graph: {}", ref_name)
+            })?;
+        let ref_path = gfa.paths.swap_remove(ref_ix);
+        let steps: Vec<(usize, Orientation)> = ref_path.iter().collect();
+        let segment_map = node_seq::segment_sequences(gfa);
+        let seq = node_seq::traversal_sequence(&segment_map, &steps)?;
+
+        for (start, end, comp) in
+            stats::window_compositions(&seq, args.window, step)
+        {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{:.4}\t{:.4}",
+                ref_name,
+                start,
+                end,
+                comp.gc_fraction(),
+                comp.n_fraction()
+            )?;
+        }
+    } else {
+        let seg_comps = stats::segment_compositions(&gfa);
+
+        writeln!(out, "path\tlength\tgc_fraction\tn_fraction")?;
+        for (name, comp) in stats::path_compositions(&gfa, &seg_comps) {
+            writeln!(
+                out,
+                "{}\t{}\t{:.4}\t{:.4}",
+                name,
+                comp.length,
+                comp.gc_fraction(),
+                comp.n_fraction()
+            )?;
+        }
+    }
+    out.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use bstr::{io::*, ByteSlice};
+use structopt::StructOpt;
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::gfa::GFA;
+
+use crate::node_seq;
+
+use super::{load_numeric_gfa, Result};
+
+/// Read node IDs from stdin and output their sequences.
+///
+/// Each input line holds one or more node IDs, optionally oriented
+/// (`12+`, `12-`, `>12`, `<12`), separated by whitespace or commas,
+/// or a GAF-style walk such as `>1<2>3`. Reverse nodes are output as
+/// their reverse complement.
+#[derive(StructOpt, Debug)]
+pub struct GetSeqArgs {
+    /// Read the node IDs from this file instead of stdin.
+    #[structopt(name = "node ID file", long = "ids", parse(from_os_str))]
+    ids: Option<PathBuf>,
+    /// Concatenate the nodes on each input line into the sequence of
+    /// the traversal, instead of outputting one line per node.
+    #[structopt(name = "concatenate", long = "concat")]
+    concat: bool,
+    /// Write the sequences to this file instead of stdout.
+    #[structopt(
+        name = "sequence output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+}
+
+pub fn get_seq(gfa_path: &PathBuf, args: &GetSeqArgs) -> Result<()> {
+    let gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+    let segment_map = node_seq::segment_sequences(gfa);
+
+    let input: Box<dyn std::io::Read> = if let Some(ids_path) = &args.ids {
+        Box::new(File::open(ids_path)?)
+    } else {
+        Box::new(std::io::stdin())
+    };
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    for (line_ix, line) in BufReader::new(input).byte_lines().enumerate() {
+        let line = line?;
+        let with_line = |err: node_seq::NodeSeqError| {
+            format!("Error on input line {}: {}", line_ix + 1, err)
+        };
+
+        let steps = node_seq::parse_oriented_nodes(&line).map_err(with_line)?;
+        if steps.is_empty() {
+            continue;
+        }
+
+        if args.concat {
+            let seq = node_seq::traversal_sequence(&segment_map, &steps)
+                .map_err(with_line)?;
+            writeln!(out, "{}", seq.as_bstr())?;
+        } else {
+            for (node, orient) in steps {
+                let seq =
+                    node_seq::oriented_sequence(&segment_map, node, orient)
+                        .map_err(with_line)?;
+                let orient = if orient.is_reverse() { '-' } else { '+' };
+                writeln!(out, "{}{}\t{}", node, orient, seq.as_bstr())?;
+            }
+        }
+        // Flush per line, so the command can be used interactively
+        // from another process
+        out.flush()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+use gfa::gfa::GFA;
+
+use crate::graphml;
+
+use super::{load_numeric_gfa, Result};
+
+/// Export the graph topology as GraphML, for network analysis tools
+/// such as Gephi or Cytoscape.
+///
+/// Each segment becomes a node with the attributes length, coverage
+/// (number of path steps), paths (number of distinct paths), samples
+/// (number of PanSN samples), component, and class (core, shell,
+/// cloud, or uncovered, by the number of samples, as in
+/// core-accessory). Each link becomes a directed edge with its
+/// orientations and overlap.
+#[derive(StructOpt, Debug)]
+pub struct GFA2GraphMLArgs {
+    /// Write the GraphML to this file instead of stdout.
+    #[structopt(
+        name = "GraphML output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    /// The fraction of the samples a segment must be in to be core.
+    #[structopt(
+        name = "core fraction",
+        long = "core-fraction",
+        default_value = "1.0"
+    )]
+    core_fraction: f64,
+    /// Segments in at most this many samples are cloud, and those
+    /// between cloud and core are shell.
+    #[structopt(
+        name = "cloud max samples",
+        long = "cloud-max-samples",
+        default_value = "1"
+    )]
+    cloud_max: usize,
+}
+
+pub fn gfa2graphml(gfa_path: &PathBuf, args: &GFA2GraphMLArgs) -> Result<()> {
+    if !(args.core_fraction > 0.0 && args.core_fraction <= 1.0) {
+        return Err("The core fraction must be in (0, 1]".into());
+    }
+
+    let gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    graphml::write_graphml(&gfa, args.core_fraction, args.cloud_max, &mut out)?;
+    out.flush()?;
+
+    Ok(())
+}
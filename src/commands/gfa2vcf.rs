@@ -3,18 +3,44 @@ use fnv::{FnvHashMap, FnvHashSet};
 use indicatif::{
     ParallelProgressIterator, ProgressBar, ProgressIterator, ProgressStyle,
 };
+use memmap::Mmap;
 use rayon::prelude::*;
-use std::{fs::File, io::BufReader, path::PathBuf};
+use serde_json::json;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use structopt::StructOpt;
 
 #[allow(unused_imports)]
 use log::{debug, info, log_enabled, warn};
 
-use gfa::gfa::GFA;
-
-use crate::{util::progress_bar, variants, variants::PathStep};
+use crate::{
+    manifest::StageManifest,
+    metrics,
+    output_pool::{file_name_for, OutputPool},
+    util::{bytes_from_os_str, progress_bar, Heartbeat, NodeSet},
+    variants,
+    variants::{
+        compact::{self, CompactRecords},
+        density::{self, VariantDensity},
+        dotplot,
+        dotplot::DotplotRow,
+        truth::TruthSet,
+        validate::RefSequences,
+        vcf,
+        vcf::VCFRecord,
+        HaploidSamples, PathData, PathIndices, PathStep, QualityScore,
+    },
+};
 
-use super::{load_gfa, Result};
+use super::{load_path_data, load_path_data_and_ultrabubbles, Result};
 
 /// Output a VCF for the given GFA, using the graph's ultrabubbles to
 /// identify areas of variation.
@@ -31,16 +57,186 @@ pub struct GFA2VCFArgs {
     /// don't match each other
     #[structopt(name = "ignore inverted paths", long = "no-inv")]
     ignore_inverted_paths: bool,
+    /// Split MNPs with equal-length reference and alternate alleles
+    /// into separate SNV records
+    #[structopt(name = "decompose MNPs", long = "decompose-mnp")]
+    decompose_mnp: bool,
+    /// Add the QNAME and QSTART INFO fields, with the names of the
+    /// query paths containing each alternate allele, and the allele
+    /// positions in those paths
+    #[structopt(name = "report query coordinates", long = "query-coords")]
+    query_coords: bool,
+    /// Add the PATHS INFO field, with the names of all query paths
+    /// containing any of the alternate alleles. Duplicate records
+    /// are merged, keeping the path names of each.
+    #[structopt(name = "report path names", long = "paths-info")]
+    path_names: bool,
+    /// Don't remove duplicate records, e.g. when the same variant is
+    /// found in several nested bubbles. Useful for debugging.
+    #[structopt(name = "keep duplicates", long = "no-dedup")]
+    no_dedup: bool,
+    /// Treat each path as a haploid sample: add a GT column per path,
+    /// with the index of the path's allele, or . if the path doesn't
+    /// traverse the bubble, and the AC and AN INFO fields.
+    #[structopt(name = "haploid", long = "haploid")]
+    haploid: bool,
+    /// How to fill the QUAL column, based on the paths traversing
+    /// each bubble: the number of paths supporting an alternate
+    /// allele, the percentage of paths supporting one, or the
+    /// phred-scaled fraction of paths that don't. By default, QUAL
+    /// is left as `.`.
+    #[structopt(
+        name = "none|count|fraction|phred",
+        long = "qual-score",
+        default_value = "none",
+        possible_values = &QualityScore::variants(),
+        case_insensitive = true
+    )]
+    quality: QualityScore,
+    /// Don't check that each record's position is within its
+    /// reference path, that REF matches the path sequence, and that
+    /// ALT is not empty
+    #[structopt(name = "skip validation", long = "no-validate")]
+    no_validate: bool,
+    /// Fail if any record is invalid, instead of dropping it
+    #[structopt(name = "strict validation", long = "strict")]
+    strict: bool,
+    /// Write the VCF to this file instead of stdout.
+    #[structopt(
+        name = "VCF output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    /// Write a separate VCF per reference path to this directory,
+    /// named after the path, instead of a single VCF. The files are
+    /// only given their final names once all records are written,
+    /// along with a manifest.tsv listing them.
+    #[structopt(
+        name = "split output directory",
+        long = "split-dir",
+        parse(from_os_str),
+        conflicts_with = "VCF output file"
+    )]
+    split_dir: Option<PathBuf>,
+    /// The maximum number of files to keep open at a time when
+    /// writing with --split-dir.
+    #[structopt(
+        name = "max open files",
+        long = "max-open-files",
+        default_value = "64",
+        requires = "split output directory"
+    )]
+    max_open_files: usize,
+    /// Log the number of processed and remaining bubbles every N
+    /// minutes.
+    #[structopt(name = "heartbeat minutes", long = "heartbeat")]
+    heartbeat: Option<u64>,
+    /// Process the bubbles in blocks of N, writing and flushing the
+    /// records of each block as soon as it's done, so that an
+    /// interrupted run still leaves usable output. Records are sorted
+    /// and deduplicated within each block. When all the blocks have
+    /// been written to the -o file, they're merged so that the whole
+    /// file is sorted, but not deduplicated. Output to stdout, with
+    /// --split-dir, or of only some blocks with --batch, is only
+    /// sorted within each block, and must be sorted before it's
+    /// indexed.
+    #[structopt(name = "bubbles per block", long = "flush-every")]
+    flush_every: Option<usize>,
+    /// Only process these blocks of bubbles, given by their 0-based
+    /// index, e.g. to rerun the blocks that failed in an earlier run.
+    /// The blocks are the same between runs with the same
+    /// ultrabubbles and --flush-every.
+    #[structopt(
+        name = "block index",
+        long = "batch",
+        requires = "bubbles per block"
+    )]
+    batches: Vec<usize>,
+    /// Write a JSONL manifest to this file, with one line per
+    /// finished stage: the ultrabubbles, the assignment of bubbles
+    /// to each block, and each completed block, so that a scheduler
+    /// can split a run across nodes with --batch, and rerun only the
+    /// blocks that didn't complete. With --batch, the lines are
+    /// appended to the file.
+    #[structopt(name = "manifest", long = "manifest", parse(from_os_str))]
+    manifest: Option<PathBuf>,
+    /// Compare the emitted records to the alleles in this VCF. Each
+    /// ALT allele is marked as TP or FP in the TRUTH INFO field, and
+    /// TP/FP/FN counts per reference are reported at the end.
+    #[structopt(name = "truth VCF", long = "truth", parse(from_os_str))]
+    truth: Option<PathBuf>,
+    /// Write the truth set comparison summary to this file instead
+    /// of stderr.
+    #[structopt(
+        name = "truth summary file",
+        long = "truth-summary",
+        parse(from_os_str),
+        requires = "truth VCF"
+    )]
+    truth_summary: Option<PathBuf>,
     #[structopt(
         name = "file containing paths to use as references",
         long = "paths-file"
     )]
     ref_paths_file: Option<PathBuf>,
-    #[structopt(name = "list of paths to use as references", long = "refs")]
-    ref_paths_vec: Option<Vec<String>>,
+    #[structopt(
+        name = "list of paths to use as references",
+        long = "refs",
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    ref_paths_vec: Option<Vec<BString>>,
+    /// Load the path data, and the ultrabubbles unless -u is given,
+    /// from this binary file, if it exists, instead of parsing the GFA;
+    /// otherwise build them from the GFA and save them to the file.
+    /// The file is rejected if the GFA has changed since.
+    #[structopt(name = "path data file", long = "path-data")]
+    path_data: Option<PathBuf>,
+    /// Write the k-mer anchor matches between the reference and
+    /// query alleles of complex bubbles, which can't be decomposed
+    /// into simple variants, to this TSV file, for dotplots.
+    #[structopt(name = "dotplot TSV", long = "dotplot", parse(from_os_str))]
+    dotplot: Option<PathBuf>,
+    /// The k-mer size used for the dotplot anchors.
+    #[structopt(
+        name = "dotplot k",
+        long = "dotplot-k",
+        default_value = "11",
+        requires = "dotplot TSV"
+    )]
+    dotplot_k: usize,
+    /// Write the number of variants per window along each reference
+    /// path to this BED file, with the columns path, start, end,
+    /// variant count, and variants per kb.
+    #[structopt(name = "density BED", long = "density", parse(from_os_str))]
+    density: Option<PathBuf>,
+    /// The size of the density windows, in bp.
+    #[structopt(
+        name = "density window",
+        long = "density-window",
+        default_value = "100000"
+    )]
+    density_window: usize,
+    /// How far apart the density windows start, in bp. Defaults to
+    /// the window size, i.e. non-overlapping windows.
+    #[structopt(name = "density step", long = "density-step")]
+    density_step: Option<usize>,
+    /// Write the hotspots, the merged density windows with at least
+    /// --hotspot-min variants per kb, to this BED file, in the same
+    /// format as --density.
+    #[structopt(name = "hotspots BED", long = "hotspots", parse(from_os_str))]
+    hotspots: Option<PathBuf>,
+    /// The minimum number of variants per kb in a hotspot window.
+    #[structopt(
+        name = "hotspot variants per kb",
+        long = "hotspot-min",
+        default_value = "10"
+    )]
+    hotspot_min: f64,
 }
 
-fn load_paths_file(file_path: PathBuf) -> Result<Vec<BString>> {
+pub(super) fn load_paths_file(file_path: PathBuf) -> Result<Vec<BString>> {
     let file = File::open(file_path)?;
     let reader = BufReader::new(file);
     let lines = reader.byte_lines();
@@ -54,12 +250,8 @@ fn load_paths_file(file_path: PathBuf) -> Result<Vec<BString>> {
     Ok(paths)
 }
 
-fn paths_list(paths: Vec<String>) -> Vec<BString> {
-    paths.into_iter().map(BString::from).collect()
-}
-
 pub fn gfa2vcf(gfa_path: &PathBuf, args: GFA2VCFArgs) -> Result<()> {
-    let ref_paths_list = args.ref_paths_vec.map(paths_list).unwrap_or_default();
+    let ref_paths_list = args.ref_paths_vec.unwrap_or_default();
 
     let ref_paths_file = args
         .ref_paths_file
@@ -85,18 +277,30 @@ pub fn gfa2vcf(gfa_path: &PathBuf, args: GFA2VCFArgs) -> Result<()> {
         }
     };
 
-    let path_data = {
-        let gfa: GFA<usize, ()> = load_gfa(&gfa_path)?;
+    // Without an ultrabubbles file, the ultrabubbles are found along
+    // with the path data, so that they can be cached with it
+    let (path_data, found_ultrabubbles) = {
+        let (path_data, found_ultrabubbles) =
+            if args.ultrabubbles_file.is_some() {
+                (load_path_data(&gfa_path, args.path_data.as_ref())?, None)
+            } else {
+                let (path_data, ultrabubbles) =
+                    load_path_data_and_ultrabubbles(
+                        &gfa_path,
+                        args.path_data.as_ref(),
+                    )?;
+                (path_data, Some(ultrabubbles))
+            };
 
-        if gfa.paths.len() < 2 {
+        if path_data.paths.len() < 2 {
             panic!("GFA must contain at least two paths");
         }
 
         if let Some(ref_paths) = ref_path_names.as_ref() {
-            let gfa_paths = gfa
-                .paths
+            let gfa_paths = path_data
+                .path_names
                 .iter()
-                .map(|path| path.path_name.as_bstr())
+                .map(|name| name.as_bstr())
                 .collect::<FnvHashSet<_>>();
 
             for path in ref_paths.iter() {
@@ -110,50 +314,234 @@ pub fn gfa2vcf(gfa_path: &PathBuf, args: GFA2VCFArgs) -> Result<()> {
             }
         }
 
-        info!("GFA has {} paths", gfa.paths.len());
+        info!("GFA has {} paths", path_data.paths.len());
 
-        variants::gfa_path_data(gfa)
+        (path_data, found_ultrabubbles)
     };
 
     let mut ultrabubbles = if let Some(path) = &args.ultrabubbles_file {
-        super::saboten::load_ultrabubbles(path)
+        super::saboten::load_ultrabubbles(path)?
     } else {
-        super::saboten::find_ultrabubbles(gfa_path)
-    }?;
+        found_ultrabubbles.unwrap_or_default()
+    };
 
     info!("Using {} ultrabubbles", ultrabubbles.len());
 
     ultrabubbles.sort();
 
+    let batches = &args.batches;
+
+    let mut manifest = args
+        .manifest
+        .as_ref()
+        .map(|path| StageManifest::create(path, !batches.is_empty()))
+        .transpose()?;
+
+    if let Some(manifest) = manifest.as_mut() {
+        let file = args
+            .ultrabubbles_file
+            .as_ref()
+            .map(|path| path.display().to_string());
+        manifest.record(
+            "ultrabubbles",
+            json!({ "bubbles": ultrabubbles.len(), "file": file }),
+        )?;
+    }
+
     let ultrabubble_nodes = ultrabubbles
         .iter()
         .flat_map(|&(a, b)| {
             use std::iter::once;
             once(a).chain(once(b))
         })
-        .collect::<FnvHashSet<_>>();
+        .collect::<NodeSet>();
 
     let path_indices =
         variants::bubble_path_indices(&path_data.paths, &ultrabubble_nodes);
 
-    let mut all_vcf_records = Vec::new();
-
     let var_config = variants::VariantConfig {
         ignore_inverted_paths: args.ignore_inverted_paths,
+        decompose_mnp: args.decompose_mnp,
+        query_coords: args.query_coords,
+        path_names: args.path_names,
+        quality: args.quality,
     };
 
+    let mut vcf_header = variants::vcf::VCFHeader::new(gfa_path);
+    if var_config.query_coords {
+        vcf_header.add_info(
+            "QNAME",
+            "A",
+            "String",
+            "Query paths containing each allele, separated by |",
+        );
+        vcf_header.add_info(
+            "QSTART",
+            "A",
+            "String",
+            "Position of each allele in the corresponding QNAME paths",
+        );
+    }
+
+    if var_config.path_names {
+        vcf_header.add_info(
+            "PATHS",
+            ".",
+            "String",
+            "Query paths containing any of the alternate alleles",
+        );
+    }
+
+    let haploid_samples = if args.haploid {
+        let samples = HaploidSamples::new(&path_data);
+        vcf_header.add_info(
+            "AC",
+            "A",
+            "Integer",
+            "Number of samples with each alternate allele",
+        );
+        vcf_header.add_info(
+            "AN",
+            "1",
+            "Integer",
+            "Number of samples that traverse the bubble",
+        );
+        vcf_header.add_format("GT", "1", "String", "Haploid genotype");
+        vcf_header.set_samples(samples.names.clone());
+        Some(samples)
+    } else {
+        None
+    };
+
+    let mut truth = if let Some(truth_path) = &args.truth {
+        info!("Loading truth set from {}", truth_path.display());
+        vcf_header.add_info(
+            "TRUTH",
+            "A",
+            "String",
+            "Whether each allele is in the truth set (TP) or not (FP)",
+        );
+        Some(TruthSet::from_file(truth_path)?)
+    } else {
+        None
+    };
+
+    let mut out: Box<dyn Write> = if args.split_dir.is_some() {
+        Box::new(std::io::sink())
+    } else if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    let mut split_pool = if let Some(split_dir) = &args.split_dir {
+        Some(OutputPool::new(
+            split_dir,
+            args.max_open_files,
+            Some(vcf_header.to_string()),
+        )?)
+    } else {
+        writeln!(out, "{}", vcf_header)?;
+        None
+    };
+
+    // With --flush-every, each block is only sorted on its own, so the
+    // offsets of the header and the blocks in the output file are
+    // kept, to merge the blocks once they've all been written
+    let merged_out = match (&args.out, args.flush_every) {
+        (Some(out_path), Some(_))
+            if split_pool.is_none() && batches.is_empty() =>
+        {
+            Some(out_path)
+        }
+        _ => None,
+    };
+    let mut block_offsets = Vec::new();
+    if let Some(out_path) = merged_out {
+        out.flush()?;
+        block_offsets.push(std::fs::metadata(out_path)?.len());
+    }
+
     info!(
         "Identifying variants in {} ultrabubbles",
         ultrabubbles.len()
     );
 
+    let validate = !args.no_validate;
+    let ref_sequences = RefSequences::new(&path_data);
+    let invalid_records = AtomicUsize::new(0);
+
+    let processed = Arc::new(AtomicUsize::new(0));
+    let _heartbeat = args.heartbeat.map(|minutes| {
+        Heartbeat::start(
+            "ultrabubbles",
+            Duration::from_secs(minutes * 60),
+            ultrabubbles.len(),
+            processed.clone(),
+        )
+    });
+
+    // Without --flush-every, all bubbles are processed as a single
+    // block, so the output is sorted and deduplicated as a whole
+    let block_size = args.flush_every.unwrap_or(ultrabubbles.len()).max(1);
+
+    let block_count = (ultrabubbles.len() + block_size - 1) / block_size;
+    if let Some(&batch) = batches.iter().find(|&&b| b >= block_count) {
+        return Err(format!(
+            "Block {} does not exist, there are {} blocks",
+            batch, block_count
+        )
+        .into());
+    }
+    let is_selected =
+        |batch: usize| batches.is_empty() || batches.contains(&batch);
+
+    if let Some(manifest) = manifest.as_mut() {
+        for (batch, block) in ultrabubbles.chunks(block_size).enumerate() {
+            if !is_selected(batch) {
+                continue;
+            }
+            manifest.record(
+                "batch_assigned",
+                json!({
+                    "batch": batch,
+                    "bubbles": block.len(),
+                    "first": block.first(),
+                    "last": block.last(),
+                }),
+            )?;
+        }
+    }
+
     let p_bar = progress_bar(ultrabubbles.len(), false);
 
-    all_vcf_records.par_extend(
-        ultrabubbles
+    let mut records_written = 0;
+
+    let mut density = if args.density.is_some() || args.hotspots.is_some() {
+        let mut density = VariantDensity::default();
+        for path in ref_path_names.iter().flatten() {
+            density.add_chromosome(path);
+        }
+        Some(density)
+    } else {
+        None
+    };
+
+    let mut batches_completed = 0;
+
+    for (batch, block) in ultrabubbles.chunks(block_size).enumerate() {
+        if !is_selected(batch) {
+            continue;
+        }
+        let written_before = records_written;
+
+        let mut block_records: CompactRecords = block
             .par_iter()
-            .progress_with(p_bar)
+            .progress_with(p_bar.clone())
             .filter_map(|&(from, to)| {
+                processed.fetch_add(1, Ordering::Relaxed);
+                metrics::BUBBLES_PROCESSED.fetch_add(1, Ordering::Relaxed);
+
                 let vars = variants::detect_variants_in_sub_paths(
                     &var_config,
                     &path_data,
@@ -163,24 +551,182 @@ pub fn gfa2vcf(gfa_path: &PathBuf, args: GFA2VCFArgs) -> Result<()> {
                     to,
                 )?;
 
-                let vcf_records = variants::variant_vcf_record(&vars);
+                let bubble_paths =
+                    variants::bubble_path_ixs(&path_indices, from, to);
+                let genotypes = haploid_samples
+                    .as_ref()
+                    .map(|samples| (samples, bubble_paths.as_slice()));
+                let mut vcf_records = variants::variant_vcf_record(
+                    &var_config,
+                    bubble_paths.len(),
+                    &vars,
+                    genotypes,
+                );
+
+                if validate {
+                    vcf_records.retain(|vcf| {
+                        if let Err(err) = ref_sequences.validate_record(vcf) {
+                            warn!(
+                                "Invalid record {}:{} in bubble {}-{}: {}",
+                                vcf.chromosome, vcf.position, from, to, err
+                            );
+                            invalid_records.fetch_add(1, Ordering::Relaxed);
+                            false
+                        } else {
+                            true
+                        }
+                    });
+                }
+
                 Some(vcf_records)
             })
-            .flatten(),
-    );
+            .fold(CompactRecords::default, |mut records, vcf_records| {
+                records.extend(vcf_records);
+                records
+            })
+            .reduce(CompactRecords::default, |mut records, other| {
+                records.append(other);
+                records
+            });
+
+        if args.strict && invalid_records.load(Ordering::Relaxed) > 0 {
+            return Err(format!(
+                "{} VCF records failed validation",
+                invalid_records.load(Ordering::Relaxed)
+            )
+            .into());
+        }
+
+        block_records.sort();
+
+        let mut write_record = |mut vcf: VCFRecord| -> Result<()> {
+            if let Some(truth) = truth.as_mut() {
+                truth.annotate(&mut vcf);
+            }
+            if let Some(density) = density.as_mut() {
+                density.add(&vcf);
+            }
+            if let Some(pool) = split_pool.as_mut() {
+                let file_name = format!(
+                    "{}.vcf",
+                    file_name_for(&vcf.chromosome.to_string())
+                );
+                pool.write_record(&file_name, &vcf)?;
+            } else {
+                writeln!(out, "{}", vcf)?;
+            }
+            records_written += 1;
+            Ok(())
+        };
+
+        // The records are only turned back into text here, and
+        // duplicates are merged as they're written, like
+        // `Vec::dedup_by` would
+        let mut kept: Option<VCFRecord> = None;
+        for mut vcf in block_records.iter() {
+            if let Some(kept) = kept.as_mut() {
+                if !args.no_dedup && vcf::merge_path_names(&mut vcf, kept) {
+                    continue;
+                }
+            }
+            if let Some(prev) = kept.replace(vcf) {
+                write_record(prev)?;
+            }
+        }
+        if let Some(prev) = kept {
+            write_record(prev)?;
+        }
+        out.flush()?;
+        if let Some(out_path) = merged_out {
+            block_offsets.push(std::fs::metadata(out_path)?.len());
+        }
+
+        batches_completed += 1;
+        if let Some(manifest) = manifest.as_mut() {
+            manifest.record(
+                "batch_completed",
+                json!({
+                    "batch": batch,
+                    "records": records_written - written_before,
+                }),
+            )?;
+        }
+    }
+
     info!("Variant identification complete");
 
-    all_vcf_records.sort_by(|v0, v1| v0.vcf_cmp(v1));
-    all_vcf_records.dedup();
+    if let Some(out_path) = merged_out {
+        drop(out);
+        if block_offsets.len() > 2 {
+            info!("Merging {} sorted blocks", block_offsets.len() - 1);
+            merge_flushed_blocks(out_path, &block_offsets)?;
+        }
+    }
+
+    if let Some(pool) = split_pool {
+        pool.finish()?;
+    }
 
-    info!("Writing {} unique VCF records", all_vcf_records.len());
+    if let Some(manifest) = manifest.as_mut() {
+        manifest.record(
+            "done",
+            json!({
+                "batches": batches_completed,
+                "records": records_written,
+            }),
+        )?;
+    }
 
-    let vcf_header = variants::vcf::VCFHeader::new(gfa_path);
+    let invalid_records = invalid_records.into_inner();
+    if invalid_records > 0 {
+        warn!("Dropped {} invalid VCF records", invalid_records);
+    }
 
-    println!("{}", vcf_header);
+    if args.no_dedup {
+        info!("Wrote {} VCF records", records_written);
+    } else {
+        info!("Wrote {} unique VCF records", records_written);
+    }
 
-    for vcf in all_vcf_records {
-        println!("{}", vcf);
+    if let Some(dotplot_path) = &args.dotplot {
+        write_dotplot(
+            dotplot_path,
+            args.dotplot_k,
+            &path_data,
+            ref_path_names.as_ref(),
+            &path_indices,
+            &ultrabubbles,
+        )?;
+    }
+
+    if let Some(density) = density.as_mut() {
+        write_density(
+            density,
+            &path_data,
+            args.density.as_ref(),
+            args.hotspots.as_ref(),
+            args.density_window,
+            args.density_step.unwrap_or(args.density_window),
+            args.hotspot_min,
+        )?;
+    }
+
+    if let Some(truth) = truth {
+        let mut summary: Box<dyn Write> =
+            if let Some(summary_path) = &args.truth_summary {
+                Box::new(File::create(summary_path)?)
+            } else {
+                Box::new(std::io::stderr())
+            };
+
+        writeln!(summary, "reference\tTP\tFP\tFN")?;
+        for (reference, counts) in truth.summary() {
+            writeln!(
+                summary,
+                "{}\t{}\t{}\t{}",
+                reference, counts.true_pos, counts.false_pos, counts.false_neg
+            )?;
+        }
     }
 
     Ok(())
@@ -215,6 +761,149 @@ pub fn gfa2vcf(gfa_path: &PathBuf, args: GFA2VCFArgs) -> Result<()> {
     */
 }
 
+/// Merge the blocks that --flush-every wrote to the output file, each
+/// sorted on its own, so that the whole file is sorted. `offsets` are
+/// the end of the header, followed by the end of each block. The
+/// merged file only replaces the output once it's complete, so an
+/// interrupted merge still leaves the blocks.
+fn merge_flushed_blocks(out_path: &Path, offsets: &[u64]) -> Result<()> {
+    let file = File::open(out_path)?;
+    // The output isn't written to again while it's mapped
+    let mmap = unsafe { Mmap::map(&file)? };
+    let runs: Vec<&[u8]> = offsets
+        .windows(2)
+        .map(|ends| &mmap[ends[0] as usize..ends[1] as usize])
+        .collect();
+
+    let mut merged_path = out_path.as_os_str().to_owned();
+    merged_path.push(".merging");
+    let merged_path = PathBuf::from(merged_path);
+
+    let mut merged = BufWriter::new(File::create(&merged_path)?);
+    merged.write_all(&mmap[..offsets[0] as usize])?;
+    compact::merge_sorted_runs(&runs, &mut merged)?;
+    merged.flush()?;
+    drop(merged);
+
+    std::fs::rename(&merged_path, out_path)?;
+    Ok(())
+}
+
+fn write_dotplot(
+    dotplot_path: &PathBuf,
+    k: usize,
+    path_data: &PathData,
+    ref_path_names: Option<&FnvHashSet<BString>>,
+    path_indices: &PathIndices,
+    ultrabubbles: &[(u64, u64)],
+) -> Result<()> {
+    info!("Finding complex bubbles for dotplot");
+
+    let mut rows: Vec<DotplotRow> = ultrabubbles
+        .par_iter()
+        .flat_map(|&(from, to)| {
+            dotplot::complex_bubble_alleles(
+                path_data,
+                ref_path_names,
+                path_indices,
+                from,
+                to,
+            )
+            .into_iter()
+            .flat_map(move |allele| {
+                dotplot::kmer_anchors(&allele, k).into_iter().map(
+                    move |anchor| DotplotRow {
+                        ref_name: allele.ref_name.clone(),
+                        from,
+                        to,
+                        query_name: allele.query_name.clone(),
+                        anchor,
+                    },
+                )
+            })
+            .collect::<Vec<_>>()
+        })
+        .collect();
+
+    rows.sort_by(|r0, r1| {
+        (
+            &r0.ref_name,
+            r0.from,
+            r0.to,
+            &r0.query_name,
+            r0.anchor.ref_start,
+        )
+            .cmp(&(
+                &r1.ref_name,
+                r1.from,
+                r1.to,
+                &r1.query_name,
+                r1.anchor.ref_start,
+            ))
+            .then(r0.anchor.query_start.cmp(&r1.anchor.query_start))
+    });
+
+    let mut out = BufWriter::new(File::create(dotplot_path)?);
+    writeln!(
+        out,
+        "#ref\tfrom\tto\tquery\tref_start\tref_end\tquery_start\tquery_end"
+    )?;
+    for row in rows.iter() {
+        writeln!(out, "{}", row)?;
+    }
+    out.flush()?;
+
+    info!(
+        "Wrote {} dotplot anchors to {}",
+        rows.len(),
+        dotplot_path.display()
+    );
+
+    Ok(())
+}
+
+fn write_density(
+    density: &mut VariantDensity,
+    path_data: &PathData,
+    density_path: Option<&PathBuf>,
+    hotspots_path: Option<&PathBuf>,
+    window: usize,
+    step: usize,
+    hotspot_min: f64,
+) -> Result<()> {
+    let path_lens = density::path_lengths(path_data);
+    let windows = density.windows(&path_lens, window, step);
+
+    if let Some(density_path) = density_path {
+        let mut out = BufWriter::new(File::create(density_path)?);
+        for window in windows.iter() {
+            writeln!(out, "{}", window)?;
+        }
+        out.flush()?;
+        info!(
+            "Wrote {} density windows to {}",
+            windows.len(),
+            density_path.display()
+        );
+    }
+
+    if let Some(hotspots_path) = hotspots_path {
+        let hotspots = density.hotspots(&windows, hotspot_min);
+        let mut out = BufWriter::new(File::create(hotspots_path)?);
+        for hotspot in hotspots.iter() {
+            writeln!(out, "{}", hotspot)?;
+        }
+        out.flush()?;
+        info!(
+            "Wrote {} hotspots to {}",
+            hotspots.len(),
+            hotspots_path.display()
+        );
+    }
+
+    Ok(())
+}
+
 #[allow(dead_code)]
 fn find_representative_paths(
     ultrabubbles: &[(u64, u64)],
@@ -0,0 +1,81 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::gfa::GFA;
+
+use crate::node_coverage;
+
+use super::{load_gfa, Result};
+
+/// Report how many paths, and which samples, traverse each segment.
+///
+/// The output is a TSV with one line per segment, with its name,
+/// length, the number of distinct paths that step on it, the total
+/// number of steps on it, the number of samples, and the comma
+/// separated names of the samples, or * if no path visits it. Samples
+/// are taken from PanSN path names, sample#haplotype#contig; paths
+/// whose names don't follow PanSN are samples of their own.
+#[derive(StructOpt, Debug)]
+pub struct NodeCoverageArgs {
+    /// Write the TSV to this file instead of stdout.
+    #[structopt(
+        name = "output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+}
+
+pub fn node_coverage(
+    gfa_path: &PathBuf,
+    args: &NodeCoverageArgs,
+) -> Result<()> {
+    let gfa: GFA<Vec<u8>, ()> = load_gfa(gfa_path)?;
+
+    let coverage = node_coverage::path_coverage(&gfa);
+    info!(
+        "Found {} samples in {} paths",
+        coverage.samples.len(),
+        gfa.paths.len()
+    );
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    writeln!(out, "segment\tlength\tpaths\tsteps\tsamples\tsample_names")?;
+    for (seg, seg_cov) in gfa.segments.iter().zip(coverage.segments.iter()) {
+        out.write_all(&seg.name)?;
+        write!(
+            out,
+            "\t{}\t{}\t{}\t{}\t",
+            seg.sequence.len(),
+            seg_cov.paths,
+            seg_cov.steps,
+            seg_cov.samples.len()
+        )?;
+        if seg_cov.samples.is_empty() {
+            out.write_all(b"*")?;
+        }
+        for (i, &sample) in seg_cov.samples.iter().enumerate() {
+            if i > 0 {
+                out.write_all(b",")?;
+            }
+            out.write_all(&coverage.samples[sample])?;
+        }
+        out.write_all(b"\n")?;
+    }
+    out.flush()?;
+
+    Ok(())
+}
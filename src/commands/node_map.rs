@@ -0,0 +1,166 @@
+use bstr::BString;
+use fnv::FnvHashSet;
+use structopt::StructOpt;
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    process,
+};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::gfa::GFA;
+
+use crate::{
+    node_map,
+    provenance::{load_provenance, Provenance},
+    util::bytes_from_os_str,
+    variants,
+};
+
+use super::{gfa2vcf::load_paths_file, load_numeric_gfa, Result};
+
+/// Output a TSV mapping each step of the reference paths to the
+/// interval it covers on the path, sorted by path and start position.
+///
+/// The columns are node ID, path, start, end, orientation, and the
+/// step's rank on the path. Coordinates are 0-based and half-open.
+#[derive(StructOpt, Debug)]
+pub struct NodeMapArgs {
+    /// Write the table to this file instead of stdout.
+    #[structopt(
+        name = "TSV output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    /// Compress the output with `bgzip` and index it with `tabix`,
+    /// both of which must be available in PATH. Requires `-o`.
+    #[structopt(name = "bgzip", long = "bgzip", requires = "TSV output file")]
+    bgzip: bool,
+    /// A file with the names of the paths to include, one per line.
+    /// If neither this nor --refs is given, all paths are included.
+    #[structopt(
+        name = "file containing paths to use as references",
+        long = "paths-file",
+        parse(from_os_str)
+    )]
+    ref_paths_file: Option<PathBuf>,
+    #[structopt(
+        name = "list of paths to use as references",
+        long = "refs",
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    ref_paths_vec: Option<Vec<BString>>,
+    /// Add the columns orig_node, orig_start, and orig_end, with
+    /// the interval each node covers on the segment it was derived
+    /// from, according to the segment's OI provenance tag. Nodes
+    /// without the tag are their own origin.
+    #[structopt(name = "provenance", long = "provenance")]
+    provenance: bool,
+}
+
+fn run_tool(program: &str, args: &[&str]) -> Result<()> {
+    debug!("Running {} {}", program, args.join(" "));
+    let status = process::Command::new(program).args(args).status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} exited with {}", program, status).into())
+    }
+}
+
+pub fn node_map(gfa_path: &PathBuf, args: &NodeMapArgs) -> Result<()> {
+    let mut ref_paths: FnvHashSet<BString> = args
+        .ref_paths_vec
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+
+    if let Some(paths_file) = &args.ref_paths_file {
+        ref_paths.extend(load_paths_file(paths_file.clone())?);
+    }
+
+    let path_data = {
+        let gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+
+        for path in ref_paths.iter() {
+            if !gfa.paths.iter().any(|p| p.path_name == *path) {
+                return Err(format!(
+                    "Reference path does not exist in graph: {}",
+                    path
+                )
+                .into());
+            }
+        }
+
+        variants::gfa_path_data(gfa)
+    };
+
+    let ref_paths = if ref_paths.is_empty() {
+        None
+    } else {
+        Some(&ref_paths)
+    };
+
+    let provenance = if args.provenance {
+        Some(load_provenance(gfa_path)?)
+    } else {
+        None
+    };
+
+    let positions = node_map::node_positions(&path_data, ref_paths);
+    info!("Writing {} node positions", positions.len());
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    if let Some(provenance) = provenance {
+        writeln!(
+            out,
+            "#node\tpath\tstart\tend\torient\trank\t\
+             orig_node\torig_start\torig_end"
+        )?;
+        for pos in positions {
+            let orig = provenance
+                .get(&pos.node)
+                .cloned()
+                .unwrap_or_else(|| Provenance::new(pos.node.to_string(), 0));
+            let orig_end = orig.offset + pos.end - pos.start;
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}",
+                pos, orig.orig_id, orig.offset, orig_end
+            )?;
+        }
+    } else {
+        writeln!(out, "#node\tpath\tstart\tend\torient\trank")?;
+        for pos in positions {
+            writeln!(out, "{}", pos)?;
+        }
+    }
+    out.flush()?;
+    drop(out);
+
+    if let (true, Some(out_path)) = (args.bgzip, &args.out) {
+        let out_path = out_path.to_string_lossy();
+        run_tool("bgzip", &["-f", &out_path])?;
+
+        let gz_path = format!("{}.gz", out_path);
+        info!("Indexing {}", gz_path);
+        run_tool(
+            "tabix",
+            &["-f", "-0", "-s", "2", "-b", "3", "-e", "4", &gz_path],
+        )?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use std::{io::Write, path::PathBuf};
+use structopt::StructOpt;
+
+use gfa::{gfa::GFA, optfields::OptionalFields};
+
+use crate::{
+    compression::{self, Compression},
+    paf_convert,
+};
+
+use super::{load_gfa, Result};
+
+/// Convert a file of PAF records, aligned against the segments or
+/// paths of the GFA, into GAF records.
+///
+/// Consecutive records of the same query that continue from the end
+/// of one segment to the start of the next are merged into a single
+/// GAF record, with the CIGARs concatenated. Records against paths
+/// use the path's stable coordinates. The PAF may be gzip or zstd
+/// compressed.
+#[derive(StructOpt, Debug)]
+pub struct PAF2GAFArgs {
+    #[structopt(name = "path to PAF file", long = "paf", parse(from_os_str))]
+    paf: PathBuf,
+    #[structopt(name = "GAF output file", short = "o", long = "gaf")]
+    out: Option<PathBuf>,
+    /// Compress the output with gzip or zstd. Zstd requires `zstd`
+    /// in PATH.
+    #[structopt(
+        name = "none|gzip|zstd",
+        long = "compress",
+        default_value = "none",
+        possible_values = &Compression::variants(),
+        case_insensitive = true
+    )]
+    compress: Compression,
+}
+
+pub fn paf2gaf(gfa_path: &PathBuf, args: &PAF2GAFArgs) -> Result<()> {
+    let gfa: GFA<Vec<u8>, OptionalFields> = load_gfa(gfa_path)?;
+
+    let pafs = paf_convert::load_pafs(&args.paf)?;
+    let gafs = paf_convert::paf_to_gaf(&gfa, &pafs);
+
+    let mut out = compression::create_output(args.out.as_ref(), args.compress)?;
+
+    for gaf in gafs {
+        writeln!(out, "{}", gaf)?;
+    }
+    out.finish()?;
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use bstr::BString;
+use std::{io::Write, path::PathBuf};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::{gfa::GFA, optfields::OptionalFields};
+
+use crate::{
+    compression::{self, Compression},
+    paf_convert,
+    util::bytes_from_os_str,
+};
+
+use super::{load_gfa, Result};
+
+/// Lift PAF records against the segments of the GFA, such as the
+/// output of gaf2paf, onto a reference path, so they can be compared
+/// with alignments against the linear reference.
+///
+/// The target name, length, and range of each record are rewritten
+/// to the path's coordinates, and the strand is flipped for segments
+/// that are reversed on the path. Consecutive records of a query that
+/// stay on the path are merged into one record. Records against
+/// segments that aren't on the path are dropped. The PAF may be gzip
+/// or zstd compressed.
+#[derive(StructOpt, Debug)]
+pub struct PAFLiftArgs {
+    #[structopt(name = "path to PAF file", long = "paf", parse(from_os_str))]
+    paf: PathBuf,
+    /// The name of the path to lift the records onto.
+    #[structopt(
+        name = "reference path",
+        long = "ref",
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    ref_path: BString,
+    /// Write the PAF to this file instead of stdout.
+    #[structopt(
+        name = "PAF output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    /// Compress the output with gzip or zstd. Zstd requires `zstd`
+    /// in PATH.
+    #[structopt(
+        name = "none|gzip|zstd",
+        long = "compress",
+        default_value = "none",
+        possible_values = &Compression::variants(),
+        case_insensitive = true
+    )]
+    compress: Compression,
+}
+
+pub fn paf_lift(gfa_path: &PathBuf, args: &PAFLiftArgs) -> Result<()> {
+    let gfa: GFA<Vec<u8>, OptionalFields> = load_gfa(gfa_path)?;
+
+    let index = paf_convert::PathLiftIndex::new(&gfa, &args.ref_path)
+        .ok_or_else(|| {
+            format!("Reference path does not exist in graph: {}", args.ref_path)
+        })?;
+    if index.repeated_segments > 0 {
+        warn!(
+            "{} steps on {} revisit a segment, only the first visit of \
+             each segment is used",
+            index.repeated_segments, index.name
+        );
+    }
+
+    let pafs = paf_convert::load_pafs(&args.paf)?;
+    let (lifted, skipped) = paf_convert::lift_pafs(&index, &pafs);
+
+    let mut out = compression::create_output(args.out.as_ref(), args.compress)?;
+    for paf in lifted.iter() {
+        writeln!(out, "{}", paf)?;
+    }
+    out.finish()?;
+
+    info!(
+        "Lifted {} PAF records onto {} as {} records, {} not on the path",
+        pafs.len() - skipped,
+        index.name,
+        lifted.len(),
+        skipped
+    );
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use bstr::BString;
+use structopt::StructOpt;
+
+use std::{fs::File, io::Write, path::PathBuf};
+
+use gfa::{
+    gafpaf::PAF,
+    gfa::{Orientation, GFA},
+    optfields::{OptField, OptFieldVal, OptionalFields},
+};
+
+use crate::{path_align, util::bytes_from_os_str, variants};
+
+use super::{load_numeric_gfa, Result};
+
+/// Compare two paths in the graph by their shared nodes, and output
+/// the result as a PAF record with a CIGAR string in the `cg` tag.
+///
+/// Nodes that occur exactly once in both paths are used as anchors
+/// and reported as matches, while the nodes between anchors are
+/// reported as insertions or deletions. No sequence alignment is
+/// performed.
+#[derive(StructOpt, Debug)]
+pub struct PathAlignArgs {
+    /// Name of the query path
+    #[structopt(
+        name = "query path",
+        long = "query",
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    query: BString,
+    /// Name of the target path
+    #[structopt(
+        name = "target path",
+        long = "target",
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    target: BString,
+    #[structopt(name = "PAF output file", short = "o", long = "paf")]
+    out: Option<PathBuf>,
+}
+
+pub fn path_align(gfa_path: &PathBuf, args: &PathAlignArgs) -> Result<()> {
+    let gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+    let path_data = variants::gfa_path_data(gfa);
+
+    let find_path = |name: &BString| {
+        path_data
+            .path_names
+            .iter()
+            .position(|p| p == name)
+            .ok_or_else(|| format!("Path does not exist in graph: {}", name))
+    };
+
+    let query_ix = find_path(&args.query)?;
+    let target_ix = find_path(&args.target)?;
+
+    let query = &path_data.paths[query_ix];
+    let target = &path_data.paths[target_ix];
+
+    let alignment =
+        path_align::align_paths(&path_data.segment_map, query, target)
+            .ok_or_else(|| {
+                format!(
+                    "Paths {} and {} have no shared anchor nodes",
+                    args.query, args.target
+                )
+            })?;
+
+    let cigar: BString = alignment.cigar_string().into();
+    let optional: OptionalFields = vec![OptField {
+        tag: *b"cg",
+        value: OptFieldVal::Z(cigar),
+    }];
+
+    let paf = PAF {
+        query_seq_name: args.query.clone().into(),
+        query_seq_len: path_data.path_length(query_ix),
+        query_seq_range: alignment.query_range,
+        strand: Orientation::Forward,
+        target_seq_name: args.target.clone().into(),
+        target_seq_len: path_data.path_length(target_ix),
+        target_seq_range: alignment.target_range,
+        residue_matches: alignment.matches,
+        block_length: alignment.block_length,
+        quality: 255,
+        optional,
+    };
+
+    if let Some(out_path) = &args.out {
+        let mut out_file = File::create(&out_path)?;
+        writeln!(out_file, "{}", paf)?;
+    } else {
+        println!("{}", paf);
+    }
+
+    Ok(())
+}
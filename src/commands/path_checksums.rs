@@ -0,0 +1,150 @@
+use bstr::{BString, ByteSlice};
+use fnv::FnvHashMap;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::gfa::{Orientation, GFA};
+
+use crate::{
+    compression,
+    fasta::{FastaReader, SequenceChecksums},
+    node_seq,
+};
+
+use super::{load_numeric_gfa, Result};
+
+/// Compute the MD5 and SHA-256 checksums of the sequence spelled by
+/// each path.
+///
+/// The checksums are of the uppercase sequence, like the M5 tag of
+/// SAM headers and the md5 field of VCF contig lines. The output is
+/// TSV, with the name, length, and checksums of each path. With
+/// --fasta, the paths are compared against the FASTA records of the
+/// same name, e.g. the assemblies the graph was built from, and a
+/// column is added that is `match`, `mismatch`, or `missing` if there
+/// is no record with the path's name.
+#[derive(StructOpt, Debug)]
+pub struct PathChecksumsArgs {
+    /// Write the checksums to this file instead of stdout.
+    #[structopt(
+        name = "output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    /// Compare the paths against the records in this FASTA file,
+    /// which may be gzip or zstd compressed.
+    #[structopt(name = "FASTA file", long = "fasta", parse(from_os_str))]
+    fasta: Option<PathBuf>,
+}
+
+struct PathChecksum {
+    name: BString,
+    length: usize,
+    checksums: SequenceChecksums,
+}
+
+pub fn path_checksums(
+    gfa_path: &PathBuf,
+    args: &PathChecksumsArgs,
+) -> Result<()> {
+    let mut gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+
+    let paths = std::mem::take(&mut gfa.paths);
+    let segment_map = node_seq::segment_sequences(gfa);
+
+    let mut path_sums = Vec::with_capacity(paths.len());
+    for path in paths.iter() {
+        let steps: Vec<(usize, Orientation)> = path.iter().collect();
+        let seq = node_seq::traversal_sequence(&segment_map, &steps).map_err(
+            |err| format!("Error in path {}: {}", path.path_name, err),
+        )?;
+        path_sums.push(PathChecksum {
+            name: path.path_name.clone(),
+            length: seq.len(),
+            checksums: SequenceChecksums::from_sequence(&seq),
+        });
+    }
+    info!("Computed checksums of {} paths", path_sums.len());
+
+    let mut fasta_matches: Option<Vec<Option<bool>>> = None;
+    if let Some(fasta_path) = &args.fasta {
+        let path_ix: FnvHashMap<&[u8], usize> = path_sums
+            .iter()
+            .enumerate()
+            .map(|(ix, sum)| (sum.name.as_slice(), ix))
+            .collect();
+
+        let mut matches = vec![None; path_sums.len()];
+        let mut unused_records = 0;
+
+        let input = compression::open_input(fasta_path)?;
+        for record in FastaReader::new(BufReader::new(input)) {
+            let record = record?;
+            if let Some(&ix) = path_ix.get(record.name.as_slice()) {
+                let checksums =
+                    SequenceChecksums::from_sequence(&record.sequence);
+                matches[ix] = Some(checksums == path_sums[ix].checksums);
+            } else {
+                unused_records += 1;
+            }
+        }
+
+        let missing = matches.iter().filter(|m| m.is_none()).count();
+        let mismatches = matches.iter().filter(|&&m| m == Some(false)).count();
+        if missing > 0 {
+            warn!("{} paths have no FASTA record", missing);
+        }
+        if mismatches > 0 {
+            warn!("{} paths don't match their FASTA records", mismatches);
+        }
+        if unused_records > 0 {
+            info!("{} FASTA records have no path", unused_records);
+        }
+
+        fasta_matches = Some(matches);
+    }
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    write!(out, "path\tlength\tmd5\tsha256")?;
+    if fasta_matches.is_some() {
+        write!(out, "\tfasta")?;
+    }
+    writeln!(out)?;
+
+    for (ix, sum) in path_sums.iter().enumerate() {
+        write!(
+            out,
+            "{}\t{}\t{}\t{}",
+            sum.name.as_bstr(),
+            sum.length,
+            sum.checksums.md5,
+            sum.checksums.sha256
+        )?;
+        if let Some(matches) = &fasta_matches {
+            let status = match matches[ix] {
+                Some(true) => "match",
+                Some(false) => "mismatch",
+                None => "missing",
+            };
+            write!(out, "\t{}", status)?;
+        }
+        writeln!(out)?;
+    }
+    out.flush()?;
+
+    Ok(())
+}
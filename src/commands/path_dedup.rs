@@ -0,0 +1,82 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+use gfa::{gfa::GFA, optfields::OptionalFields};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use crate::{path_dedup, util::write_gfa_bytes};
+
+use super::{load_gfa, Result};
+
+/// Find paths that are identical or nearly identical to another path
+/// in the graph, e.g. from redundant input assemblies.
+///
+/// Outputs a TSV with one row per duplicate path, with the columns
+/// path, the earlier path it duplicates, and the number of steps by
+/// which they differ.
+#[derive(StructOpt, Debug)]
+pub struct PathDedupArgs {
+    /// Also report paths that differ from another path by at most
+    /// this many inserted, deleted, or substituted steps.
+    #[structopt(
+        name = "max step differences",
+        long = "max-diff",
+        default_value = "0"
+    )]
+    max_diff: usize,
+    /// Write the report to this file instead of stdout.
+    #[structopt(
+        name = "report output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    /// Write a copy of the GFA without the duplicate paths to this
+    /// file.
+    #[structopt(name = "deduplicated GFA", long = "drop", parse(from_os_str))]
+    drop: Option<PathBuf>,
+}
+
+pub fn path_dedup(gfa_path: &PathBuf, args: &PathDedupArgs) -> Result<()> {
+    let mut gfa: GFA<Vec<u8>, OptionalFields> = load_gfa(gfa_path)?;
+
+    let duplicates = path_dedup::find_duplicate_paths(&gfa, args.max_diff);
+    info!(
+        "Found {} duplicate paths out of {}",
+        duplicates.len(),
+        gfa.paths.len()
+    );
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    writeln!(out, "#path\tduplicate_of\tdifferences")?;
+    for dup in duplicates.iter() {
+        writeln!(
+            out,
+            "{}\t{}\t{}",
+            dup.path, dup.duplicate_of, dup.differences
+        )?;
+    }
+    out.flush()?;
+
+    if let Some(drop_path) = &args.drop {
+        path_dedup::drop_duplicate_paths(&mut gfa, &duplicates);
+        let mut gfa_out = BufWriter::new(File::create(drop_path)?);
+        write_gfa_bytes(&gfa, &mut gfa_out)?;
+        gfa_out.flush()?;
+        info!("Wrote {} paths to {}", gfa.paths.len(), drop_path.display());
+    }
+
+    Ok(())
+}
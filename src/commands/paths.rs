@@ -0,0 +1,92 @@
+use bstr::ByteSlice;
+use structopt::StructOpt;
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use gfa::gfa::{Orientation, GFA};
+
+use crate::{node_seq, stats};
+
+use super::{load_numeric_gfa, Result};
+
+/// List every path with its number of steps, sequence length, first
+/// and last segments, and the fraction of its steps that are on the
+/// reverse strand, as TSV.
+///
+/// With --fasta, the sequences spelled by the paths are also written
+/// as FASTA.
+#[derive(StructOpt, Debug)]
+pub struct PathsArgs {
+    /// Write the TSV to this file instead of stdout.
+    #[structopt(
+        name = "output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    /// Write the sequence of each path to this FASTA file.
+    #[structopt(
+        name = "FASTA output file",
+        long = "fasta",
+        parse(from_os_str)
+    )]
+    fasta: Option<PathBuf>,
+}
+
+fn segment_or_star(segment: Option<usize>) -> String {
+    segment.map_or_else(|| "*".to_string(), |seg| seg.to_string())
+}
+
+pub fn paths(gfa_path: &PathBuf, args: &PathsArgs) -> Result<()> {
+    let mut gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    writeln!(out, "path\tsteps\tlength\tfirst\tlast\treverse_fraction")?;
+    for summary in stats::path_summaries(&gfa) {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}\t{:.4}",
+            summary.name,
+            summary.steps,
+            summary.length,
+            segment_or_star(summary.first),
+            segment_or_star(summary.last),
+            summary.reverse_fraction()
+        )?;
+    }
+    out.flush()?;
+
+    if let Some(fasta_path) = &args.fasta {
+        let paths = std::mem::take(&mut gfa.paths);
+        let segment_map = node_seq::segment_sequences(gfa);
+
+        let mut fasta = BufWriter::new(File::create(fasta_path)?);
+        for path in paths.iter() {
+            let steps: Vec<(usize, Orientation)> = path.iter().collect();
+            let seq = node_seq::traversal_sequence(&segment_map, &steps)
+                .map_err(|err| {
+                    format!("Error in path {}: {}", path.path_name, err)
+                })?;
+            fasta.write_all(b">")?;
+            fasta.write_all(&path.path_name)?;
+            writeln!(fasta, "\n{}", seq.as_bstr())?;
+        }
+        fasta.flush()?;
+        info!("Wrote {} path sequences", paths.len());
+    }
+
+    Ok(())
+}
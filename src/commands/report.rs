@@ -0,0 +1,178 @@
+use std::{fs::File, io::Write, path::PathBuf};
+use structopt::StructOpt;
+
+use gfa::gfa::GFA;
+use handlegraph::hashgraph::HashGraph;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use crate::{edges, report::HtmlReport, stats, util::NodeSet, variants};
+
+use super::{load_numeric_gfa, Result};
+
+/// Render the stats, path, degree, ultrabubble, and component
+/// analyses of the input GFA into a single self-contained HTML file.
+#[derive(StructOpt, Debug)]
+pub struct ReportArgs {
+    /// Load ultrabubbles from a file instead of calculating them.
+    #[structopt(
+        name = "ultrabubbles file",
+        long = "ultrabubbles",
+        short = "u"
+    )]
+    ultrabubbles_file: Option<PathBuf>,
+    /// Skip the ultrabubble analysis.
+    #[structopt(name = "skip ultrabubbles", long = "no-bubbles")]
+    no_bubbles: bool,
+    /// Write the report to this file instead of stdout.
+    #[structopt(
+        name = "HTML output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+}
+
+// Only this many rows are shown in the per-path and per-component
+// charts, as large graphs can have thousands of either
+const MAX_CHART_BARS: usize = 40;
+
+fn histogram_bars(hist: &[(usize, usize)]) -> Vec<(String, f64)> {
+    hist.iter()
+        .take(MAX_CHART_BARS)
+        .map(|(v, c)| (v.to_string(), *c as f64))
+        .collect()
+}
+
+pub fn report_html(gfa_path: &PathBuf, args: &ReportArgs) -> Result<()> {
+    let gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+
+    let mut report =
+        HtmlReport::new(&format!("gfautil report: {}", gfa_path.display()));
+
+    info!("Computing graph summary");
+    let summary = stats::graph_summary(&gfa);
+    report.heading("Summary");
+    report.table(
+        &["Measure", "Value"],
+        &[
+            vec!["Segments".into(), summary.segments.to_string()],
+            vec!["Links".into(), summary.links.to_string()],
+            vec!["Containments".into(), summary.containments.to_string()],
+            vec!["Paths".into(), summary.paths.to_string()],
+            vec!["Total bp".into(), summary.total_bp.to_string()],
+        ],
+    );
+
+    info!("Computing path statistics");
+    let path_summaries = stats::path_summaries(&gfa);
+    report.heading("Paths");
+    let rows: Vec<Vec<String>> = path_summaries
+        .iter()
+        .map(|p| {
+            vec![
+                p.name.to_string(),
+                p.steps.to_string(),
+                p.length.to_string(),
+                format!("{:.3}", p.reverse_fraction()),
+            ]
+        })
+        .collect();
+    report.table(&["Path", "Steps", "Length", "Reverse fraction"], &rows);
+    let path_bars: Vec<(String, f64)> = path_summaries
+        .iter()
+        .take(MAX_CHART_BARS)
+        .map(|p| (p.name.to_string(), p.length as f64))
+        .collect();
+    report.bar_chart("Path lengths (bp)", &path_bars);
+
+    info!("Computing node degrees");
+    let edge_counts = {
+        let hashgraph = HashGraph::from_gfa(&gfa);
+        edges::graph_edge_count(&hashgraph)
+    };
+    let degree_hist = edges::total_degree_histogram(&edge_counts);
+    report.heading("Node degree");
+    let rows: Vec<Vec<String>> = degree_hist
+        .iter()
+        .map(|(d, c)| vec![d.to_string(), c.to_string()])
+        .collect();
+    report.table(&["Total degree", "Nodes"], &rows);
+    report.bar_chart("Nodes per total degree", &histogram_bars(&degree_hist));
+
+    info!("Computing connected components");
+    let components = stats::weakly_connected_components(&gfa);
+    report.heading("Connected components");
+    report.paragraph(&format!(
+        "{} weakly connected components",
+        components.len()
+    ));
+    let rows: Vec<Vec<String>> = components
+        .iter()
+        .enumerate()
+        .take(MAX_CHART_BARS)
+        .map(|(ix, comp)| {
+            vec![
+                ix.to_string(),
+                comp.segments.len().to_string(),
+                comp.total_bp.to_string(),
+            ]
+        })
+        .collect();
+    report.table(&["Component", "Segments", "bp"], &rows);
+
+    if !args.no_bubbles {
+        info!("Computing ultrabubble statistics");
+        let ultrabubbles = if let Some(path) = &args.ultrabubbles_file {
+            super::saboten::load_ultrabubbles(path)
+        } else {
+            super::saboten::find_ultrabubbles(gfa_path)
+        }?;
+
+        let path_data = variants::gfa_path_data(gfa);
+
+        let ultrabubble_nodes = ultrabubbles
+            .iter()
+            .flat_map(|&(a, b)| {
+                use std::iter::once;
+                once(a).chain(once(b))
+            })
+            .collect::<NodeSet>();
+
+        let path_indices =
+            variants::bubble_path_indices(&path_data.paths, &ultrabubble_nodes);
+
+        let traversals = variants::bubble_traversal_counts(
+            &path_data,
+            &path_indices,
+            &ultrabubbles,
+        );
+        let traversal_hist = stats::histogram(traversals);
+
+        report.heading("Ultrabubbles");
+        report.paragraph(&format!("{} ultrabubbles", ultrabubbles.len()));
+        let rows: Vec<Vec<String>> = traversal_hist
+            .iter()
+            .map(|(t, c)| vec![t.to_string(), c.to_string()])
+            .collect();
+        report.table(&["Distinct path traversals", "Bubbles"], &rows);
+        report.bar_chart(
+            "Bubbles per number of traversals",
+            &histogram_bars(&traversal_hist),
+        );
+    }
+
+    let html = report.finish();
+
+    if let Some(out_path) = &args.out {
+        let mut out_file = File::create(&out_path)?;
+        out_file.write_all(html.as_bytes())?;
+        info!("Saved report to {}", out_path.display());
+    } else {
+        print!("{}", html);
+    }
+
+    Ok(())
+}
@@ -45,7 +45,13 @@ pub fn find_ultrabubbles(gfa_path: &PathBuf) -> Result<Vec<(u64, u64)>> {
 
     info!("Computing ultrabubbles");
     let be_graph = {
-        let gfa: GFA<usize, ()> = parser.parse_file(gfa_path)?;
+        // The biedged graph has the vertices 2 * id and 2 * id + 1 for
+        // each segment, so the IDs are bounded tighter than by usize
+        let gfa: GFA<usize, ()> = super::parse_numeric_gfa(
+            &parser,
+            gfa_path,
+            crate::util::MAX_BUBBLE_SEGMENT_ID,
+        )?;
 
         debug!("Building biedged graph");
         let t = std::time::Instant::now();
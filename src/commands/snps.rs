@@ -1,30 +1,46 @@
-use bstr::BString;
+use bstr::{BString, ByteSlice};
 use fnv::{FnvHashMap, FnvHashSet};
-use std::path::PathBuf;
+use std::{collections::BTreeMap, path::PathBuf};
 use structopt::StructOpt;
 
-use indicatif::ProgressIterator;
-
-use gfa::gfa::GFA;
+use indicatif::ParallelProgressIterator;
+use rayon::prelude::*;
 
 #[allow(unused_imports)]
 use log::{debug, info, log_enabled, warn};
 
 use crate::{
-    util::progress_bar,
+    util::{bytes_from_os_str, progress_bar, BedRegions, NodeSet},
     variants,
-    variants::{PathStep, SNPRow},
+    variants::{
+        vcf::{VCFHeader, VCFRecord},
+        PathData, PathIndices, PathStep, VariantKind, VariantRow,
+    },
 };
 
-use super::{load_gfa, Result};
+use super::{gfa2vcf::load_paths_file, load_path_data, Result};
 
-/// Given a reference path from the GFA, by name, find and report the
-/// SNPs for all other paths compared to the reference.
+/// Given one or more reference paths from the GFA, by name, find and
+/// report the SNPs for all other paths compared to each reference.
 #[derive(StructOpt, Debug)]
 pub struct SNPArgs {
-    #[structopt(name = "name of reference path", long = "ref", short = "r")]
-    /// The name of the path to be used as reference.
-    ref_path: String,
+    /// The names of the paths to be used as reference.
+    #[structopt(
+        name = "name of reference path",
+        long = "ref",
+        short = "r",
+        required_unless("reference paths file"),
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    ref_paths: Vec<BString>,
+    /// Path to a file containing the names of the reference paths,
+    /// one per line.
+    #[structopt(
+        name = "reference paths file",
+        long = "refs-file",
+        parse(from_os_str)
+    )]
+    ref_paths_file: Option<PathBuf>,
     /// A list of SNP positions to use.
     #[structopt(
         name = "SNP positions",
@@ -49,6 +65,173 @@ pub struct SNPArgs {
         required_unless_one(&["SNP positions", "SNP positions file"])
     )]
     ultrabubbles_file: Option<PathBuf>,
+    /// Only evaluate the bubbles whose span on the reference overlaps
+    /// a region in this BED file, in reference path coordinates.
+    #[structopt(name = "BED regions", long = "bed", parse(from_os_str))]
+    bed: Option<PathBuf>,
+    /// Output the SNPs as VCF, with one record per reference
+    /// position, instead of one TSV row per path and SNP.
+    #[structopt(name = "VCF output", long = "vcf", conflicts_with = "matrix")]
+    vcf: bool,
+    /// Output a genotype matrix with one row per SNP site and one
+    /// column per path, where 0 is the reference base, 1..n are the
+    /// ALT bases, and . means the path doesn't traverse the site.
+    #[structopt(name = "matrix", long = "matrix")]
+    matrix: bool,
+    /// Also report insertions and deletions in the TSV output, with
+    /// an additional column for the variant type. Indels are
+    /// anchored on the preceding reference base, as in VCF.
+    #[structopt(
+        name = "include indels",
+        long = "indels",
+        conflicts_with_all(&["VCF output", "matrix"])
+    )]
+    indels: bool,
+    /// Load the path data from this binary file, if it exists,
+    /// instead of parsing the GFA; otherwise build it from the GFA
+    /// and save it to the file. The file is rejected if the GFA has
+    /// changed since.
+    #[structopt(name = "path data file", long = "path-data")]
+    path_data: Option<PathBuf>,
+}
+
+/// The paths that traverse a SNP site, and the ALT bases of the
+/// paths that differ from the reference there
+#[derive(Debug, Default)]
+struct GenotypeSite {
+    alts: FnvHashMap<BString, u8>,
+    traversing: FnvHashSet<BString>,
+}
+
+/// Add the SNPs found in one bubble to the genotype sites. Every path
+/// in the results traverses the bubble, so paths without a SNP at a
+/// site in the bubble have the reference base there.
+fn add_genotype_sites(
+    sites: &mut BTreeMap<(usize, u8), GenotypeSite>,
+    snp_results: &FnvHashMap<BString, Vec<VariantRow>>,
+) {
+    let bubble_sites: FnvHashSet<(usize, u8)> = snp_results
+        .values()
+        .flatten()
+        .filter(|row| row.kind == VariantKind::Snv)
+        .map(|snp| (snp.ref_pos, snp.ref_seq[0]))
+        .collect();
+
+    for site_key in bubble_sites {
+        let site = sites.entry(site_key).or_default();
+        site.traversing.extend(snp_results.keys().cloned());
+    }
+
+    for (name, snp_rows) in snp_results.iter() {
+        for snp in snp_rows.iter() {
+            if snp.kind != VariantKind::Snv {
+                continue;
+            }
+            if let Some(site) = sites.get_mut(&(snp.ref_pos, snp.ref_seq[0])) {
+                site.alts.insert(name.clone(), snp.query_seq[0]);
+            }
+        }
+    }
+}
+
+type SNPAccumulator = (
+    FnvHashMap<BString, Vec<VariantRow>>,
+    BTreeMap<(usize, u8), GenotypeSite>,
+);
+
+fn merge_accumulators(
+    mut acc: SNPAccumulator,
+    other: SNPAccumulator,
+) -> SNPAccumulator {
+    let (other_rows, other_sites) = other;
+
+    for (name, rows) in other_rows {
+        acc.0.entry(name).or_default().extend(rows);
+    }
+
+    for (key, other_site) in other_sites {
+        let site = acc.1.entry(key).or_default();
+        site.alts.extend(other_site.alts);
+        site.traversing.extend(other_site.traversing);
+    }
+
+    acc
+}
+
+fn print_genotype_matrix(
+    ref_name: &BString,
+    path_names: &[BString],
+    sites: &BTreeMap<(usize, u8), GenotypeSite>,
+) {
+    for (&(ref_pos, ref_base), site) in sites.iter() {
+        let mut alts: Vec<u8> = site.alts.values().copied().collect();
+        alts.sort();
+        alts.dedup();
+
+        let alt_str: Vec<u8> = bstr::join(",", alts.chunks(1));
+
+        print!(
+            "{}\t{}\t{}\t{}",
+            ref_name,
+            ref_pos,
+            char::from(ref_base),
+            alt_str.as_bstr()
+        );
+
+        for name in path_names {
+            if let Some(base) = site.alts.get(name) {
+                let alt_ix = alts.iter().position(|b| b == base).unwrap();
+                print!("\t{}", alt_ix + 1);
+            } else if site.traversing.contains(name) {
+                print!("\t0");
+            } else {
+                print!("\t.");
+            }
+        }
+        println!();
+    }
+}
+
+/// Merge the SNPs of all paths into one VCF record per reference
+/// position, with the distinct query bases as the ALT alleles
+fn snp_vcf_records(
+    ref_path_name: &BString,
+    path_snp_rows: &FnvHashMap<BString, Vec<VariantRow>>,
+) -> Vec<VCFRecord> {
+    let mut sites: BTreeMap<(usize, u8), FnvHashSet<u8>> = BTreeMap::new();
+
+    for snp in path_snp_rows.values().flatten() {
+        if snp.kind == VariantKind::Snv {
+            sites
+                .entry((snp.ref_pos, snp.ref_seq[0]))
+                .or_default()
+                .insert(snp.query_seq[0]);
+        }
+    }
+
+    sites
+        .into_iter()
+        .map(|((ref_pos, ref_base), query_bases)| {
+            let mut alts: Vec<u8> = query_bases.into_iter().collect();
+            alts.sort();
+
+            let alternate: Vec<u8> = bstr::join(",", alts.chunks(1));
+            let types = vec!["snv"; alts.len()].join(",");
+
+            VCFRecord {
+                chromosome: ref_path_name.clone(),
+                position: ref_pos as i64,
+                id: None,
+                reference: std::iter::once(ref_base).collect(),
+                alternate: Some(alternate.into()),
+                quality: None,
+                filter: None,
+                info: Some(format!("TYPE={}", types).into()),
+                format: None,
+                sample_name: None,
+            }
+        })
+        .collect()
 }
 
 fn snp_positions(args: &SNPArgs) -> Result<Vec<usize>> {
@@ -64,7 +247,7 @@ fn snp_positions(args: &SNPArgs) -> Result<Vec<usize>> {
     }
 
     if res.is_empty() {
-        panic!("No SNPs were provided");
+        return Err("No SNPs were provided".into());
     }
 
     Ok(res)
@@ -114,90 +297,263 @@ fn build_snp_reference_bubbles(
     res
 }
 
+/// The 0-based, half-open interval on the reference path that is
+/// spanned by the bubble, including its boundary nodes
+fn bubble_ref_span(
+    path_data: &PathData,
+    path_indices: &PathIndices,
+    ref_path_ix: usize,
+    from: u64,
+    to: u64,
+) -> Option<(usize, usize)> {
+    let from_ix = *path_indices.get(&from)?.get(&ref_path_ix)?;
+    let to_ix = *path_indices.get(&to)?.get(&ref_path_ix)?;
+
+    let ref_path = &path_data.paths[ref_path_ix];
+    let (_, start, _) = ref_path[from_ix.min(to_ix)];
+    let (last_node, last_offset, _) = ref_path[from_ix.max(to_ix)];
+    let last_len = path_data.segment_map[&last_node].len();
+
+    Some((start - 1, last_offset - 1 + last_len))
+}
+
 pub fn gfa2snps(gfa_path: &PathBuf, args: SNPArgs) -> Result<()> {
-    let ref_path_name: BString = BString::from(args.ref_path.as_str());
+    let mut ref_path_names: Vec<BString> = args.ref_paths.clone();
+
+    if let Some(file_path) = &args.ref_paths_file {
+        ref_path_names.extend(load_paths_file(file_path.clone())?);
+    }
+
+    ref_path_names.sort();
+    ref_path_names.dedup();
 
     let path_data = {
-        let gfa: GFA<usize, ()> = load_gfa(&gfa_path)?;
+        let path_data = load_path_data(&gfa_path, args.path_data.as_ref())?;
 
-        if gfa.paths.len() < 2 {
+        if path_data.paths.len() < 2 {
             panic!("GFA must contain at least two paths");
         }
 
-        info!("GFA has {} paths", gfa.paths.len());
+        info!("GFA has {} paths", path_data.paths.len());
 
-        variants::gfa_path_data(gfa)
+        path_data
     };
 
-    info!("Using reference path: {}", ref_path_name);
-
-    let ref_path_ix = path_data
-        .path_names
-        .iter()
-        .position(|name| name == &ref_path_name)
-        .expect("Reference path does not exist in graph");
-
-    let ref_path = &path_data.paths[ref_path_ix];
-
-    let ultrabubbles = if let Ok(mut positions) = snp_positions(&args) {
-        Ok(build_snp_reference_bubbles(&ref_path, &mut positions))
-    } else if let Some(path) = &args.ultrabubbles_file {
-        super::saboten::load_ultrabubbles(path)
-    } else {
-        unreachable!()
-    }?;
+    let mut ref_path_ixs = Vec::with_capacity(ref_path_names.len());
+    for ref_path_name in ref_path_names.iter() {
+        info!("Using reference path: {}", ref_path_name);
+        let ix = path_data
+            .path_names
+            .iter()
+            .position(|name| name == ref_path_name)
+            .ok_or_else(|| {
+                format!(
+                    "Reference path does not exist in graph: {}",
+                    ref_path_name
+                )
+            })?;
+        ref_path_ixs.push(ix);
+    }
 
-    info!("Found ultrabubbles for {} SNPs", ultrabubbles.len());
+    // With SNP positions, each reference gets the bubbles around
+    // the given positions on that reference; with an ultrabubbles
+    // file, all references share the loaded bubbles
+    let ref_bubbles: Vec<Vec<(u64, u64)>> =
+        if let Ok(positions) = snp_positions(&args) {
+            ref_path_ixs
+                .iter()
+                .map(|&ix| {
+                    let mut positions = positions.clone();
+                    build_snp_reference_bubbles(
+                        &path_data.paths[ix],
+                        &mut positions,
+                    )
+                })
+                .collect()
+        } else if let Some(path) = &args.ultrabubbles_file {
+            let ultrabubbles = super::saboten::load_ultrabubbles(path)?;
+            vec![ultrabubbles; ref_path_ixs.len()]
+        } else {
+            unreachable!()
+        };
+
+    for (name, bubbles) in ref_path_names.iter().zip(ref_bubbles.iter()) {
+        info!(
+            "Found {} ultrabubbles for reference {}",
+            bubbles.len(),
+            name
+        );
 
-    if log_enabled!(log::Level::Debug) {
-        debug!("Bubbles:");
-        for &(from, to) in ultrabubbles.iter() {
-            debug!("{}\t{}", from, to);
+        if log_enabled!(log::Level::Debug) {
+            debug!("Bubbles:");
+            for &(from, to) in bubbles.iter() {
+                debug!("{}\t{}", from, to);
+            }
         }
     }
 
-    let ultrabubble_nodes = ultrabubbles
+    let ultrabubble_nodes = ref_bubbles
         .iter()
+        .flatten()
         .flat_map(|&(a, b)| {
             use std::iter::once;
             once(a).chain(once(b))
         })
-        .collect::<FnvHashSet<_>>();
+        .collect::<NodeSet>();
 
     let path_indices =
         variants::bubble_path_indices(&path_data.paths, &ultrabubble_nodes);
 
-    let p_bar = progress_bar(ultrabubbles.len(), false);
+    let ref_bubbles = if let Some(bed_path) = &args.bed {
+        let regions = BedRegions::from_file(bed_path)?;
+
+        ref_path_ixs
+            .iter()
+            .zip(ref_bubbles.into_iter())
+            .map(|(&ref_path_ix, bubbles)| {
+                let ref_name = &path_data.path_names[ref_path_ix];
+                let before = bubbles.len();
+                let bubbles: Vec<_> = bubbles
+                    .into_iter()
+                    .filter(|&(from, to)| {
+                        bubble_ref_span(
+                            &path_data,
+                            &path_indices,
+                            ref_path_ix,
+                            from,
+                            to,
+                        )
+                        .map(|(start, end)| {
+                            regions.overlaps(ref_name, start, end)
+                        })
+                        .unwrap_or(false)
+                    })
+                    .collect();
+                info!(
+                    "{} of {} bubbles overlap the BED regions on {}",
+                    bubbles.len(),
+                    before,
+                    ref_name
+                );
+                bubbles
+            })
+            .collect()
+    } else {
+        ref_bubbles
+    };
 
-    let mut path_snp_rows: FnvHashMap<BString, Vec<SNPRow>> =
-        FnvHashMap::default();
+    let total_bubbles = ref_bubbles.iter().map(|b| b.len()).sum();
+    let p_bar = progress_bar(total_bubbles, false);
+
+    let mut ref_snp_rows: Vec<FnvHashMap<BString, Vec<VariantRow>>> =
+        Vec::with_capacity(ref_path_ixs.len());
+
+    let mut ref_sites: Vec<BTreeMap<(usize, u8), GenotypeSite>> =
+        Vec::with_capacity(ref_path_ixs.len());
+
+    let include_indels = args.indels;
+    let matrix = args.matrix;
+
+    for (&ref_path_ix, bubbles) in ref_path_ixs.iter().zip(ref_bubbles.iter()) {
+        // Each rayon job accumulates the results of its bubbles, and
+        // the accumulators are merged at the end
+        let (path_snp_rows, sites) = bubbles
+            .par_iter()
+            .progress_with(p_bar.clone())
+            .fold(SNPAccumulator::default, |mut acc, &(from, to)| {
+                let results = variants::find_variant_rows_in_sub_paths(
+                    &path_data,
+                    ref_path_ix,
+                    &path_indices,
+                    from,
+                    to,
+                    include_indels,
+                );
+
+                if let Some(snp_results) = results {
+                    if matrix {
+                        add_genotype_sites(&mut acc.1, &snp_results);
+                    }
+                    for (name, snp_rows) in snp_results.into_iter() {
+                        let entry = acc.0.entry(name).or_default();
+                        entry.extend(snp_rows);
+                    }
+                }
+
+                acc
+            })
+            .reduce(SNPAccumulator::default, merge_accumulators);
+
+        ref_snp_rows.push(path_snp_rows);
+        ref_sites.push(sites);
+    }
 
-    for &(from, to) in ultrabubbles.iter().progress_with(p_bar) {
-        let results = variants::find_snps_in_sub_paths(
-            &path_data,
-            ref_path_ix,
-            &path_indices,
-            from,
-            to,
-        );
+    p_bar.finish();
 
-        if let Some(snp_results) = results {
-            for (name, snp_rows) in snp_results.into_iter() {
-                let entry = path_snp_rows.entry(name).or_default();
-                entry.extend(snp_rows);
+    if args.vcf {
+        println!("{}", VCFHeader::new(gfa_path));
+        for (ref_name, path_snp_rows) in
+            ref_path_names.iter().zip(ref_snp_rows.iter())
+        {
+            for record in snp_vcf_records(ref_name, path_snp_rows) {
+                println!("{}", record);
             }
         }
+        return Ok(());
     }
 
-    println!("path\treference base\treference pos\tquery base\tquery pos");
-    for (name, snp_rows) in path_snp_rows.into_iter() {
-        for snp in snp_rows.into_iter() {
-            let ref_base = char::from(snp.ref_base);
-            let query_base = char::from(snp.query_base);
-            println!(
-                "{}\t{}\t{}\t{}\t{}",
-                &name, ref_base, snp.ref_pos, query_base, snp.query_pos
-            );
+    if args.matrix {
+        print!("reference\tposition\tref\talt");
+        for name in path_data.path_names.iter() {
+            print!("\t{}", name);
+        }
+        println!();
+
+        for (ref_name, sites) in ref_path_names.iter().zip(ref_sites.iter()) {
+            print_genotype_matrix(ref_name, &path_data.path_names, sites);
+        }
+        return Ok(());
+    }
+
+    // The reference column is only included when there is more than
+    // one reference, to keep the single reference output unchanged
+    let multiple_refs = ref_path_names.len() > 1;
+
+    if multiple_refs {
+        print!("reference\t");
+    }
+    print!("path\treference base\treference pos\tquery base\tquery pos");
+    if args.indels {
+        print!("\ttype");
+    }
+    println!();
+    for (ref_name, path_snp_rows) in
+        ref_path_names.iter().zip(ref_snp_rows.into_iter())
+    {
+        // Sort the output, as neither the hash map's iteration order
+        // nor the order the bubbles are processed in is deterministic
+        let mut path_snp_rows: Vec<_> = path_snp_rows.into_iter().collect();
+        path_snp_rows.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        for (name, mut snp_rows) in path_snp_rows.into_iter() {
+            snp_rows.sort();
+            for row in snp_rows.into_iter() {
+                if multiple_refs {
+                    print!("{}\t", ref_name);
+                }
+                print!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    &name,
+                    row.ref_seq,
+                    row.ref_pos,
+                    row.query_seq,
+                    row.query_pos
+                );
+                if args.indels {
+                    print!("\t{}", row.kind);
+                }
+                println!();
+            }
         }
     }
 
@@ -1,22 +1,343 @@
+use clap::arg_enum;
+use serde::Serialize;
+use structopt::StructOpt;
+
 use std::path::PathBuf;
 
 use gfa::gfa::GFA;
 
 use handlegraph::hashgraph::HashGraph;
 
-use crate::edges;
+use crate::{
+    edges,
+    stats::{self, Composition, GraphSummary, LengthSummary},
+};
+
+use super::{load_numeric_gfa, write_json, JsonOpt, Result};
+
+arg_enum! {
+    #[derive(Debug, PartialEq)]
+    pub enum CompositionBy {
+        Segments,
+        Paths,
+        Components,
+    }
+}
+
+/// Report summary statistics for the input GFA.
+///
+/// By default, the number of each kind of line, the total sequence
+/// length, and the number of weakly connected components and the size
+/// of the largest are printed. With --json, each report is written as
+/// a JSON object, or an array with one object per row.
+#[derive(StructOpt, Debug)]
+pub struct StatsArgs {
+    /// Report the GC content, N fraction, and longest homopolymer
+    /// run per segment, path, or connected component, as TSV
+    #[structopt(
+        name = "segments|paths|components",
+        long = "composition",
+        possible_values = &["segments", "paths", "components"],
+        case_insensitive = true
+    )]
+    composition: Option<CompositionBy>,
+    /// Report summary statistics of the segment lengths, including
+    /// the number of 1 bp segments, followed by the length histogram
+    #[structopt(
+        name = "node lengths",
+        long = "node-lengths",
+        conflicts_with_all = &["segments|paths|components", "degree histogram"]
+    )]
+    node_lengths: bool,
+    /// Report the number of segments and bases of each weakly
+    /// connected component, from the most segments to the fewest, as
+    /// TSV
+    #[structopt(
+        name = "component sizes",
+        long = "component-sizes",
+        conflicts_with_all = &[
+            "segments|paths|components",
+            "degree histogram",
+            "node lengths"
+        ]
+    )]
+    component_sizes: bool,
+    /// Report the number of nodes with each inbound, outbound, and
+    /// total degree, followed by the nodes with the highest total
+    /// degree, as TSV
+    #[structopt(
+        name = "degree histogram",
+        long = "degree-hist",
+        conflicts_with = "segments|paths|components"
+    )]
+    degree_hist: bool,
+    /// The number of highest degree nodes to list with --degree-hist
+    #[structopt(
+        name = "top degree nodes",
+        long = "top-degree",
+        default_value = "10",
+        requires = "degree histogram"
+    )]
+    top_degree: usize,
+    #[structopt(flatten)]
+    json: JsonOpt,
+}
+
+#[derive(Serialize)]
+struct SummaryReport {
+    #[serde(flatten)]
+    summary: GraphSummary,
+    components: usize,
+    largest_component_bp: Option<usize>,
+    largest_component_segments: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct ComponentSize {
+    component: usize,
+    segments: usize,
+    bp: usize,
+}
+
+#[derive(Serialize)]
+struct DegreeCounts {
+    degree: usize,
+    inbound: usize,
+    outbound: usize,
+    total: usize,
+}
+
+#[derive(Serialize)]
+struct NodeDegree {
+    node: u64,
+    inbound: usize,
+    outbound: usize,
+    total: usize,
+}
+
+#[derive(Serialize)]
+struct DegreeReport {
+    histogram: Vec<DegreeCounts>,
+    top_nodes: Vec<NodeDegree>,
+}
+
+#[derive(Serialize)]
+struct NodeLengthReport {
+    summary: Option<LengthSummary>,
+    histogram: Vec<(usize, usize)>,
+}
 
-use super::{load_gfa, Result};
+#[derive(Serialize)]
+struct CompositionRow {
+    name: String,
+    length: usize,
+    gc_fraction: f64,
+    n_fraction: f64,
+    max_homopolymer: usize,
+}
 
-pub fn edge_count(gfa_path: &PathBuf) -> Result<()> {
-    let gfa: GFA<usize, ()> = load_gfa(gfa_path)?;
+impl CompositionRow {
+    fn new<D: std::fmt::Display>(name: D, comp: &Composition) -> Self {
+        Self {
+            name: name.to_string(),
+            length: comp.length,
+            gc_fraction: comp.gc_fraction(),
+            n_fraction: comp.n_fraction(),
+            max_homopolymer: comp.max_homopolymer,
+        }
+    }
+}
 
-    let hashgraph = HashGraph::from_gfa(&gfa);
+/// Print the degree histogram as DH rows of degree and the number of
+/// nodes with that inbound, outbound, and total degree, followed by
+/// the `top` highest degree nodes as TD rows of node ID and its
+/// inbound, outbound, and total degree
+fn degree_stats(gfa: &GFA<usize, ()>, top: usize, json: bool) -> Result<()> {
+    let hashgraph = HashGraph::from_gfa(gfa);
     let edge_counts = edges::graph_edge_count(&hashgraph);
-    println!("nodeid,inbound,outbound,total");
-    edge_counts
-        .iter()
-        .for_each(|(id, i, o, t)| println!("{},{},{},{}", id, i, o, t));
+
+    let histogram = edges::degree_histogram(&edge_counts);
+    let top_nodes = edges::top_degree_nodes(&edge_counts, top);
+
+    if json {
+        let report = DegreeReport {
+            histogram: histogram
+                .into_iter()
+                .map(|(degree, inbound, outbound, total)| DegreeCounts {
+                    degree,
+                    inbound,
+                    outbound,
+                    total,
+                })
+                .collect(),
+            top_nodes: top_nodes
+                .into_iter()
+                .map(|(node, inbound, outbound, total)| NodeDegree {
+                    node,
+                    inbound,
+                    outbound,
+                    total,
+                })
+                .collect(),
+        };
+        return write_json(std::io::stdout(), &report);
+    }
+
+    for (degree, i, o, t) in histogram {
+        println!("DH\t{}\t{}\t{}\t{}", degree, i, o, t);
+    }
+    for (id, i, o, t) in top_nodes {
+        println!("TD\t{}\t{}\t{}\t{}", id, i, o, t);
+    }
+
+    Ok(())
+}
+
+/// Print summary statistics of the segment lengths as SN rows of name
+/// and value, followed by the length histogram as LH rows of length
+/// and the number of segments with that length
+fn node_length_stats(gfa: &GFA<usize, ()>, json: bool) -> Result<()> {
+    let lengths: Vec<usize> =
+        gfa.segments.iter().map(|seg| seg.sequence.len()).collect();
+
+    if json {
+        let report = NodeLengthReport {
+            summary: stats::length_summary(&lengths),
+            histogram: stats::histogram(lengths),
+        };
+        return write_json(std::io::stdout(), &report);
+    }
+
+    if let Some(summary) = stats::length_summary(&lengths) {
+        println!("SN\tsegments\t{}", summary.count);
+        println!("SN\ttotal_bp\t{}", summary.total);
+        println!("SN\tmin_length\t{}", summary.min);
+        println!("SN\tmax_length\t{}", summary.max);
+        println!("SN\tmean_length\t{:.2}", summary.mean);
+        println!("SN\tmedian_length\t{}", summary.median);
+        println!("SN\tn50\t{}", summary.n50);
+        println!("SN\tsingle_bp_segments\t{}", summary.single_bp);
+    }
+
+    for (length, count) in stats::histogram(lengths) {
+        println!("LH\t{}\t{}", length, count);
+    }
+
+    Ok(())
+}
+
+fn composition_stats(
+    gfa: &GFA<usize, ()>,
+    composition_by: &CompositionBy,
+    json: bool,
+) -> Result<()> {
+    let seg_comps = stats::segment_compositions(gfa);
+
+    let rows: Vec<CompositionRow> = match composition_by {
+        CompositionBy::Segments => gfa
+            .segments
+            .iter()
+            .map(|seg| CompositionRow::new(seg.name, &seg_comps[&seg.name]))
+            .collect(),
+        CompositionBy::Paths => stats::path_compositions(gfa, &seg_comps)
+            .into_iter()
+            .map(|(name, comp)| CompositionRow::new(name, &comp))
+            .collect(),
+        CompositionBy::Components => {
+            let components = stats::weakly_connected_components(gfa);
+            components
+                .iter()
+                .enumerate()
+                .map(|(ix, component)| {
+                    let mut comp = Composition::default();
+                    for seg in component.segments.iter() {
+                        comp.merge(&seg_comps[seg]);
+                    }
+                    CompositionRow::new(ix, &comp)
+                })
+                .collect()
+        }
+    };
+
+    if json {
+        return write_json(std::io::stdout(), &rows);
+    }
+
+    println!("name\tlength\tgc_fraction\tn_fraction\tmax_homopolymer");
+    for row in rows {
+        println!(
+            "{}\t{}\t{:.4}\t{:.4}\t{}",
+            row.name,
+            row.length,
+            row.gc_fraction,
+            row.n_fraction,
+            row.max_homopolymer
+        );
+    }
+
+    Ok(())
+}
+
+pub fn stats(gfa_path: &PathBuf, args: &StatsArgs) -> Result<()> {
+    let gfa: GFA<usize, ()> = load_numeric_gfa(gfa_path)?;
+    let json = args.json.json;
+
+    if let Some(composition_by) = &args.composition {
+        return composition_stats(&gfa, composition_by, json);
+    }
+
+    if args.degree_hist {
+        return degree_stats(&gfa, args.top_degree, json);
+    }
+
+    if args.node_lengths {
+        return node_length_stats(&gfa, json);
+    }
+
+    let components = stats::weakly_connected_components(&gfa);
+
+    if args.component_sizes {
+        let sizes: Vec<ComponentSize> = components
+            .iter()
+            .enumerate()
+            .map(|(ix, comp)| ComponentSize {
+                component: ix,
+                segments: comp.segments.len(),
+                bp: comp.total_bp,
+            })
+            .collect();
+        if json {
+            return write_json(std::io::stdout(), &sizes);
+        }
+        println!("component\tsegments\tbp");
+        for size in sizes {
+            println!("{}\t{}\t{}", size.component, size.segments, size.bp);
+        }
+        return Ok(());
+    }
+
+    let largest = components.iter().max_by_key(|c| c.total_bp);
+    let report = SummaryReport {
+        summary: stats::graph_summary(&gfa),
+        components: components.len(),
+        largest_component_bp: largest.map(|c| c.total_bp),
+        largest_component_segments: largest.map(|c| c.segments.len()),
+    };
+    if json {
+        return write_json(std::io::stdout(), &report);
+    }
+
+    let summary = &report.summary;
+    println!("segments\t{}", summary.segments);
+    println!("links\t{}", summary.links);
+    println!("containments\t{}", summary.containments);
+    println!("paths\t{}", summary.paths);
+    println!("total_bp\t{}", summary.total_bp);
+    println!("components\t{}", report.components);
+    if let Some(largest) = largest {
+        println!("largest_component_bp\t{}", largest.total_bp);
+        println!("largest_component_segments\t{}", largest.segments.len());
+    }
 
     Ok(())
 }
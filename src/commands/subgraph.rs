@@ -1,14 +1,35 @@
 use clap::arg_enum;
 use structopt::{clap::ArgGroup, StructOpt};
 
-use bstr::{ByteSlice, ByteVec};
-use std::{fs::File, path::PathBuf};
+use bstr::{BString, ByteSlice, ByteVec};
+use fnv::FnvHashSet;
+use rayon::prelude::*;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
 
-use gfa::{gfa::GFA, optfields::OptionalFields, writer::gfa_string};
+use gfa::{gfa::GFA, optfields::OptionalFields};
 
-use crate::subgraph;
+use crate::{
+    compression::{self, Compression},
+    gaf_convert::{self, SegmentIndex},
+    subgraph::{
+        self, Adjacency, BubbleEnds, NodeRange, PathInterval, PathOffsets,
+        SubgraphSummary, TagPredicate,
+    },
+    util::{
+        add_program_tag, bytes_from_os_str, pansn_sample, program_record,
+        write_gfa_bytes,
+    },
+};
 
-use super::{byte_lines_iter, load_gfa, Result};
+use super::{
+    byte_lines_iter, load_gfa, load_numeric_gfa,
+    saboten::{find_ultrabubbles, load_ultrabubbles},
+    Result,
+};
 
 #[allow(unused_imports)]
 use log::{debug, info, warn};
@@ -24,7 +45,39 @@ arg_enum! {
 /// Generate a subgraph of the input GFA.
 ///
 /// The output will be the lines of the input GFA that include the
-/// provided segment or path names.
+/// provided segment or path names. When selecting segments, paths
+/// are split into the fragments that only use selected segments,
+/// named path:1, path:2, etc. Paths can also be selected by a regex
+/// matching their names, with --paths-regex, or by their PanSN
+/// sample, with --sample.
+///
+/// Segments can also be selected by a range of integer IDs, with
+/// --node-range, by their tags, with --tag, by the intervals of paths
+/// they cover, with --path-interval or --bed, as the interior of a
+/// bubble, with --bubble or --bubbles-file, or by the alignments in a
+/// GAF file, with --gaf. With --context, the selected segments are
+/// expanded by the segments up to that many links away, in either
+/// direction, before the lines are filtered, or with --context-bp, by
+/// the segments less than that many bases away.
+///
+/// With --batch, one subgraph is extracted for each line of a TSV
+/// file, in parallel, and with --all-bubbles, the subgraph of every
+/// ultrabubble is written to its own file. With --renumber, the
+/// segments of the subgraph are renamed to integer IDs, as by
+/// id-convert --to-int. With --stubs, the
+/// links that leave the subgraph are kept, leading to stub segments
+/// that stand in for the rest of the graph.
+///
+/// A summary of the records that were retained and dropped is printed
+/// to stderr, or written to the file given with --stats-out. With
+/// --fasta, the sequences of the subgraph are also written as FASTA.
+///
+/// With --streaming, the GFA is read line by line instead of being
+/// loaded, which only works with --names and --file.
+///
+/// With --node-range, if every segment has an integer ID, the graph
+/// is loaded with integer names, which is faster, unless --context,
+/// --context-bp, --stubs, --renumber, or --fasta is also given.
 #[derive(StructOpt, Debug)]
 #[structopt(group = ArgGroup::with_name("names").required(true))]
 pub struct SubgraphArgs {
@@ -40,40 +93,804 @@ pub struct SubgraphArgs {
     )]
     file: Option<PathBuf>,
     /// Provide a list of names on the command line
-    #[structopt(name = "List of names", long = "names", group = "names")]
-    list: Option<Vec<String>>,
+    #[structopt(
+        name = "List of names",
+        long = "names",
+        group = "names",
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    list: Option<Vec<BString>>,
+    /// Select the paths whose names match this regex, e.g. 'HG002#.*'
+    /// for every haplotype of a sample. The regex must match the
+    /// whole name. Only used with `paths`.
+    #[structopt(name = "path regex", long = "paths-regex", group = "names")]
+    paths_regex: Option<String>,
+    /// Select the paths of this sample, i.e. the paths whose PanSN
+    /// names, sample#haplotype#contig, start with it. Can be given
+    /// several times. Only used with `paths`.
+    #[structopt(
+        name = "sample",
+        long = "sample",
+        group = "names",
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    samples: Vec<BString>,
+    /// Select the segments with integer IDs in this range, including
+    /// both ends, e.g. 100..250. Only used with `segments`.
+    #[structopt(name = "START..END", long = "node-range", group = "names")]
+    node_range: Option<NodeRange>,
+    /// Select the segments whose optional fields satisfy this
+    /// predicate, e.g. SR:i<=1 for the rank of an rGFA segment, or
+    /// SN:Z=chr1. Integer and float tags can be compared with =, !=,
+    /// <, <=, >, and >=, and string and character tags with = and !=.
+    /// Can be given several times, to select the segments that
+    /// satisfy all of them. Only used with `segments`.
+    #[structopt(name = "TAG:TYPE<OP>VALUE", long = "tag", group = "names")]
+    tags: Vec<TagPredicate>,
+    /// Select the segments covering an interval of a path, given as
+    /// PATH:START-END with 1-based inclusive coordinates. Can be given
+    /// several times. Only used with `segments`.
+    #[structopt(
+        name = "PATH:START-END",
+        long = "path-interval",
+        group = "names"
+    )]
+    path_intervals: Vec<PathInterval>,
+    /// Select the segments covering the intervals in a BED file,
+    /// whose chromosome names are path names. Only used with
+    /// `segments`.
+    #[structopt(
+        name = "BED file",
+        long = "bed",
+        group = "names",
+        parse(from_os_str)
+    )]
+    bed: Option<PathBuf>,
+    /// Select the segments of the bubble between two boundary
+    /// segments, given as FROM,TO, found by following the links out
+    /// of FROM until they reach TO. Can be given several times. Only
+    /// used with `segments`.
+    #[structopt(name = "FROM,TO", long = "bubble", group = "names")]
+    bubbles: Vec<BubbleEnds>,
+    /// Select the segments of every bubble in an ultrabubbles file,
+    /// as produced by the saboten command. Only used with `segments`.
+    #[structopt(
+        name = "ultrabubbles file",
+        long = "bubbles-file",
+        group = "names",
+        parse(from_os_str)
+    )]
+    bubbles_file: Option<PathBuf>,
+    /// Select the segments covered by the alignments in a GAF file,
+    /// which may be gzip or zstd compressed. Only used with
+    /// `segments`.
+    #[structopt(
+        name = "GAF file",
+        long = "gaf",
+        group = "names",
+        parse(from_os_str)
+    )]
+    gaf: Option<PathBuf>,
+    /// Extract one subgraph per line of this TSV file, which has an
+    /// output name and a comma-separated list of segment or path
+    /// names on each line. The subgraphs are written to --out-dir in
+    /// parallel, as NAME.gfa.
+    #[structopt(
+        name = "batch file",
+        long = "batch",
+        group = "names",
+        requires = "output directory",
+        parse(from_os_str)
+    )]
+    batch: Option<PathBuf>,
+    /// Write the subgraph of every ultrabubble to its own GFA file in
+    /// this directory, named FROM_TO.gfa after the bubble's boundary
+    /// segments. The ultrabubbles are computed, unless they're given
+    /// with --ultrabubbles. Only used with `segments`.
+    #[structopt(
+        name = "bubbles output directory",
+        long = "all-bubbles",
+        group = "names",
+        conflicts_with = "GFA output file",
+        parse(from_os_str)
+    )]
+    all_bubbles: Option<PathBuf>,
+    /// Load the ultrabubbles of --all-bubbles from this file, as
+    /// written by the saboten command, instead of computing them.
+    #[structopt(
+        name = "precomputed ultrabubbles",
+        long = "ultrabubbles",
+        requires = "bubbles output directory",
+        parse(from_os_str)
+    )]
+    ultrabubbles: Option<PathBuf>,
+    /// Skip bubbles with more segments than this, rather than
+    /// traversing large parts of the graph when the boundaries don't
+    /// enclose a bubble.
+    #[structopt(
+        name = "max bubble segments",
+        long = "max-bubble-segments",
+        default_value = "10000"
+    )]
+    max_bubble_segments: usize,
+    /// Also include the segments up to this many links away from the
+    /// selected segments.
+    #[structopt(name = "context steps", long = "context", default_value = "0")]
+    context: usize,
+    /// Also include the segments less than this many bases away from
+    /// the selected segments, so that at least this much sequence is
+    /// added on each side where the graph continues.
+    #[structopt(
+        name = "context bases",
+        long = "context-bp",
+        conflicts_with = "context steps"
+    )]
+    context_bp: Option<usize>,
+    /// Copy the lines of the subgraph while reading the GFA, instead
+    /// of loading it, for graphs that don't fit in memory. With
+    /// `paths`, the GFA is read twice. The lines are written in the
+    /// order of the input.
+    #[structopt(
+        name = "streaming",
+        long = "streaming",
+        conflicts_with_all = &[
+            "START..END",
+            "TAG:TYPE<OP>VALUE",
+            "path regex",
+            "sample",
+            "PATH:START-END",
+            "BED file",
+            "FROM,TO",
+            "ultrabubbles file",
+            "GAF file",
+            "batch file",
+            "bubbles output directory",
+            "context bases",
+            "renumber",
+            "stats file",
+            "stubs",
+            "FASTA output file",
+        ]
+    )]
+    streaming: bool,
+    /// Write the subgraph to this file instead of stdout.
+    #[structopt(
+        name = "GFA output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    /// Write the subgraphs of --batch to this directory.
+    #[structopt(
+        name = "output directory",
+        long = "out-dir",
+        requires = "batch file",
+        conflicts_with = "GFA output file",
+        parse(from_os_str)
+    )]
+    out_dir: Option<PathBuf>,
+    /// Replace each region of the graph outside the subgraph that's
+    /// linked to it by a stub segment with the sequence N, keeping the
+    /// links that leave the subgraph.
+    #[structopt(name = "stubs", long = "stubs")]
+    stubs: bool,
+    /// Also write the sequences of the segments of the subgraph to
+    /// this FASTA file.
+    #[structopt(
+        name = "FASTA output file",
+        long = "fasta",
+        conflicts_with_all = &["batch file", "bubbles output directory"],
+        parse(from_os_str)
+    )]
+    fasta: Option<PathBuf>,
+    /// Add the sequences of the paths and path fragments of the
+    /// subgraph to the --fasta file.
+    #[structopt(
+        name = "FASTA paths",
+        long = "fasta-paths",
+        requires = "FASTA output file"
+    )]
+    fasta_paths: bool,
+    /// Rename the segments of the subgraph to integer IDs, in the
+    /// order they're written, as id-convert --to-int does, and write
+    /// the name map to the --renumber-map file.
+    #[structopt(name = "renumber", long = "renumber")]
+    renumber: bool,
+    /// Where to write the name map of --renumber, in the JSON format
+    /// of id-convert, so that id-convert --namemap can restore the
+    /// names. With --batch or --all-bubbles, the maps are written to
+    /// the output directory as NAME.name_map.json instead.
+    #[structopt(
+        name = "renumber map",
+        long = "renumber-map",
+        requires = "renumber",
+        conflicts_with_all = &["batch file", "bubbles output directory"],
+        parse(from_os_str)
+    )]
+    renumber_map: Option<PathBuf>,
+    /// Write the summary of the retained and dropped records to this
+    /// file, instead of stderr.
+    #[structopt(
+        name = "stats file",
+        long = "stats-out",
+        conflicts_with_all = &["batch file", "bubbles output directory"],
+        parse(from_os_str)
+    )]
+    stats_out: Option<PathBuf>,
+    /// Compress the output with gzip or zstd. Zstd requires `zstd` in
+    /// PATH.
+    #[structopt(
+        name = "none|gzip|zstd",
+        long = "compress",
+        default_value = "none",
+        possible_values = &Compression::variants(),
+        case_insensitive = true
+    )]
+    compress: Compression,
 }
 
 pub fn subgraph(gfa_path: &PathBuf, args: &SubgraphArgs) -> Result<()> {
+    if args.streaming {
+        return streaming_subgraph(gfa_path, args);
+    }
+
+    if let Some(range) = args.node_range {
+        require_subgraph_by(args, SubgraphBy::Segments, "--node-range")?;
+        if !needs_segment_names(args) {
+            match load_numeric_gfa(gfa_path) {
+                Ok(gfa) => return numeric_range_subgraph(&gfa, range, args),
+                Err(err) => info!("Loading the segment names, as {}", err),
+            }
+        }
+    }
+
     let gfa: GFA<Vec<u8>, OptionalFields> = load_gfa(gfa_path)?;
 
-    let names: Vec<Vec<u8>> = if let Some(list) = &args.list {
-        list.iter().map(|s| s.bytes().collect()).collect()
+    if let Some(batch_path) = &args.batch {
+        return batch_subgraphs(&gfa, batch_path, args);
+    }
+
+    if let Some(out_dir) = &args.all_bubbles {
+        return bubble_subgraphs(gfa_path, &gfa, out_dir, args);
+    }
+
+    let map_path = match (&args.renumber_map, args.renumber) {
+        (Some(path), _) => Some(path),
+        (None, true) => return Err("--renumber requires --renumber-map".into()),
+        (None, false) => None,
+    };
+
+    let names = selected_names(&gfa, args)?;
+    let mut new_gfa = extract_subgraph(&gfa, names, args);
+
+    let summary = SubgraphSummary::new(&gfa, &new_gfa);
+    if let Some(stats_path) = &args.stats_out {
+        summary.write_tsv(BufWriter::new(File::create(stats_path)?))?;
     } else {
-        let in_lines = if let Some(path) = &args.file {
-            byte_lines_iter(File::open(path).unwrap())
-        } else {
-            byte_lines_iter(std::io::stdin())
-        };
+        summary.write_tsv(std::io::stderr())?;
+    }
 
-        if args.subgraph_by == SubgraphBy::Segments {
-            in_lines
-                .flat_map(|line| {
-                    line.split_str("\t")
-                        .map(Vec::from_slice)
-                        .collect::<Vec<_>>()
-                })
-                .collect()
-        } else {
-            in_lines.collect()
+    if args.stubs {
+        let stubs = subgraph::add_stub_segments(&gfa, &mut new_gfa);
+        info!("Added {} stub segments", stubs);
+    }
+
+    if let Some(map_path) = map_path {
+        subgraph::renumber_segments(&mut new_gfa).save_json(map_path)?;
+        info!("Renumbered {} segments", new_gfa.segments.len());
+    }
+
+    if let Some(fasta_path) = &args.fasta {
+        let fasta = BufWriter::new(File::create(fasta_path)?);
+        subgraph::write_fasta(&new_gfa, args.fasta_paths, fasta)?;
+    }
+
+    add_program_tag(&mut new_gfa.header, &program_record(std::env::args_os()));
+    let mut out = compression::create_output(args.out.as_ref(), args.compress)?;
+    write_gfa_bytes(&new_gfa, &mut out)?;
+    out.finish()?;
+
+    Ok(())
+}
+
+/// Whether any of the options that only work on a graph loaded with
+/// its segment names as bytes is given
+fn needs_segment_names(args: &SubgraphArgs) -> bool {
+    args.context > 0
+        || args.context_bp.is_some()
+        || args.stubs
+        || args.renumber
+        || args.fasta.is_some()
+}
+
+/// Extract the segments of a --node-range from a graph loaded with
+/// integer IDs, which compares the IDs directly instead of parsing
+/// every segment name
+fn numeric_range_subgraph(
+    gfa: &GFA<usize, OptionalFields>,
+    range: NodeRange,
+    args: &SubgraphArgs,
+) -> Result<()> {
+    let (names, _) = subgraph::node_range_segments(gfa, range);
+    info!("Selected {} segments in {}", names.len(), range);
+    let mut new_gfa = subgraph::segments_subgraph(gfa, &names);
+
+    let summary = SubgraphSummary::new(gfa, &new_gfa);
+    if let Some(stats_path) = &args.stats_out {
+        summary.write_tsv(BufWriter::new(File::create(stats_path)?))?;
+    } else {
+        summary.write_tsv(std::io::stderr())?;
+    }
+
+    add_program_tag(&mut new_gfa.header, &program_record(std::env::args_os()));
+    let mut out = compression::create_output(args.out.as_ref(), args.compress)?;
+    write_gfa_bytes(&new_gfa, &mut out)?;
+    out.finish()?;
+
+    Ok(())
+}
+
+/// Expand the selected segments with their context, if requested
+fn with_context(
+    gfa: &GFA<Vec<u8>, OptionalFields>,
+    segments: Vec<Vec<u8>>,
+    args: &SubgraphArgs,
+) -> Vec<Vec<u8>> {
+    let selected = segments.len();
+    if let Some(bp) = args.context_bp {
+        let expanded = Adjacency::new(gfa).expand_bp(&segments, bp);
+        info!(
+            "Added {} segments within {} bp of the selection",
+            expanded.len() - selected,
+            bp
+        );
+        expanded
+    } else if args.context > 0 {
+        let expanded = Adjacency::new(gfa).expand(&segments, args.context);
+        info!(
+            "Added {} segments within {} links of the selection",
+            expanded.len() - selected,
+            args.context
+        );
+        expanded
+    } else {
+        segments
+    }
+}
+
+/// The subgraph of the selected segments or paths
+fn extract_subgraph(
+    gfa: &GFA<Vec<u8>, OptionalFields>,
+    names: Vec<Vec<u8>>,
+    args: &SubgraphArgs,
+) -> GFA<Vec<u8>, OptionalFields> {
+    match args.subgraph_by {
+        SubgraphBy::Paths => {
+            let segments = subgraph::path_segments(gfa, &names);
+            let segments = with_context(gfa, segments, args);
+            subgraph::paths_segments_subgraph(gfa, &names, &segments)
         }
+        SubgraphBy::Segments => {
+            subgraph::segments_subgraph(gfa, &with_context(gfa, names, args))
+        }
+    }
+}
+
+/// Extract the subgraph of each line of the batch file, in parallel,
+/// and write them to the output directory
+fn batch_subgraphs(
+    gfa: &GFA<Vec<u8>, OptionalFields>,
+    batch_path: &PathBuf,
+    args: &SubgraphArgs,
+) -> Result<()> {
+    let out_dir = args.out_dir.as_ref().ok_or("--batch requires --out-dir")?;
+    std::fs::create_dir_all(out_dir)?;
+
+    let batch = subgraph::read_batch(File::open(batch_path)?)?;
+    let out_paths = batch_output_paths(&batch, out_dir, args.compress)?;
+    info!("Extracting {} subgraphs", batch.len());
+
+    batch
+        .par_iter()
+        .zip(out_paths.par_iter())
+        .map(|((name, names), out_path)| {
+            let new_gfa = extract_subgraph(gfa, names.clone(), args);
+            write_output_subgraph(gfa, new_gfa, name, out_dir, out_path, args)
+        })
+        .collect::<std::io::Result<()>>()?;
+
+    Ok(())
+}
+
+/// Add stubs to and renumber one of several subgraphs, if requested,
+/// and write it to its file in the output directory, along with its
+/// name map, as NAME.name_map.json
+fn write_output_subgraph(
+    gfa: &GFA<Vec<u8>, OptionalFields>,
+    mut new_gfa: GFA<Vec<u8>, OptionalFields>,
+    name: &str,
+    out_dir: &Path,
+    out_path: &Path,
+    args: &SubgraphArgs,
+) -> std::io::Result<()> {
+    if args.stubs {
+        subgraph::add_stub_segments(gfa, &mut new_gfa);
+    }
+    if args.renumber {
+        let name_map = subgraph::renumber_segments(&mut new_gfa);
+        name_map.save_json(out_dir.join(format!("{}.name_map.json", name)))?;
+    }
+    add_program_tag(&mut new_gfa.header, &program_record(std::env::args_os()));
+    let mut out = compression::create_output(Some(out_path), args.compress)?;
+    write_gfa_bytes(&new_gfa, &mut out)?;
+    out.finish()?;
+    debug!(
+        "Wrote {} segments to {}",
+        new_gfa.segments.len(),
+        out_path.display()
+    );
+    Ok(())
+}
+
+/// Write the subgraph of each ultrabubble, in parallel, to the output
+/// directory
+fn bubble_subgraphs(
+    gfa_path: &PathBuf,
+    gfa: &GFA<Vec<u8>, OptionalFields>,
+    out_dir: &Path,
+    args: &SubgraphArgs,
+) -> Result<()> {
+    require_subgraph_by(args, SubgraphBy::Segments, "--all-bubbles")?;
+
+    let ultrabubbles = if let Some(path) = &args.ultrabubbles {
+        load_ultrabubbles(path)?
+    } else {
+        find_ultrabubbles(gfa_path)?
     };
+    std::fs::create_dir_all(out_dir)?;
+    info!("Extracting {} bubbles", ultrabubbles.len());
+
+    let extension = output_extension(args.compress);
+    let adjacency = Adjacency::new(gfa);
+
+    let written: Vec<bool> = ultrabubbles
+        .par_iter()
+        .map(|&(from, to)| -> std::io::Result<bool> {
+            let (from, to) = (from.to_string(), to.to_string());
+            let segments = match adjacency.bubble_segments(
+                from.as_bytes(),
+                to.as_bytes(),
+                args.max_bubble_segments,
+            ) {
+                Some(segments) => segments,
+                None => return Ok(false),
+            };
 
-    let new_gfa = match args.subgraph_by {
-        SubgraphBy::Paths => subgraph::paths_new_subgraph(&gfa, &names),
-        SubgraphBy::Segments => subgraph::segments_subgraph(&gfa, &names),
+            let segments = if let Some(bp) = args.context_bp {
+                adjacency.expand_bp(&segments, bp)
+            } else if args.context > 0 {
+                adjacency.expand(&segments, args.context)
+            } else {
+                segments
+            };
+
+            let new_gfa = subgraph::segments_subgraph(gfa, &segments);
+            let name = format!("{}_{}", from, to);
+            let out_path = out_dir.join(format!("{}.{}", name, extension));
+            write_output_subgraph(
+                gfa, new_gfa, &name, out_dir, &out_path, args,
+            )?;
+            Ok(true)
+        })
+        .collect::<std::io::Result<_>>()?;
+
+    let skipped = written.iter().filter(|&&w| !w).count();
+    if skipped > 0 {
+        warn!(
+            "Skipped {} bubbles that weren't found within {} segments",
+            skipped, args.max_bubble_segments
+        );
+    }
+    info!(
+        "Wrote {} bubbles to {}",
+        written.len() - skipped,
+        out_dir.display()
+    );
+
+    Ok(())
+}
+
+/// The extension of the GFA files written with `compress`, e.g. the
+/// subgraph files of a batch
+pub(crate) fn output_extension(compress: Compression) -> &'static str {
+    match compress {
+        Compression::None => "gfa",
+        Compression::Gzip => "gfa.gz",
+        Compression::Zstd => "gfa.zst",
+    }
+}
+
+/// The output file of each subgraph of a batch, named after its
+/// output name
+fn batch_output_paths(
+    batch: &[(String, Vec<Vec<u8>>)],
+    out_dir: &Path,
+    compress: Compression,
+) -> Result<Vec<PathBuf>> {
+    let extension = output_extension(compress);
+
+    let mut seen = FnvHashSet::default();
+    let mut paths = Vec::with_capacity(batch.len());
+
+    for (name, _) in batch {
+        if name.is_empty() || name.contains('/') {
+            return Err(format!("Invalid output name: {}", name).into());
+        }
+        if !seen.insert(name.as_str()) {
+            return Err(
+                format!("Output name {} is used more than once", name).into()
+            );
+        }
+        paths.push(out_dir.join(format!("{}.{}", name, extension)));
+    }
+
+    Ok(paths)
+}
+
+/// Extract the subgraph without loading the GFA, by first reading the
+/// segments of the selected paths, if needed, and then copying the
+/// lines that belong to the subgraph
+fn streaming_subgraph(gfa_path: &PathBuf, args: &SubgraphArgs) -> Result<()> {
+    if args.context > 0 {
+        return Err("--context can't be used with --streaming".into());
+    }
+
+    let names = listed_names(args)?;
+    let (segments, paths) = match args.subgraph_by {
+        SubgraphBy::Paths => {
+            let input = compression::open_input(gfa_path)?;
+            let segments = subgraph::stream_path_segments(input, &names)?;
+            info!("Found {} segments in {} paths", segments.len(), names.len());
+            (segments, Some(names.as_slice()))
+        }
+        SubgraphBy::Segments => (names.clone(), None),
     };
-    println!("{}", gfa_string(&new_gfa));
 
+    let input = compression::open_input(gfa_path)?;
+    let mut out = compression::create_output(args.out.as_ref(), args.compress)?;
+    let program = program_record(std::env::args_os());
+    let lines = subgraph::stream_subgraph(
+        input,
+        &mut out,
+        &segments,
+        paths,
+        Some(program.as_slice()),
+    )?;
+    out.finish()?;
+    info!("Wrote {} lines", lines);
+
+    Ok(())
+}
+
+/// Fail if a selection that only picks segments, or paths, is used
+/// with the other kind of subgraph
+fn require_subgraph_by(
+    args: &SubgraphArgs,
+    subgraph_by: SubgraphBy,
+    option: &str,
+) -> Result<()> {
+    if args.subgraph_by != subgraph_by {
+        let kind = subgraph_by.to_string().to_lowercase();
+        return Err(format!("{} can only be used with {}", option, kind).into());
+    }
     Ok(())
 }
+
+/// The names of the selected segments or paths
+fn selected_names(
+    gfa: &GFA<Vec<u8>, OptionalFields>,
+    args: &SubgraphArgs,
+) -> Result<Vec<Vec<u8>>> {
+    if let Some(range) = args.node_range {
+        require_subgraph_by(args, SubgraphBy::Segments, "--node-range")?;
+        let (names, non_numeric) = subgraph::node_range_segments(gfa, range);
+        if non_numeric > 0 {
+            warn!("Skipped {} segments without integer IDs", non_numeric);
+        }
+        info!("Selected {} segments in {}", names.len(), range);
+        return Ok(names);
+    }
+
+    if let Some(pattern) = &args.paths_regex {
+        require_subgraph_by(args, SubgraphBy::Paths, "--paths-regex")?;
+        let names = subgraph::matching_paths(gfa, pattern)?;
+        if names.is_empty() {
+            warn!("No path names match {}", pattern);
+        }
+        info!("Selected {} paths matching {}", names.len(), pattern);
+        return Ok(names);
+    }
+
+    if !args.samples.is_empty() {
+        require_subgraph_by(args, SubgraphBy::Paths, "--sample")?;
+        let samples: Vec<Vec<u8>> =
+            args.samples.iter().map(|s| s.to_vec()).collect();
+        let names = subgraph::sample_paths(gfa, &samples);
+        for sample in samples.iter() {
+            let found = names
+                .iter()
+                .any(|n| pansn_sample(n) == Some(sample.as_slice()));
+            if !found {
+                warn!("No paths found for sample {}", sample.as_bstr());
+            }
+        }
+        info!(
+            "Selected {} paths of {} samples",
+            names.len(),
+            samples.len()
+        );
+        return Ok(names);
+    }
+
+    if !args.tags.is_empty() {
+        require_subgraph_by(args, SubgraphBy::Segments, "--tag")?;
+        let names = subgraph::tag_segments(gfa, &args.tags);
+        info!("Selected {} segments by their tags", names.len());
+        return Ok(names);
+    }
+
+    if !args.path_intervals.is_empty() {
+        require_subgraph_by(args, SubgraphBy::Segments, "--path-interval")?;
+        let offsets = PathOffsets::new(gfa);
+        let mut names = Vec::new();
+        for interval in args.path_intervals.iter() {
+            let segments = offsets
+                .segments(&interval.path, interval.start, interval.end)
+                .ok_or_else(|| {
+                    format!("Path {} not found", interval.path.as_bstr())
+                })?;
+            info!("Selected {} segments in {}", segments.len(), interval);
+            names.extend(segments);
+        }
+        return Ok(names);
+    }
+
+    if let Some(bed_path) = &args.bed {
+        require_subgraph_by(args, SubgraphBy::Segments, "--bed")?;
+        let intervals = subgraph::read_bed_intervals(File::open(bed_path)?)?;
+        let offsets = PathOffsets::new(gfa);
+        let mut names = Vec::new();
+        let mut missing = 0;
+        for interval in intervals.iter() {
+            match offsets.segments(&interval.path, interval.start, interval.end)
+            {
+                Some(segments) => names.extend(segments),
+                None => {
+                    debug!("Path {} not found", interval.path.as_bstr());
+                    missing += 1;
+                }
+            }
+        }
+        if missing > 0 {
+            warn!(
+                "Skipped {} BED intervals on paths that aren't in the graph",
+                missing
+            );
+        }
+        info!(
+            "Selected segments covering {} BED intervals",
+            intervals.len() - missing
+        );
+        return Ok(names);
+    }
+
+    if !args.bubbles.is_empty() || args.bubbles_file.is_some() {
+        let option = if args.bubbles.is_empty() {
+            "--bubbles-file"
+        } else {
+            "--bubble"
+        };
+        require_subgraph_by(args, SubgraphBy::Segments, option)?;
+
+        let mut bubbles = args.bubbles.clone();
+        if let Some(path) = &args.bubbles_file {
+            bubbles.extend(load_ultrabubbles(path)?.into_iter().map(
+                |(from, to)| BubbleEnds {
+                    from: from.to_string().into_bytes(),
+                    to: to.to_string().into_bytes(),
+                },
+            ));
+        }
+
+        let adjacency = Adjacency::new(gfa);
+        let mut names = Vec::new();
+        let mut skipped = 0;
+        for bubble in bubbles.iter() {
+            match adjacency.bubble_segments(
+                &bubble.from,
+                &bubble.to,
+                args.max_bubble_segments,
+            ) {
+                Some(segments) => {
+                    debug!(
+                        "Selected {} segments in bubble {}",
+                        segments.len(),
+                        bubble
+                    );
+                    names.extend(segments);
+                }
+                None => {
+                    warn!(
+                        "Skipping bubble {}, {} isn't reached from {} \
+                         within {} segments",
+                        bubble,
+                        bubble.to.as_bstr(),
+                        bubble.from.as_bstr(),
+                        args.max_bubble_segments
+                    );
+                    skipped += 1;
+                }
+            }
+        }
+        info!(
+            "Selected the segments of {} bubbles",
+            bubbles.len() - skipped
+        );
+        return Ok(names);
+    }
+
+    if let Some(gaf_path) = &args.gaf {
+        require_subgraph_by(args, SubgraphBy::Segments, "--gaf")?;
+        let index = SegmentIndex::new(gfa);
+        let mut read_error = None;
+        let records =
+            gaf_convert::gaf_records(compression::open_input(gaf_path)?)
+                .scan((), |_, gaf| {
+                    gaf.map_err(|err| read_error = Some(err)).ok()
+                });
+        let (names, unresolved) = subgraph::gaf_segments(&index, records);
+        if let Some(err) = read_error {
+            return Err(err.into());
+        }
+        if unresolved > 0 {
+            warn!(
+                "Skipped {} GAF records whose paths aren't in the graph",
+                unresolved
+            );
+        }
+        info!("Selected {} segments covered by alignments", names.len());
+        return Ok(names);
+    }
+
+    listed_names(args)
+}
+
+/// The names given with --names, or in the file given with --file, or
+/// on stdin
+fn listed_names(args: &SubgraphArgs) -> Result<Vec<Vec<u8>>> {
+    if let Some(list) = &args.list {
+        return Ok(list.iter().map(|s| s.to_vec()).collect());
+    }
+
+    let in_lines = if let Some(path) = &args.file {
+        byte_lines_iter(File::open(path)?)
+    } else {
+        byte_lines_iter(std::io::stdin())
+    };
+
+    let in_lines = in_lines.collect::<std::io::Result<Vec<_>>>()?;
+
+    if args.subgraph_by == SubgraphBy::Segments {
+        Ok(in_lines
+            .iter()
+            .flat_map(|line| {
+                line.split_str("\t")
+                    .map(Vec::from_slice)
+                    .collect::<Vec<_>>()
+            })
+            .collect())
+    } else {
+        Ok(in_lines)
+    }
+}
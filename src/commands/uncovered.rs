@@ -0,0 +1,101 @@
+use bstr::ByteSlice;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+use gfa::{gfa::GFA, optfields::OptionalFields};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use crate::{
+    clean,
+    util::{add_program_tag, program_record, write_gfa_bytes},
+};
+
+use super::{load_gfa, Result};
+
+/// List the segments and links that no path traverses.
+///
+/// Variants can only be called from the paths, so these parts of the
+/// graph never show up in gfa2vcf's output. The report is TSV, with
+/// the kind of each row in the first column: SN rows with the number
+/// of uncovered segments, their total length, and the number of
+/// uncovered links, followed by a US row with the name and length of
+/// each uncovered segment, and a UL row with each uncovered link.
+#[derive(StructOpt, Debug)]
+pub struct UncoveredArgs {
+    /// Write the report to this file instead of stdout.
+    #[structopt(
+        name = "output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    /// Write the GFA without the uncovered segments and links, and
+    /// the containments of the removed segments, to this file.
+    #[structopt(name = "GFA output file", long = "gfa", parse(from_os_str))]
+    gfa_out: Option<PathBuf>,
+}
+
+pub fn uncovered(gfa_path: &PathBuf, args: &UncoveredArgs) -> Result<()> {
+    let gfa: GFA<Vec<u8>, OptionalFields> = load_gfa(gfa_path)?;
+
+    let uncovered = clean::uncovered_by_paths(&gfa);
+
+    let uncovered_bp: usize = uncovered
+        .segments
+        .iter()
+        .map(|&ix| gfa.segments[ix].sequence.len())
+        .sum();
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    writeln!(out, "SN\tuncovered_segments\t{}", uncovered.segments.len())?;
+    writeln!(out, "SN\tuncovered_bp\t{}", uncovered_bp)?;
+    writeln!(out, "SN\tuncovered_links\t{}", uncovered.links.len())?;
+
+    for &ix in uncovered.segments.iter() {
+        let seg = &gfa.segments[ix];
+        writeln!(out, "US\t{}\t{}", seg.name.as_bstr(), seg.sequence.len())?;
+    }
+    for &ix in uncovered.links.iter() {
+        let link = &gfa.links[ix];
+        writeln!(
+            out,
+            "UL\t{}{}\t{}{}",
+            link.from_segment.as_bstr(),
+            link.from_orient,
+            link.to_segment.as_bstr(),
+            link.to_orient
+        )?;
+    }
+    out.flush()?;
+
+    if let Some(gfa_out) = &args.gfa_out {
+        let mut new_gfa = clean::remove_uncovered(&gfa, &uncovered);
+        add_program_tag(
+            &mut new_gfa.header,
+            &program_record(std::env::args_os()),
+        );
+
+        let mut out_file = BufWriter::new(File::create(gfa_out)?);
+        write_gfa_bytes(&new_gfa, &mut out_file)?;
+        out_file.flush()?;
+        info!(
+            "Wrote GFA with {} segments and {} links",
+            new_gfa.segments.len(),
+            new_gfa.links.len()
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use bstr::{io::*, BString, ByteSlice};
+use fnv::FnvHashSet;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use crate::{
+    util::bytes_from_os_str,
+    variants::{
+        traversal::record_traversals, validate::RefSequences, vcf::parse_record,
+    },
+};
+
+use super::{load_path_data, Result};
+
+/// Look up the node traversals and sequences of the alleles in a VCF
+/// produced by gfa2vcf, to spot-check calls against the graph.
+///
+/// The REF allele of each record is looked up in the reference path
+/// at the record's position, and the ALT alleles in the query paths
+/// listed in the QNAME and QSTART INFO fields, which gfa2vcf adds
+/// with --query-coords. The output is a TSV with one row per allele
+/// and path, with the columns chromosome, position, allele index,
+/// allele, path, position on the path, traversal, path sequence,
+/// and whether the sequence matches the allele.
+#[derive(StructOpt, Debug)]
+pub struct VCF2TraversalArgs {
+    #[structopt(name = "path to VCF file", long = "vcf", parse(from_os_str))]
+    vcf: PathBuf,
+    /// Only output the records at these positions, given as
+    /// CHROM:POS. All records are output if omitted.
+    #[structopt(
+        name = "record positions",
+        long = "records",
+        parse(from_os_str = bytes_from_os_str)
+    )]
+    records: Option<Vec<BString>>,
+    /// Write the TSV to this file instead of stdout.
+    #[structopt(
+        name = "TSV output file",
+        short = "o",
+        long = "out",
+        parse(from_os_str)
+    )]
+    out: Option<PathBuf>,
+    /// Load the path data from this binary file, if it exists,
+    /// instead of parsing the GFA; otherwise build it from the GFA
+    /// and save it to the file. The file is rejected if the GFA has
+    /// changed since.
+    #[structopt(name = "path data file", long = "path-data")]
+    path_data: Option<PathBuf>,
+}
+
+fn parse_record_position(pos: &[u8]) -> Result<(BString, i64)> {
+    let ix = pos.rfind_byte(b':').ok_or_else(|| {
+        format!("Record position must be CHROM:POS: {}", pos.as_bstr())
+    })?;
+    let chrom = &pos[..ix];
+    let pos = pos[ix + 1..]
+        .to_str()
+        .ok()
+        .and_then(|p| p.parse().ok())
+        .ok_or_else(|| format!("Invalid record position: {}", pos.as_bstr()))?;
+    Ok((chrom.into(), pos))
+}
+
+pub fn vcf2traversal(
+    gfa_path: &PathBuf,
+    args: &VCF2TraversalArgs,
+) -> Result<()> {
+    let selected: Option<FnvHashSet<(BString, i64)>> = args
+        .records
+        .as_ref()
+        .map(|records| {
+            records
+                .iter()
+                .map(|pos| parse_record_position(pos))
+                .collect::<Result<_>>()
+        })
+        .transpose()?;
+
+    let path_data = load_path_data(gfa_path, args.path_data.as_ref())?;
+    let ref_seqs = RefSequences::new(&path_data);
+
+    let mut out: Box<dyn Write> = if let Some(out_path) = &args.out {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    writeln!(
+        out,
+        "#chrom\tpos\tallele_ix\tallele\tpath\tpath_pos\ttraversal\t\
+         sequence\tmatches"
+    )?;
+
+    let vcf_reader = BufReader::new(File::open(&args.vcf)?);
+    let mut records = 0;
+    let mut mismatches = 0;
+
+    for (i, line) in vcf_reader.byte_lines().enumerate() {
+        let line = line?;
+        if line.is_empty() || line.starts_with(b"#") {
+            continue;
+        }
+        let record = match parse_record(&line) {
+            Some(record) => record,
+            None => {
+                warn!("Error parsing VCF line {}", i + 1);
+                continue;
+            }
+        };
+
+        if let Some(selected) = &selected {
+            let key = (record.chromosome.clone(), record.position);
+            if !selected.contains(&key) {
+                continue;
+            }
+        }
+
+        records += 1;
+        for traversal in record_traversals(&ref_seqs, &record) {
+            if traversal.path.is_some() && !traversal.matches() {
+                mismatches += 1;
+                debug!(
+                    "Allele {} of {}:{} doesn't match path {}",
+                    traversal.allele_ix,
+                    record.chromosome,
+                    record.position,
+                    traversal.path.as_ref().unwrap().0.as_bstr()
+                );
+            }
+            writeln!(out, "{}", traversal)?;
+        }
+    }
+    out.flush()?;
+
+    info!("Looked up the alleles of {} records", records);
+    if mismatches > 0 {
+        warn!("{} alleles don't match the graph", mismatches);
+    }
+
+    Ok(())
+}
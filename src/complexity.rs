@@ -0,0 +1,202 @@
+//! Graph complexity along a reference path, counted in windows of the
+//! path's linear coordinates, for locating the regions of a pangenome
+//! with the most variation.
+
+use std::ops::Range;
+
+use fnv::{FnvHashMap, FnvHashSet};
+
+use gfa::gfa::Orientation;
+
+use crate::{util::window_ranges, variants::PathData};
+
+/// The complexity of the graph in one window of the reference path
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ComplexityWindow {
+    /// The 0-based start of the window on the reference
+    pub start: usize,
+    /// The 0-based, exclusive end of the window on the reference
+    pub end: usize,
+    /// The number of ultrabubbles whose interior overlaps the window
+    pub bubbles: usize,
+    /// The number of reference segments overlapping the window that
+    /// have more than one inbound or outbound link
+    pub branching_nodes: usize,
+    /// The total length of the segments that aren't on the reference,
+    /// placed where their paths leave the reference
+    pub nonref_bp: usize,
+}
+
+/// The complexity windows along a reference path
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ComplexityTrack {
+    pub windows: Vec<ComplexityWindow>,
+    /// The number of ultrabubbles that don't have both ends on the
+    /// reference, and so couldn't be placed
+    pub unplaced_bubbles: usize,
+    /// The number of non-reference segments on paths that never
+    /// touch the reference, which couldn't be placed
+    pub unplaced_segments: usize,
+}
+
+impl ComplexityTrack {
+    /// Windows of `window` bp every `step` bp along a reference of
+    /// `length` bp, with the last window ending at the end of the
+    /// reference
+    fn new(length: usize, window: usize, step: usize) -> Self {
+        let windows = window_ranges(length, window, step)
+            .into_iter()
+            .map(|(start, end)| ComplexityWindow {
+                start,
+                end,
+                ..ComplexityWindow::default()
+            })
+            .collect();
+
+        Self {
+            windows,
+            ..Self::default()
+        }
+    }
+
+    /// The indices of the windows that overlap the interval
+    /// `[start, end)`, given the window size and step. An empty
+    /// interval is treated as the single base at `start`.
+    fn overlapping(
+        &self,
+        window: usize,
+        step: usize,
+        start: usize,
+        end: usize,
+    ) -> Range<usize> {
+        let end = end.max(start + 1);
+        let first = if start + 1 > window {
+            (start + 1 - window + step - 1) / step
+        } else {
+            0
+        };
+        let last = ((end - 1) / step + 1).min(self.windows.len());
+        first..last.max(first)
+    }
+}
+
+/// The interval of each segment on the reference path, at its first
+/// visit, and its orientation there
+fn reference_intervals(
+    path_data: &PathData,
+    ref_ix: usize,
+) -> FnvHashMap<usize, (usize, usize, Orientation)> {
+    let mut intervals = FnvHashMap::default();
+    for &(node, offset, orient) in path_data.paths[ref_ix].iter() {
+        let len = path_data.segment_map.get(&node).map_or(0, |s| s.len());
+        intervals
+            .entry(node)
+            .or_insert((offset - 1, offset - 1 + len, orient));
+    }
+    intervals
+}
+
+/// Count the ultrabubbles, branching segments, and non-reference
+/// sequence in windows of `window` bp, every `step` bp, along the
+/// path `ref_ix`.
+///
+/// An ultrabubble is placed between its two boundary segments, if both
+/// are on the reference. A segment is branching if it's in
+/// `branching`, e.g. from `edges::branching_nodes`. Each segment that
+/// isn't on the reference is counted once, at the reference position
+/// where the first path that visits it left the reference, or where it
+/// joins it, if the path starts off the reference.
+pub fn complexity_track(
+    path_data: &PathData,
+    ref_ix: usize,
+    ultrabubbles: &[(u64, u64)],
+    branching: &FnvHashSet<u64>,
+    window: usize,
+    step: usize,
+) -> ComplexityTrack {
+    let intervals = reference_intervals(path_data, ref_ix);
+    let length = intervals.values().map(|&(_, end, _)| end).max();
+    let mut track = ComplexityTrack::new(length.unwrap_or(0), window, step);
+
+    for &(x, y) in ultrabubbles {
+        let ends = (intervals.get(&(x as usize)), intervals.get(&(y as usize)));
+        if let (Some(&(x_start, x_end, _)), Some(&(y_start, y_end, _))) = ends {
+            let (start, end) = if x_start <= y_start {
+                (x_end, y_start)
+            } else {
+                (y_end, x_start)
+            };
+            for ix in track.overlapping(window, step, start, end) {
+                track.windows[ix].bubbles += 1;
+            }
+        } else {
+            track.unplaced_bubbles += 1;
+        }
+    }
+
+    for (&node, &(start, end, _)) in intervals.iter() {
+        if branching.contains(&(node as u64)) {
+            for ix in track.overlapping(window, step, start, end) {
+                track.windows[ix].branching_nodes += 1;
+            }
+        }
+    }
+
+    // Non-reference segments are placed at the last base of the
+    // reference segment their path last left, in the path's direction
+    let exit_position = |&(start, end, _): &(usize, usize, _), along: bool| {
+        if along {
+            end.saturating_sub(1)
+        } else {
+            start
+        }
+    };
+
+    let mut placed: FnvHashSet<usize> = FnvHashSet::default();
+    let mut unplaced: FnvHashSet<usize> = FnvHashSet::default();
+
+    for (path_ix, path) in path_data.paths.iter().enumerate() {
+        if path_ix == ref_ix {
+            continue;
+        }
+
+        let mut position: Option<usize> = None;
+        let mut pending: Vec<usize> = Vec::new();
+
+        for &(node, _, orient) in path.iter() {
+            let mut place = |node: usize, pos: usize| {
+                if placed.insert(node) {
+                    let len = path_data.segment_map[&node].len();
+                    for ix in track.overlapping(window, step, pos, pos) {
+                        track.windows[ix].nonref_bp += len;
+                    }
+                }
+            };
+
+            if let Some(interval) = intervals.get(&node) {
+                let along = orient == interval.2;
+                if position.is_none() {
+                    // The path started off the reference, so the
+                    // segments before this one are placed where it
+                    // joins the reference
+                    let joined = exit_position(interval, !along);
+                    pending.drain(..).for_each(|node| place(node, joined));
+                }
+                position = Some(exit_position(interval, along));
+            } else if let Some(pos) = position {
+                place(node, pos);
+            } else {
+                pending.push(node);
+            }
+        }
+
+        unplaced.extend(pending);
+    }
+
+    track.unplaced_segments = unplaced
+        .iter()
+        .filter(|node| !placed.contains(node))
+        .count();
+
+    track
+}
@@ -0,0 +1,112 @@
+//! Splitting a graph into its weakly connected components.
+
+use bstr::BString;
+use fnv::FnvHashMap;
+
+use gfa::{gfa::GFA, optfields::OptFields};
+
+use crate::{stats::component_roots, util::split_path};
+
+/// The component of each segment, numbered from 0 in the order of
+/// the first segment of each component, and the number of components.
+/// Like `stats::weakly_connected_components`, but for segment names
+/// that aren't integers, the links and containments are the edges,
+/// ignoring orientation.
+pub fn component_ids<T: OptFields>(
+    gfa: &GFA<Vec<u8>, T>,
+) -> (FnvHashMap<&[u8], usize>, usize) {
+    let roots = component_roots(gfa);
+
+    let mut root_ids: FnvHashMap<usize, usize> = FnvHashMap::default();
+    let mut components = FnvHashMap::default();
+    for (seg, root) in gfa.segments.iter().zip(roots) {
+        let next_id = root_ids.len();
+        let id = *root_ids.entry(root).or_insert(next_id);
+        components.insert(seg.name.as_slice(), id);
+    }
+
+    (components, root_ids.len())
+}
+
+/// Split a GFA into one GFA per weakly connected component, sorted by
+/// total sequence length, largest first. Links and containments go
+/// with the component of their segments. Paths that step between
+/// components, which only happens when links are missing, are split
+/// into the fragments in each component, like `split_path` does.
+pub fn split_components<T: OptFields + Clone>(
+    gfa: &GFA<Vec<u8>, T>,
+) -> Vec<GFA<Vec<u8>, T>> {
+    let (components, count) = component_ids(gfa);
+    let component = |name: &[u8]| components.get(name).copied();
+
+    let mut graphs: Vec<GFA<Vec<u8>, T>> = (0..count)
+        .map(|_| GFA {
+            header: gfa.header.clone(),
+            segments: Vec::new(),
+            links: Vec::new(),
+            paths: Vec::new(),
+            containments: Vec::new(),
+        })
+        .collect();
+
+    for seg in gfa.segments.iter() {
+        if let Some(c) = component(&seg.name) {
+            graphs[c].segments.push(seg.clone());
+        }
+    }
+
+    for link in gfa.links.iter() {
+        let c = component(&link.from_segment)
+            .or_else(|| component(&link.to_segment));
+        if let Some(c) = c {
+            graphs[c].links.push(link.clone());
+        }
+    }
+
+    for cont in gfa.containments.iter() {
+        let c = component(&cont.container_name)
+            .or_else(|| component(&cont.contained_name));
+        if let Some(c) = c {
+            graphs[c].containments.push(cont.clone());
+        }
+    }
+
+    for path in gfa.paths.iter() {
+        let mut path_components: Vec<usize> = path
+            .iter()
+            .filter_map(|(seg, _)| {
+                let seg: &[u8] = seg.as_ref();
+                component(seg)
+            })
+            .collect();
+        path_components.sort_unstable();
+        path_components.dedup();
+
+        if let [c] = path_components.as_slice() {
+            graphs[*c].paths.push(path.clone());
+        } else {
+            for c in path_components {
+                let fragments = split_path(path, |seg| {
+                    component(seg.as_slice()) == Some(c)
+                });
+                graphs[c].paths.extend(fragments);
+            }
+        }
+    }
+
+    graphs.sort_by_key(|graph| std::cmp::Reverse(total_length(graph)));
+    graphs
+}
+
+/// The total length of the segments of a graph
+pub fn total_length<T: OptFields>(gfa: &GFA<Vec<u8>, T>) -> usize {
+    gfa.segments.iter().map(|seg| seg.sequence.len()).sum()
+}
+
+/// The first path name of a component, if any, e.g. to tell which
+/// chromosome it is
+pub fn first_path_name<T: OptFields>(
+    gfa: &GFA<Vec<u8>, T>,
+) -> Option<&BString> {
+    gfa.paths.first().map(|path| &path.path_name)
+}
@@ -0,0 +1,280 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use clap::arg_enum;
+use flate2::{read::MultiGzDecoder, write::GzEncoder};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Compression {
+        None,
+        Gzip,
+        Zstd,
+    }
+}
+
+/// Wait for a zstd process, failing if it didn't exit successfully
+fn wait_for_zstd(child: &mut Child) -> io::Result<()> {
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("zstd exited with {}", status),
+        ))
+    }
+}
+
+/// Reads the decompressed output of a `zstd -dc` process
+struct ZstdReader {
+    child: Child,
+    stdout: Option<ChildStdout>,
+}
+
+impl ZstdReader {
+    /// Close the pipe and wait for zstd, returning an error if it
+    /// failed, e.g. because the input is corrupt or truncated
+    fn finish(&mut self) -> io::Result<()> {
+        match self.stdout.take() {
+            Some(stdout) => {
+                drop(stdout);
+                wait_for_zstd(&mut self.child)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl Read for ZstdReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = match self.stdout.as_mut() {
+            Some(stdout) => stdout.read(buf)?,
+            None => return Ok(0),
+        };
+        // The end of the output is only the end of the file if zstd
+        // succeeded, so check its exit status before reporting it
+        if read == 0 && !buf.is_empty() {
+            self.finish()?;
+        }
+        Ok(read)
+    }
+}
+
+impl Drop for ZstdReader {
+    fn drop(&mut self) {
+        // Only reached with the pipe open if the input wasn't read
+        // to the end, in which case closing it makes zstd fail
+        if self.stdout.take().is_some() {
+            if let Err(err) = self.child.wait() {
+                error!("Error waiting for zstd: {}", err);
+            }
+        }
+    }
+}
+
+/// Writes to the input of a `zstd` process, which writes the
+/// compressed output to the file or stdout
+struct ZstdWriter {
+    child: Child,
+    stdin: Option<ChildStdin>,
+}
+
+impl Write for ZstdWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.stdin.as_mut() {
+            Some(stdin) => stdin.write(buf),
+            None => Err(io::Error::new(io::ErrorKind::BrokenPipe, "zstd")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.stdin.as_mut() {
+            Some(stdin) => stdin.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl ZstdWriter {
+    /// Close zstd's input and wait for it to write the rest of the
+    /// output, returning an error if it failed
+    fn finish(&mut self) -> io::Result<()> {
+        match self.stdin.take() {
+            Some(stdin) => {
+                drop(stdin);
+                wait_for_zstd(&mut self.child)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ZstdWriter {
+    fn drop(&mut self) {
+        // Only reached with the pipe open if the output wasn't
+        // finished, e.g. on an error, so the output is incomplete
+        if self.stdin.is_some() {
+            if let Err(err) = self.finish() {
+                error!("{}", err);
+            }
+        }
+    }
+}
+
+enum OutputKind {
+    Plain(BufWriter<Box<dyn Write>>),
+    Gzip(BufWriter<GzEncoder<Box<dyn Write>>>),
+    Zstd(BufWriter<ZstdWriter>),
+}
+
+/// A buffered output, which may be compressed. It must be finished
+/// with `finish`, which writes the end of the compressed stream, and
+/// returns any error from the compression, rather than dropped.
+pub struct Output {
+    kind: OutputKind,
+}
+
+impl Output {
+    /// Flush the output and finish the compression, checking that
+    /// it succeeded
+    pub fn finish(self) -> io::Result<()> {
+        match self.kind {
+            OutputKind::Plain(mut out) => out.flush(),
+            OutputKind::Gzip(out) => out.into_inner()?.finish()?.flush(),
+            OutputKind::Zstd(out) => out.into_inner()?.finish(),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.kind {
+            OutputKind::Plain(out) => out.write(buf),
+            OutputKind::Gzip(out) => out.write(buf),
+            OutputKind::Zstd(out) => out.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.kind {
+            OutputKind::Plain(out) => out.flush(),
+            OutputKind::Gzip(out) => out.flush(),
+            OutputKind::Zstd(out) => out.flush(),
+        }
+    }
+}
+
+/// Whether the bytes start like a gzip or zstd compressed file
+pub fn is_compressed(head: &[u8]) -> bool {
+    head.starts_with(GZIP_MAGIC) || head.starts_with(ZSTD_MAGIC)
+}
+
+/// Open a file for reading, decompressing it if it's gzip or zstd
+/// compressed, as detected from the first bytes of the file. Gzip is
+/// decompressed in-process, and zstd by running `zstd -dc`, which
+/// must be available in PATH.
+pub fn open_input<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn Read>> {
+    let path = path.as_ref();
+    let mut reader = BufReader::new(File::open(path)?);
+    let (is_gzip, is_zstd) = {
+        let head = reader.fill_buf()?;
+        (head.starts_with(GZIP_MAGIC), head.starts_with(ZSTD_MAGIC))
+    };
+
+    if is_gzip {
+        debug!("Reading gzip-compressed {}", path.display());
+        Ok(Box::new(MultiGzDecoder::new(reader)))
+    } else if is_zstd {
+        debug!("Reading zstd-compressed {}", path.display());
+        let mut child = Command::new("zstd")
+            .arg("-dc")
+            .arg(path)
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take();
+        Ok(Box::new(ZstdReader { child, stdout }))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Create a buffered writer to the given file, or stdout, compressing
+/// the output if requested. Zstd compression runs `zstd`, which must
+/// be available in PATH. The output must be finished with
+/// `Output::finish`.
+pub fn create_output<P: AsRef<Path>>(
+    path: Option<P>,
+    compression: Compression,
+) -> io::Result<Output> {
+    let output = || -> io::Result<Box<dyn Write>> {
+        match &path {
+            Some(path) => Ok(Box::new(File::create(path)?)),
+            None => Ok(Box::new(io::stdout())),
+        }
+    };
+
+    let kind = match compression {
+        Compression::None => OutputKind::Plain(BufWriter::new(output()?)),
+        Compression::Gzip => OutputKind::Gzip(BufWriter::new(GzEncoder::new(
+            output()?,
+            flate2::Compression::default(),
+        ))),
+        Compression::Zstd => {
+            let stdout = match &path {
+                Some(path) => Stdio::from(File::create(path)?),
+                None => Stdio::inherit(),
+            };
+            let mut child = Command::new("zstd")
+                .arg("-q")
+                .arg("-c")
+                .stdin(Stdio::piped())
+                .stdout(stdout)
+                .spawn()?;
+            let stdin = child.stdin.take();
+            OutputKind::Zstd(BufWriter::new(ZstdWriter { child, stdin }))
+        }
+    };
+
+    Ok(Output { kind })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gzip_roundtrip() {
+        let path = std::env::temp_dir()
+            .join(format!("gfautil_compression_{}.gz", std::process::id()));
+
+        {
+            let mut out =
+                create_output(Some(&path), Compression::Gzip).unwrap();
+            writeln!(out, "read1\t6\t0\t6").unwrap();
+            out.finish().unwrap();
+        }
+
+        let raw = std::fs::read(&path).unwrap();
+        assert!(raw.starts_with(GZIP_MAGIC));
+
+        let mut text = String::new();
+        open_input(&path)
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "read1\t6\t0\t6\n");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,316 @@
+//! Finding cycles in the bidirected graph of a GFA.
+//!
+//! Each segment is split into two oriented segments, one per strand,
+//! and each link is an edge between them, along with its reverse
+//! complement, from the other strand of its target to the other
+//! strand of its source. The graph has a cycle if an oriented segment
+//! can be reached from itself, i.e. if it's in a strongly connected
+//! component with more than one oriented segment, or with a self-loop.
+
+use std::collections::{HashSet, VecDeque};
+
+use fnv::FnvHashMap;
+
+use gfa::{gfa::GFA, optfields::OptFields};
+
+use crate::components;
+
+/// An oriented segment, as its index in the GFA, and whether it's
+/// the reverse strand
+pub type OrientedSegment = (usize, bool);
+
+/// The cycles of a graph, and the links that often cause them
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CycleReport {
+    /// The indices of the links from a segment to itself
+    pub self_loops: Vec<usize>,
+    /// The indices of the links that join the strands of different
+    /// orientations, e.g. `A+` to `B-`, including self-loops that
+    /// reverse a segment
+    pub reversals: Vec<usize>,
+    /// The number of segments that are part of a cycle, on either
+    /// strand
+    pub cyclic_segments: usize,
+    /// The shortest cycles that were found, each starting from its
+    /// lowest oriented segment, sorted by length. A cycle and its
+    /// reverse complement are only listed once.
+    pub cycles: Vec<Vec<OrientedSegment>>,
+}
+
+impl CycleReport {
+    pub fn is_acyclic(&self) -> bool {
+        self.cyclic_segments == 0
+    }
+}
+
+/// An oriented segment as a single index, `2 * segment + reverse`
+fn handle(segment: usize, reverse: bool) -> usize {
+    2 * segment + reverse as usize
+}
+
+fn flip(handle: usize) -> usize {
+    handle ^ 1
+}
+
+fn oriented_segment(handle: usize) -> OrientedSegment {
+    (handle / 2, handle % 2 == 1)
+}
+
+/// The successors of each oriented segment
+fn successors<T: OptFields>(
+    gfa: &GFA<Vec<u8>, T>,
+    seg_ix: &FnvHashMap<&[u8], usize>,
+) -> Vec<Vec<usize>> {
+    let mut edges = vec![Vec::new(); 2 * gfa.segments.len()];
+    for link in gfa.links.iter() {
+        let from = seg_ix.get(link.from_segment.as_slice());
+        let to = seg_ix.get(link.to_segment.as_slice());
+        if let (Some(&from), Some(&to)) = (from, to) {
+            let from = handle(from, link.from_orient.is_reverse());
+            let to = handle(to, link.to_orient.is_reverse());
+            edges[from].push(to);
+            edges[flip(to)].push(flip(from));
+        }
+    }
+    for succs in edges.iter_mut() {
+        succs.sort_unstable();
+        succs.dedup();
+    }
+    edges
+}
+
+/// The nodes of a directed graph in the order a depth-first search
+/// finishes them
+fn finish_order(edges: &[Vec<usize>]) -> Vec<usize> {
+    let mut visited = vec![false; edges.len()];
+    let mut order = Vec::with_capacity(edges.len());
+
+    for start in 0..edges.len() {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut stack = vec![(start, 0)];
+        while let Some((node, next)) = stack.last_mut() {
+            if let Some(&succ) = edges[*node].get(*next) {
+                *next += 1;
+                if !visited[succ] {
+                    visited[succ] = true;
+                    stack.push((succ, 0));
+                }
+            } else {
+                order.push(*node);
+                stack.pop();
+            }
+        }
+    }
+    order
+}
+
+/// The strongly connected component of each node of a directed
+/// graph, using Kosaraju's algorithm
+fn strong_components(edges: &[Vec<usize>]) -> Vec<usize> {
+    let mut reversed = vec![Vec::new(); edges.len()];
+    for (from, succs) in edges.iter().enumerate() {
+        for &to in succs {
+            reversed[to].push(from);
+        }
+    }
+
+    let mut component = vec![usize::MAX; edges.len()];
+    let mut next_component = 0;
+    for &start in finish_order(edges).iter().rev() {
+        if component[start] != usize::MAX {
+            continue;
+        }
+        component[start] = next_component;
+        let mut stack = vec![start];
+        while let Some(node) = stack.pop() {
+            for &pred in reversed[node].iter() {
+                if component[pred] == usize::MAX {
+                    component[pred] = next_component;
+                    stack.push(pred);
+                }
+            }
+        }
+        next_component += 1;
+    }
+    component
+}
+
+/// The shortest cycle through `start`, with at most `max_length`
+/// oriented segments, staying inside its strongly connected component
+fn shortest_cycle(
+    edges: &[Vec<usize>],
+    component: &[usize],
+    start: usize,
+    max_length: usize,
+) -> Option<Vec<usize>> {
+    let mut parents: FnvHashMap<usize, usize> = FnvHashMap::default();
+    let mut queue = VecDeque::new();
+    queue.push_back((start, 1));
+
+    while let Some((node, length)) = queue.pop_front() {
+        for &succ in edges[node].iter() {
+            if succ == start {
+                let mut cycle = vec![node];
+                while let Some(&parent) = parents.get(cycle.last().unwrap()) {
+                    cycle.push(parent);
+                }
+                cycle.reverse();
+                return Some(cycle);
+            }
+            if length < max_length
+                && component[succ] == component[start]
+                && !parents.contains_key(&succ)
+            {
+                parents.insert(succ, node);
+                queue.push_back((succ, length + 1));
+            }
+        }
+    }
+    None
+}
+
+/// The rotation of a cycle, or of its reverse complement, that is
+/// the smallest, so that each cycle has one representation
+fn canonical_cycle(cycle: &[usize]) -> Vec<usize> {
+    let complement: Vec<usize> = cycle.iter().rev().map(|&h| flip(h)).collect();
+    let mut best: Option<Vec<usize>> = None;
+    for cycle in [cycle, complement.as_slice()].iter() {
+        for i in 0..cycle.len() {
+            let rotated: Vec<usize> = cycle[i..]
+                .iter()
+                .chain(cycle[..i].iter())
+                .copied()
+                .collect();
+            if best.as_ref().map_or(true, |best| rotated < *best) {
+                best = Some(rotated);
+            }
+        }
+    }
+    best.unwrap_or_default()
+}
+
+/// Find the cycles of the bidirected graph, and list the self-loops
+/// and reversing links. The shortest cycle through each oriented
+/// segment is searched for, up to `max_length` oriented segments, and
+/// the `max_cycles` shortest of them are reported.
+pub fn find_cycles<T: OptFields>(
+    gfa: &GFA<Vec<u8>, T>,
+    max_cycles: usize,
+    max_length: usize,
+) -> CycleReport {
+    let seg_ix: FnvHashMap<&[u8], usize> = gfa
+        .segments
+        .iter()
+        .enumerate()
+        .map(|(ix, seg)| (seg.name.as_slice(), ix))
+        .collect();
+
+    let mut report = CycleReport::default();
+    for (ix, link) in gfa.links.iter().enumerate() {
+        if link.from_segment == link.to_segment {
+            report.self_loops.push(ix);
+        }
+        if link.from_orient != link.to_orient {
+            report.reversals.push(ix);
+        }
+    }
+
+    let edges = successors(gfa, &seg_ix);
+    let component = strong_components(&edges);
+
+    let mut component_sizes: FnvHashMap<usize, usize> = FnvHashMap::default();
+    for &c in component.iter() {
+        *component_sizes.entry(c).or_default() += 1;
+    }
+    let is_cyclic =
+        |h: usize| component_sizes[&component[h]] > 1 || edges[h].contains(&h);
+
+    report.cyclic_segments = (0..gfa.segments.len())
+        .filter(|&seg| {
+            is_cyclic(handle(seg, false)) || is_cyclic(handle(seg, true))
+        })
+        .count();
+
+    let mut cycles: HashSet<Vec<usize>> = HashSet::new();
+    if max_cycles > 0 {
+        for h in (0..edges.len()).filter(|&h| is_cyclic(h)) {
+            if let Some(cycle) =
+                shortest_cycle(&edges, &component, h, max_length)
+            {
+                cycles.insert(canonical_cycle(&cycle));
+            }
+        }
+    }
+
+    let mut cycles: Vec<Vec<usize>> = cycles.into_iter().collect();
+    cycles.sort_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)));
+    cycles.truncate(max_cycles);
+    report.cycles = cycles
+        .into_iter()
+        .map(|cycle| cycle.into_iter().map(oriented_segment).collect())
+        .collect();
+
+    report
+}
+
+/// The self-loops and reversing links in a weakly connected component
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentLinks {
+    /// The component's ID, as given by `components::component_ids`
+    pub component: usize,
+    /// The index of the component's first segment in the GFA
+    pub first_segment: usize,
+    pub segments: usize,
+    pub self_loops: usize,
+    pub reversals: usize,
+}
+
+/// Count the self-loops and reversing links of the report in each
+/// weakly connected component of the graph. Only the components with
+/// at least one of either are returned, sorted by component ID.
+pub fn links_per_component<T: OptFields>(
+    gfa: &GFA<Vec<u8>, T>,
+    report: &CycleReport,
+) -> Vec<ComponentLinks> {
+    let (component_ids, count) = components::component_ids(gfa);
+
+    let mut components: Vec<ComponentLinks> = Vec::with_capacity(count);
+    for (ix, seg) in gfa.segments.iter().enumerate() {
+        let id = component_ids[seg.name.as_slice()];
+        if id == components.len() {
+            components.push(ComponentLinks {
+                component: id,
+                first_segment: ix,
+                segments: 0,
+                self_loops: 0,
+                reversals: 0,
+            });
+        }
+        components[id].segments += 1;
+    }
+
+    let link_component = |ix: usize| {
+        component_ids
+            .get(gfa.links[ix].from_segment.as_slice())
+            .copied()
+    };
+
+    for id in report
+        .self_loops
+        .iter()
+        .filter_map(|&ix| link_component(ix))
+    {
+        components[id].self_loops += 1;
+    }
+    for id in report.reversals.iter().filter_map(|&ix| link_component(ix)) {
+        components[id].reversals += 1;
+    }
+
+    components
+        .into_iter()
+        .filter(|comp| comp.self_loops > 0 || comp.reversals > 0)
+        .collect()
+}
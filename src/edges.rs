@@ -1,5 +1,8 @@
+use fnv::{FnvHashMap, FnvHashSet};
 use handlegraph::handlegraph::*;
 
+use gfa::{gfa::GFA, optfields::OptFields};
+
 /// Return the inbound and outbound edge counts for each node in the
 /// graph
 pub fn graph_edge_count<T: HandleGraphRef>(
@@ -18,3 +21,127 @@ pub fn graph_edge_count<T: HandleGraphRef>(
         })
         .collect()
 }
+
+/// Return the inbound and outbound link counts, and their total, for
+/// each segment of the GFA, in the order of the segments, counted in
+/// one pass over the links.
+///
+/// As in `graph_edge_count`, the inbound links of a segment are those
+/// attached to the start of its forward strand, and the outbound links
+/// those attached to its end, so a link from `A-` is an inbound link of
+/// `A`. Every L line is counted, and links to segments that aren't in
+/// the GFA are ignored.
+pub fn gfa_edge_count<T: OptFields>(
+    gfa: &GFA<Vec<u8>, T>,
+) -> Vec<(usize, usize, usize)> {
+    let seg_ix: FnvHashMap<&[u8], usize> = gfa
+        .segments
+        .iter()
+        .enumerate()
+        .map(|(ix, seg)| (seg.name.as_slice(), ix))
+        .collect();
+
+    let mut counts = vec![(0, 0); gfa.segments.len()];
+    for link in gfa.links.iter() {
+        if let Some(&from) = seg_ix.get(link.from_segment.as_slice()) {
+            if link.from_orient.is_reverse() {
+                counts[from].0 += 1;
+            } else {
+                counts[from].1 += 1;
+            }
+        }
+        if let Some(&to) = seg_ix.get(link.to_segment.as_slice()) {
+            if link.to_orient.is_reverse() {
+                counts[to].1 += 1;
+            } else {
+                counts[to].0 += 1;
+            }
+        }
+    }
+
+    counts
+        .into_iter()
+        .map(|(inbound, outbound)| (inbound, outbound, inbound + outbound))
+        .collect()
+}
+
+/// Build a histogram of the total degree of each node, from the
+/// output of `graph_edge_count`
+pub fn total_degree_histogram(
+    edge_counts: &[(u64, usize, usize, usize)],
+) -> Vec<(usize, usize)> {
+    crate::stats::histogram(edge_counts.iter().map(|&(_, _, _, t)| t))
+}
+
+/// Count the nodes with each inbound, outbound, and total degree,
+/// from the output of `graph_edge_count`. Returns a (degree, inbound,
+/// outbound, total) row for every degree from 0 to the highest total
+/// degree.
+pub fn degree_histogram(
+    edge_counts: &[(u64, usize, usize, usize)],
+) -> Vec<(usize, usize, usize, usize)> {
+    let max_degree = match edge_counts.iter().map(|&(_, _, _, t)| t).max() {
+        Some(max_degree) => max_degree,
+        None => return Vec::new(),
+    };
+
+    let mut rows: Vec<_> = (0..=max_degree).map(|d| (d, 0, 0, 0)).collect();
+    for &(_, inbound, outbound, total) in edge_counts {
+        rows[inbound].1 += 1;
+        rows[outbound].2 += 1;
+        rows[total].3 += 1;
+    }
+    rows
+}
+
+/// The `k` nodes with the highest total degree, from the output of
+/// `graph_edge_count`, with ties broken by the lowest node ID
+pub fn top_degree_nodes(
+    edge_counts: &[(u64, usize, usize, usize)],
+    k: usize,
+) -> Vec<(u64, usize, usize, usize)> {
+    let mut nodes = edge_counts.to_vec();
+    nodes.sort_by(|a, b| b.3.cmp(&a.3).then(a.0.cmp(&b.0)));
+    nodes.truncate(k);
+    nodes
+}
+
+/// The nodes with more than one inbound or outbound edge, from the
+/// output of `graph_edge_count`
+pub fn branching_nodes(
+    edge_counts: &[(u64, usize, usize, usize)],
+) -> FnvHashSet<u64> {
+    edge_counts
+        .iter()
+        .filter(|&&(_, inbound, outbound, _)| inbound > 1 || outbound > 1)
+        .map(|&(id, _, _, _)| id)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn degree_histogram_and_top_nodes() {
+        let edge_counts = vec![
+            (1, 0, 2, 2),
+            (2, 1, 1, 2),
+            (3, 1, 2, 3),
+            (4, 3, 0, 3),
+            (5, 0, 0, 0),
+        ];
+
+        assert_eq!(
+            degree_histogram(&edge_counts),
+            vec![(0, 2, 2, 1), (1, 2, 1, 0), (2, 0, 2, 2), (3, 1, 0, 2)]
+        );
+        assert!(degree_histogram(&[]).is_empty());
+
+        assert_eq!(
+            top_degree_nodes(&edge_counts, 3),
+            vec![(3, 1, 2, 3), (4, 3, 0, 3), (1, 0, 2, 2)]
+        );
+        assert_eq!(top_degree_nodes(&edge_counts, 10).len(), 5);
+    }
+}
@@ -0,0 +1,143 @@
+//! Reading FASTA files, and the sequence checksums recorded in
+//! sequence dictionaries.
+
+use std::io::{self, BufRead};
+
+use bstr::{BString, ByteSlice};
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+/// A FASTA record, with the name taken from its header line up to
+/// the first whitespace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FastaRecord {
+    pub name: BString,
+    pub sequence: Vec<u8>,
+}
+
+/// Reads the records of a FASTA file one at a time, with the
+/// sequence lines joined
+pub struct FastaReader<R> {
+    reader: R,
+    line: Vec<u8>,
+    next_name: Option<BString>,
+}
+
+impl<R: BufRead> FastaReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: Vec::new(),
+            next_name: None,
+        }
+    }
+
+    /// Read the next line, without its line ending, returning false
+    /// at the end of the file
+    fn read_line(&mut self) -> io::Result<bool> {
+        self.line.clear();
+        if self.reader.read_until(b'\n', &mut self.line)? == 0 {
+            return Ok(false);
+        }
+        while self.line.ends_with(b"\n") || self.line.ends_with(b"\r") {
+            self.line.pop();
+        }
+        Ok(true)
+    }
+
+    fn header_name(line: &[u8]) -> BString {
+        line[1..]
+            .fields()
+            .next()
+            .map(BString::from)
+            .unwrap_or_default()
+    }
+
+    fn read_record(&mut self) -> io::Result<Option<FastaRecord>> {
+        let name = match self.next_name.take() {
+            Some(name) => name,
+            None => loop {
+                if !self.read_line()? {
+                    return Ok(None);
+                }
+                if self.line.starts_with(b">") {
+                    break Self::header_name(&self.line);
+                } else if !self.line.trim().is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "FASTA sequence before the first header line",
+                    ));
+                }
+            },
+        };
+
+        let mut sequence = Vec::new();
+        while self.read_line()? {
+            if self.line.starts_with(b">") {
+                self.next_name = Some(Self::header_name(&self.line));
+                break;
+            }
+            sequence
+                .extend(self.line.iter().filter(|b| !b.is_ascii_whitespace()));
+        }
+
+        Ok(Some(FastaRecord { name, sequence }))
+    }
+}
+
+impl<R: BufRead> Iterator for FastaReader<R> {
+    type Item = io::Result<FastaRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// The first position, 0-based, at which the two sequences differ,
+/// ignoring case, or `None` if they're the same. If one sequence is a
+/// prefix of the other, they differ at the end of the shorter one.
+pub fn first_mismatch(a: &[u8], b: &[u8]) -> Option<usize> {
+    a.iter()
+        .zip(b.iter())
+        .position(|(x, y)| !x.eq_ignore_ascii_case(y))
+        .or_else(|| {
+            if a.len() == b.len() {
+                None
+            } else {
+                Some(a.len().min(b.len()))
+            }
+        })
+}
+
+/// The MD5 and SHA-256 checksums of a sequence, as lowercase hex
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceChecksums {
+    pub md5: String,
+    pub sha256: String,
+}
+
+impl SequenceChecksums {
+    /// Like the M5 tag of SAM headers and the md5 field of VCF
+    /// contigs, the checksums are of the sequence in uppercase, with
+    /// any whitespace or other characters outside `!`-`~` removed, so
+    /// that soft-masking doesn't change them.
+    pub fn from_sequence(seq: &[u8]) -> Self {
+        let mut md5 = Md5::new();
+        let mut sha256 = Sha256::new();
+
+        for chunk in seq.chunks(1 << 16) {
+            let normalized: Vec<u8> = chunk
+                .iter()
+                .filter(|&&b| (b'!'..=b'~').contains(&b))
+                .map(|b| b.to_ascii_uppercase())
+                .collect();
+            md5.update(&normalized);
+            sha256.update(&normalized);
+        }
+
+        Self {
+            md5: format!("{:x}", md5.finalize()),
+            sha256: format!("{:x}", sha256.finalize()),
+        }
+    }
+}
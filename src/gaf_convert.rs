@@ -1,23 +1,160 @@
-use std::{cmp::Ordering, fs::File, io::BufReader, path::Path};
+use std::{
+    cmp::Ordering,
+    fmt,
+    io::{self, BufReader, Read},
+    path::Path,
+};
 
-use bstr::{io::*, ByteSlice};
+use bstr::{io::*, BString, ByteSlice};
+use fnv::FnvHashMap;
 
 use gfa::{
     cigar::{CIGAROp, CIGAR},
     gafpaf::{parse_gaf, GAFPath, GAFStep},
     gfa::{Link, Orientation, Segment, GFA},
-    optfields::{OptFieldVal, OptFields, OptionalFields},
+    optfields::{OptField, OptFieldVal, OptFields, OptionalFields},
 };
 
 type GAF = gfa::gafpaf::GAF<OptionalFields>;
 type PAF = gfa::gafpaf::PAF<OptionalFields>;
 
+/// Why a GAF line couldn't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GafParseError {
+    /// 1-based line number
+    pub line: usize,
+    /// The name of the offending column, if the problem could be
+    /// narrowed down to one
+    pub field: Option<&'static str>,
+    pub reason: String,
+}
+
+impl fmt::Display for GafParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.field {
+            Some(field) => write!(
+                f,
+                "GAF line {}: invalid {}: {}",
+                self.line, field, self.reason
+            ),
+            None => write!(f, "GAF line {}: {}", self.line, self.reason),
+        }
+    }
+}
+
+const GAF_COLUMNS: [&str; 12] = [
+    "query name",
+    "query length",
+    "query start",
+    "query end",
+    "strand",
+    "path",
+    "path length",
+    "path start",
+    "path end",
+    "residue matches",
+    "block length",
+    "mapping quality",
+];
+
+/// Find out why a GAF line, numbered from 1, couldn't be parsed
+pub fn diagnose_gaf_line(line_no: usize, line: &[u8]) -> GafParseError {
+    let error = |field: Option<&'static str>, reason: String| GafParseError {
+        line: line_no,
+        field,
+        reason,
+    };
+
+    let fields: Vec<&[u8]> = line.split_str(b"\t").collect();
+    if fields.len() < GAF_COLUMNS.len() {
+        return error(
+            None,
+            format!(
+                "expected at least {} tab-separated columns, found {}",
+                GAF_COLUMNS.len(),
+                fields.len()
+            ),
+        );
+    }
+
+    for &ix in &[1, 2, 3, 6, 7, 8, 9, 10, 11] {
+        let value = fields[ix];
+        let is_int = value
+            .to_str()
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .is_some();
+        if !is_int {
+            return error(
+                Some(GAF_COLUMNS[ix]),
+                format!("expected an integer, found {:?}", value.as_bstr()),
+            );
+        }
+    }
+
+    if fields[4] != b"+" && fields[4] != b"-" {
+        return error(
+            Some(GAF_COLUMNS[4]),
+            format!("expected + or -, found {:?}", fields[4].as_bstr()),
+        );
+    }
+
+    if fields[5].is_empty() {
+        return error(Some(GAF_COLUMNS[5]), "the path is empty".to_string());
+    }
+
+    error(
+        None,
+        "the path or the optional fields could not be parsed".to_string(),
+    )
+}
+
+/// Errors that prevent a GAF record from being converted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GafConvertError {
+    Parse(GafParseError),
+    MissingCigar(BString),
+    UnknownSegment(BString),
+    UnresolvedInterval(BString, usize, usize),
+    /// The GAF couldn't be read, with the I/O error's message
+    Read(String),
+}
+
+impl fmt::Display for GafConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GafConvertError::Parse(err) => write!(f, "{}", err),
+            GafConvertError::MissingCigar(name) => {
+                write!(f, "GAF record for {} has no cg:Z: CIGAR", name)
+            }
+            GafConvertError::UnknownSegment(name) => {
+                write!(f, "segment {} does not exist in the graph", name)
+            }
+            GafConvertError::UnresolvedInterval(name, from, to) => write!(
+                f,
+                "stable interval {}:{}-{} does not match the boundaries \
+                 of segments with SN/SO tags",
+                name, from, to
+            ),
+            GafConvertError::Read(err) => {
+                write!(f, "could not read the GAF: {}", err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GafConvertError {}
+
 fn set_cigar(opts: &mut OptionalFields, cg: CIGAR) {
-    let cg_tag = opts.iter_mut().find(|o| &o.tag == b"cg").unwrap();
-    cg_tag.value = OptFieldVal::Z(cg.to_string().into());
+    let value = OptFieldVal::Z(cg.to_string().into());
+    if let Some(cg_tag) = opts.iter_mut().find(|o| &o.tag == b"cg") {
+        cg_tag.value = value;
+    } else {
+        opts.push(OptField { tag: *b"cg", value });
+    }
 }
 
-fn get_cigar<T: OptFields>(opts: &T) -> Option<CIGAR> {
+pub(crate) fn get_cigar<T: OptFields>(opts: &T) -> Option<CIGAR> {
     let cg = opts.get_field(b"cg")?;
     if let OptFieldVal::Z(cg) = &cg.value {
         CIGAR::from_bytestring(&cg)
@@ -47,7 +184,7 @@ fn gaf_to_paf_clone(gaf: &GAF) -> PAF {
 }
 
 fn find_segment<'a, T: OptFields>(
-    segs: &'a [Segment<Vec<u8>, T>],
+    segs: &[&'a Segment<Vec<u8>, T>],
     name: &[u8],
 ) -> Option<&'a Segment<Vec<u8>, T>> {
     let ix = segs
@@ -56,7 +193,7 @@ fn find_segment<'a, T: OptFields>(
             seg.cmp(name)
         })
         .ok()?;
-    segs.get(ix)
+    segs.get(ix).copied()
 }
 
 fn cmp_links_find<T: OptFields, B: AsRef<[u8]>>(
@@ -81,82 +218,406 @@ fn cmp_links<T: OptFields>(
     cmp_links_find(l1, &l2.from_segment, &l2.to_segment)
 }
 
-fn unwrap_step(step: &GAFStep) -> (Orientation, &[u8]) {
-    match step {
-        GAFStep::SegId(o, id) => (*o, id.as_ref()),
-        GAFStep::StableIntv(o, id, _from, _to) => (*o, id.as_ref()),
+fn step_strand(gaf_strand: Orientation, orient: Orientation) -> Orientation {
+    use Orientation::*;
+    match (gaf_strand, orient) {
+        (Forward, Forward) => Forward,
+        (Forward, Backward) => Backward,
+        (Backward, Forward) => Backward,
+        (Backward, Backward) => Forward,
     }
 }
 
-// must take sorted segment and link slices
-fn gaf_line_to_pafs<T: OptFields>(
-    segments: &[Segment<Vec<u8>, T>],
+/// Scale `part` of `whole` to the same fraction of `total`
+fn proportional(total: usize, part: usize, whole: usize) -> usize {
+    if whole == 0 {
+        0
+    } else {
+        ((total as u128 * part as u128) / whole as u128) as usize
+    }
+}
+
+/// The links from `from` to `to`, in the links sorted by `cmp_links`
+fn find_links<'b, 'a, T: OptFields>(
+    links: &'b [&'a Link<Vec<u8>, T>],
+    from: &[u8],
+    to: &[u8],
+) -> &'b [&'a Link<Vec<u8>, T>] {
+    let ix = match links.binary_search_by(|l| cmp_links_find(l, from, to)) {
+        Ok(ix) => ix,
+        Err(_) => return &[],
+    };
+    let is_match =
+        |l: &Link<Vec<u8>, T>| cmp_links_find(l, from, to) == Ordering::Equal;
+    let mut start = ix;
+    while start > 0 && is_match(links[start - 1]) {
+        start -= 1;
+    }
+    let mut end = ix + 1;
+    while end < links.len() && is_match(links[end]) {
+        end += 1;
+    }
+    &links[start..end]
+}
+
+/// The number of query bases in a CIGAR
+fn cigar_query_len(cigar: &CIGAR) -> usize {
+    use CIGAROp::*;
+    cigar
+        .iter_single()
+        .filter(|op| match op {
+            I | S => true,
+            op => op.is_match_or_mismatch(),
+        })
+        .count()
+}
+
+/// The number of reference bases in a CIGAR
+fn cigar_ref_len(cigar: &CIGAR) -> usize {
+    use CIGAROp::*;
+    cigar
+        .iter_single()
+        .filter(|op| match op {
+            D | N => true,
+            op => op.is_match_or_mismatch(),
+        })
+        .count()
+}
+
+/// The number of residue matches and the alignment block length of
+/// a CIGAR, as in the PAF columns 10 and 11. Mismatches (`X`) aren't
+/// counted as matches, but `M` operations are, as they can't be told
+/// apart from matches. The block length includes the gaps.
+fn cigar_matches_and_block_length(cigar: &CIGAR) -> (usize, usize) {
+    use CIGAROp::*;
+    let mut matches = 0;
+    let mut block_length = 0;
+    for op in cigar.iter_single() {
+        match op {
+            X => block_length += 1,
+            I | D => block_length += 1,
+            _ if op.is_match_or_mismatch() => {
+                matches += 1;
+                block_length += 1;
+            }
+            _ => (),
+        }
+    }
+    (matches, block_length)
+}
+
+/// Split a GAF record without a CIGAR into one PAF record per
+/// segment. Without a CIGAR, the query coordinates, residue matches,
+/// and block length are divided among the segments in proportion to
+/// the part of the alignment's path interval each segment covers.
+fn gaf_line_to_pafs_without_cigar<T: OptFields>(
     gaf: &GAF,
+    seg_steps: &[(Orientation, &Segment<Vec<u8>, T>)],
+    overlaps: &[usize],
 ) -> Vec<PAF> {
+    let (path_start, path_end) = gaf.path_range;
+    let (query_start, query_end) = gaf.seq_range;
+    let path_aln_len = path_end.saturating_sub(path_start);
+    let query_aln_len = query_end.saturating_sub(query_start);
+
+    let to_query = |path_pos: usize| {
+        query_start
+            + proportional(query_aln_len, path_pos - path_start, path_aln_len)
+    };
+
+    let mut pafs = Vec::new();
+    // The path position of the first base of each segment that
+    // isn't shared with the previous segment
+    let mut seg_start = 0;
+
+    for ((orient, target), &overlap) in seg_steps.iter().zip(overlaps) {
+        let seg_len = target.sequence.len();
+        let seg_end = seg_start + seg_len - overlap;
+
+        let from = seg_start.max(path_start);
+        let to = seg_end.min(path_end);
+
+        if from < to {
+            let aln_len = to - from;
+            pafs.push(PAF {
+                query_seq_name: gaf.seq_name.clone(),
+                query_seq_len: gaf.seq_len,
+                query_seq_range: (to_query(from), to_query(to)),
+                strand: step_strand(gaf.strand, *orient),
+                target_seq_name: target.name.clone().into(),
+                target_seq_len: seg_len,
+                target_seq_range: (
+                    from - seg_start + overlap,
+                    to - seg_start + overlap,
+                ),
+                residue_matches: proportional(
+                    gaf.residue_matches,
+                    aln_len,
+                    path_aln_len,
+                ),
+                block_length: proportional(
+                    gaf.block_length,
+                    aln_len,
+                    path_aln_len,
+                ),
+                quality: gaf.quality,
+                optional: gaf.optional.clone(),
+            });
+        }
+
+        seg_start = seg_end;
+    }
+
+    pafs
+}
+
+/// The segments of the GFA sorted by name, the links sorted by their
+/// segment names, and, for rGFA graphs, the segments on each stable
+/// sequence, by their SN and SO tags. The segments and links are
+/// borrowed from the GFA.
+pub struct SegmentIndex<'a, T: OptFields> {
+    segments: Vec<&'a Segment<Vec<u8>, T>>,
+    links: Vec<&'a Link<Vec<u8>, T>>,
+    /// Stable sequence name to (offset, segment index) pairs, sorted
+    /// by offset
+    stable: FnvHashMap<BString, Vec<(usize, usize)>>,
+}
+
+impl<'a, T: OptFields> SegmentIndex<'a, T> {
+    pub fn new(gfa: &'a GFA<Vec<u8>, T>) -> Self {
+        let mut segments: Vec<_> = gfa.segments.iter().collect();
+        segments.sort_by(|s1, s2| s1.name.cmp(&s2.name));
+
+        let mut links: Vec<_> = gfa.links.iter().collect();
+        links.sort_by(|l1, l2| cmp_links(l1, l2));
+
+        let mut stable: FnvHashMap<BString, Vec<(usize, usize)>> =
+            FnvHashMap::default();
+
+        for (ix, seg) in segments.iter().enumerate() {
+            let name =
+                seg.optional.get_field(b"SN").and_then(|f| match &f.value {
+                    OptFieldVal::Z(name) => Some(name.clone()),
+                    _ => None,
+                });
+            let offset =
+                seg.optional.get_field(b"SO").and_then(|f| match f.value {
+                    OptFieldVal::Int(offset) if offset >= 0 => {
+                        Some(offset as usize)
+                    }
+                    _ => None,
+                });
+            if let (Some(name), Some(offset)) = (name, offset) {
+                stable.entry(name).or_default().push((offset, ix));
+            }
+        }
+
+        for segs in stable.values_mut() {
+            segs.sort_unstable();
+        }
+
+        Self {
+            segments,
+            links,
+            stable,
+        }
+    }
+
+    pub fn segments(&self) -> &[&'a Segment<Vec<u8>, T>] {
+        &self.segments
+    }
+
+    /// The number of bases at the start of the oriented segment `to`
+    /// that overlap the end of `from`, according to the CIGAR of the
+    /// link between them. Links may be given in either direction.
+    /// Returns 0 if there is no such link, or its overlap is `*`.
+    pub(crate) fn link_overlap(
+        &self,
+        (from_orient, from): (Orientation, &[u8]),
+        (to_orient, to): (Orientation, &[u8]),
+    ) -> usize {
+        let forward = find_links(&self.links, from, to)
+            .iter()
+            .find(|l| l.from_orient == from_orient && l.to_orient == to_orient)
+            .and_then(|l| CIGAR::from_bytestring(&l.overlap))
+            .map(|cigar| cigar_query_len(&cigar));
+
+        // The same link, given from `to` to `from` with the
+        // orientations flipped, overlaps `to` with its reference
+        forward
+            .or_else(|| {
+                find_links(&self.links, to, from)
+                    .iter()
+                    .find(|l| {
+                        l.from_orient != to_orient && l.to_orient != from_orient
+                    })
+                    .and_then(|l| CIGAR::from_bytestring(&l.overlap))
+                    .map(|cigar| cigar_ref_len(&cigar))
+            })
+            .unwrap_or(0)
+    }
+
+    /// The overlap of each oriented segment with the one before it,
+    /// which is 0 for the first one, capped at the segment lengths
+    pub(crate) fn step_overlaps(
+        &self,
+        seg_steps: &[(Orientation, &Segment<Vec<u8>, T>)],
+    ) -> Vec<usize> {
+        let mut overlaps = Vec::with_capacity(seg_steps.len());
+        if !seg_steps.is_empty() {
+            overlaps.push(0);
+        }
+        for pair in seg_steps.windows(2) {
+            let (from_orient, from) = pair[0];
+            let (to_orient, to) = pair[1];
+            let overlap = self.link_overlap(
+                (from_orient, from.name.as_slice()),
+                (to_orient, to.name.as_slice()),
+            );
+            overlaps
+                .push(overlap.min(from.sequence.len()).min(to.sequence.len()));
+        }
+        overlaps
+    }
+
+    /// Resolve a GAF step to the oriented segments it covers. A
+    /// stable interval must start and end at segment boundaries on
+    /// its stable sequence, which is always the case for the paths
+    /// in GAF records against rGFA graphs.
+    pub(crate) fn resolve_step(
+        &self,
+        step: &GAFStep,
+    ) -> Result<Vec<(Orientation, &Segment<Vec<u8>, T>)>, GafConvertError> {
+        match step {
+            GAFStep::SegId(orient, id) => find_segment(&self.segments, id)
+                .map(|seg| vec![(*orient, seg)])
+                .ok_or_else(|| GafConvertError::UnknownSegment(id.clone())),
+            GAFStep::StableIntv(orient, name, from, to) => {
+                let unresolved = || {
+                    GafConvertError::UnresolvedInterval(
+                        name.clone(),
+                        *from,
+                        *to,
+                    )
+                };
+
+                let stable_segs =
+                    self.stable.get(name).ok_or_else(unresolved)?;
+
+                let mut steps = Vec::new();
+                let mut pos = *from;
+                let start = stable_segs
+                    .iter()
+                    .position(|&(offset, _)| offset == *from)
+                    .ok_or_else(unresolved)?;
+
+                for &(offset, seg_ix) in stable_segs[start..].iter() {
+                    if pos == *to {
+                        break;
+                    }
+                    if offset != pos {
+                        return Err(unresolved());
+                    }
+                    let seg = self.segments[seg_ix];
+                    pos += seg.sequence.len();
+                    steps.push((*orient, seg));
+                }
+
+                if pos != *to {
+                    return Err(unresolved());
+                }
+
+                if orient.is_reverse() {
+                    steps.reverse();
+                }
+
+                Ok(steps)
+            }
+        }
+    }
+}
+
+fn gaf_line_to_pafs<T: OptFields>(
+    index: &SegmentIndex<'_, T>,
+    gaf: &GAF,
+    require_cigar: bool,
+) -> Result<Vec<PAF>, GafConvertError> {
     match &gaf.path {
         GAFPath::StableId(id) => {
             let paf = PAF {
                 target_seq_name: id.clone(),
                 ..gaf_to_paf_clone(gaf)
             };
-            vec![paf]
+            Ok(vec![paf])
         }
         GAFPath::OrientIntv(steps) => {
-            let seg_steps: Vec<(Orientation, &Segment<_, _>)> = steps
-                .iter()
-                .map(|s| {
-                    let (o, id) = unwrap_step(s);
-                    let segment = find_segment(segments, id).unwrap();
-                    (o, segment)
-                })
-                .collect();
+            let mut seg_steps: Vec<(Orientation, &Segment<_, _>)> =
+                Vec::with_capacity(steps.len());
+            for step in steps.iter() {
+                seg_steps.extend(index.resolve_step(step)?);
+            }
 
-            let mut query_index = gaf.seq_range.0;
-            let mut tgt_offset = gaf.path_range.0;
-            let mut query_remaining = gaf.seq_len;
+            let overlaps = index.step_overlaps(&seg_steps);
 
-            let mut seqs: Vec<Vec<u8>> = Vec::new();
+            let (path_start, path_end) = gaf.path_range;
+            let mut query_index = gaf.seq_range.0;
+            let mut tgt_offset = path_start;
+            let mut path_remaining = path_end.saturating_sub(path_start);
 
             let mut pafs = Vec::new();
 
-            let mut gaf_cigar =
-                get_gaf_cigar(gaf).expect("missing cigar in GAF record");
+            let mut gaf_cigar = match get_gaf_cigar(gaf) {
+                Some(cigar) => cigar,
+                None if require_cigar => {
+                    return Err(GafConvertError::MissingCigar(
+                        gaf.seq_name.clone(),
+                    ))
+                }
+                None => {
+                    return Ok(gaf_line_to_pafs_without_cigar(
+                        gaf, &seg_steps, &overlaps,
+                    ))
+                }
+            };
 
-            for (orient, target) in seg_steps {
+            for (ix, (orient, target)) in seg_steps.iter().copied().enumerate()
+            {
                 let seg_len = target.sequence.len();
 
-                let step_len = query_remaining.min(seg_len - tgt_offset);
-                query_remaining -= step_len;
+                // The bases a segment shares with the previous one
+                // were already aligned as part of that segment
+                if ix > 0 {
+                    tgt_offset = overlaps[ix];
+                }
 
-                let query_start = query_index;
-                let query_end = query_start + step_len;
+                let step_len =
+                    path_remaining.min(seg_len.saturating_sub(tgt_offset));
+                path_remaining -= step_len;
 
                 let target_seq_name = target.name.clone();
                 let target_seq_len = seg_len;
 
                 let target_seq_range = (tgt_offset, tgt_offset + step_len);
 
-                let sequence =
-                    target.sequence[tgt_offset..tgt_offset + step_len].into();
+                let strand = step_strand(gaf.strand, orient);
 
-                use Orientation::*;
-
-                let strand = match (gaf.strand, orient) {
-                    (Forward, Forward) => Forward,
-                    (Forward, Backward) => Backward,
-                    (Backward, Forward) => Backward,
-                    (Backward, Backward) => Forward,
-                };
-
-                let q_ix = gaf_cigar.query_index(step_len);
+                // Split after the operations covering the reference
+                // bases of this step, bounded by the query bases before
+                // them, so that an insertion at the boundary is left
+                // to the next step
                 let r_ix = gaf_cigar.ref_index(step_len);
+                let head = gaf_cigar.split_with_index(r_ix).0;
+                let head_ops: Vec<CIGAROp> = head.iter_single().collect();
+                let boundary_ins = head_ops
+                    .iter()
+                    .rev()
+                    .take_while(|op| matches!(op, CIGAROp::I))
+                    .count();
+                let q_ix = gaf_cigar
+                    .query_index(cigar_query_len(&head) - boundary_ins);
                 let cg_ix = q_ix.min(r_ix);
                 let split_cg = gaf_cigar.split_with_index(cg_ix);
 
-                seqs.push(sequence);
-
-                query_index = query_end;
-
                 let mut optional = gaf.optional.clone();
 
                 let paf_cigar;
@@ -168,32 +629,17 @@ fn gaf_line_to_pafs<T: OptFields>(
                     gaf_cigar = split_cg.1;
                 }
 
-                paf_cigar.iter_single().for_each(|op| {
-                    use CIGAROp::*;
-                    match op {
-                        D | N => {
-                            query_remaining += 1;
-                        }
-                        _ => (),
-                    }
-                });
+                // Only the query bases of the CIGAR advance the query,
+                // so deletions don't shift the following records
+                let query_start = query_index;
+                let query_end = query_start + cigar_query_len(&paf_cigar);
+                query_index = query_end;
 
-                let residue_matches =
-                    paf_cigar.iter_single().fold(0, |acc, op| {
-                        if op.is_match_or_mismatch() {
-                            acc + 1
-                        } else {
-                            acc
-                        }
-                    });
+                let (residue_matches, block_length) =
+                    cigar_matches_and_block_length(&paf_cigar);
 
                 set_cigar(&mut optional, paf_cigar);
 
-                let block_length = step_len;
-
-                // TODO several of these fields need to be changed,
-                // including strand and everything after the target
-                // sequence fields
                 let paf = PAF {
                     query_seq_name: gaf.seq_name.clone(),
                     query_seq_len: gaf.seq_len,
@@ -209,42 +655,168 @@ fn gaf_line_to_pafs<T: OptFields>(
                 };
 
                 pafs.push(paf);
-                tgt_offset = 0;
             }
 
-            pafs
+            Ok(pafs)
         }
     }
 }
 
-pub fn gaf_to_paf<T: OptFields>(
-    gfa: GFA<Vec<u8>, T>,
-    gaf_path: &Path,
-) -> Vec<PAF> {
-    let mut segments = gfa.segments;
-    segments.sort_by(|s1, s2| s1.name.cmp(&s2.name));
-    let mut links = gfa.links;
-    links.sort_by(cmp_links);
+/// Build a GFA P-line named `name` that follows the oriented segments
+/// of a GAF record's path, or, if `walk` is set, a W-line, with the
+/// name as both the sample and sequence name, and the aligned query
+/// interval as the start and end. Stable interval steps are resolved
+/// to segments using the index.
+pub fn gaf_path_line<T: OptFields>(
+    index: &SegmentIndex<'_, T>,
+    gaf: &GAF,
+    name: &[u8],
+    walk: bool,
+) -> Result<BString, GafConvertError> {
+    let steps = match &gaf.path {
+        GAFPath::OrientIntv(steps) => steps,
+        GAFPath::StableId(id) => {
+            let (start, end) = gaf.path_range;
+            return Err(GafConvertError::UnresolvedInterval(
+                id.clone(),
+                start,
+                end,
+            ));
+        }
+    };
 
-    let file = File::open(gaf_path).unwrap();
-    let lines = BufReader::new(file).byte_lines().map(|l| l.unwrap());
-    let mut gafs: Vec<GAF> = Vec::new();
+    let mut seg_steps = Vec::with_capacity(steps.len());
+    for step in steps.iter() {
+        seg_steps.extend(index.resolve_step(step)?);
+    }
 
-    for (i, line) in lines.enumerate() {
-        let fields = line.split_str(b"\t");
-        if let Some(gaf) = parse_gaf(fields) {
-            gafs.push(gaf);
-        } else {
-            eprintln!("Error parsing GAF line {}", i);
+    let mut line: BString = BString::from(if walk { "W\t" } else { "P\t" });
+    line.extend_from_slice(name);
+    line.push(b'\t');
+
+    if walk {
+        let (start, end) = gaf.seq_range;
+        line.extend_from_slice(b"0\t");
+        line.extend_from_slice(name);
+        line.extend_from_slice(format!("\t{}\t{}\t", start, end).as_bytes());
+        for (orient, seg) in seg_steps {
+            line.push(if orient.is_reverse() { b'<' } else { b'>' });
+            line.extend_from_slice(&seg.name);
         }
+    } else {
+        for (ix, (orient, seg)) in seg_steps.into_iter().enumerate() {
+            if ix > 0 {
+                line.push(b',');
+            }
+            line.extend_from_slice(&seg.name);
+            line.push(if orient.is_reverse() { b'-' } else { b'+' });
+        }
+        line.extend_from_slice(b"\t*");
     }
 
-    let mut pafs: Vec<PAF> = Vec::new();
+    Ok(line)
+}
 
-    gafs.iter().for_each(|gaf| {
-        let cur_pafs = gaf_line_to_pafs(&segments, &gaf);
-        pafs.extend(cur_pafs);
-    });
+/// Parse a GAF line, given its index from 0, with the reason it was
+/// rejected if it can't be parsed
+pub fn parse_gaf_line(i: usize, line: &[u8]) -> Result<GAF, GafParseError> {
+    parse_gaf(line.split_str(b"\t"))
+        .ok_or_else(|| diagnose_gaf_line(i + 1, line))
+}
 
-    pafs
+/// Parse the GAF records from a reader, one line at a time, with the
+/// reason each line that can't be parsed was rejected. An error
+/// reading the GAF gives a `GafConvertError::Read`.
+pub fn gaf_record_results<R: Read>(
+    reader: R,
+) -> impl Iterator<Item = Result<GAF, GafConvertError>> {
+    let lines = BufReader::new(reader).byte_lines();
+    lines.enumerate().map(|(i, line)| {
+        let line =
+            line.map_err(|err| GafConvertError::Read(err.to_string()))?;
+        parse_gaf_line(i, &line).map_err(GafConvertError::Parse)
+    })
+}
+
+/// Parse the GAF records from a reader, one line at a time. Lines
+/// that can't be parsed are reported and skipped, but errors reading
+/// the GAF are returned.
+pub fn gaf_records<R: Read>(
+    reader: R,
+) -> impl Iterator<Item = io::Result<GAF>> {
+    let lines = BufReader::new(reader).byte_lines();
+    lines.enumerate().filter_map(|(i, line)| match line {
+        Ok(line) => match parse_gaf_line(i, &line) {
+            Ok(gaf) => Some(Ok(gaf)),
+            Err(err) => {
+                eprintln!("{}", err);
+                None
+            }
+        },
+        Err(err) => Some(Err(err)),
+    })
+}
+
+/// Convert one GAF record, returning either its PAF records or the
+/// error that prevented the conversion
+fn gaf_to_paf_results<T: OptFields>(
+    index: &SegmentIndex<'_, T>,
+    gaf: &GAF,
+    require_cigar: bool,
+) -> Vec<Result<PAF, GafConvertError>> {
+    match gaf_line_to_pafs(index, gaf, require_cigar) {
+        Ok(pafs) => pafs.into_iter().map(Ok).collect(),
+        Err(err) => vec![Err(err)],
+    }
+}
+
+/// Parse and convert one GAF line, given its index from 0, like
+/// `gaf_reader_to_pafs`
+pub fn gaf_line_to_paf_results<T: OptFields>(
+    index: &SegmentIndex<'_, T>,
+    i: usize,
+    line: &[u8],
+    require_cigar: bool,
+) -> Vec<Result<PAF, GafConvertError>> {
+    match parse_gaf_line(i, line) {
+        Ok(gaf) => gaf_to_paf_results(index, &gaf, require_cigar),
+        Err(err) => vec![Err(GafConvertError::Parse(err))],
+    }
+}
+
+/// Convert the GAF records from a reader into PAF records lazily, so
+/// that only one GAF record is in memory at a time. Stable interval
+/// steps are resolved to segments using the index's SN/SO tags.
+///
+/// Records without a CIGAR are split proportionally across their
+/// segments, unless `require_cigar` is set, in which case an error
+/// is returned for them. Lines that can't be parsed give a
+/// `GafConvertError::Parse`, and errors reading the GAF a
+/// `GafConvertError::Read`.
+pub fn gaf_reader_to_pafs<'a, T, R>(
+    index: &'a SegmentIndex<'a, T>,
+    reader: R,
+    require_cigar: bool,
+) -> impl Iterator<Item = Result<PAF, GafConvertError>> + 'a
+where
+    T: OptFields,
+    R: Read + 'a,
+{
+    gaf_record_results(reader).flat_map(move |gaf| match gaf {
+        Ok(gaf) => gaf_to_paf_results(index, &gaf, require_cigar),
+        Err(err) => vec![Err(err)],
+    })
+}
+
+/// Convert every record of a GAF file into PAF records, returning
+/// the first error if the file can't be read or a record can't be
+/// converted
+pub fn gaf_to_paf<T: OptFields>(
+    gfa: GFA<Vec<u8>, T>,
+    gaf_path: &Path,
+) -> Result<Vec<PAF>, GafConvertError> {
+    let index = SegmentIndex::new(&gfa);
+    let file = crate::compression::open_input(gaf_path)
+        .map_err(|err| GafConvertError::Read(err.to_string()))?;
+    gaf_reader_to_pafs(&index, file, false).collect()
 }
@@ -0,0 +1,162 @@
+//! Removing duplicate GAF alignments, and picking the best alignment
+//! of each read.
+
+use std::{
+    cmp::Ordering,
+    io::{self, BufReader, Read},
+};
+
+use bstr::{io::*, ByteSlice};
+use clap::arg_enum;
+use fnv::FnvHashMap;
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum BestBy {
+        Matches,
+        Identity,
+    }
+}
+
+/// The columns of a GAF line needed to deduplicate it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GafAlignment<'a> {
+    pub read: &'a [u8],
+    /// The 12 mandatory columns, which are the same for duplicate
+    /// alignments, even if their optional fields differ
+    pub key: &'a [u8],
+    pub matches: usize,
+    pub block_length: usize,
+}
+
+fn parse_usize(field: &[u8]) -> Option<usize> {
+    field.to_str().ok()?.parse().ok()
+}
+
+impl<'a> GafAlignment<'a> {
+    /// Parse the columns of a line, or return `None` if it has fewer
+    /// than 12 columns, or the matches or block length aren't
+    /// integers
+    pub fn parse(line: &'a [u8]) -> Option<Self> {
+        let fields: Vec<&[u8]> = line.split_str(b"\t").take(12).collect();
+        if fields.len() < 12 {
+            return None;
+        }
+        let key_len = fields.iter().map(|f| f.len()).sum::<usize>() + 11;
+        Some(GafAlignment {
+            read: fields[0],
+            key: &line[..key_len],
+            matches: parse_usize(fields[9])?,
+            block_length: parse_usize(fields[10])?,
+        })
+    }
+
+    pub fn identity(&self) -> f64 {
+        if self.block_length == 0 {
+            0.0
+        } else {
+            self.matches as f64 / self.block_length as f64
+        }
+    }
+
+    /// Compare by the number of matches, or by identity, using the
+    /// other as the tie breaker
+    pub fn cmp_by(&self, other: &Self, by: BestBy) -> Ordering {
+        let identity = self
+            .identity()
+            .partial_cmp(&other.identity())
+            .unwrap_or(Ordering::Equal);
+        match by {
+            BestBy::Matches => self.matches.cmp(&other.matches).then(identity),
+            BestBy::Identity => identity.then(self.matches.cmp(&other.matches)),
+        }
+    }
+}
+
+/// Find the best alignment of each read in a GAF file, and return
+/// the index, from 0, of its line, by read name. When several
+/// alignments of a read are equally good, the first is picked. Lines
+/// that can't be parsed are ignored.
+pub fn best_alignments<R: Read>(
+    reader: R,
+    by: BestBy,
+) -> io::Result<FnvHashMap<Vec<u8>, usize>> {
+    let mut best: FnvHashMap<Vec<u8>, (usize, usize, usize)> =
+        FnvHashMap::default();
+
+    for (i, line) in BufReader::new(reader).byte_lines().enumerate() {
+        let line = line?;
+        let alignment = match GafAlignment::parse(&line) {
+            Some(alignment) => alignment,
+            None => continue,
+        };
+
+        match best.get_mut(alignment.read) {
+            Some((line_ix, matches, block_length)) => {
+                let current = GafAlignment {
+                    matches: *matches,
+                    block_length: *block_length,
+                    ..alignment
+                };
+                if alignment.cmp_by(&current, by) == Ordering::Greater {
+                    *line_ix = i;
+                    *matches = alignment.matches;
+                    *block_length = alignment.block_length;
+                }
+            }
+            None => {
+                best.insert(
+                    alignment.read.to_vec(),
+                    (i, alignment.matches, alignment.block_length),
+                );
+            }
+        }
+    }
+
+    Ok(best
+        .into_iter()
+        .map(|(read, (line_ix, _, _))| (read, line_ix))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAF: &str = "\
+read1\t10\t0\t10\t+\t>1>2\t20\t0\t10\t8\t10\t60\tcg:Z:10M
+read2\t10\t0\t10\t+\t>3\t10\t0\t10\t9\t12\t60
+read1\t10\t0\t10\t+\t>1>2\t20\t0\t10\t8\t10\t60\tcg:Z:10M\tdp:f:1
+read2\t10\t0\t10\t-\t>4\t10\t0\t10\t7\t7\t60
+read1\t10\t0\t10\t+\t>5\t10\t0\t10\t9\t12\t60
+";
+
+    #[test]
+    fn duplicate_alignments_share_a_key() {
+        let lines: Vec<&str> = GAF.lines().collect();
+        let first = GafAlignment::parse(lines[0].as_bytes()).unwrap();
+        let dup = GafAlignment::parse(lines[2].as_bytes()).unwrap();
+        assert_eq!(first.read, b"read1");
+        assert_eq!(first.key, dup.key);
+        assert_eq!(first.key, lines[0][..lines[0].len() - 9].as_bytes());
+        assert_eq!((first.matches, first.block_length), (8, 10));
+
+        let no_tags = GafAlignment::parse(lines[1].as_bytes()).unwrap();
+        assert_eq!(no_tags.key, lines[1].as_bytes());
+
+        assert!(GafAlignment::parse(b"read1\t10\t0\t10\t+\t>1").is_none());
+    }
+
+    #[test]
+    fn best_alignment_per_read() {
+        let by_matches =
+            best_alignments(GAF.as_bytes(), BestBy::Matches).unwrap();
+        assert_eq!(by_matches[&b"read1".to_vec()], 4);
+        assert_eq!(by_matches[&b"read2".to_vec()], 1);
+
+        let by_identity =
+            best_alignments(GAF.as_bytes(), BestBy::Identity).unwrap();
+        assert_eq!(by_identity[&b"read1".to_vec()], 0);
+        assert_eq!(by_identity[&b"read2".to_vec()], 3);
+    }
+}
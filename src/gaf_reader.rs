@@ -0,0 +1,267 @@
+//! Reading large GAF files in parallel.
+//!
+//! Uncompressed GAF files are memory-mapped, and split into chunks of
+//! a few megabytes that end at line breaks, which are processed by
+//! the rayon thread pool. Compressed files, and pipes and other
+//! inputs that can't be mapped, are read through a buffer a fixed
+//! number of lines at a time instead. Either way, the result of each
+//! line is passed on in the order of the lines, so the output of a
+//! command doesn't depend on how its input was read.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+use bstr::{io::*, ByteSlice};
+use memmap::Mmap;
+use rayon::prelude::*;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use crate::compression;
+
+/// The number of lines per chunk when reading through a buffer, if
+/// the command doesn't have an option for it
+pub const DEFAULT_CHUNK_LINES: usize = 10000;
+
+/// The target size of the chunks of a memory-mapped file
+const MAPPED_CHUNK_BYTES: usize = 4 << 20;
+
+/// A GAF file, either memory-mapped or read through a buffer
+pub enum GafInput {
+    Mapped(Mmap),
+    Buffered(Box<dyn Read>),
+}
+
+impl GafInput {
+    /// Open a GAF file, memory-mapping it if it's an uncompressed
+    /// regular file, and otherwise reading it like
+    /// `compression::open_input`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+
+        if metadata.is_file() && metadata.len() > 0 {
+            // The file mustn't be modified while it's mapped, which
+            // is the case for every input of these commands
+            let mmap = unsafe { Mmap::map(&file)? };
+            if !compression::is_compressed(&mmap) {
+                debug!("Memory-mapped {}", path.display());
+                return Ok(GafInput::Mapped(mmap));
+            }
+        }
+
+        Ok(GafInput::Buffered(compression::open_input(path)?))
+    }
+
+    /// Read the GAF lines from a reader, through a buffer
+    pub fn from_reader<R: Read + 'static>(reader: R) -> Self {
+        GafInput::Buffered(Box::new(reader))
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        matches!(self, GafInput::Mapped(_))
+    }
+
+    /// Apply `map` to every line, along with its index from 0, in
+    /// parallel, and pass the results to `sink` in the order of the
+    /// lines. A buffered input is read `chunk_lines` lines at a time.
+    /// Stops at the first error returned by `sink`, or from reading
+    /// the input.
+    pub fn for_each_line<T, E, F, S>(
+        self,
+        chunk_lines: usize,
+        map: F,
+        sink: S,
+    ) -> Result<(), E>
+    where
+        T: Send,
+        E: From<io::Error>,
+        F: Fn(usize, &[u8]) -> T + Sync,
+        S: FnMut(T) -> Result<(), E>,
+    {
+        match self {
+            GafInput::Mapped(mmap) => for_each_mapped_line(&mmap, map, sink),
+            GafInput::Buffered(reader) => {
+                for_each_buffered_line(reader, chunk_lines, map, sink)
+            }
+        }
+    }
+}
+
+/// Split `data` into chunks of about `chunk_bytes` bytes, each ending
+/// at a line break, except for the last if the data doesn't end with
+/// one
+fn line_chunks(data: &[u8], chunk_bytes: usize) -> Vec<&[u8]> {
+    let chunk_bytes = chunk_bytes.max(1);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+
+    while start < data.len() {
+        let mut end = (start + chunk_bytes).min(data.len());
+        if end < data.len() {
+            end = match data[end - 1..].find_byte(b'\n') {
+                Some(ix) => end + ix,
+                None => data.len(),
+            };
+        }
+        chunks.push(&data[start..end]);
+        start = end;
+    }
+
+    chunks
+}
+
+fn for_each_mapped_line<T, E, F, S>(
+    data: &[u8],
+    map: F,
+    mut sink: S,
+) -> Result<(), E>
+where
+    T: Send,
+    F: Fn(usize, &[u8]) -> T + Sync,
+    S: FnMut(T) -> Result<(), E>,
+{
+    let chunks = line_chunks(data, MAPPED_CHUNK_BYTES);
+    debug!("Reading {} bytes in {} chunks", data.len(), chunks.len());
+
+    // Only a few chunks per thread are processed at a time, so that
+    // the results of the whole file never have to fit in memory
+    let batch_size = rayon::current_num_threads() * 2;
+    let mut line_ix = 0;
+
+    for batch in chunks.chunks(batch_size) {
+        let line_counts: Vec<usize> = batch
+            .par_iter()
+            .map(|chunk| chunk.lines().count())
+            .collect();
+
+        let mut first_lines = Vec::with_capacity(batch.len());
+        for count in line_counts {
+            first_lines.push(line_ix);
+            line_ix += count;
+        }
+
+        let results: Vec<Vec<T>> = batch
+            .par_iter()
+            .zip(first_lines.into_par_iter())
+            .map(|(chunk, first_line)| {
+                chunk
+                    .lines()
+                    .enumerate()
+                    .map(|(i, line)| map(first_line + i, line))
+                    .collect()
+            })
+            .collect();
+
+        for result in results.into_iter().flatten() {
+            sink(result)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn for_each_buffered_line<T, E, F, S>(
+    reader: Box<dyn Read>,
+    chunk_lines: usize,
+    map: F,
+    mut sink: S,
+) -> Result<(), E>
+where
+    T: Send,
+    E: From<io::Error>,
+    F: Fn(usize, &[u8]) -> T + Sync,
+    S: FnMut(T) -> Result<(), E>,
+{
+    let chunk_lines = chunk_lines.max(1);
+    let mut lines = BufReader::new(reader).byte_lines();
+    let mut line_ix = 0;
+
+    loop {
+        let mut chunk: Vec<Vec<u8>> = Vec::with_capacity(chunk_lines);
+        for line in lines.by_ref().take(chunk_lines) {
+            chunk.push(line?);
+        }
+        if chunk.is_empty() {
+            return Ok(());
+        }
+
+        let first_line = line_ix;
+        line_ix += chunk.len();
+
+        let results: Vec<T> = chunk
+            .par_iter()
+            .enumerate()
+            .map(|(i, line)| map(first_line + i, line.as_slice()))
+            .collect();
+
+        for result in results {
+            sink(result)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect_lines(input: GafInput, chunk_lines: usize) -> Vec<String> {
+        let mut lines = Vec::new();
+        input
+            .for_each_line(
+                chunk_lines,
+                |i, line| format!("{}:{}", i, line.as_bstr()),
+                |line| -> io::Result<()> {
+                    lines.push(line);
+                    Ok(())
+                },
+            )
+            .unwrap();
+        lines
+    }
+
+    #[test]
+    fn chunks_end_at_line_breaks() {
+        let data = b"aaa\nbb\ncccc\nd";
+        let chunks = line_chunks(data, 5);
+        assert_eq!(chunks, vec![&b"aaa\nbb\n"[..], &b"cccc\n"[..], &b"d"[..]]);
+        assert_eq!(
+            line_chunks(data, 4),
+            vec![&b"aaa\n"[..], &b"bb\ncccc\n"[..], &b"d"[..]]
+        );
+        assert_eq!(line_chunks(data, 100), vec![&data[..]]);
+        assert!(line_chunks(b"", 4).is_empty());
+    }
+
+    #[test]
+    fn mapped_and_buffered_lines_match() {
+        let text: String = (0..2500).map(|i| format!("read{}\n", i)).collect();
+        let path = std::env::temp_dir()
+            .join(format!("gfautil_gaf_reader_{}.gaf", std::process::id()));
+        std::fs::write(&path, &text).unwrap();
+
+        let mapped = GafInput::open(&path).unwrap();
+        assert!(mapped.is_mapped());
+        let mapped_lines = collect_lines(mapped, 7);
+
+        let buffered = GafInput::from_reader(io::Cursor::new(text.clone()));
+        assert!(!buffered.is_mapped());
+        let buffered_lines = collect_lines(buffered, 7);
+
+        let expected: Vec<String> =
+            (0..2500).map(|i| format!("{}:read{}", i, i)).collect();
+        assert_eq!(mapped_lines, expected);
+        assert_eq!(buffered_lines, expected);
+
+        let small_chunks = line_chunks(text.as_bytes(), 100);
+        assert!(small_chunks.iter().all(|chunk| chunk.ends_with(b"\n")));
+        assert_eq!(small_chunks.concat(), text.as_bytes());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+//! Assignment of GAF records to samples by their read names, for
+//! splitting a GAF into one file per sample.
+
+use std::io::{self, BufReader, Read};
+
+use bstr::{io::*, BString, ByteSlice};
+use fnv::FnvHashMap;
+use regex::bytes::Regex;
+
+/// How the sample of a read is found from its name
+#[derive(Debug, Clone)]
+pub enum SampleAssigner {
+    /// The first capture group of the regex, or the whole match if
+    /// it has no groups
+    Regex(Regex),
+    /// A map from read names to samples
+    Map(FnvHashMap<Vec<u8>, BString>),
+}
+
+impl SampleAssigner {
+    /// Load a map from a TSV file with the columns read name and
+    /// sample. Empty lines and lines starting with `#` are skipped.
+    pub fn from_tsv<R: Read>(reader: R) -> io::Result<Self> {
+        let mut map = FnvHashMap::default();
+        for (i, line) in BufReader::new(reader).byte_lines().enumerate() {
+            let line = line?;
+            if line.is_empty() || line.starts_with(b"#") {
+                continue;
+            }
+            let mut fields = line.split_str(b"\t");
+            match (fields.next(), fields.next()) {
+                (Some(read), Some(sample)) if !sample.is_empty() => {
+                    map.insert(read.to_vec(), sample.into());
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "line {} of the sample map does not have a read \
+                             name and a sample",
+                            i + 1
+                        ),
+                    ))
+                }
+            }
+        }
+        Ok(SampleAssigner::Map(map))
+    }
+
+    /// The sample of the read, if it has one
+    pub fn sample<'a>(&'a self, read_name: &'a [u8]) -> Option<&'a [u8]> {
+        match self {
+            SampleAssigner::Regex(regex) => {
+                let captures = regex.captures(read_name)?;
+                let matched = captures.get(1).or_else(|| captures.get(0))?;
+                Some(matched.as_bytes())
+            }
+            SampleAssigner::Map(map) => {
+                map.get(read_name).map(|s| s.as_slice())
+            }
+        }
+    }
+}
+
+/// The read name of a GAF line, i.e. its first column
+pub fn gaf_read_name(line: &[u8]) -> &[u8] {
+    line.split_str(b"\t").next().unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_from_regex_and_map() {
+        let regex = SampleAssigner::Regex(Regex::new(r"^([^#]+)#").unwrap());
+        assert_eq!(regex.sample(b"HG002#1#read7"), Some(&b"HG002"[..]));
+        assert_eq!(regex.sample(b"read7"), None);
+
+        let whole = SampleAssigner::Regex(Regex::new(r"HG\d+").unwrap());
+        assert_eq!(whole.sample(b"x_HG003_y"), Some(&b"HG003"[..]));
+
+        let tsv = "# read\tsample\nread1\tA\n\nread2\tB\n";
+        let map = SampleAssigner::from_tsv(tsv.as_bytes()).unwrap();
+        assert_eq!(map.sample(b"read2"), Some(&b"B"[..]));
+        assert_eq!(map.sample(b"read3"), None);
+
+        assert!(SampleAssigner::from_tsv("read1\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn read_name_is_first_column() {
+        assert_eq!(gaf_read_name(b"read1\t6\t0\t6"), b"read1");
+        assert_eq!(gaf_read_name(b"read1"), b"read1");
+    }
+}
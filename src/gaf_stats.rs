@@ -0,0 +1,380 @@
+use std::{
+    fmt::Write as FmtWrite,
+    io::{BufReader, Read},
+};
+
+use bstr::{io::*, BString, ByteSlice};
+use fnv::{FnvHashMap, FnvHashSet};
+use serde::Serialize;
+
+use gfa::{
+    gafpaf::{parse_gaf, GAFPath},
+    optfields::{OptFieldVal, OptFields, OptionalFields},
+};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+use crate::{
+    gaf_convert::{GafConvertError, SegmentIndex},
+    gaf_reader::{GafInput, DEFAULT_CHUNK_LINES},
+    stats::histogram,
+};
+
+type GAF = gfa::gafpaf::GAF<OptionalFields>;
+
+/// The alignments covering a single node
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NodeCoverage {
+    /// The number of records whose path includes the node
+    pub records: usize,
+    /// The number of the node's bases covered by those records
+    pub bases: usize,
+}
+
+/// Summary statistics over the records of one or more GAF files
+#[derive(Debug, Default)]
+pub struct GafStats {
+    pub records: usize,
+    pub primary: usize,
+    pub secondary: usize,
+    pub unmapped_records: usize,
+    pub invalid_lines: usize,
+    reads: FnvHashSet<BString>,
+    mapped_reads: FnvHashSet<BString>,
+    residue_matches: usize,
+    block_length: usize,
+    read_lengths: Vec<usize>,
+    identities: Vec<usize>,
+    mapqs: Vec<usize>,
+    coverage: FnvHashMap<BString, NodeCoverage>,
+}
+
+/// A line of a GAF file, parsed for the statistics
+pub enum StatsLine {
+    Empty,
+    /// A read with no alignment, i.e. with `*` as the path
+    Unmapped(BString),
+    Record(GAF),
+    /// A line that couldn't be parsed, with its index from 0
+    Invalid(usize),
+}
+
+impl StatsLine {
+    pub fn parse(i: usize, line: &[u8]) -> Self {
+        if line.is_empty() {
+            return StatsLine::Empty;
+        }
+        let fields: Vec<&[u8]> = line.split_str(b"\t").collect();
+        if fields.len() > 5 && fields[5] == b"*" {
+            return StatsLine::Unmapped(fields[0].into());
+        }
+        match parse_gaf(fields.into_iter()) {
+            Some(gaf) => StatsLine::Record(gaf),
+            None => StatsLine::Invalid(i),
+        }
+    }
+}
+
+/// Whether the record has the `tp:A:S` tag that marks secondary
+/// alignments. Records without a `tp` tag are counted as primary.
+fn is_secondary(gaf: &GAF) -> bool {
+    match gaf.optional.get_field(b"tp").map(|f| &f.value) {
+        Some(OptFieldVal::A(tp)) => *tp == b'S',
+        _ => false,
+    }
+}
+
+impl GafStats {
+    pub fn reads(&self) -> usize {
+        self.reads.len()
+    }
+
+    pub fn mapped_reads(&self) -> usize {
+        self.mapped_reads.len()
+    }
+
+    /// The fraction of reads with at least one mapped record
+    pub fn mapping_rate(&self) -> f64 {
+        if self.reads.is_empty() {
+            0.0
+        } else {
+            self.mapped_reads.len() as f64 / self.reads.len() as f64
+        }
+    }
+
+    /// The mean identity over all mapped records, weighted by their
+    /// alignment block lengths
+    pub fn identity(&self) -> f64 {
+        if self.block_length == 0 {
+            0.0
+        } else {
+            self.residue_matches as f64 / self.block_length as f64
+        }
+    }
+
+    /// The query lengths of the primary records
+    pub fn read_length_histogram(&self) -> Vec<(usize, usize)> {
+        histogram(self.read_lengths.iter().copied())
+    }
+
+    /// The identities of all mapped records, as whole percentages
+    pub fn identity_histogram(&self) -> Vec<(usize, usize)> {
+        histogram(self.identities.iter().copied())
+    }
+
+    pub fn mapq_histogram(&self) -> Vec<(usize, usize)> {
+        histogram(self.mapqs.iter().copied())
+    }
+
+    pub fn node_coverage(&self, node: &[u8]) -> NodeCoverage {
+        self.coverage
+            .get(node.as_bstr())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Record a read with no alignment, e.g. a GAF line whose path
+    /// is `*`
+    pub fn add_unmapped(&mut self, name: &[u8]) {
+        self.unmapped_records += 1;
+        self.reads.insert(name.into());
+    }
+
+    /// Add a mapped record, and the bases it covers on each node of
+    /// its path. Records aligned to a stable sequence as a whole,
+    /// rather than to oriented steps, don't contribute to the node
+    /// coverage.
+    pub fn add_record<T: OptFields>(
+        &mut self,
+        index: &SegmentIndex<'_, T>,
+        gaf: &GAF,
+    ) -> Result<(), GafConvertError> {
+        self.records += 1;
+        self.reads.insert(gaf.seq_name.clone());
+        self.mapped_reads.insert(gaf.seq_name.clone());
+
+        if is_secondary(gaf) {
+            self.secondary += 1;
+        } else {
+            self.primary += 1;
+            self.read_lengths.push(gaf.seq_len);
+        }
+
+        self.residue_matches += gaf.residue_matches;
+        self.block_length += gaf.block_length;
+        if gaf.block_length > 0 {
+            self.identities
+                .push((gaf.residue_matches * 100) / gaf.block_length);
+        }
+        self.mapqs.push(gaf.quality as usize);
+
+        if let GAFPath::OrientIntv(steps) = &gaf.path {
+            let mut seg_steps = Vec::new();
+            for step in steps {
+                seg_steps.extend(index.resolve_step(step)?);
+            }
+
+            // Each segment starts where the one before it ends, minus
+            // the overlap of the link between them, so that the bases
+            // of the path range are placed as in the GAF
+            let overlaps = index.step_overlaps(&seg_steps);
+            let (path_start, path_end) = gaf.path_range;
+            let mut offset = 0;
+            for ((_, seg), overlap) in seg_steps.iter().zip(overlaps) {
+                let seg_start = offset - overlap;
+                let seg_end = seg_start + seg.sequence.len();
+                let start = seg_start.max(path_start);
+                let end = seg_end.min(path_end);
+                if start < end {
+                    let cov = self
+                        .coverage
+                        .entry(seg.name.as_slice().into())
+                        .or_default();
+                    cov.records += 1;
+                    cov.bases += end - start;
+                }
+                offset = seg_end;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add a parsed line. Lines that couldn't be parsed are reported
+    /// and counted.
+    pub fn add_line<T: OptFields>(
+        &mut self,
+        index: &SegmentIndex<'_, T>,
+        line: StatsLine,
+    ) -> Result<(), GafConvertError> {
+        match line {
+            StatsLine::Empty => (),
+            StatsLine::Unmapped(name) => self.add_unmapped(&name),
+            StatsLine::Record(gaf) => self.add_record(index, &gaf)?,
+            StatsLine::Invalid(i) => {
+                warn!("Error parsing GAF line {}", i);
+                self.invalid_lines += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the records of a GAF file. Lines with `*` as the path are
+    /// counted as unmapped reads, and lines that can't be parsed are
+    /// reported and skipped.
+    pub fn add_reader<T: OptFields, R: Read>(
+        &mut self,
+        index: &SegmentIndex<'_, T>,
+        reader: R,
+    ) -> Result<(), GafConvertError> {
+        let lines = BufReader::new(reader).byte_lines().map(|l| l.unwrap());
+        for (i, line) in lines.enumerate() {
+            self.add_line(index, StatsLine::parse(i, &line))?;
+        }
+        Ok(())
+    }
+
+    /// Like `add_reader`, but parses the lines in parallel, and
+    /// memory-maps the file if it's uncompressed
+    pub fn add_input<T: OptFields>(
+        &mut self,
+        index: &SegmentIndex<'_, T>,
+        input: GafInput,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        input.for_each_line(
+            DEFAULT_CHUNK_LINES,
+            StatsLine::parse,
+            |line| -> Result<(), Box<dyn std::error::Error>> {
+                self.add_line(index, line)?;
+                Ok(())
+            },
+        )
+    }
+
+    /// The summary values, as (name, value) pairs
+    pub fn summary(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("records", self.records.to_string()),
+            ("primary", self.primary.to_string()),
+            ("secondary", self.secondary.to_string()),
+            ("unmapped_records", self.unmapped_records.to_string()),
+            ("invalid_lines", self.invalid_lines.to_string()),
+            ("reads", self.reads().to_string()),
+            ("mapped_reads", self.mapped_reads().to_string()),
+            ("mapping_rate", format!("{:.4}", self.mapping_rate())),
+            ("identity", format!("{:.4}", self.identity())),
+        ]
+    }
+
+    /// Format the statistics as TSV, with the kind of each row in the
+    /// first column, like `samtools stats`: SN for the summary, RL,
+    /// ID, and MQ for the read length, identity percentage, and
+    /// mapping quality histograms, and NC for the coverage of each
+    /// of the `nodes`, given as (name, length) pairs.
+    pub fn to_tsv(&self, nodes: &[(BString, usize)]) -> String {
+        let mut out = String::new();
+
+        for (name, value) in self.summary() {
+            writeln!(out, "SN\t{}\t{}", name, value).unwrap();
+        }
+
+        let histograms = [
+            ("RL", self.read_length_histogram()),
+            ("ID", self.identity_histogram()),
+            ("MQ", self.mapq_histogram()),
+        ];
+        for (kind, hist) in histograms.iter() {
+            for (value, count) in hist {
+                writeln!(out, "{}\t{}\t{}", kind, value, count).unwrap();
+            }
+        }
+
+        for (name, len) in nodes {
+            let cov = self.node_coverage(name);
+            writeln!(
+                out,
+                "NC\t{}\t{}\t{}\t{}\t{:.4}",
+                name,
+                len,
+                cov.records,
+                cov.bases,
+                mean_depth(cov.bases, *len)
+            )
+            .unwrap();
+        }
+
+        out
+    }
+
+    /// The statistics with the same contents as the TSV, for output
+    /// as a JSON object
+    pub fn report(&self, nodes: &[(BString, usize)]) -> GafStatsReport {
+        let nodes = nodes
+            .iter()
+            .map(|(name, len)| {
+                let cov = self.node_coverage(name);
+                NodeCoverageRow {
+                    node: name.to_str_lossy().into_owned(),
+                    length: *len,
+                    records: cov.records,
+                    bases: cov.bases,
+                    depth: mean_depth(cov.bases, *len),
+                }
+            })
+            .collect();
+
+        GafStatsReport {
+            records: self.records,
+            primary: self.primary,
+            secondary: self.secondary,
+            unmapped_records: self.unmapped_records,
+            invalid_lines: self.invalid_lines,
+            reads: self.reads(),
+            mapped_reads: self.mapped_reads(),
+            mapping_rate: self.mapping_rate(),
+            identity: self.identity(),
+            read_lengths: self.read_length_histogram(),
+            identity_percent: self.identity_histogram(),
+            mapq: self.mapq_histogram(),
+            nodes,
+        }
+    }
+}
+
+/// The coverage of one node, in the JSON output of gafstats
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NodeCoverageRow {
+    pub node: String,
+    pub length: usize,
+    pub records: usize,
+    pub bases: usize,
+    pub depth: f64,
+}
+
+/// The summary, histograms, and node coverage of `GafStats`, with
+/// the histograms as (value, count) pairs
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GafStatsReport {
+    pub records: usize,
+    pub primary: usize,
+    pub secondary: usize,
+    pub unmapped_records: usize,
+    pub invalid_lines: usize,
+    pub reads: usize,
+    pub mapped_reads: usize,
+    pub mapping_rate: f64,
+    pub identity: f64,
+    pub read_lengths: Vec<(usize, usize)>,
+    pub identity_percent: Vec<(usize, usize)>,
+    pub mapq: Vec<(usize, usize)>,
+    pub nodes: Vec<NodeCoverageRow>,
+}
+
+fn mean_depth(bases: usize, len: usize) -> f64 {
+    if len == 0 {
+        0.0
+    } else {
+        bases as f64 / len as f64
+    }
+}
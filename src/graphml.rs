@@ -0,0 +1,259 @@
+use bstr::ByteSlice;
+use fnv::FnvHashMap;
+use std::io::{self, Write};
+
+use gfa::{gfa::GFA, optfields::OptFields};
+
+use crate::{
+    node_coverage::{path_coverage, SegmentClass},
+    stats::weakly_connected_components,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeAttributes {
+    pub id: usize,
+    pub length: usize,
+    /// The number of path steps on the segment
+    pub coverage: usize,
+    /// The number of distinct paths that contain the segment
+    pub paths: usize,
+    /// The number of PanSN samples whose paths contain the segment
+    pub samples: usize,
+    /// The index of the segment's weakly connected component, with
+    /// 0 being the largest component
+    pub component: usize,
+    /// The class of the segment by its number of samples, like
+    /// core-accessory gives it
+    pub class: SegmentClass,
+}
+
+/// Compute the attributes of every segment, in the order of the
+/// segments in the GFA. The segments are classified like
+/// `PathCoverage::segment_classes` does, with `core_fraction` and
+/// `cloud_max`.
+pub fn node_attributes<T: OptFields + Sync>(
+    gfa: &GFA<usize, T>,
+    core_fraction: f64,
+    cloud_max: usize,
+) -> Vec<NodeAttributes> {
+    let coverage = path_coverage(gfa);
+    let classes = coverage.segment_classes(core_fraction, cloud_max);
+
+    let component_of: FnvHashMap<usize, usize> =
+        weakly_connected_components(gfa)
+            .into_iter()
+            .enumerate()
+            .flat_map(|(ix, comp)| {
+                comp.segments.into_iter().map(move |seg| (seg, ix))
+            })
+            .collect();
+
+    gfa.segments
+        .iter()
+        .zip(coverage.segments.iter())
+        .zip(classes)
+        .map(|((seg, seg_coverage), class)| NodeAttributes {
+            id: seg.name,
+            length: seg.sequence.len(),
+            coverage: seg_coverage.steps,
+            paths: seg_coverage.paths,
+            samples: seg_coverage.samples.len(),
+            component: component_of.get(&seg.name).copied().unwrap_or(0),
+            class,
+        })
+        .collect()
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Write the graph as GraphML, with a node per segment and a
+/// directed edge per link, with the segment attributes from
+/// `node_attributes`, and the orientations and overlap of each link
+pub fn write_graphml<T: OptFields + Sync, W: Write>(
+    gfa: &GFA<usize, T>,
+    core_fraction: f64,
+    cloud_max: usize,
+    mut out: W,
+) -> io::Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+
+    let keys = [
+        ("length", "node", "int"),
+        ("coverage", "node", "int"),
+        ("paths", "node", "int"),
+        ("samples", "node", "int"),
+        ("component", "node", "int"),
+        ("class", "node", "string"),
+        ("from_orient", "edge", "string"),
+        ("to_orient", "edge", "string"),
+        ("overlap", "edge", "string"),
+    ];
+    for (name, domain, attr_type) in keys.iter() {
+        writeln!(
+            out,
+            r#"  <key id="{0}" for="{1}" attr.name="{0}" attr.type="{2}"/>"#,
+            name, domain, attr_type
+        )?;
+    }
+
+    writeln!(out, r#"  <graph id="G" edgedefault="directed">"#)?;
+
+    for node in node_attributes(gfa, core_fraction, cloud_max) {
+        writeln!(out, r#"    <node id="{}">"#, node.id)?;
+        writeln!(out, r#"      <data key="length">{}</data>"#, node.length)?;
+        writeln!(
+            out,
+            r#"      <data key="coverage">{}</data>"#,
+            node.coverage
+        )?;
+        writeln!(out, r#"      <data key="paths">{}</data>"#, node.paths)?;
+        writeln!(out, r#"      <data key="samples">{}</data>"#, node.samples)?;
+        writeln!(
+            out,
+            r#"      <data key="component">{}</data>"#,
+            node.component
+        )?;
+        writeln!(out, r#"      <data key="class">{}</data>"#, node.class)?;
+        writeln!(out, "    </node>")?;
+    }
+
+    let orient_str = |is_reverse: bool| if is_reverse { "-" } else { "+" };
+
+    for (ix, link) in gfa.links.iter().enumerate() {
+        writeln!(
+            out,
+            r#"    <edge id="e{}" source="{}" target="{}">"#,
+            ix, link.from_segment, link.to_segment
+        )?;
+        writeln!(
+            out,
+            r#"      <data key="from_orient">{}</data>"#,
+            orient_str(link.from_orient.is_reverse())
+        )?;
+        writeln!(
+            out,
+            r#"      <data key="to_orient">{}</data>"#,
+            orient_str(link.to_orient.is_reverse())
+        )?;
+        writeln!(
+            out,
+            r#"      <data key="overlap">{}</data>"#,
+            escape_xml(&link.overlap.to_str_lossy())
+        )?;
+        writeln!(out, "    </edge>")?;
+    }
+
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</graphml>")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gfa::parser::GFAParser;
+
+    fn pansn_gfa() -> GFA<usize, ()> {
+        let lines = [
+            "H\tVN:Z:1.0",
+            "S\t1\tACGT",
+            "S\t2\tG",
+            "S\t3\tTT",
+            "S\t4\tC",
+            "S\t5\tA",
+            "L\t1\t+\t2\t+\t0M",
+            "L\t2\t+\t3\t+\t0M",
+            "L\t3\t+\t4\t-\t0M",
+            "P\tA#1#chr1\t1+,2+\t*",
+            "P\tA#1#chr2\t3+\t*",
+            "P\tB#1#chr1\t1+\t*",
+            "P\tB#1#chr2\t3+,4-\t*",
+        ];
+        GFAParser::new()
+            .parse_lines(lines.iter().map(|line| line.as_bytes().to_vec()))
+            .unwrap()
+    }
+
+    #[test]
+    fn classes_by_sample() {
+        let gfa = pansn_gfa();
+        let nodes = node_attributes(&gfa, 1.0, 1);
+
+        // Every sample has segment 1 on one of its chromosomes, even
+        // though only half of the paths contain it
+        assert_eq!(
+            nodes[0],
+            NodeAttributes {
+                id: 1,
+                length: 4,
+                coverage: 2,
+                paths: 2,
+                samples: 2,
+                component: 0,
+                class: SegmentClass::Core,
+            }
+        );
+        let classes: Vec<_> = nodes.iter().map(|node| node.class).collect();
+        assert_eq!(
+            classes,
+            vec![
+                SegmentClass::Core,
+                SegmentClass::Cloud,
+                SegmentClass::Core,
+                SegmentClass::Cloud,
+                SegmentClass::Uncovered,
+            ]
+        );
+        assert_eq!(nodes[4].component, 1);
+    }
+
+    #[test]
+    fn graphml_output() {
+        let mut gfa = pansn_gfa();
+        gfa.links[0].overlap = "1M\"<&>".into();
+
+        let mut out = Vec::new();
+        write_graphml(&gfa, 1.0, 1, &mut out).unwrap();
+        let xml = String::from_utf8(out).unwrap();
+
+        assert!(xml.starts_with(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n"
+        ));
+        assert!(xml.contains(
+            "  <key id=\"samples\" for=\"node\" attr.name=\"samples\" \
+             attr.type=\"int\"/>\n"
+        ));
+        assert!(xml.contains(
+            "    <node id=\"1\">\n\
+             \x20     <data key=\"length\">4</data>\n\
+             \x20     <data key=\"coverage\">2</data>\n\
+             \x20     <data key=\"paths\">2</data>\n\
+             \x20     <data key=\"samples\">2</data>\n\
+             \x20     <data key=\"component\">0</data>\n\
+             \x20     <data key=\"class\">core</data>\n\
+             \x20   </node>\n"
+        ));
+        assert!(xml.contains(
+            "    <edge id=\"e2\" source=\"3\" target=\"4\">\n\
+             \x20     <data key=\"from_orient\">+</data>\n\
+             \x20     <data key=\"to_orient\">-</data>\n\
+             \x20     <data key=\"overlap\">0M</data>\n\
+             \x20   </edge>\n"
+        ));
+        assert!(xml.contains(
+            "      <data key=\"overlap\">1M&quot;&lt;&amp;&gt;</data>\n"
+        ));
+        assert!(xml.ends_with("  </graph>\n</graphml>\n"));
+    }
+}
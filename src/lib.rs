@@ -1,6 +1,31 @@
+pub mod bubble_analysis;
+pub mod clean;
 pub mod commands;
+pub mod complexity;
+pub mod components;
+pub mod compression;
+pub mod cycles;
 pub mod edges;
+pub mod fasta;
 pub mod gaf_convert;
+pub mod gaf_dedup;
+pub mod gaf_reader;
+pub mod gaf_split;
+pub mod gaf_stats;
+pub mod graphml;
+pub mod manifest;
+pub mod metrics;
+pub mod node_coverage;
+pub mod node_map;
+pub mod node_seq;
+pub mod output_pool;
+pub mod paf_convert;
+pub mod path_align;
+pub mod path_dedup;
+pub mod provenance;
+pub mod report;
+pub mod stats;
 pub mod subgraph;
+pub mod surject;
 pub mod util;
 pub mod variants;
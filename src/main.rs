@@ -1,29 +1,99 @@
 use structopt::StructOpt;
 
-use std::path::PathBuf;
+use std::{net::IpAddr, path::PathBuf};
 
 use gfautil::{
     commands,
     commands::{
-        convert_names::GfaIdConvertArgs, gaf2paf::GAF2PAFArgs,
-        gfa2vcf::GFA2VCFArgs, snps::SNPArgs, subgraph::SubgraphArgs, Result,
+        allele_lengths::AlleleLengthsArgs, ambiguous::AmbiguousArgs,
+        check_links::CheckLinksArgs, check_paths::CheckPathsArgs,
+        clean::CleanArgs, compare::CompareArgs, complexity::ComplexityArgs,
+        components::ComponentsArgs, convert_names::GfaIdConvertArgs,
+        core_accessory::CoreAccessoryArgs, cycles::CyclesArgs,
+        divergence::DivergenceArgs, edge_count::EdgeCountArgs,
+        gaf2bed::GAF2BEDArgs, gaf2gfa_paths::GAF2GFAPathsArgs,
+        gaf2paf::GAF2PAFArgs, gaf2sam::GAF2SAMArgs, gafdedup::GAFDedupArgs,
+        gafdepth::GAFDepthArgs, gafsplit::GAFSplitArgs, gafstats::GAFStatsArgs,
+        gc::GCArgs, get_seq::GetSeqArgs, gfa2graphml::GFA2GraphMLArgs,
+        gfa2vcf::GFA2VCFArgs, node_coverage::NodeCoverageArgs,
+        node_map::NodeMapArgs, paf2gaf::PAF2GAFArgs, paf_lift::PAFLiftArgs,
+        path_align::PathAlignArgs, path_checksums::PathChecksumsArgs,
+        path_dedup::PathDedupArgs, paths::PathsArgs, report::ReportArgs,
+        snps::SNPArgs, stats::StatsArgs, subgraph::SubgraphArgs,
+        uncovered::UncoveredArgs, vcf2traversal::VCF2TraversalArgs, Result,
     },
 };
 
 #[derive(StructOpt, Debug)]
 enum Command {
     Subgraph(SubgraphArgs),
-    EdgeCount,
+    Components(ComponentsArgs),
+    Stats(StatsArgs),
+    Cycles(CyclesArgs),
+    Paths(PathsArgs),
+    #[structopt(name = "path-checksums")]
+    PathChecksums(PathChecksumsArgs),
+    Gc(GCArgs),
+    Complexity(ComplexityArgs),
+    EdgeCount(EdgeCountArgs),
     #[structopt(name = "gaf2paf")]
     Gaf2Paf(GAF2PAFArgs),
+    #[structopt(name = "gaf2sam", alias = "gaf2bam")]
+    Gaf2Sam(GAF2SAMArgs),
+    #[structopt(name = "gaf2bed")]
+    Gaf2Bed(GAF2BEDArgs),
+    #[structopt(name = "gaf2gfa-paths")]
+    Gaf2GfaPaths(GAF2GFAPathsArgs),
+    #[structopt(name = "paf2gaf")]
+    Paf2Gaf(PAF2GAFArgs),
+    #[structopt(name = "paf-lift")]
+    PafLift(PAFLiftArgs),
+    #[structopt(name = "gafstats")]
+    GafStats(GAFStatsArgs),
+    #[structopt(name = "gafdepth")]
+    GafDepth(GAFDepthArgs),
+    #[structopt(name = "gafsplit")]
+    GafSplit(GAFSplitArgs),
+    #[structopt(name = "gafdedup")]
+    GafDedup(GAFDedupArgs),
     #[structopt(name = "id-convert")]
     GfaSegmentIdConversion(GfaIdConvertArgs),
+    #[structopt(name = "gfa2graphml")]
+    Gfa2GraphML(GFA2GraphMLArgs),
     #[structopt(name = "gfa2vcf")]
     Gfa2Vcf(GFA2VCFArgs),
-    #[structopt(name = "snps")]
+    #[structopt(name = "vcf2traversal")]
+    Vcf2Traversal(VCF2TraversalArgs),
+    #[structopt(name = "snps", alias = "gfa2snps")]
     Snps(SNPArgs),
+    Divergence(DivergenceArgs),
+    #[structopt(name = "allele-lengths")]
+    AlleleLengths(AlleleLengthsArgs),
     #[structopt(name = "ultrabubbles")]
     Saboten,
+    Clean(CleanArgs),
+    Ambiguous(AmbiguousArgs),
+    Uncovered(UncoveredArgs),
+    #[structopt(name = "report-html")]
+    ReportHtml(ReportArgs),
+    #[structopt(name = "node-map")]
+    NodeMap(NodeMapArgs),
+    #[structopt(name = "node-coverage")]
+    NodeCoverage(NodeCoverageArgs),
+    #[structopt(name = "core-accessory")]
+    CoreAccessory(CoreAccessoryArgs),
+    #[structopt(name = "path-align")]
+    PathAlign(PathAlignArgs),
+    #[structopt(name = "path-dedup")]
+    PathDedup(PathDedupArgs),
+    #[structopt(name = "compare-variants")]
+    CompareVariants(CompareArgs),
+    #[structopt(name = "check-links")]
+    CheckLinks(CheckLinksArgs),
+    #[structopt(name = "check-paths")]
+    CheckPaths(CheckPathsArgs),
+    #[structopt(name = "get-seq")]
+    GetSeq(GetSeqArgs),
 }
 
 #[derive(StructOpt, Debug)]
@@ -41,8 +111,10 @@ struct LogOpt {
 
 #[derive(StructOpt, Debug)]
 struct Opt {
+    /// The input GFA file, required by every command except gafsplit
+    /// and gafdedup.
     #[structopt(name = "input GFA file", short, parse(from_os_str))]
-    in_gfa: PathBuf,
+    in_gfa: Option<PathBuf>,
     #[structopt(subcommand)]
     command: Command,
     #[structopt(flatten)]
@@ -52,6 +124,22 @@ struct Opt {
     /// environment variable, or the number of logical CPUs.
     #[structopt(short, long)]
     threads: Option<usize>,
+    /// Serve Prometheus metrics over HTTP on this port while the
+    /// command is running.
+    #[structopt(long = "metrics-port")]
+    metrics_port: Option<u16>,
+    /// The address to serve the metrics on. Only local connections
+    /// are accepted by default; use e.g. 0.0.0.0 to accept them on
+    /// every interface.
+    #[structopt(long = "metrics-addr", default_value = "127.0.0.1")]
+    metrics_addr: IpAddr,
+}
+
+impl Command {
+    /// Whether the command reads the input GFA
+    fn uses_gfa(&self) -> bool {
+        !matches!(self, Command::GafSplit(_) | Command::GafDedup(_))
+    }
 }
 
 fn init_logger(opt: &LogOpt) {
@@ -82,27 +170,135 @@ fn main() -> Result<()> {
             .build_global()?;
     }
 
+    if let Some(port) = opt.metrics_port {
+        gfautil::metrics::serve(opt.metrics_addr, port)?;
+    }
+
+    if opt.in_gfa.is_none() && opt.command.uses_gfa() {
+        return Err("The input GFA file must be given with -i".into());
+    }
+    let in_gfa = opt.in_gfa.clone().unwrap_or_default();
+
     match opt.command {
         Command::Gfa2Vcf(args) => {
-            commands::gfa2vcf::gfa2vcf(&opt.in_gfa, args)?;
+            commands::gfa2vcf::gfa2vcf(&in_gfa, args)?;
+        }
+        Command::Vcf2Traversal(args) => {
+            commands::vcf2traversal::vcf2traversal(&in_gfa, &args)?;
+        }
+        Command::Gfa2GraphML(args) => {
+            commands::gfa2graphml::gfa2graphml(&in_gfa, &args)?;
         }
         Command::Snps(args) => {
-            commands::snps::gfa2snps(&opt.in_gfa, args)?;
+            commands::snps::gfa2snps(&in_gfa, args)?;
+        }
+        Command::Divergence(args) => {
+            commands::divergence::divergence(&in_gfa, &args)?;
+        }
+        Command::AlleleLengths(args) => {
+            commands::allele_lengths::allele_lengths(&in_gfa, &args)?;
         }
         Command::Subgraph(args) => {
-            commands::subgraph::subgraph(&opt.in_gfa, &args)?;
+            commands::subgraph::subgraph(&in_gfa, &args)?;
+        }
+        Command::Components(args) => {
+            commands::components::components(&in_gfa, &args)?;
         }
         Command::Gaf2Paf(args) => {
-            commands::gaf2paf::gaf2paf(&opt.in_gfa, &args)?;
+            commands::gaf2paf::gaf2paf(&in_gfa, &args)?;
+        }
+        Command::Gaf2Sam(args) => {
+            commands::gaf2sam::gaf2sam(&in_gfa, &args)?;
+        }
+        Command::Gaf2Bed(args) => {
+            commands::gaf2bed::gaf2bed(&in_gfa, &args)?;
+        }
+        Command::Gaf2GfaPaths(args) => {
+            commands::gaf2gfa_paths::gaf2gfa_paths(&in_gfa, &args)?;
+        }
+        Command::Paf2Gaf(args) => {
+            commands::paf2gaf::paf2gaf(&in_gfa, &args)?;
+        }
+        Command::PafLift(args) => {
+            commands::paf_lift::paf_lift(&in_gfa, &args)?;
+        }
+        Command::GafStats(args) => {
+            commands::gafstats::gafstats(&in_gfa, &args)?;
+        }
+        Command::GafDepth(args) => {
+            commands::gafdepth::gafdepth(&in_gfa, &args)?;
+        }
+        Command::GafSplit(args) => {
+            commands::gafsplit::gafsplit(&args)?;
         }
-        Command::EdgeCount => {
-            commands::stats::edge_count(&opt.in_gfa)?;
+        Command::GafDedup(args) => {
+            commands::gafdedup::gafdedup(&args)?;
+        }
+        Command::Stats(args) => {
+            commands::stats::stats(&in_gfa, &args)?;
+        }
+        Command::Paths(args) => {
+            commands::paths::paths(&in_gfa, &args)?;
+        }
+        Command::PathChecksums(args) => {
+            commands::path_checksums::path_checksums(&in_gfa, &args)?;
+        }
+        Command::Gc(args) => {
+            commands::gc::gc(&in_gfa, &args)?;
+        }
+        Command::Complexity(args) => {
+            commands::complexity::complexity(&in_gfa, &args)?;
+        }
+        Command::Cycles(args) => {
+            commands::cycles::cycles(&in_gfa, &args)?;
+        }
+        Command::EdgeCount(args) => {
+            commands::edge_count::edge_count(&in_gfa, &args)?;
         }
         Command::GfaSegmentIdConversion(args) => {
-            commands::convert_names::convert_segment_ids(&opt.in_gfa, &args)?;
+            commands::convert_names::convert_segment_ids(&in_gfa, &args)?;
         }
         Command::Saboten => {
-            commands::saboten::run_saboten(&opt.in_gfa)?;
+            commands::saboten::run_saboten(&in_gfa)?;
+        }
+        Command::Clean(args) => {
+            commands::clean::clean(&in_gfa, &args)?;
+        }
+        Command::Ambiguous(args) => {
+            commands::ambiguous::ambiguous(&in_gfa, &args)?;
+        }
+        Command::Uncovered(args) => {
+            commands::uncovered::uncovered(&in_gfa, &args)?;
+        }
+        Command::ReportHtml(args) => {
+            commands::report::report_html(&in_gfa, &args)?;
+        }
+        Command::NodeMap(args) => {
+            commands::node_map::node_map(&in_gfa, &args)?;
+        }
+        Command::NodeCoverage(args) => {
+            commands::node_coverage::node_coverage(&in_gfa, &args)?;
+        }
+        Command::CoreAccessory(args) => {
+            commands::core_accessory::core_accessory(&in_gfa, &args)?;
+        }
+        Command::PathAlign(args) => {
+            commands::path_align::path_align(&in_gfa, &args)?;
+        }
+        Command::PathDedup(args) => {
+            commands::path_dedup::path_dedup(&in_gfa, &args)?;
+        }
+        Command::CompareVariants(args) => {
+            commands::compare::compare_variants(&in_gfa, &args)?;
+        }
+        Command::CheckLinks(args) => {
+            commands::check_links::check_links(&in_gfa, &args)?;
+        }
+        Command::CheckPaths(args) => {
+            commands::check_paths::check_paths(&in_gfa, &args)?;
+        }
+        Command::GetSeq(args) => {
+            commands::get_seq::get_seq(&in_gfa, &args)?;
         }
     }
     Ok(())
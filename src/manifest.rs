@@ -0,0 +1,84 @@
+//! Machine-readable progress manifests, for running long commands
+//! under external workflow schedulers.
+//!
+//! A manifest is a JSONL file with one object per completed stage,
+//! each with a `stage` field naming it. Lines are flushed as soon as
+//! they're written, so a manifest left behind by a failed run lists
+//! exactly the stages that were finished.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use serde_json::{Map, Value};
+
+pub struct StageManifest {
+    out: BufWriter<File>,
+}
+
+impl StageManifest {
+    /// Create the manifest file, or, if `append` is set, add to the
+    /// end of an existing one, e.g. when rerunning failed stages.
+    pub fn create<P: AsRef<Path>>(path: P, append: bool) -> io::Result<Self> {
+        let file = if append {
+            OpenOptions::new().create(true).append(true).open(path)?
+        } else {
+            File::create(path)?
+        };
+        Ok(Self {
+            out: BufWriter::new(file),
+        })
+    }
+
+    /// Write a line for a completed stage, with the fields of
+    /// `fields`, which must be a JSON object, after the stage name
+    pub fn record(&mut self, stage: &str, fields: Value) -> io::Result<()> {
+        let mut line = Map::new();
+        line.insert("stage".to_string(), Value::from(stage));
+        if let Value::Object(fields) = fields {
+            line.extend(fields);
+        }
+        writeln!(self.out, "{}", Value::Object(line))?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn stages_are_appended_as_jsonl() {
+        let path = std::env::temp_dir()
+            .join(format!("gfautil_manifest_{}.jsonl", std::process::id()));
+
+        let mut manifest = StageManifest::create(&path, false).unwrap();
+        manifest
+            .record("ultrabubbles", json!({ "bubbles": 3 }))
+            .unwrap();
+        drop(manifest);
+
+        let mut manifest = StageManifest::create(&path, true).unwrap();
+        manifest
+            .record("batch_completed", json!({ "batch": 0, "records": 2 }))
+            .unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                json!({ "stage": "ultrabubbles", "bubbles": 3 }),
+                json!({ "stage": "batch_completed", "batch": 0, "records": 2 }),
+            ]
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
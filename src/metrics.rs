@@ -0,0 +1,123 @@
+use std::{
+    fmt::Write as FmtWrite,
+    io::{Read, Write},
+    net::{IpAddr, TcpListener},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// Number of GFA files that have been parsed
+pub static GRAPHS_LOADED: AtomicU64 = AtomicU64::new(0);
+
+/// Number of bubbles that have been processed by variant calling
+pub static BUBBLES_PROCESSED: AtomicU64 = AtomicU64::new(0);
+
+/// Resident set size of the process, from the VmRSS line of
+/// /proc/self/status, so only available on Linux
+fn resident_memory_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let mut fields = line["VmRSS:".len()..].split_whitespace();
+    let value: u64 = fields.next()?.parse().ok()?;
+    match fields.next() {
+        Some("kB") => Some(value * 1024),
+        _ => None,
+    }
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, value: f64) {
+    let _ = writeln!(out, "# HELP gfautil_{} {}", name, help);
+    let _ = writeln!(out, "# TYPE gfautil_{} {}", name, kind);
+    let _ = writeln!(out, "gfautil_{} {}", name, value);
+}
+
+/// Render the current counters in the Prometheus text format
+pub fn render(start: Instant) -> String {
+    let uptime = start.elapsed().as_secs_f64();
+    let bubbles = BUBBLES_PROCESSED.load(Ordering::Relaxed) as f64;
+
+    let mut out = String::new();
+    metric(
+        &mut out,
+        "graphs_loaded_total",
+        "counter",
+        "Number of GFA files loaded",
+        GRAPHS_LOADED.load(Ordering::Relaxed) as f64,
+    );
+    metric(
+        &mut out,
+        "bubbles_processed_total",
+        "counter",
+        "Number of bubbles processed",
+        bubbles,
+    );
+    metric(
+        &mut out,
+        "bubbles_per_second",
+        "gauge",
+        "Average number of bubbles processed per second",
+        if uptime > 0.0 { bubbles / uptime } else { 0.0 },
+    );
+    metric(
+        &mut out,
+        "uptime_seconds",
+        "gauge",
+        "Seconds since the command started",
+        uptime,
+    );
+    if let Some(rss) = resident_memory_bytes() {
+        metric(
+            &mut out,
+            "resident_memory_bytes",
+            "gauge",
+            "Resident memory of the process",
+            rss as f64,
+        );
+    }
+    out
+}
+
+/// Serve the metrics over HTTP on the given address and port, from a
+/// background thread that lives as long as the command is running.
+/// Every request is answered with the metrics, regardless of the
+/// path.
+pub fn serve(addr: IpAddr, port: u16) -> std::io::Result<()> {
+    let listener = TcpListener::bind((addr, port))?;
+    let start = Instant::now();
+    info!("Serving metrics on {}", listener.local_addr()?);
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    debug!("Metrics connection failed: {}", err);
+                    continue;
+                }
+            };
+
+            // The request itself is ignored, but has to be read
+            // before responding
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf);
+
+            let body = render(start);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\n\
+                 Content-Type: text/plain; version=0.0.4\r\n\
+                 Content-Length: {}\r\n\
+                 Connection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            if let Err(err) = stream.write_all(response.as_bytes()) {
+                debug!("Error writing metrics response: {}", err);
+            }
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,203 @@
+//! Coverage of the segments of a graph by its paths, and by the
+//! samples the paths belong to, for telling the segments shared by
+//! every sample from those only some of them have.
+
+use std::fmt;
+
+use fnv::FnvHashMap;
+use rayon::prelude::*;
+
+use gfa::{
+    gfa::{Path, GFA},
+    optfields::OptFields,
+};
+
+use crate::util::{pansn_sample, SegmentName};
+
+/// The paths that traverse a segment
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SegmentCoverage {
+    /// The number of distinct paths that step on the segment
+    pub paths: usize,
+    /// The number of steps on the segment, counting every time a path
+    /// visits it
+    pub steps: usize,
+    /// The samples of the paths, as sorted indices into
+    /// `PathCoverage::samples`
+    pub samples: Vec<usize>,
+}
+
+/// The coverage of every segment of a graph
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PathCoverage {
+    /// The samples of the paths, in the order they first appear in
+    /// the GFA
+    pub samples: Vec<Vec<u8>>,
+    /// The coverage of each segment, in the order of the segments
+    pub segments: Vec<SegmentCoverage>,
+}
+
+/// How widely a segment is shared among the samples of a pangenome
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SegmentClass {
+    /// In all, or nearly all, samples
+    Core,
+    /// In more than a few samples, but not nearly all of them
+    Shell,
+    /// In only a few samples
+    Cloud,
+    /// Not on any path
+    Uncovered,
+}
+
+impl SegmentClass {
+    pub const ALL: [SegmentClass; 4] = [
+        SegmentClass::Core,
+        SegmentClass::Shell,
+        SegmentClass::Cloud,
+        SegmentClass::Uncovered,
+    ];
+}
+
+impl fmt::Display for SegmentClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SegmentClass::Core => "core",
+            SegmentClass::Shell => "shell",
+            SegmentClass::Cloud => "cloud",
+            SegmentClass::Uncovered => "uncovered",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl PathCoverage {
+    /// Classify each segment by the number of samples whose paths
+    /// visit it. A segment is core if it's in at least `core_fraction`
+    /// of the samples, cloud if it's in at most `cloud_max` samples,
+    /// and shell otherwise. Core takes precedence, so with a single
+    /// sample, every covered segment is core.
+    pub fn segment_classes(
+        &self,
+        core_fraction: f64,
+        cloud_max: usize,
+    ) -> Vec<SegmentClass> {
+        let core_min = core_fraction * self.samples.len() as f64;
+        self.segments
+            .iter()
+            .map(|seg| {
+                let samples = seg.samples.len();
+                if samples == 0 {
+                    SegmentClass::Uncovered
+                } else if samples as f64 >= core_min {
+                    SegmentClass::Core
+                } else if samples <= cloud_max {
+                    SegmentClass::Cloud
+                } else {
+                    SegmentClass::Shell
+                }
+            })
+            .collect()
+    }
+}
+
+/// Project the class of each segment onto a path, as 0-based,
+/// end-exclusive intervals of the path's sequence, merging the
+/// consecutive steps with the same class. `classes` is indexed like
+/// the segments of `gfa`, and steps on segments that aren't in the
+/// graph, or empty, are skipped.
+pub fn class_intervals<T: OptFields>(
+    gfa: &GFA<Vec<u8>, T>,
+    path: &Path<Vec<u8>, T>,
+    classes: &[SegmentClass],
+) -> Vec<(usize, usize, SegmentClass)> {
+    let seg_ix: FnvHashMap<&[u8], usize> = gfa
+        .segments
+        .iter()
+        .enumerate()
+        .map(|(ix, seg)| (seg.name.as_slice(), ix))
+        .collect();
+
+    let mut intervals: Vec<(usize, usize, SegmentClass)> = Vec::new();
+    let mut offset = 0;
+    for (seg, _) in path.iter() {
+        let seg: &[u8] = seg.as_ref();
+        let ix = match seg_ix.get(seg) {
+            Some(&ix) => ix,
+            None => continue,
+        };
+        let end = offset + gfa.segments[ix].sequence.len();
+        if end == offset {
+            continue;
+        }
+        match intervals.last_mut() {
+            Some(last) if last.2 == classes[ix] => last.1 = end,
+            _ => intervals.push((offset, end, classes[ix])),
+        }
+        offset = end;
+    }
+    intervals
+}
+
+/// The sample a path belongs to, i.e. its PanSN sample, or the whole
+/// path name if it doesn't follow PanSN
+pub fn path_sample(path_name: &[u8]) -> &[u8] {
+    pansn_sample(path_name).unwrap_or(path_name)
+}
+
+/// Find the paths, and samples, that traverse each segment, in one
+/// pass over the paths, which are processed in parallel. Steps on
+/// segments that aren't in the graph are ignored.
+pub fn path_coverage<N, T>(gfa: &GFA<N, T>) -> PathCoverage
+where
+    N: SegmentName + Sync,
+    T: OptFields + Sync,
+{
+    let seg_ix: FnvHashMap<&N, usize> = gfa
+        .segments
+        .iter()
+        .enumerate()
+        .map(|(ix, seg)| (&seg.name, ix))
+        .collect();
+
+    // The number of steps of each path on each segment it visits
+    let path_steps: Vec<Vec<(usize, usize)>> = gfa
+        .paths
+        .par_iter()
+        .map(|path| {
+            let mut steps: FnvHashMap<usize, usize> = FnvHashMap::default();
+            for (seg, _) in N::path_steps(path) {
+                if let Some(&ix) = seg_ix.get(&seg) {
+                    *steps.entry(ix).or_default() += 1;
+                }
+            }
+            steps.into_iter().collect()
+        })
+        .collect();
+
+    let mut samples: Vec<Vec<u8>> = Vec::new();
+    let mut sample_ids: FnvHashMap<&[u8], usize> = FnvHashMap::default();
+    let mut segments = vec![SegmentCoverage::default(); gfa.segments.len()];
+
+    for (path, steps) in gfa.paths.iter().zip(path_steps) {
+        let sample = path_sample(&path.path_name);
+        let sample_id = *sample_ids.entry(sample).or_insert_with(|| {
+            samples.push(sample.to_vec());
+            samples.len() - 1
+        });
+
+        for (ix, count) in steps {
+            let coverage = &mut segments[ix];
+            coverage.paths += 1;
+            coverage.steps += count;
+            coverage.samples.push(sample_id);
+        }
+    }
+
+    for coverage in segments.iter_mut() {
+        coverage.samples.sort_unstable();
+        coverage.samples.dedup();
+    }
+
+    PathCoverage { samples, segments }
+}
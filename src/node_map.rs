@@ -0,0 +1,122 @@
+use bstr::BString;
+use fnv::FnvHashSet;
+use std::fmt;
+
+use gfa::gfa::Orientation;
+
+use crate::variants::PathData;
+
+/// A single step on a reference path, along with the interval on the
+/// path that the node covers, as 0-based, half-open coordinates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodePosition {
+    pub node: usize,
+    pub path: BString,
+    pub start: usize,
+    pub end: usize,
+    pub orient: Orientation,
+    pub rank: usize,
+}
+
+impl fmt::Display for NodePosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            self.node,
+            self.path,
+            self.start,
+            self.end,
+            if self.orient.is_reverse() { '-' } else { '+' },
+            self.rank
+        )
+    }
+}
+
+/// Map every step of the given paths, or of all paths if `ref_paths`
+/// is `None`, to its position on the path. The result is sorted by
+/// path name and then by start position, so that it can be indexed
+/// as a coordinate-sorted table.
+pub fn node_positions(
+    path_data: &PathData,
+    ref_paths: Option<&FnvHashSet<BString>>,
+) -> Vec<NodePosition> {
+    let mut positions: Vec<NodePosition> = path_data
+        .path_names
+        .iter()
+        .zip(path_data.paths.iter())
+        .filter(|(name, _)| {
+            ref_paths.map(|refs| refs.contains(*name)).unwrap_or(true)
+        })
+        .flat_map(|(name, steps)| {
+            steps.iter().enumerate().map(
+                move |(rank, &(node, offset, orient))| {
+                    let len =
+                        path_data.segment_map.get(&node).map_or(0, |s| s.len());
+                    NodePosition {
+                        node,
+                        path: name.clone(),
+                        start: offset - 1,
+                        end: offset - 1 + len,
+                        orient,
+                        rank,
+                    }
+                },
+            )
+        })
+        .collect();
+
+    positions.sort_by(|a, b| {
+        a.path
+            .cmp(&b.path)
+            .then(a.start.cmp(&b.start))
+            .then(a.rank.cmp(&b.rank))
+    });
+
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fnv::FnvHashMap;
+    use gfa::gfa::Orientation::{Backward, Forward};
+
+    #[test]
+    fn positions_sorted_by_path_and_start() {
+        let segment_map: FnvHashMap<usize, BString> =
+            vec![(1, "ACGT".into()), (2, "GG".into()), (3, "T".into())]
+                .into_iter()
+                .collect();
+
+        let path_data = PathData {
+            segment_map,
+            path_names: vec!["b".into(), "a".into()],
+            paths: vec![
+                vec![(1, 1, Forward), (3, 5, Forward)],
+                vec![(3, 1, Forward), (2, 2, Backward), (1, 4, Forward)],
+            ],
+        };
+
+        let refs: FnvHashSet<BString> =
+            vec![BString::from("a")].into_iter().collect();
+        let a_only = node_positions(&path_data, Some(&refs));
+        assert_eq!(a_only.len(), 3);
+        assert_eq!(a_only[1].to_string(), "2\ta\t1\t3\t-\t1");
+
+        let all: Vec<(String, usize)> = node_positions(&path_data, None)
+            .into_iter()
+            .map(|p| (p.path.to_string(), p.start))
+            .collect();
+        assert_eq!(
+            all,
+            vec![
+                ("a".to_string(), 0),
+                ("a".to_string(), 1),
+                ("a".to_string(), 3),
+                ("b".to_string(), 0),
+                ("b".to_string(), 4),
+            ]
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use bstr::{BString, ByteSlice};
+use fnv::FnvHashMap;
+use std::fmt;
+
+use gfa::gfa::{Orientation, GFA};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeSeqError {
+    InvalidStep(BString),
+    MissingNode(usize),
+}
+
+impl fmt::Display for NodeSeqError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeSeqError::InvalidStep(step) => {
+                write!(f, "invalid node or oriented step: {}", step)
+            }
+            NodeSeqError::MissingNode(node) => {
+                write!(f, "node {} does not exist in graph", node)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NodeSeqError {}
+
+/// Build the map from segment IDs to sequences, dropping the rest of
+/// the graph
+pub fn segment_sequences<T: gfa::optfields::OptFields>(
+    gfa: GFA<usize, T>,
+) -> FnvHashMap<usize, BString> {
+    gfa.segments
+        .into_iter()
+        .map(|seg| (seg.name, seg.sequence.into()))
+        .collect()
+}
+
+/// Parse a single node ID, optionally oriented, in any of the forms
+/// `12`, `12+`, `12-`, `>12`, or `<12`. Unoriented IDs are forward.
+pub fn parse_oriented_node(
+    step: &[u8],
+) -> Result<(usize, Orientation), NodeSeqError> {
+    let (id, orient) = if let Some(id) = step.strip_prefix(b">") {
+        (id, Orientation::Forward)
+    } else if let Some(id) = step.strip_prefix(b"<") {
+        (id, Orientation::Backward)
+    } else if let Some(id) = step.strip_suffix(b"+") {
+        (id, Orientation::Forward)
+    } else if let Some(id) = step.strip_suffix(b"-") {
+        (id, Orientation::Backward)
+    } else {
+        (step, Orientation::Forward)
+    };
+
+    id.to_str()
+        .ok()
+        .and_then(|id| id.parse::<usize>().ok())
+        .map(|id| (id, orient))
+        .ok_or_else(|| NodeSeqError::InvalidStep(step.into()))
+}
+
+/// Parse a list of oriented nodes, separated by whitespace or commas,
+/// or given as a GAF-style walk like `>1<2>3`.
+pub fn parse_oriented_nodes(
+    line: &[u8],
+) -> Result<Vec<(usize, Orientation)>, NodeSeqError> {
+    let mut steps = Vec::new();
+    for token in line
+        .split(|&b| b == b',' || b.is_ascii_whitespace())
+        .filter(|token| !token.is_empty())
+    {
+        if token[1..].iter().any(|&b| b == b'>' || b == b'<') {
+            // A walk, split before each orientation marker
+            let mut start = 0;
+            for ix in 1..=token.len() {
+                if ix == token.len() || token[ix] == b'>' || token[ix] == b'<' {
+                    steps.push(parse_oriented_node(&token[start..ix])?);
+                    start = ix;
+                }
+            }
+        } else {
+            steps.push(parse_oriented_node(token)?);
+        }
+    }
+    Ok(steps)
+}
+
+/// The sequence of a node in the given orientation
+pub fn oriented_sequence(
+    segment_map: &FnvHashMap<usize, BString>,
+    node: usize,
+    orient: Orientation,
+) -> Result<BString, NodeSeqError> {
+    let seq = segment_map
+        .get(&node)
+        .ok_or(NodeSeqError::MissingNode(node))?;
+    if orient.is_reverse() {
+        Ok(handlegraph::util::dna::rev_comp_iter(seq.as_slice()).collect())
+    } else {
+        Ok(seq.clone())
+    }
+}
+
+/// The sequence spelled by a traversal of oriented nodes
+pub fn traversal_sequence(
+    segment_map: &FnvHashMap<usize, BString>,
+    steps: &[(usize, Orientation)],
+) -> Result<BString, NodeSeqError> {
+    let mut seq = BString::from(Vec::new());
+    for &(node, orient) in steps {
+        seq.extend_from_slice(&oriented_sequence(segment_map, node, orient)?);
+    }
+    Ok(seq)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Orientation::*;
+
+    #[test]
+    fn parse_node_lists() {
+        assert_eq!(
+            parse_oriented_nodes(b"1 2+,3-").unwrap(),
+            vec![(1, Forward), (2, Forward), (3, Backward)]
+        );
+        assert_eq!(
+            parse_oriented_nodes(b">1<2>3").unwrap(),
+            vec![(1, Forward), (2, Backward), (3, Forward)]
+        );
+        assert_eq!(
+            parse_oriented_nodes(b"1 x"),
+            Err(NodeSeqError::InvalidStep("x".into()))
+        );
+    }
+
+    #[test]
+    fn traversal_sequences() {
+        let segment_map: FnvHashMap<usize, BString> =
+            vec![(1, "AAC".into()), (2, "GTT".into())]
+                .into_iter()
+                .collect();
+
+        assert_eq!(
+            traversal_sequence(&segment_map, &[(1, Forward), (2, Backward)])
+                .unwrap(),
+            "AACAAC"
+        );
+        assert_eq!(
+            traversal_sequence(&segment_map, &[(3, Forward)]),
+            Err(NodeSeqError::MissingNode(3))
+        );
+    }
+}
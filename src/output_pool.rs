@@ -0,0 +1,234 @@
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use fnv::FnvHashMap;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// The name of the manifest file written by `OutputPool::finish`
+pub const MANIFEST_NAME: &str = "manifest.tsv";
+
+const TMP_SUFFIX: &str = ".tmp";
+
+#[derive(Debug, Default)]
+struct OutputFile {
+    records: usize,
+    bytes: usize,
+    writer: Option<BufWriter<File>>,
+}
+
+/// Writes records to many files in one directory, keeping at most
+/// `max_open` of them open at a time; the least recently used file
+/// is closed when another must be opened, and reopened for appending
+/// when it's written to again.
+///
+/// Files are written with a `.tmp` suffix, and only renamed to their
+/// final names by `finish`, which then writes a manifest listing
+/// every file with its record and byte counts. If the command fails
+/// partway, the output directory has no manifest, and only `.tmp`
+/// files, so half-written outputs can't be mistaken for complete
+/// ones.
+pub struct OutputPool {
+    dir: PathBuf,
+    max_open: usize,
+    header: Option<String>,
+    files: FnvHashMap<String, OutputFile>,
+    /// The names of the open files, least recently used first
+    open: VecDeque<String>,
+}
+
+/// Replace the characters that can't be used in file names, so that
+/// e.g. a contig name can be used as one
+pub fn file_name_for(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | '\0' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+impl OutputPool {
+    /// Create the output directory if needed. If `header` is given,
+    /// it's written at the start of every file.
+    pub fn new<P: AsRef<Path>>(
+        dir: P,
+        max_open: usize,
+        header: Option<String>,
+    ) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            max_open: max_open.max(1),
+            header,
+            files: FnvHashMap::default(),
+            open: VecDeque::new(),
+        })
+    }
+
+    fn tmp_path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}{}", name, TMP_SUFFIX))
+    }
+
+    fn close_lru(&mut self) -> io::Result<()> {
+        if let Some(name) = self.open.pop_front() {
+            debug!("Closing output file {}", name);
+            if let Some(mut writer) = self
+                .files
+                .get_mut(&name)
+                .and_then(|file| file.writer.take())
+            {
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn writer(&mut self, name: &str) -> io::Result<&mut BufWriter<File>> {
+        let is_open = self
+            .files
+            .get(name)
+            .map_or(false, |file| file.writer.is_some());
+
+        if is_open {
+            if let Some(ix) = self.open.iter().position(|n| n == name) {
+                let name = self.open.remove(ix).unwrap();
+                self.open.push_back(name);
+            }
+        } else {
+            while self.open.len() >= self.max_open {
+                self.close_lru()?;
+            }
+
+            let tmp_path = self.tmp_path(name);
+            let is_new = !self.files.contains_key(name);
+            let file = if is_new {
+                File::create(&tmp_path)?
+            } else {
+                OpenOptions::new().append(true).open(&tmp_path)?
+            };
+            let mut writer = BufWriter::new(file);
+
+            let entry = self.files.entry(name.to_string()).or_default();
+            if is_new {
+                if let Some(header) = &self.header {
+                    writeln!(writer, "{}", header)?;
+                    entry.bytes += header.len() + 1;
+                }
+            }
+            entry.writer = Some(writer);
+            self.open.push_back(name.to_string());
+        }
+
+        Ok(self
+            .files
+            .get_mut(name)
+            .and_then(|file| file.writer.as_mut())
+            .unwrap())
+    }
+
+    /// Write a record, followed by a newline, to the named file
+    pub fn write_record<D: Display>(
+        &mut self,
+        name: &str,
+        record: &D,
+    ) -> io::Result<()> {
+        self.write_line(name, record.to_string().as_bytes())
+    }
+
+    /// Write a record given as bytes, followed by a newline, to the
+    /// named file
+    pub fn write_line(&mut self, name: &str, line: &[u8]) -> io::Result<()> {
+        let writer = self.writer(name)?;
+        writer.write_all(line)?;
+        writer.write_all(b"\n")?;
+        let file = self.files.get_mut(name).unwrap();
+        file.records += 1;
+        file.bytes += line.len() + 1;
+        Ok(())
+    }
+
+    /// Close all files, rename them to their final names, and write
+    /// the manifest, with the columns file, records, and bytes.
+    /// Returns the paths of the finished files, sorted by name.
+    pub fn finish(mut self) -> io::Result<Vec<PathBuf>> {
+        while !self.open.is_empty() {
+            self.close_lru()?;
+        }
+
+        let mut names: Vec<&String> = self.files.keys().collect();
+        names.sort();
+
+        let mut paths = Vec::with_capacity(names.len());
+        let mut manifest = String::from("#file\trecords\tbytes\n");
+
+        for name in names {
+            let file = &self.files[name];
+            let path = self.dir.join(name);
+            fs::rename(self.tmp_path(name), &path)?;
+            manifest.push_str(&format!(
+                "{}\t{}\t{}\n",
+                name, file.records, file.bytes
+            ));
+            paths.push(path);
+        }
+
+        let manifest_tmp = self.tmp_path(MANIFEST_NAME);
+        fs::write(&manifest_tmp, manifest)?;
+        fs::rename(manifest_tmp, self.dir.join(MANIFEST_NAME))?;
+
+        info!(
+            "Wrote {} files and {} to {}",
+            paths.len(),
+            MANIFEST_NAME,
+            self.dir.display()
+        );
+
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounded_pool_reopens_and_renames() {
+        let dir = std::env::temp_dir()
+            .join(format!("gfautil_output_pool_{}", std::process::id()));
+
+        let mut pool =
+            OutputPool::new(&dir, 2, Some("#header".to_string())).unwrap();
+        for (name, record) in
+            &[("a", 1), ("b", 2), ("c", 3), ("a", 4), ("b", 5), ("a", 6)]
+        {
+            pool.write_record(name, record).unwrap();
+            assert!(pool.open.len() <= 2);
+        }
+
+        assert!(dir.join("a.tmp").exists());
+        assert!(!dir.join(MANIFEST_NAME).exists());
+
+        let paths = pool.finish().unwrap();
+        assert_eq!(paths, vec![dir.join("a"), dir.join("b"), dir.join("c")]);
+        assert!(!dir.join("a.tmp").exists());
+
+        let a = fs::read_to_string(dir.join("a")).unwrap();
+        assert_eq!(a, "#header\n1\n4\n6\n");
+
+        let manifest = fs::read_to_string(dir.join(MANIFEST_NAME)).unwrap();
+        assert_eq!(
+            manifest,
+            "#file\trecords\tbytes\na\t3\t14\nb\t2\t12\nc\t1\t10\n"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
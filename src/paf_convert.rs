@@ -0,0 +1,443 @@
+use std::{io::BufReader, path::Path};
+
+use bstr::{io::*, BString, ByteSlice};
+use fnv::{FnvHashMap, FnvHashSet};
+
+use gfa::{
+    gafpaf::{parse_paf, GAFPath, GAFStep},
+    gfa::{Orientation, GFA},
+    optfields::{OptField, OptFieldVal, OptFields, OptionalFields},
+};
+
+type GAF = gfa::gafpaf::GAF<OptionalFields>;
+type PAF = gfa::gafpaf::PAF<OptionalFields>;
+
+/// Parse a CIGAR string into (length, op) pairs
+fn parse_cigar(cigar: &[u8]) -> Option<Vec<(usize, u8)>> {
+    let mut ops = Vec::new();
+    let mut len = 0usize;
+    let mut has_len = false;
+    for &byte in cigar {
+        if byte.is_ascii_digit() {
+            len = len.checked_mul(10)?.checked_add((byte - b'0') as usize)?;
+            has_len = true;
+        } else if has_len {
+            ops.push((len, byte));
+            len = 0;
+            has_len = false;
+        } else {
+            return None;
+        }
+    }
+    if has_len {
+        return None;
+    }
+    Some(ops)
+}
+
+fn get_cigar(opts: &OptionalFields) -> Option<Vec<(usize, u8)>> {
+    let cg = opts.get_field(b"cg")?;
+    if let OptFieldVal::Z(cg) = &cg.value {
+        parse_cigar(cg)
+    } else {
+        None
+    }
+}
+
+/// Concatenate the CIGARs of consecutive PAF records, merging the
+/// adjacent operations that were split at segment boundaries
+fn merge_cigars<'a>(
+    cigars: impl Iterator<Item = &'a [(usize, u8)]>,
+) -> BString {
+    let mut merged: Vec<(usize, u8)> = Vec::new();
+    for &(len, op) in cigars.flatten() {
+        match merged.last_mut() {
+            Some((last_len, last_op)) if *last_op == op => *last_len += len,
+            _ => merged.push((len, op)),
+        }
+    }
+
+    let mut cigar = BString::from(Vec::new());
+    for (len, op) in merged {
+        cigar.extend_from_slice(len.to_string().as_bytes());
+        cigar.push(op);
+    }
+    cigar
+}
+
+/// The start and end of a PAF record's target range, on the segment
+/// as it's traversed by the query
+fn oriented_target_range(paf: &PAF) -> (usize, usize) {
+    let (start, end) = paf.target_seq_range;
+    match paf.strand {
+        Orientation::Forward => (start, end),
+        Orientation::Backward => {
+            (paf.target_seq_len - end, paf.target_seq_len - start)
+        }
+    }
+}
+
+/// Whether `next` continues the alignment of `prev` on the next
+/// segment of a graph path: the query ranges must be adjacent, and
+/// the alignment must leave `prev`'s segment at its end and enter
+/// `next`'s at its start.
+fn continues(prev: &PAF, next: &PAF) -> bool {
+    prev.query_seq_name == next.query_seq_name
+        && prev.query_seq_range.1 == next.query_seq_range.0
+        && oriented_target_range(prev).1 == prev.target_seq_len
+        && oriented_target_range(next).0 == 0
+}
+
+/// Build a single GAF record from a chain of PAF records against
+/// segments, in query order
+fn segment_chain_to_gaf(chain: &[&PAF]) -> GAF {
+    let first = chain[0];
+    let last = chain[chain.len() - 1];
+
+    let steps: Vec<GAFStep> = chain
+        .iter()
+        .map(|paf| GAFStep::SegId(paf.strand, paf.target_seq_name.clone()))
+        .collect();
+
+    let path_len: usize = chain.iter().map(|paf| paf.target_seq_len).sum();
+    let path_start = oriented_target_range(first).0;
+    let path_end =
+        path_len - (last.target_seq_len - oriented_target_range(last).1);
+
+    let mut optional: OptionalFields = first
+        .optional
+        .iter()
+        .filter(|field| &field.tag != b"cg")
+        .cloned()
+        .collect();
+
+    // Only add a CIGAR if every record in the chain has one
+    let cigars: Option<Vec<_>> =
+        chain.iter().map(|paf| get_cigar(&paf.optional)).collect();
+    if let Some(cigars) = cigars {
+        let cigar = merge_cigars(cigars.iter().map(|cg| cg.as_slice()));
+        optional.push(OptField {
+            tag: *b"cg",
+            value: OptFieldVal::Z(cigar),
+        });
+    }
+
+    GAF {
+        seq_name: first.query_seq_name.clone(),
+        seq_len: first.query_seq_len,
+        seq_range: (first.query_seq_range.0, last.query_seq_range.1),
+        strand: Orientation::Forward,
+        path: GAFPath::OrientIntv(steps),
+        path_len,
+        path_range: (path_start, path_end),
+        residue_matches: chain.iter().map(|paf| paf.residue_matches).sum(),
+        block_length: chain.iter().map(|paf| paf.block_length).sum(),
+        quality: chain.iter().map(|paf| paf.quality).min().unwrap_or(255),
+        optional,
+    }
+}
+
+/// A PAF record against a path, in the path's stable coordinates,
+/// is the same as a GAF record with a stable ID as its path
+fn path_paf_to_gaf(paf: &PAF) -> GAF {
+    GAF {
+        seq_name: paf.query_seq_name.clone(),
+        seq_len: paf.query_seq_len,
+        seq_range: paf.query_seq_range,
+        strand: paf.strand,
+        path: GAFPath::StableId(paf.target_seq_name.clone()),
+        path_len: paf.target_seq_len,
+        path_range: paf.target_seq_range,
+        residue_matches: paf.residue_matches,
+        block_length: paf.block_length,
+        quality: paf.quality,
+        optional: paf.optional.clone(),
+    }
+}
+
+/// Reconstruct GAF records from PAF records whose targets are either
+/// segments or paths in the GFA. Consecutive records of the same
+/// query that continue from one segment into the next, as produced by
+/// `gaf_to_paf`, are merged into one GAF record with an oriented
+/// segment path and the concatenated CIGAR. Records against paths
+/// are output as GAF records with stable path coordinates. Records
+/// whose target is neither are skipped with a warning.
+pub fn paf_to_gaf<T: OptFields>(
+    gfa: &GFA<Vec<u8>, T>,
+    pafs: &[PAF],
+) -> Vec<GAF> {
+    let segment_lens: FnvHashMap<&[u8], usize> = gfa
+        .segments
+        .iter()
+        .map(|seg| (seg.name.as_slice(), seg.sequence.len()))
+        .collect();
+
+    let path_names: FnvHashSet<&[u8]> = gfa
+        .paths
+        .iter()
+        .map(|path| path.path_name.as_slice())
+        .collect();
+
+    let mut gafs = Vec::new();
+    let mut chain: Vec<&PAF> = Vec::new();
+
+    for paf in pafs {
+        let target: &[u8] = paf.target_seq_name.as_ref();
+
+        if segment_lens.contains_key(target) {
+            if let Some(prev) = chain.last() {
+                if !continues(prev, paf) {
+                    gafs.push(segment_chain_to_gaf(&chain));
+                    chain.clear();
+                }
+            }
+            chain.push(paf);
+            continue;
+        }
+
+        if !chain.is_empty() {
+            gafs.push(segment_chain_to_gaf(&chain));
+            chain.clear();
+        }
+
+        if path_names.contains(target) {
+            gafs.push(path_paf_to_gaf(paf));
+        } else {
+            log::warn!(
+                "Skipping PAF record for {}: target {} is not a segment \
+                 or path in the graph",
+                paf.query_seq_name,
+                target.as_bstr()
+            );
+        }
+    }
+
+    if !chain.is_empty() {
+        gafs.push(segment_chain_to_gaf(&chain));
+    }
+
+    gafs
+}
+
+/// The offset, orientation, and length of each segment on a
+/// reference path, for lifting PAF records against segments onto the
+/// path. Segments that occur more than once on the path are indexed
+/// at their first occurrence.
+#[derive(Debug, Clone)]
+pub struct PathLiftIndex {
+    pub name: BString,
+    pub length: usize,
+    segments: FnvHashMap<Vec<u8>, (usize, Orientation, usize)>,
+    pub repeated_segments: usize,
+}
+
+impl PathLiftIndex {
+    /// Index the path named `path_name`, if it's in the GFA
+    pub fn new<T: OptFields>(
+        gfa: &GFA<Vec<u8>, T>,
+        path_name: &[u8],
+    ) -> Option<Self> {
+        let path = gfa
+            .paths
+            .iter()
+            .find(|path| path.path_name.as_slice() == path_name)?;
+
+        let segment_lens: FnvHashMap<&[u8], usize> = gfa
+            .segments
+            .iter()
+            .map(|seg| (seg.name.as_slice(), seg.sequence.len()))
+            .collect();
+
+        let mut segments = FnvHashMap::default();
+        let mut repeated_segments = 0;
+        let mut length = 0;
+
+        for (seg, orient) in path.iter() {
+            let seg: &[u8] = seg.as_ref();
+            let seg_len = segment_lens.get(seg).copied().unwrap_or(0);
+            if segments.contains_key(seg) {
+                repeated_segments += 1;
+            } else {
+                segments.insert(seg.to_vec(), (length, orient, seg_len));
+            }
+            length += seg_len;
+        }
+
+        Some(Self {
+            name: path_name.into(),
+            length,
+            segments,
+            repeated_segments,
+        })
+    }
+}
+
+fn cigar_to_bstring(cigar: &[(usize, u8)]) -> BString {
+    merge_cigars(std::iter::once(cigar))
+}
+
+fn replace_cigar(
+    optional: &OptionalFields,
+    cigar: Option<BString>,
+) -> OptionalFields {
+    let mut optional: OptionalFields = optional
+        .iter()
+        .filter(|field| &field.tag != b"cg")
+        .cloned()
+        .collect();
+    if let Some(cigar) = cigar {
+        optional.push(OptField {
+            tag: *b"cg",
+            value: OptFieldVal::Z(cigar),
+        });
+    }
+    optional
+}
+
+/// Rewrite a PAF record against a segment into one against the
+/// reference path, if the segment is on the path. If the segment is
+/// reversed on the path, the strand is flipped, and the CIGAR is
+/// reversed to match.
+pub fn lift_paf(index: &PathLiftIndex, paf: &PAF) -> Option<PAF> {
+    let &(offset, orient, seg_len) =
+        index.segments.get(paf.target_seq_name.as_slice())?;
+    let (start, end) = paf.target_seq_range;
+    if end > seg_len || start > end {
+        return None;
+    }
+
+    let (target_seq_range, strand, optional) = if orient.is_reverse() {
+        let strand = match paf.strand {
+            Orientation::Forward => Orientation::Backward,
+            Orientation::Backward => Orientation::Forward,
+        };
+        let cigar = get_cigar(&paf.optional).map(|mut cigar| {
+            cigar.reverse();
+            cigar_to_bstring(&cigar)
+        });
+        let range = (offset + seg_len - end, offset + seg_len - start);
+        (range, strand, replace_cigar(&paf.optional, cigar))
+    } else {
+        let range = (offset + start, offset + end);
+        (range, paf.strand, paf.optional.clone())
+    };
+
+    Some(PAF {
+        target_seq_name: index.name.clone(),
+        target_seq_len: index.length,
+        target_seq_range,
+        strand,
+        optional,
+        ..paf.clone()
+    })
+}
+
+/// Whether the lifted record `next` continues `prev` on the path
+fn lifted_continues(prev: &PAF, next: &PAF) -> bool {
+    let adjacent = match prev.strand {
+        Orientation::Forward => {
+            prev.target_seq_range.1 == next.target_seq_range.0
+        }
+        Orientation::Backward => {
+            next.target_seq_range.1 == prev.target_seq_range.0
+        }
+    };
+    prev.query_seq_name == next.query_seq_name
+        && prev.query_seq_range.1 == next.query_seq_range.0
+        && prev.strand == next.strand
+        && adjacent
+}
+
+fn merge_lifted(prev: &mut PAF, next: PAF) {
+    let cigars = get_cigar(&prev.optional).and_then(|prev_cg| {
+        let next_cg = get_cigar(&next.optional)?;
+        // The CIGAR follows the target, so on the reverse strand the
+        // later part of the query comes first
+        let merged = match prev.strand {
+            Orientation::Forward => merge_cigars(
+                vec![prev_cg.as_slice(), next_cg.as_slice()].into_iter(),
+            ),
+            Orientation::Backward => merge_cigars(
+                vec![next_cg.as_slice(), prev_cg.as_slice()].into_iter(),
+            ),
+        };
+        Some(merged)
+    });
+
+    prev.query_seq_range.1 = next.query_seq_range.1;
+    prev.target_seq_range = (
+        prev.target_seq_range.0.min(next.target_seq_range.0),
+        prev.target_seq_range.1.max(next.target_seq_range.1),
+    );
+    prev.residue_matches += next.residue_matches;
+    prev.block_length += next.block_length;
+    prev.quality = prev.quality.min(next.quality);
+    prev.optional = replace_cigar(&prev.optional, cigars);
+}
+
+/// Lift PAF records against segments, as produced by `gaf_to_paf`,
+/// onto a reference path. Consecutive records of a query that are
+/// also consecutive on the path are merged into one record, so that
+/// an alignment that follows the path becomes a single record, as it
+/// would be against the linear reference. Records against segments
+/// that aren't on the path are left out. Returns the lifted records,
+/// and the number of records left out.
+pub fn lift_pafs(index: &PathLiftIndex, pafs: &[PAF]) -> (Vec<PAF>, usize) {
+    let mut lifted: Vec<PAF> = Vec::new();
+    let mut skipped = 0;
+
+    for paf in pafs {
+        let next = match lift_paf(index, paf) {
+            Some(next) => next,
+            None => {
+                skipped += 1;
+                continue;
+            }
+        };
+        match lifted.last_mut() {
+            Some(prev) if lifted_continues(prev, &next) => {
+                merge_lifted(prev, next)
+            }
+            _ => lifted.push(next),
+        }
+    }
+
+    (lifted, skipped)
+}
+
+pub fn load_pafs(paf_path: &Path) -> std::io::Result<Vec<PAF>> {
+    let file = crate::compression::open_input(paf_path)?;
+    let lines = BufReader::new(file).byte_lines();
+    let mut pafs: Vec<PAF> = Vec::new();
+
+    for (i, line) in lines.enumerate() {
+        let line = line?;
+        let fields = line.split_str(b"\t");
+        if let Some(paf) = parse_paf(fields) {
+            pafs.push(paf);
+        } else {
+            eprintln!("Error parsing PAF line {}", i);
+        }
+    }
+
+    Ok(pafs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cigars_are_merged_at_boundaries() {
+        let a = parse_cigar(b"1M").unwrap();
+        let b = parse_cigar(b"3M1I").unwrap();
+        let c = parse_cigar(b"2I10M").unwrap();
+        let merged = merge_cigars(
+            vec![a.as_slice(), b.as_slice(), c.as_slice()].into_iter(),
+        );
+        assert_eq!(merged, "4M3I10M");
+
+        assert_eq!(parse_cigar(b"M"), None);
+        assert_eq!(parse_cigar(b"10"), None);
+    }
+}
@@ -0,0 +1,213 @@
+use bstr::BString;
+use fnv::FnvHashMap;
+
+use crate::variants::PathStep;
+
+/// A comparison of two paths through the graph, using the nodes that
+/// occur exactly once in both paths as anchors. Ranges are 0-based,
+/// half-open positions on the path sequences.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathAlignment {
+    pub query_range: (usize, usize),
+    pub target_range: (usize, usize),
+    /// Total length of the anchor nodes
+    pub matches: usize,
+    /// Total length of the matches, insertions, and deletions
+    pub block_length: usize,
+    pub cigar: Vec<(usize, char)>,
+}
+
+impl PathAlignment {
+    pub fn cigar_string(&self) -> String {
+        self.cigar
+            .iter()
+            .map(|(len, op)| format!("{}{}", len, op))
+            .collect()
+    }
+}
+
+fn push_op(cigar: &mut Vec<(usize, char)>, len: usize, op: char) {
+    if len == 0 {
+        return;
+    }
+    match cigar.last_mut() {
+        Some((last_len, last_op)) if *last_op == op => *last_len += len,
+        _ => cigar.push((len, op)),
+    }
+}
+
+/// Indices into `values` of a longest strictly increasing subsequence
+fn longest_increasing(values: &[usize]) -> Vec<usize> {
+    // tails[k] is the index of the smallest value that ends an
+    // increasing subsequence of length k + 1
+    let mut tails: Vec<usize> = Vec::new();
+    let mut parents: Vec<Option<usize>> = vec![None; values.len()];
+
+    for (ix, &val) in values.iter().enumerate() {
+        let len = tails
+            .binary_search_by(|&t| values[t].cmp(&val))
+            .unwrap_or_else(|e| e);
+        if len > 0 {
+            parents[ix] = Some(tails[len - 1]);
+        }
+        if len == tails.len() {
+            tails.push(ix);
+        } else {
+            tails[len] = ix;
+        }
+    }
+
+    let mut result = Vec::with_capacity(tails.len());
+    let mut cur = tails.last().copied();
+    while let Some(ix) = cur {
+        result.push(ix);
+        cur = parents[ix];
+    }
+    result.reverse();
+    result
+}
+
+/// Compare two paths by the nodes they share, without aligning any
+/// sequence. Nodes that occur once in each path, in the same
+/// orientation, are anchors; the longest chain of anchors that is
+/// collinear in both paths is reported as matches, and the nodes
+/// between consecutive anchors as insertions (only in the query) and
+/// deletions (only in the target). Returns `None` if the paths share
+/// no anchors.
+pub fn align_paths(
+    segment_map: &FnvHashMap<usize, BString>,
+    query: &[PathStep],
+    target: &[PathStep],
+) -> Option<PathAlignment> {
+    let node_len = |node: usize| segment_map.get(&node).map_or(0, |s| s.len());
+
+    let step_counts = |path: &[PathStep]| {
+        let mut counts: FnvHashMap<(usize, bool), (usize, usize)> =
+            FnvHashMap::default();
+        for (ix, &(node, _, orient)) in path.iter().enumerate() {
+            let entry =
+                counts.entry((node, orient.is_reverse())).or_insert((0, ix));
+            entry.0 += 1;
+        }
+        counts
+    };
+
+    let query_counts = step_counts(query);
+    let target_counts = step_counts(target);
+
+    let anchors: Vec<(usize, usize)> = query
+        .iter()
+        .enumerate()
+        .filter_map(|(query_ix, &(node, _, orient))| {
+            let key = (node, orient.is_reverse());
+            let (query_count, _) = query_counts[&key];
+            let &(target_count, target_ix) = target_counts.get(&key)?;
+            if query_count == 1 && target_count == 1 {
+                Some((query_ix, target_ix))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let target_ixs: Vec<usize> = anchors.iter().map(|&(_, t)| t).collect();
+    let chain: Vec<(usize, usize)> = longest_increasing(&target_ixs)
+        .into_iter()
+        .map(|ix| anchors[ix])
+        .collect();
+
+    let &(first_query, first_target) = chain.first()?;
+    let &(last_query, last_target) = chain.last()?;
+
+    let span_len = |path: &[PathStep], from: usize, to: usize| -> usize {
+        path[from..to]
+            .iter()
+            .map(|&(node, _, _)| node_len(node))
+            .sum()
+    };
+
+    let mut cigar = Vec::new();
+    let mut matches = 0;
+    let mut block_length = 0;
+
+    let mut prev: Option<(usize, usize)> = None;
+
+    for &(query_ix, target_ix) in chain.iter() {
+        if let Some((prev_query, prev_target)) = prev {
+            let ins = span_len(query, prev_query + 1, query_ix);
+            let del = span_len(target, prev_target + 1, target_ix);
+            push_op(&mut cigar, ins, 'I');
+            push_op(&mut cigar, del, 'D');
+            block_length += ins + del;
+        }
+
+        let len = node_len(query[query_ix].0);
+        push_op(&mut cigar, len, 'M');
+        matches += len;
+        block_length += len;
+
+        prev = Some((query_ix, target_ix));
+    }
+
+    let range = |path: &[PathStep], first: usize, last: usize| {
+        let (_, start, _) = path[first];
+        let (last_node, last_offset, _) = path[last];
+        (start - 1, last_offset - 1 + node_len(last_node))
+    };
+
+    Some(PathAlignment {
+        query_range: range(query, first_query, last_query),
+        target_range: range(target, first_target, last_target),
+        matches,
+        block_length,
+        cigar,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gfa::gfa::Orientation::Forward;
+
+    fn steps(
+        segment_map: &FnvHashMap<usize, BString>,
+        nodes: &[usize],
+    ) -> Vec<PathStep> {
+        let mut offset = 1;
+        nodes
+            .iter()
+            .map(|&node| {
+                let step = (node, offset, Forward);
+                offset += segment_map[&node].len();
+                step
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shared_nodes_as_anchors() {
+        let segment_map: FnvHashMap<usize, BString> = vec![
+            (1, "AAAA"),
+            (2, "CC"),
+            (3, "GGG"),
+            (4, "T"),
+            (5, "ACGTA"),
+            (6, "TT"),
+        ]
+        .into_iter()
+        .map(|(id, seq)| (id, seq.into()))
+        .collect();
+
+        // Node 6 is only in the query, and is also the first query
+        // step, so it's not part of the aligned range
+        let query = steps(&segment_map, &[6, 1, 2, 4, 5]);
+        let target = steps(&segment_map, &[1, 3, 4, 5]);
+
+        let aln = align_paths(&segment_map, &query, &target).unwrap();
+        assert_eq!(aln.cigar_string(), "4M2I3D6M");
+        assert_eq!(aln.query_range, (2, 14));
+        assert_eq!(aln.target_range, (0, 13));
+        assert_eq!(aln.matches, 10);
+        assert_eq!(aln.block_length, 15);
+    }
+}
@@ -0,0 +1,149 @@
+use bstr::BString;
+use fnv::FnvHashMap;
+
+use gfa::{gfa::GFA, optfields::OptFields};
+
+/// A path that's identical or nearly identical to an earlier path in
+/// the GFA
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathDuplicate {
+    pub path: BString,
+    pub duplicate_of: BString,
+    /// The number of inserted, deleted, or substituted steps between
+    /// the two paths
+    pub differences: usize,
+}
+
+type Step<'a> = (&'a [u8], bool);
+
+/// The edit distance between two step sequences, if it's at most
+/// `max`. Only the diagonal band of width `max` is computed, so this
+/// is linear in the path lengths for small `max`.
+pub fn step_distance(
+    a: &[Step<'_>],
+    b: &[Step<'_>],
+    max: usize,
+) -> Option<usize> {
+    let (n, m) = (a.len(), b.len());
+    if n.max(m) - n.min(m) > max {
+        return None;
+    }
+
+    let inf = usize::MAX / 2;
+    let mut prev: Vec<usize> =
+        (0..=m).map(|j| if j <= max { j } else { inf }).collect();
+    let mut cur: Vec<usize> = vec![inf; m + 1];
+
+    for i in 1..=n {
+        let lo = i.saturating_sub(max).max(1);
+        let hi = (i + max).min(m);
+
+        cur[0] = if i <= max { i } else { inf };
+        if lo > 1 {
+            cur[lo - 1] = inf;
+        }
+
+        let mut row_min = cur[0];
+        for j in lo..=hi {
+            let subst = prev[j - 1] + if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let dist = subst.min(prev[j] + 1).min(cur[j - 1] + 1);
+            cur[j] = dist;
+            row_min = row_min.min(dist);
+        }
+
+        if row_min > max {
+            return None;
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+
+    Some(prev[m]).filter(|&d| d <= max)
+}
+
+/// Find the paths that are duplicates of an earlier path in the GFA,
+/// i.e. that have the same steps, or, if `max_diff` is above zero,
+/// that differ from it in at most `max_diff` steps. Each duplicate
+/// is assigned to the first matching path that isn't a duplicate
+/// itself.
+pub fn find_duplicate_paths<T: OptFields>(
+    gfa: &GFA<Vec<u8>, T>,
+    max_diff: usize,
+) -> Vec<PathDuplicate> {
+    let mut identical: FnvHashMap<Vec<Step<'_>>, usize> = FnvHashMap::default();
+    let mut representatives: Vec<(usize, Vec<Step<'_>>)> = Vec::new();
+    let mut duplicates = Vec::new();
+
+    for (ix, path) in gfa.paths.iter().enumerate() {
+        let steps: Vec<Step<'_>> = path
+            .iter()
+            .map(|(seg, orient)| {
+                let seg: &[u8] = seg.as_ref();
+                (seg, orient.is_reverse())
+            })
+            .collect();
+
+        let found = identical.get(&steps).map(|&rep| (rep, 0)).or_else(|| {
+            if max_diff == 0 {
+                return None;
+            }
+            representatives.iter().find_map(|(rep, rep_steps)| {
+                let dist = step_distance(rep_steps, &steps, max_diff)?;
+                Some((*rep, dist))
+            })
+        });
+
+        match found {
+            Some((rep, differences)) => duplicates.push(PathDuplicate {
+                path: path.path_name.clone().into(),
+                duplicate_of: gfa.paths[rep].path_name.clone().into(),
+                differences,
+            }),
+            None => {
+                identical.insert(steps.clone(), ix);
+                if max_diff > 0 {
+                    representatives.push((ix, steps));
+                }
+            }
+        }
+    }
+
+    duplicates
+}
+
+/// Remove the duplicate paths from the GFA
+pub fn drop_duplicate_paths<T: OptFields>(
+    gfa: &mut GFA<Vec<u8>, T>,
+    duplicates: &[PathDuplicate],
+) {
+    let names: fnv::FnvHashSet<&[u8]> =
+        duplicates.iter().map(|dup| dup.path.as_slice()).collect();
+    gfa.paths
+        .retain(|path| !names.contains(path.path_name.as_slice()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps(names: &[&'static str]) -> Vec<Step<'static>> {
+        names.iter().map(|n| (n.as_bytes(), false)).collect()
+    }
+
+    #[test]
+    fn banded_step_distance() {
+        let a = steps(&["1", "2", "3", "4", "5"]);
+        let b = steps(&["1", "2", "6", "4", "5"]);
+        let c = steps(&["1", "3", "4", "5", "7"]);
+
+        assert_eq!(step_distance(&a, &a, 0), Some(0));
+        assert_eq!(step_distance(&a, &b, 0), None);
+        assert_eq!(step_distance(&a, &b, 1), Some(1));
+        assert_eq!(step_distance(&a, &c, 1), None);
+        assert_eq!(step_distance(&a, &c, 2), Some(2));
+        assert_eq!(step_distance(&a, &c[..3], 2), Some(2));
+
+        let mut rev = a.clone();
+        rev[2].1 = true;
+        assert_eq!(step_distance(&a, &rev, 3), Some(1));
+    }
+}
@@ -0,0 +1,112 @@
+//! Provenance of rewritten segments, stored in the `OI` optional
+//! field of each S line as `OI:Z:<original ID>:<offset>`, where the
+//! offset is the position in the original segment where the new
+//! segment starts. Coordinates computed on a graph before it was
+//! transformed can then be compared to those computed after.
+
+use bstr::{io::*, BString, ByteSlice};
+use fnv::FnvHashMap;
+use std::{
+    fmt,
+    io::{self, BufReader},
+    path::Path,
+};
+
+use gfa::optfields::{OptField, OptFieldVal};
+
+use crate::compression;
+
+pub const PROVENANCE_TAG: &[u8; 2] = b"OI";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    pub orig_id: BString,
+    pub offset: usize,
+}
+
+impl Provenance {
+    pub fn new<B: Into<BString>>(orig_id: B, offset: usize) -> Self {
+        Self {
+            orig_id: orig_id.into(),
+            offset,
+        }
+    }
+
+    /// Parse the value of an OI tag, i.e. the part after `OI:Z:`.
+    /// The original ID may itself contain colons, so the offset is
+    /// taken from the last colon.
+    pub fn parse(value: &[u8]) -> Option<Self> {
+        let split = value.rfind_byte(b':')?;
+        let offset = value[split + 1..].to_str().ok()?.parse().ok()?;
+        Some(Self::new(&value[..split], offset))
+    }
+
+    pub fn to_opt_field(&self) -> OptField {
+        OptField {
+            tag: *PROVENANCE_TAG,
+            value: OptFieldVal::Z(self.to_string().into()),
+        }
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.orig_id, self.offset)
+    }
+}
+
+/// Read the provenance tags of all segments in a GFA with integer
+/// segment IDs, without parsing the rest of the graph. Segments
+/// without an OI tag aren't included. The GFA may be compressed.
+pub fn load_provenance<P: AsRef<Path>>(
+    path: P,
+) -> io::Result<FnvHashMap<usize, Provenance>> {
+    let input = compression::open_input(path)?;
+    let mut provenance = FnvHashMap::default();
+
+    for line in BufReader::new(input).byte_lines() {
+        let line = line?;
+        let mut fields = line.split_str("\t");
+        if fields.next() != Some(b"S") {
+            continue;
+        }
+
+        let id = fields
+            .next()
+            .and_then(|id| id.to_str().ok())
+            .and_then(|id| id.parse::<usize>().ok());
+
+        let orig = fields
+            .skip(1)
+            .find_map(|field| field.strip_prefix(b"OI:Z:"))
+            .and_then(Provenance::parse);
+
+        if let (Some(id), Some(orig)) = (id, orig) {
+            provenance.insert(id, orig);
+        }
+    }
+
+    Ok(provenance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_provenance_values() {
+        assert_eq!(
+            Provenance::parse(b"s12:40"),
+            Some(Provenance::new("s12", 40))
+        );
+        assert_eq!(
+            Provenance::parse(b"chr1:100:0"),
+            Some(Provenance::new("chr1:100", 0))
+        );
+        assert_eq!(Provenance::parse(b"s12"), None);
+        assert_eq!(Provenance::parse(b"s12:x"), None);
+
+        let prov = Provenance::new("s12", 40);
+        assert_eq!(Provenance::parse(prov.to_string().as_bytes()), Some(prov));
+    }
+}
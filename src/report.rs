@@ -0,0 +1,149 @@
+/// Builds a single, self-contained HTML document made up of
+/// headings, tables, and simple SVG bar charts. No external scripts
+/// or stylesheets are referenced, so the output can be shared as a
+/// single file.
+pub struct HtmlReport {
+    title: String,
+    body: String,
+}
+
+static STYLE: &str = r#"body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1.5em; }
+th, td { border: 1px solid #bbb; padding: 0.2em 0.6em; text-align: right; }
+th { background: #eee; }
+td:first-child, th:first-child { text-align: left; }
+svg { margin-bottom: 1.5em; }
+svg text { font-size: 11px; }"#;
+
+const CHART_WIDTH: usize = 600;
+const BAR_HEIGHT: usize = 14;
+const LABEL_WIDTH: usize = 160;
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl HtmlReport {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            body: String::new(),
+        }
+    }
+
+    pub fn heading(&mut self, text: &str) {
+        self.body
+            .push_str(&format!("<h2>{}</h2>\n", escape_html(text)));
+    }
+
+    pub fn paragraph(&mut self, text: &str) {
+        self.body
+            .push_str(&format!("<p>{}</p>\n", escape_html(text)));
+    }
+
+    pub fn table(&mut self, headers: &[&str], rows: &[Vec<String>]) {
+        self.body.push_str("<table>\n<tr>");
+        for header in headers {
+            self.body
+                .push_str(&format!("<th>{}</th>", escape_html(header)));
+        }
+        self.body.push_str("</tr>\n");
+        for row in rows {
+            self.body.push_str("<tr>");
+            for cell in row {
+                self.body
+                    .push_str(&format!("<td>{}</td>", escape_html(cell)));
+            }
+            self.body.push_str("</tr>\n");
+        }
+        self.body.push_str("</table>\n");
+    }
+
+    /// Add a horizontal bar chart, with one bar per (label, value)
+    /// pair, scaled to the largest value
+    pub fn bar_chart(&mut self, title: &str, bars: &[(String, f64)]) {
+        if bars.is_empty() {
+            return;
+        }
+
+        let max = bars.iter().map(|(_, v)| *v).fold(0.0, f64::max);
+        let bar_space = (CHART_WIDTH - LABEL_WIDTH - 80) as f64;
+        let height = BAR_HEIGHT * (bars.len() + 2);
+
+        self.body.push_str(&format!(
+            "<h3>{}</h3>\n<svg width=\"{}\" height=\"{}\" \
+             xmlns=\"http://www.w3.org/2000/svg\">\n",
+            escape_html(title),
+            CHART_WIDTH,
+            height
+        ));
+
+        for (ix, (label, value)) in bars.iter().enumerate() {
+            let y = BAR_HEIGHT * ix;
+            let width = if max > 0.0 {
+                (value / max * bar_space).round() as usize
+            } else {
+                0
+            };
+            self.body.push_str(&format!(
+                "<text x=\"0\" y=\"{}\">{}</text>\
+                 <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+                 fill=\"#4a7ab5\"/>\
+                 <text x=\"{}\" y=\"{}\">{}</text>\n",
+                y + BAR_HEIGHT - 3,
+                escape_html(label),
+                LABEL_WIDTH,
+                y + 1,
+                width,
+                BAR_HEIGHT - 2,
+                LABEL_WIDTH + width + 4,
+                y + BAR_HEIGHT - 3,
+                value
+            ));
+        }
+
+        self.body.push_str("</svg>\n");
+    }
+
+    pub fn finish(self) -> String {
+        let title = escape_html(&self.title);
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n\
+             <h1>{}</h1>\n{}</body>\n</html>\n",
+            title, STYLE, title, self.body
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_markup() {
+        assert_eq!(
+            escape_html("<a href=\"x\">&</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn table_rows_escaped() {
+        let mut report = HtmlReport::new("test");
+        report.table(&["name", "len"], &[vec!["p<1>".into(), "3".into()]]);
+        let html = report.finish();
+        assert!(html.contains("<td>p&lt;1&gt;</td><td>3</td>"));
+    }
+}
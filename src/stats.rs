@@ -0,0 +1,494 @@
+use bstr::BString;
+use fnv::FnvHashMap;
+use serde::Serialize;
+use std::hash::Hash;
+
+use gfa::{
+    gfa::{SegmentId, GFA},
+    optfields::OptFields,
+};
+
+/// Counts of the different lines in a GFA, and the total length of
+/// its segment sequences
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct GraphSummary {
+    pub segments: usize,
+    pub links: usize,
+    pub containments: usize,
+    pub paths: usize,
+    pub total_bp: usize,
+}
+
+pub fn graph_summary<N: SegmentId, T: OptFields>(
+    gfa: &GFA<N, T>,
+) -> GraphSummary {
+    let total_bp = gfa.segments.iter().map(|s| s.sequence.len()).sum();
+    GraphSummary {
+        segments: gfa.segments.len(),
+        links: gfa.links.len(),
+        containments: gfa.containments.len(),
+        paths: gfa.paths.len(),
+        total_bp,
+    }
+}
+
+/// Map from segment ID to the length of its sequence
+pub fn segment_lengths<T: OptFields>(
+    gfa: &GFA<usize, T>,
+) -> FnvHashMap<usize, usize> {
+    gfa.segments
+        .iter()
+        .map(|seg| (seg.name, seg.sequence.len()))
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathSummary {
+    pub name: BString,
+    pub steps: usize,
+    pub length: usize,
+    pub first: Option<usize>,
+    pub last: Option<usize>,
+    pub reverse_steps: usize,
+}
+
+impl PathSummary {
+    pub fn reverse_fraction(&self) -> f64 {
+        if self.steps == 0 {
+            0.0
+        } else {
+            self.reverse_steps as f64 / self.steps as f64
+        }
+    }
+}
+
+/// Summarize each path in the GFA, in the order they appear in the
+/// file
+pub fn path_summaries<T: OptFields>(gfa: &GFA<usize, T>) -> Vec<PathSummary> {
+    let seg_lens = segment_lengths(gfa);
+
+    gfa.paths
+        .iter()
+        .map(|path| {
+            let mut summary = PathSummary {
+                name: path.path_name.clone().into(),
+                steps: 0,
+                length: 0,
+                first: None,
+                last: None,
+                reverse_steps: 0,
+            };
+
+            for (seg, orient) in path.iter() {
+                summary.steps += 1;
+                summary.length += seg_lens.get(&seg).copied().unwrap_or(0);
+                if summary.first.is_none() {
+                    summary.first = Some(seg);
+                }
+                summary.last = Some(seg);
+                if orient.is_reverse() {
+                    summary.reverse_steps += 1;
+                }
+            }
+
+            summary
+        })
+        .collect()
+}
+
+/// A weakly connected component, i.e. a set of segments that are
+/// connected by links when ignoring orientation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Component {
+    pub segments: Vec<usize>,
+    pub total_bp: usize,
+}
+
+fn find_root(parents: &mut [usize], mut ix: usize) -> usize {
+    while parents[ix] != ix {
+        parents[ix] = parents[parents[ix]];
+        ix = parents[ix];
+    }
+    ix
+}
+
+/// The root of the weakly connected component of each segment, by
+/// its index in `gfa.segments`, using the links and containments as
+/// edges, ignoring orientation. Segments in the same component have
+/// the same root.
+pub(crate) fn component_roots<N, T>(gfa: &GFA<N, T>) -> Vec<usize>
+where
+    N: Eq + Hash,
+    T: OptFields,
+{
+    let seg_ix: FnvHashMap<&N, usize> = gfa
+        .segments
+        .iter()
+        .enumerate()
+        .map(|(ix, seg)| (&seg.name, ix))
+        .collect();
+
+    let mut parents: Vec<usize> = (0..gfa.segments.len()).collect();
+
+    let edges = gfa
+        .links
+        .iter()
+        .map(|l| (&l.from_segment, &l.to_segment))
+        .chain(
+            gfa.containments
+                .iter()
+                .map(|c| (&c.container_name, &c.contained_name)),
+        );
+
+    for (from, to) in edges {
+        if let (Some(&a), Some(&b)) = (seg_ix.get(from), seg_ix.get(to)) {
+            let a = find_root(&mut parents, a);
+            let b = find_root(&mut parents, b);
+            if a != b {
+                parents[a.max(b)] = a.min(b);
+            }
+        }
+    }
+
+    (0..parents.len())
+        .map(|ix| find_root(&mut parents, ix))
+        .collect()
+}
+
+/// Find the weakly connected components of the graph, using the
+/// links and containments as edges. The components are sorted by
+/// decreasing number of segments, and the segments in each
+/// component are sorted by ID.
+pub fn weakly_connected_components<T: OptFields>(
+    gfa: &GFA<usize, T>,
+) -> Vec<Component> {
+    let seg_lens = segment_lengths(gfa);
+    let roots = component_roots(gfa);
+
+    let mut by_root: FnvHashMap<usize, Component> = FnvHashMap::default();
+
+    for (seg, &root) in gfa.segments.iter().zip(roots.iter()) {
+        let component = by_root.entry(root).or_insert_with(|| Component {
+            segments: Vec::new(),
+            total_bp: 0,
+        });
+        component.segments.push(seg.name);
+        component.total_bp += seg_lens.get(&seg.name).copied().unwrap_or(0);
+    }
+
+    let mut components: Vec<Component> = by_root
+        .into_iter()
+        .map(|(_, mut comp)| {
+            comp.segments.sort();
+            comp
+        })
+        .collect();
+
+    components.sort_by(|c0, c1| {
+        c1.segments
+            .len()
+            .cmp(&c0.segments.len())
+            .then(c0.segments.cmp(&c1.segments))
+    });
+
+    components
+}
+
+/// Base composition of a sequence, or of a collection of sequences
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Composition {
+    pub length: usize,
+    pub gc: usize,
+    pub at: usize,
+    pub n: usize,
+    /// Longest run of a single base. When compositions are merged,
+    /// this is the longest run within any one of the sequences.
+    pub max_homopolymer: usize,
+}
+
+impl Composition {
+    pub fn from_sequence(seq: &[u8]) -> Self {
+        let mut comp = Composition {
+            length: seq.len(),
+            ..Composition::default()
+        };
+
+        let mut run = 0;
+        let mut prev = None;
+
+        for &base in seq {
+            let base = base.to_ascii_uppercase();
+            match base {
+                b'G' | b'C' | b'S' => comp.gc += 1,
+                b'A' | b'T' | b'W' => comp.at += 1,
+                b'N' => comp.n += 1,
+                _ => (),
+            }
+
+            if Some(base) == prev {
+                run += 1;
+            } else {
+                run = 1;
+                prev = Some(base);
+            }
+            comp.max_homopolymer = comp.max_homopolymer.max(run);
+        }
+
+        comp
+    }
+
+    pub fn merge(&mut self, other: &Composition) {
+        self.length += other.length;
+        self.gc += other.gc;
+        self.at += other.at;
+        self.n += other.n;
+        self.max_homopolymer = self.max_homopolymer.max(other.max_homopolymer);
+    }
+
+    /// GC content as a fraction of the bases that are either G/C or
+    /// A/T, i.e. ignoring N and other ambiguous bases
+    pub fn gc_fraction(&self) -> f64 {
+        let called = self.gc + self.at;
+        if called == 0 {
+            0.0
+        } else {
+            self.gc as f64 / called as f64
+        }
+    }
+
+    pub fn n_fraction(&self) -> f64 {
+        if self.length == 0 {
+            0.0
+        } else {
+            self.n as f64 / self.length as f64
+        }
+    }
+}
+
+/// Map from segment ID to the composition of its sequence
+pub fn segment_compositions<T: OptFields>(
+    gfa: &GFA<usize, T>,
+) -> FnvHashMap<usize, Composition> {
+    gfa.segments
+        .iter()
+        .map(|seg| (seg.name, Composition::from_sequence(&seg.sequence)))
+        .collect()
+}
+
+/// Composition of each path, in the order they appear in the GFA.
+/// Segments are counted once per step, so the result describes the
+/// sequence spelled out by the path.
+pub fn path_compositions<T: OptFields>(
+    gfa: &GFA<usize, T>,
+    segments: &FnvHashMap<usize, Composition>,
+) -> Vec<(BString, Composition)> {
+    gfa.paths
+        .iter()
+        .map(|path| {
+            let mut comp = Composition::default();
+            for (seg, _) in path.iter() {
+                if let Some(seg_comp) = segments.get(&seg) {
+                    comp.merge(seg_comp);
+                }
+            }
+            (path.path_name.clone().into(), comp)
+        })
+        .collect()
+}
+
+/// The composition of each window of `window` bp, every `step` bp,
+/// along a sequence, as (start, end, composition), with end-exclusive
+/// ranges
+pub fn window_compositions(
+    seq: &[u8],
+    window: usize,
+    step: usize,
+) -> Vec<(usize, usize, Composition)> {
+    crate::util::window_ranges(seq.len(), window, step)
+        .into_iter()
+        .map(|(start, end)| {
+            (start, end, Composition::from_sequence(&seq[start..end]))
+        })
+        .collect()
+}
+
+/// Whether a base is A, C, G, or T, in either case
+pub fn is_acgt(base: u8) -> bool {
+    matches!(base.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T')
+}
+
+/// The bases of a sequence that aren't A, C, G, or T
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AmbiguousBases {
+    /// The number of N bases
+    pub n: usize,
+    /// The number of other bases, e.g. IUPAC codes such as R or Y
+    pub other: usize,
+}
+
+impl AmbiguousBases {
+    pub fn from_sequence(seq: &[u8]) -> Self {
+        let mut bases = AmbiguousBases::default();
+        for &base in seq.iter().filter(|&&base| !is_acgt(base)) {
+            if base.to_ascii_uppercase() == b'N' {
+                bases.n += 1;
+            } else {
+                bases.other += 1;
+            }
+        }
+        bases
+    }
+
+    pub fn total(&self) -> usize {
+        self.n + self.other
+    }
+}
+
+/// The runs of bases in a sequence that aren't A, C, G, or T, as
+/// end-exclusive ranges
+pub fn ambiguous_runs(seq: &[u8]) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for (ix, &base) in seq.iter().enumerate() {
+        if is_acgt(base) {
+            continue;
+        }
+        match runs.last_mut() {
+            Some(run) if run.1 == ix => run.1 = ix + 1,
+            _ => runs.push((ix, ix + 1)),
+        }
+    }
+    runs
+}
+
+/// Count how many times each value occurs, returning the
+/// (value, count) pairs sorted by value
+pub fn histogram<I>(values: I) -> Vec<(usize, usize)>
+where
+    I: IntoIterator<Item = usize>,
+{
+    let mut counts: FnvHashMap<usize, usize> = FnvHashMap::default();
+    for v in values {
+        *counts.entry(v).or_default() += 1;
+    }
+    let mut hist: Vec<_> = counts.into_iter().collect();
+    hist.sort();
+    hist
+}
+
+/// Summary statistics of a set of sequence lengths
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LengthSummary {
+    pub count: usize,
+    pub total: usize,
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+    /// The middle length, or the lower of the two middle lengths if
+    /// there's an even number of them
+    pub median: usize,
+    /// The length such that the sequences at least that long make up
+    /// at least half of the total
+    pub n50: usize,
+    /// The number of sequences of length 1, e.g. the segments of
+    /// SNPs
+    pub single_bp: usize,
+}
+
+/// Summarize a set of lengths, or return `None` if it's empty
+pub fn length_summary(lengths: &[usize]) -> Option<LengthSummary> {
+    let mut sorted = lengths.to_vec();
+    sorted.sort_unstable();
+    let min = *sorted.first()?;
+    let max = *sorted.last()?;
+    let total: usize = sorted.iter().sum();
+
+    let mut n50 = 0;
+    let mut cumulative = 0;
+    for &len in sorted.iter().rev() {
+        cumulative += len;
+        if 2 * cumulative >= total {
+            n50 = len;
+            break;
+        }
+    }
+
+    Some(LengthSummary {
+        count: sorted.len(),
+        total,
+        min,
+        max,
+        mean: total as f64 / sorted.len() as f64,
+        median: sorted[(sorted.len() - 1) / 2],
+        n50,
+        single_bp: sorted.iter().filter(|&&len| len == 1).count(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gc_windows() {
+        let seq = b"GGGGAAAANNCC";
+        let windows: Vec<_> = window_compositions(seq, 4, 4)
+            .into_iter()
+            .map(|(start, end, comp)| (start, end, comp.gc, comp.n))
+            .collect();
+        assert_eq!(windows, vec![(0, 4, 4, 0), (4, 8, 0, 0), (8, 12, 2, 2)]);
+
+        let windows: Vec<_> = window_compositions(seq, 8, 3)
+            .into_iter()
+            .map(|(start, end, comp)| (start, end, comp.gc, comp.at))
+            .collect();
+        assert_eq!(windows, vec![(0, 8, 4, 4), (3, 11, 2, 4), (6, 12, 2, 2)]);
+        assert!(window_compositions(b"", 8, 3).is_empty());
+    }
+
+    #[test]
+    fn ambiguous_bases() {
+        let seq = b"ACNNgtRYNa";
+        assert_eq!(
+            AmbiguousBases::from_sequence(seq),
+            AmbiguousBases { n: 3, other: 2 }
+        );
+        assert_eq!(ambiguous_runs(seq), vec![(2, 4), (6, 9)]);
+        assert!(ambiguous_runs(b"acgtACGT").is_empty());
+    }
+
+    #[test]
+    fn length_summaries() {
+        let summary = length_summary(&[1, 10, 1, 4, 1, 3]).unwrap();
+        assert_eq!(summary.count, 6);
+        assert_eq!(summary.total, 20);
+        assert_eq!((summary.min, summary.max), (1, 10));
+        assert!((summary.mean - 20.0 / 6.0).abs() < 1e-9);
+        assert_eq!(summary.median, 1);
+        assert_eq!(summary.n50, 10);
+        assert_eq!(summary.single_bp, 3);
+
+        let summary = length_summary(&[2, 3, 4]).unwrap();
+        assert_eq!(summary.median, 3);
+        assert_eq!(summary.n50, 3);
+        assert_eq!(summary.single_bp, 0);
+
+        assert!(length_summary(&[]).is_none());
+    }
+
+    #[test]
+    fn sequence_composition() {
+        let comp = Composition::from_sequence(b"ACGGGnNTa");
+        assert_eq!(comp.length, 9);
+        assert_eq!(comp.gc, 4);
+        assert_eq!(comp.at, 3);
+        assert_eq!(comp.n, 2);
+        assert_eq!(comp.max_homopolymer, 3);
+        assert!((comp.gc_fraction() - 4.0 / 7.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn histogram_counts_sorted() {
+        let hist = histogram(vec![3, 1, 3, 2, 3, 1]);
+        assert_eq!(hist, vec![(1, 2), (2, 1), (3, 3)]);
+    }
+}
@@ -1,7 +1,29 @@
-use gfa::gfa::GFA;
-use gfa::optfields::OptFields;
+use gfa::gafpaf::{GAFPath, GAF};
+use gfa::gfa::{
+    name_conversion::NameMap, Link, Orientation, Segment, SegmentId, GFA,
+};
+use gfa::optfields::{OptFieldVal, OptFields};
 
-use std::collections::HashSet;
+use bstr::{io::*, ByteSlice};
+use fnv::FnvHashMap;
+use regex::bytes::Regex;
+
+use crate::{
+    clean::canonical_edge,
+    gaf_convert::SegmentIndex,
+    util::{
+        add_program_field, pansn_sample, path_segment_names, split_path,
+        SegmentName,
+    },
+};
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashSet, VecDeque},
+    fmt,
+    io::{self, BufReader, Read, Write},
+    str::FromStr,
+};
 
 macro_rules! filtered {
     ($coll:expr, $pred:expr) => {
@@ -12,35 +34,94 @@ macro_rules! filtered {
 /// Build a new GFA consisting of subgraphs of the given GFA that only
 /// include segments that are in the provided paths. Paths are to be
 /// provided as a slice of path names
-pub fn paths_new_subgraph<T: OptFields + Clone>(
+pub fn paths_new_subgraph<N: SegmentName, T: OptFields + Clone>(
+    gfa: &GFA<N, T>,
+    paths: &[Vec<u8>],
+) -> GFA<N, T> {
+    let segment_names = path_segments(gfa, paths);
+    paths_segments_subgraph(gfa, paths, &segment_names)
+}
+
+/// The names of the segments in the given paths
+pub fn path_segments<N: SegmentName, T: OptFields>(
+    gfa: &GFA<N, T>,
+    paths: &[Vec<u8>],
+) -> Vec<N> {
+    let path_names: HashSet<&[u8]> = paths.iter().map(|p| p.as_ref()).collect();
+
+    let mut seen: HashSet<N> = HashSet::new();
+    let mut names = Vec::new();
+    for path in gfa.paths.iter() {
+        if !path_names.contains(path.path_name.as_slice()) {
+            continue;
+        }
+        for (seg, _) in N::path_steps(path) {
+            if seen.insert(seg.clone()) {
+                names.push(seg);
+            }
+        }
+    }
+    names
+}
+
+/// The names of the paths that match a regex, which must match the
+/// whole name, e.g. `HG002#.*` for every haplotype of a PanSN sample
+pub fn matching_paths<T: OptFields>(
     gfa: &GFA<Vec<u8>, T>,
+    pattern: &str,
+) -> Result<Vec<Vec<u8>>, regex::Error> {
+    let regex = Regex::new(&format!("^(?:{})$", pattern))?;
+    Ok(gfa
+        .paths
+        .iter()
+        .filter(|p| regex.is_match(&p.path_name))
+        .map(|p| p.path_name.to_vec())
+        .collect())
+}
+
+/// The names of the paths that belong to any of the given PanSN
+/// samples, in the order of the GFA
+pub fn sample_paths<N: SegmentId, T: OptFields>(
+    gfa: &GFA<N, T>,
+    samples: &[Vec<u8>],
+) -> Vec<Vec<u8>> {
+    let samples: HashSet<&[u8]> = samples.iter().map(|s| s.as_ref()).collect();
+    gfa.paths
+        .iter()
+        .filter(|p| {
+            pansn_sample(&p.path_name).map_or(false, |s| samples.contains(s))
+        })
+        .map(|p| p.path_name.to_vec())
+        .collect()
+}
+
+/// Build a subgraph with the given paths, and the given segments,
+/// which should include the segments of the paths
+pub fn paths_segments_subgraph<N: SegmentName, T: OptFields + Clone>(
+    gfa: &GFA<N, T>,
     paths: &[Vec<u8>],
-) -> GFA<Vec<u8>, T> {
+    segment_names: &[N],
+) -> GFA<N, T> {
     let path_names: HashSet<&[u8]> = paths.iter().map(|p| p.as_ref()).collect();
 
     // Filter out the paths in the GFA we don't want
     let paths: Vec<_> =
         filtered!(gfa.paths, |p| path_names.contains(p.path_name.as_slice()));
 
-    // Set of the segments in the paths we're keeping
-    let segment_names: HashSet<&[u8]> = paths
-        .iter()
-        .flat_map(|path| path.iter().map(|(seg, _)| seg.as_ref()))
-        .collect();
+    let segment_names: HashSet<&N> = segment_names.iter().collect();
 
     // Filter out the segments in the GFA we don't want
-    let segments =
-        filtered!(gfa.segments, |s| segment_names.contains(s.name.as_slice()));
+    let segments = filtered!(gfa.segments, |s| segment_names.contains(&s.name));
 
     // Filter out the links in the GFA we don't want
     let links = filtered!(&gfa.links, |l| {
-        segment_names.contains(l.from_segment.as_slice())
-            && segment_names.contains(l.to_segment.as_slice())
+        segment_names.contains(&l.from_segment)
+            && segment_names.contains(&l.to_segment)
     });
 
     let containments = filtered!(&gfa.containments, |l| {
-        segment_names.contains(l.container_name.as_slice())
-            && segment_names.contains(l.contained_name.as_slice())
+        segment_names.contains(&l.container_name)
+            && segment_names.contains(&l.contained_name)
     });
 
     GFA {
@@ -52,31 +133,1028 @@ pub fn paths_new_subgraph<T: OptFields + Clone>(
     }
 }
 
-/// Returns a subgraph GFA that only contains elements with the
-/// provided segment names
-pub fn segments_subgraph<T: OptFields + Clone>(
+/// Replace each connected region of segments outside a subgraph that
+/// is linked to it with a stub segment, with the sequence `N`, so
+/// that the borders of the subgraph keep their topology. The links
+/// that left the subgraph are replaced by links to the stubs, with an
+/// unknown overlap. Stubs are named `stub1`, `stub2`, etc., with `_`
+/// appended if the name is taken. Returns the number of stubs added.
+pub fn add_stub_segments<T: OptFields + Default>(
     gfa: &GFA<Vec<u8>, T>,
-    segment_names: &[Vec<u8>],
-) -> GFA<Vec<u8>, T> {
-    let segment_names: HashSet<&[u8]> =
-        segment_names.iter().map(|s| s.as_ref()).collect();
+    subgraph: &mut GFA<Vec<u8>, T>,
+) -> usize {
+    let kept: HashSet<&[u8]> = subgraph
+        .segments
+        .iter()
+        .map(|s| s.name.as_slice())
+        .collect();
+    let names: HashSet<&[u8]> =
+        gfa.segments.iter().map(|s| s.name.as_slice()).collect();
+    let adjacency = Adjacency::new(gfa);
+
+    // The region of each excluded segment that's been reached from
+    // the subgraph
+    let mut regions: FnvHashMap<&[u8], usize> = FnvHashMap::default();
+    let mut stub_names: Vec<Vec<u8>> = Vec::new();
+    let mut added_links = HashSet::new();
+    let mut stub_links = Vec::new();
+
+    for link in gfa.links.iter() {
+        let from = link.from_segment.as_slice();
+        let to = link.to_segment.as_slice();
+        let outside = match (kept.contains(from), kept.contains(to)) {
+            (true, false) => to,
+            (false, true) => from,
+            _ => continue,
+        };
+
+        let region = match regions.get(outside) {
+            Some(&region) => region,
+            None => {
+                let region = stub_names.len();
+                let mut queue = VecDeque::new();
+                regions.insert(outside, region);
+                queue.push_back(outside);
+                while let Some(seg) = queue.pop_front() {
+                    for &next in adjacency.neighbors(seg) {
+                        if !kept.contains(next) && !regions.contains_key(next) {
+                            regions.insert(next, region);
+                            queue.push_back(next);
+                        }
+                    }
+                }
+
+                let mut name = format!("stub{}", region + 1).into_bytes();
+                while names.contains(name.as_slice()) {
+                    name.push(b'_');
+                }
+                stub_names.push(name);
+                region
+            }
+        };
+
+        let stub = stub_names[region].clone();
+        let (from, to) = if outside == from {
+            (stub, to.to_vec())
+        } else {
+            (from.to_vec(), stub)
+        };
+        let key = canonical_edge(
+            &from,
+            link.from_orient.is_reverse(),
+            &to,
+            link.to_orient.is_reverse(),
+        );
+        let key = (key.0.to_vec(), key.1, key.2.to_vec(), key.3);
+        if added_links.insert(key) {
+            stub_links.push(Link {
+                from_segment: from,
+                from_orient: link.from_orient,
+                to_segment: to,
+                to_orient: link.to_orient,
+                overlap: "*".into(),
+                optional: T::default(),
+            });
+        }
+    }
+
+    let stubs = stub_names.len();
+    subgraph
+        .segments
+        .extend(stub_names.into_iter().map(|name| Segment {
+            name,
+            sequence: "N".into(),
+            optional: T::default(),
+        }));
+    subgraph.links.extend(stub_links);
+    stubs
+}
+
+/// Write the sequence of each segment as a FASTA record named after
+/// the segment, one line per sequence, followed by the sequences
+/// spelled by the paths, named after the paths, if `paths` is set.
+/// Path sequences are the concatenation of their oriented segments,
+/// without accounting for overlaps.
+pub fn write_fasta<T: OptFields, W: Write>(
+    gfa: &GFA<Vec<u8>, T>,
+    paths: bool,
+    mut out: W,
+) -> io::Result<()> {
+    for seg in gfa.segments.iter() {
+        out.write_all(b">")?;
+        out.write_all(&seg.name)?;
+        out.write_all(b"\n")?;
+        out.write_all(&seg.sequence)?;
+        out.write_all(b"\n")?;
+    }
+
+    if paths {
+        let sequences: FnvHashMap<&[u8], &[u8]> = gfa
+            .segments
+            .iter()
+            .map(|seg| (seg.name.as_slice(), seg.sequence.as_slice()))
+            .collect();
+
+        for path in gfa.paths.iter() {
+            out.write_all(b">")?;
+            out.write_all(&path.path_name)?;
+            out.write_all(b"\n")?;
+            for (seg, orient) in path.iter() {
+                let seg: &[u8] = seg.as_ref();
+                let seq = sequences.get(seg).copied().unwrap_or(&[]);
+                if orient.is_reverse() {
+                    let rev: Vec<u8> =
+                        handlegraph::util::dna::rev_comp_iter(seq).collect();
+                    out.write_all(&rev)?;
+                } else {
+                    out.write_all(seq)?;
+                }
+            }
+            out.write_all(b"\n")?;
+        }
+    }
+
+    out.flush()
+}
+
+/// The number of records of each kind, and of bases, that a subgraph
+/// retained from the original graph
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SubgraphSummary {
+    pub segments: (usize, usize),
+    pub links: (usize, usize),
+    /// Paths with at least one step in the subgraph
+    pub paths: (usize, usize),
+    /// The paths and path fragments in the subgraph
+    pub path_fragments: usize,
+    pub containments: (usize, usize),
+    pub bases: (usize, usize),
+}
+
+impl SubgraphSummary {
+    /// Compare a subgraph to the graph it was extracted from. Each
+    /// pair of counts is the number retained and the total.
+    pub fn new<N: SegmentId, T: OptFields>(
+        gfa: &GFA<N, T>,
+        subgraph: &GFA<N, T>,
+    ) -> Self {
+        let bases = |gfa: &GFA<N, T>| -> usize {
+            gfa.segments.iter().map(|s| s.sequence.len()).sum()
+        };
+
+        // Path fragments are named after the path they're from, with
+        // a :N suffix
+        let path_names: HashSet<&[u8]> =
+            gfa.paths.iter().map(|p| p.path_name.as_slice()).collect();
+        let retained_paths: HashSet<&[u8]> = subgraph
+            .paths
+            .iter()
+            .filter_map(|p| {
+                let name = p.path_name.as_slice();
+                if path_names.contains(name) {
+                    return Some(name);
+                }
+                let colon = name.rfind_byte(b':')?;
+                path_names.get(&name[..colon]).copied()
+            })
+            .collect();
+
+        SubgraphSummary {
+            segments: (subgraph.segments.len(), gfa.segments.len()),
+            links: (subgraph.links.len(), gfa.links.len()),
+            paths: (retained_paths.len(), gfa.paths.len()),
+            path_fragments: subgraph.paths.len(),
+            containments: (subgraph.containments.len(), gfa.containments.len()),
+            bases: (bases(subgraph), bases(gfa)),
+        }
+    }
+
+    /// Write the summary as tab-separated lines of record kind,
+    /// retained, and dropped counts, after a header
+    pub fn write_tsv<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "records\tretained\tdropped")?;
+        let counts = [
+            ("segments", self.segments),
+            ("links", self.links),
+            ("paths", self.paths),
+            ("containments", self.containments),
+            ("bp", self.bases),
+        ];
+        for (kind, (retained, total)) in counts.iter() {
+            writeln!(out, "{}\t{}\t{}", kind, retained, total - retained)?;
+        }
+        writeln!(out, "path fragments\t{}\t-", self.path_fragments)?;
+        out.flush()
+    }
+}
+
+/// Rename the segments of a GFA to dense integer IDs, in the order of
+/// the segments, and update the links, containments, and paths to
+/// match. The IDs are assigned by a `NameMap`, as by `id-convert
+/// --to-int`, which is returned so that it can be saved, and used to
+/// restore the names.
+pub fn renumber_segments<T: OptFields>(gfa: &mut GFA<Vec<u8>, T>) -> NameMap {
+    let name_map = NameMap::build_from_gfa(gfa);
+
+    let new_name = |name: &[u8]| -> Vec<u8> {
+        match name_map.map_name(name) {
+            Some(id) => id.to_string().into_bytes(),
+            None => name.to_vec(),
+        }
+    };
+
+    for seg in gfa.segments.iter_mut() {
+        seg.name = new_name(&seg.name);
+    }
+
+    for link in gfa.links.iter_mut() {
+        link.from_segment = new_name(&link.from_segment);
+        link.to_segment = new_name(&link.to_segment);
+    }
+
+    for cont in gfa.containments.iter_mut() {
+        cont.container_name = new_name(&cont.container_name);
+        cont.contained_name = new_name(&cont.contained_name);
+    }
+
+    for path in gfa.paths.iter_mut() {
+        let steps: Vec<(Vec<u8>, Orientation)> = path
+            .iter()
+            .map(|(seg, orient)| {
+                let seg: &[u8] = seg.as_ref();
+                (new_name(seg), orient)
+            })
+            .collect();
+        path.segment_names = path_segment_names(
+            steps.iter().map(|(seg, orient)| (seg.as_slice(), *orient)),
+        );
+    }
+
+    name_map
+}
+
+/// A segment name and whether it's traversed in reverse
+type Handle<'a> = (&'a [u8], bool);
+
+/// The segments linked to each segment, in either direction and
+/// orientation, by name, and the segment lengths. The links are also
+/// stored by oriented segment, for traversals that follow the links
+/// in the direction they're read.
+pub struct Adjacency<'a> {
+    neighbors: FnvHashMap<&'a [u8], Vec<&'a [u8]>>,
+    edges: FnvHashMap<Handle<'a>, Vec<Handle<'a>>>,
+    lengths: FnvHashMap<&'a [u8], usize>,
+}
+
+impl<'a> Adjacency<'a> {
+    pub fn new<T: OptFields>(gfa: &'a GFA<Vec<u8>, T>) -> Self {
+        let mut neighbors: FnvHashMap<&[u8], Vec<&[u8]>> =
+            FnvHashMap::default();
+        let mut edges: FnvHashMap<Handle, Vec<Handle>> = FnvHashMap::default();
+        for link in gfa.links.iter() {
+            let from = link.from_segment.as_slice();
+            let to = link.to_segment.as_slice();
+            neighbors.entry(from).or_default().push(to);
+            if from != to {
+                neighbors.entry(to).or_default().push(from);
+            }
+
+            let from_rev = link.from_orient.is_reverse();
+            let to_rev = link.to_orient.is_reverse();
+            edges
+                .entry((from, from_rev))
+                .or_default()
+                .push((to, to_rev));
+            edges
+                .entry((to, !to_rev))
+                .or_default()
+                .push((from, !from_rev));
+        }
+        let lengths = gfa
+            .segments
+            .iter()
+            .map(|seg| (seg.name.as_slice(), seg.sequence.len()))
+            .collect();
+        Self {
+            neighbors,
+            edges,
+            lengths,
+        }
+    }
+
+    fn edges(&self, handle: Handle<'a>) -> &[Handle<'a>] {
+        self.edges.get(&handle).map(|e| e.as_slice()).unwrap_or(&[])
+    }
+
+    /// The segments of the bubble between the segments `from` and
+    /// `to`, found by following the links out of one side of `from`
+    /// until they reach `to`. Both sides are tried, and the one with
+    /// fewer segments is used; a side is given up on if it reaches
+    /// more than `max_segments` segments. Returns `None` if neither
+    /// side reaches `to`, or `from` isn't in the graph.
+    pub fn bubble_segments(
+        &self,
+        from: &[u8],
+        to: &[u8],
+        max_segments: usize,
+    ) -> Option<Vec<Vec<u8>>> {
+        let (&from, _) = self.lengths.get_key_value(from)?;
+        let forward = self.enclosed_segments((from, false), to, max_segments);
+        let reverse = self.enclosed_segments((from, true), to, max_segments);
+        match (forward, reverse) {
+            (Some(fwd), Some(rev)) if rev.len() < fwd.len() => Some(rev),
+            (Some(fwd), _) => Some(fwd),
+            (None, rev) => rev,
+        }
+    }
+
+    fn enclosed_segments(
+        &self,
+        start: Handle<'a>,
+        end: &[u8],
+        max_segments: usize,
+    ) -> Option<Vec<Vec<u8>>> {
+        let mut seen_segments: HashSet<&[u8]> = HashSet::new();
+        seen_segments.insert(start.0);
+        let mut names = vec![start.0.to_vec()];
+
+        let mut seen_handles: HashSet<Handle<'a>> = HashSet::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+        let mut reached_end = false;
+
+        while let Some(handle) = queue.pop_front() {
+            for &next in self.edges(handle) {
+                if next.0 == start.0 {
+                    continue;
+                }
+                if seen_segments.insert(next.0) {
+                    names.push(next.0.to_vec());
+                    if names.len() > max_segments {
+                        return None;
+                    }
+                }
+                if next.0 == end {
+                    reached_end = true;
+                } else if seen_handles.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        if reached_end {
+            Some(names)
+        } else {
+            None
+        }
+    }
+
+    fn length(&self, name: &[u8]) -> usize {
+        self.lengths.get(name).copied().unwrap_or(0)
+    }
+
+    pub fn neighbors(&self, name: &[u8]) -> &[&'a [u8]] {
+        self.neighbors
+            .get(name)
+            .map(|n| n.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Add the segments up to `steps` links away from any of the
+    /// `names`. The result starts with the `names`, followed by the
+    /// added segments in order of their distance.
+    pub fn expand(&self, names: &[Vec<u8>], steps: usize) -> Vec<Vec<u8>> {
+        let mut seen: HashSet<&[u8]> = HashSet::new();
+        let mut result: Vec<Vec<u8>> = Vec::with_capacity(names.len());
+        for name in names {
+            if seen.insert(name.as_slice()) {
+                result.push(name.clone());
+            }
+        }
+
+        let mut frontier: Vec<&[u8]> =
+            result.iter().map(|n| n.as_slice()).collect();
+        let mut added: Vec<Vec<u8>> = Vec::new();
+
+        for _ in 0..steps {
+            let mut next: Vec<&[u8]> = Vec::new();
+            for name in frontier {
+                for &neighbor in self.neighbors(name) {
+                    if seen.insert(neighbor) {
+                        next.push(neighbor);
+                        added.push(neighbor.to_vec());
+                    }
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            frontier = next;
+        }
+
+        result.extend(added);
+        result
+    }
+
+    /// Add the segments that are less than `bp` bases away from any
+    /// of the `names`, counting the lengths of the segments between
+    /// them, so that at least `bp` bases are added in every direction
+    /// where the graph continues. The result starts with the `names`,
+    /// followed by the added segments in order of their distance.
+    pub fn expand_bp(&self, names: &[Vec<u8>], bp: usize) -> Vec<Vec<u8>> {
+        let mut result: Vec<Vec<u8>> = Vec::with_capacity(names.len());
+        let mut dist: FnvHashMap<&[u8], usize> = FnvHashMap::default();
+        for name in names {
+            if dist.insert(name.as_slice(), 0).is_none() {
+                result.push(name.clone());
+            }
+        }
+
+        // The neighbors of the selected segments are at distance 0,
+        // and the length of each added segment is added to the
+        // distance of its neighbors
+        let mut heap = BinaryHeap::new();
+        if bp > 0 {
+            for name in names {
+                for &neighbor in self.neighbors(name) {
+                    if !dist.contains_key(neighbor) {
+                        dist.insert(neighbor, 0);
+                        heap.push(Reverse((0, neighbor)));
+                    }
+                }
+            }
+        }
+
+        let mut added: Vec<Vec<u8>> = Vec::new();
+        while let Some(Reverse((d, name))) = heap.pop() {
+            if dist.get(name).copied() != Some(d) {
+                continue;
+            }
+            added.push(name.to_vec());
+
+            let next = d + self.length(name);
+            for &neighbor in self.neighbors(name) {
+                let shorter =
+                    dist.get(neighbor).map(|&n| next < n).unwrap_or(true);
+                if next < bp && shorter {
+                    dist.insert(neighbor, next);
+                    heap.push(Reverse((next, neighbor)));
+                }
+            }
+        }
+
+        result.extend(added);
+        result
+    }
+}
+
+/// A comparison in a `TagPredicate`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl TagOp {
+    fn holds(self, ord: Ordering) -> bool {
+        match self {
+            TagOp::Eq => ord == Ordering::Equal,
+            TagOp::Ne => ord != Ordering::Equal,
+            TagOp::Lt => ord == Ordering::Less,
+            TagOp::Le => ord != Ordering::Greater,
+            TagOp::Gt => ord == Ordering::Greater,
+            TagOp::Ge => ord != Ordering::Less,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            TagOp::Eq => "=",
+            TagOp::Ne => "!=",
+            TagOp::Lt => "<",
+            TagOp::Le => "<=",
+            TagOp::Gt => ">",
+            TagOp::Ge => ">=",
+        }
+    }
+}
+
+/// The value a tag is compared to, by the type of the tag
+#[derive(Debug, Clone, PartialEq)]
+pub enum TagValue {
+    Char(u8),
+    Int(i64),
+    Float(f64),
+    String(Vec<u8>),
+}
+
+/// A predicate on an optional field, given as `TAG:TYPE`, a
+/// comparison, and a value, e.g. `SR:i<=1` or `SN:Z=chr1`. The types
+/// are `i` and `f`, which can use any comparison, and `Z` and `A`,
+/// which can only use `=` and `!=`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagPredicate {
+    pub tag: [u8; 2],
+    pub op: TagOp,
+    pub value: TagValue,
+}
+
+impl TagPredicate {
+    /// Whether the fields have the tag, with a value of the right
+    /// type that satisfies the comparison. Integer fields can be
+    /// compared to floats.
+    pub fn matches<T: OptFields>(&self, fields: &T) -> bool {
+        let field = match fields.get_field(&self.tag) {
+            Some(field) => field,
+            None => return false,
+        };
+        let ord = match (&field.value, &self.value) {
+            (OptFieldVal::Int(x), TagValue::Int(y)) => x.cmp(y),
+            (OptFieldVal::Int(x), TagValue::Float(y)) => {
+                match (*x as f64).partial_cmp(y) {
+                    Some(ord) => ord,
+                    None => return false,
+                }
+            }
+            (OptFieldVal::Float(x), TagValue::Float(y)) => {
+                match (*x as f64).partial_cmp(y) {
+                    Some(ord) => ord,
+                    None => return false,
+                }
+            }
+            (OptFieldVal::Z(x), TagValue::String(y)) => {
+                x.as_slice().cmp(y.as_slice())
+            }
+            (OptFieldVal::A(x), TagValue::Char(y)) => x.cmp(y),
+            _ => return false,
+        };
+        self.op.holds(ord)
+    }
+}
+
+impl fmt::Display for TagPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tag = self.tag.as_bstr();
+        let op = self.op.as_str();
+        match &self.value {
+            TagValue::Char(c) => write!(f, "{}:A{}{}", tag, op, *c as char),
+            TagValue::Int(x) => write!(f, "{}:i{}{}", tag, op, x),
+            TagValue::Float(x) => write!(f, "{}:f{}{}", tag, op, x),
+            TagValue::String(s) => write!(f, "{}:Z{}{}", tag, op, s.as_bstr()),
+        }
+    }
+}
+
+impl FromStr for TagPredicate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || format!("expected e.g. SR:i<=1 or SN:Z=chr1, found {}", s);
+        let bytes = s.as_bytes();
+        if bytes.len() < 5 || bytes[2] != b':' || !s.is_char_boundary(4) {
+            return Err(invalid());
+        }
+        let tag = [bytes[0], bytes[1]];
+        let rest = &s[4..];
+
+        let ops = [
+            ("<=", TagOp::Le),
+            (">=", TagOp::Ge),
+            ("!=", TagOp::Ne),
+            ("=", TagOp::Eq),
+            ("<", TagOp::Lt),
+            (">", TagOp::Gt),
+        ];
+        let (op_str, op) = ops
+            .iter()
+            .find(|(op_str, _)| rest.starts_with(op_str))
+            .ok_or_else(invalid)?;
+        let value = &rest[op_str.len()..];
+
+        let value = match bytes[3] {
+            b'i' => TagValue::Int(value.parse().map_err(|_| invalid())?),
+            b'f' => TagValue::Float(value.parse().map_err(|_| invalid())?),
+            b'Z' => TagValue::String(value.as_bytes().to_vec()),
+            b'A' if value.len() == 1 => TagValue::Char(value.as_bytes()[0]),
+            _ => return Err(invalid()),
+        };
+        if let TagValue::String(_) | TagValue::Char(_) = value {
+            if *op != TagOp::Eq && *op != TagOp::Ne {
+                return Err(format!(
+                    "only = and != can be used with Z and A tags: {}",
+                    s
+                ));
+            }
+        }
+
+        Ok(TagPredicate {
+            tag,
+            op: *op,
+            value,
+        })
+    }
+}
+
+/// The names of the segments whose optional fields satisfy all of the
+/// predicates
+pub fn tag_segments<T: OptFields>(
+    gfa: &GFA<Vec<u8>, T>,
+    predicates: &[TagPredicate],
+) -> Vec<Vec<u8>> {
+    gfa.segments
+        .iter()
+        .filter(|seg| predicates.iter().all(|p| p.matches(&seg.optional)))
+        .map(|seg| seg.name.clone())
+        .collect()
+}
+
+/// The boundary segments of a bubble, given as `FROM,TO`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BubbleEnds {
+    pub from: Vec<u8>,
+    pub to: Vec<u8>,
+}
+
+impl fmt::Display for BubbleEnds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.from.as_bstr(), self.to.as_bstr())
+    }
+}
+
+impl FromStr for BubbleEnds {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut ends = s.splitn(2, ',');
+        match (ends.next(), ends.next()) {
+            (Some(from), Some(to)) if !from.is_empty() && !to.is_empty() => {
+                Ok(BubbleEnds {
+                    from: from.as_bytes().to_vec(),
+                    to: to.as_bytes().to_vec(),
+                })
+            }
+            _ => Err(format!("expected FROM,TO, found {}", s)),
+        }
+    }
+}
+
+/// An inclusive range of integer segment IDs, given as `START..END`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl NodeRange {
+    pub fn contains(&self, id: usize) -> bool {
+        self.start <= id && id <= self.end
+    }
+}
+
+impl fmt::Display for NodeRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+impl FromStr for NodeRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected a range START..END, found {}", s);
+        let mut bounds = s.splitn(2, "..");
+        let start = bounds.next().ok_or_else(invalid)?;
+        let end = bounds.next().ok_or_else(invalid)?;
+        let start: usize = start.trim().parse().map_err(|_| invalid())?;
+        let end: usize = end.trim().parse().map_err(|_| invalid())?;
+        if start > end {
+            return Err(format!("range start is after its end: {}", s));
+        }
+        Ok(NodeRange { start, end })
+    }
+}
+
+/// The names of the segments whose names are integers in the range.
+/// Segments with other names are skipped, and their number returned
+/// along with the names.
+pub fn node_range_segments<N: SegmentName, T: OptFields>(
+    gfa: &GFA<N, T>,
+    range: NodeRange,
+) -> (Vec<N>, usize) {
+    let mut non_numeric = 0;
+    let names = gfa
+        .segments
+        .iter()
+        .filter(|seg| {
+            let id = seg.name.numeric_id();
+            if id.is_none() {
+                non_numeric += 1;
+            }
+            id.map(|id| range.contains(id)).unwrap_or(false)
+        })
+        .map(|seg| seg.name.clone())
+        .collect();
+    (names, non_numeric)
+}
+
+/// An interval on a path, given as `PATH:START-END`, with 1-based
+/// inclusive coordinates like samtools regions. The interval is
+/// stored 0-based and end-exclusive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathInterval {
+    pub path: Vec<u8>,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl fmt::Display for PathInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}-{}", self.path.as_bstr(), self.start + 1, self.end)
+    }
+}
+
+impl FromStr for PathInterval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected PATH:START-END, found {}", s);
+        // Path names may contain colons, so the range is after the
+        // last one
+        let colon = s.rfind(':').ok_or_else(invalid)?;
+        let (path, range) = (&s[..colon], &s[colon + 1..]);
+        let mut bounds = range.splitn(2, '-');
+        let start = bounds.next().ok_or_else(invalid)?;
+        let end = bounds.next().ok_or_else(invalid)?;
+        let parse = |n: &str| n.replace(',', "").parse::<usize>();
+        let start = parse(start).map_err(|_| invalid())?;
+        let end = parse(end).map_err(|_| invalid())?;
+        if path.is_empty() || start == 0 || start > end {
+            return Err(invalid());
+        }
+        Ok(PathInterval {
+            path: path.as_bytes().to_vec(),
+            start: start - 1,
+            end,
+        })
+    }
+}
+
+/// The names of the segments of a path that overlap the bases from
+/// `start` to `end`, 0-based and end-exclusive, in the order they
+/// appear on the path, or `None` if the path doesn't exist. Each
+/// segment is only included once, even if the path visits it
+/// several times.
+pub fn path_range_segments<T: OptFields>(
+    gfa: &GFA<Vec<u8>, T>,
+    path_name: &[u8],
+    start: usize,
+    end: usize,
+) -> Option<Vec<Vec<u8>>> {
+    PathOffsets::new(gfa).segments(path_name, start, end)
+}
+
+/// The positions of the steps of every path, for finding the segments
+/// that cover intervals of the paths
+pub struct PathOffsets<'a> {
+    /// The start and end offsets of each step, and its segment
+    paths: FnvHashMap<&'a [u8], Vec<(usize, usize, &'a [u8])>>,
+}
+
+impl<'a> PathOffsets<'a> {
+    pub fn new<T: OptFields>(gfa: &'a GFA<Vec<u8>, T>) -> Self {
+        let lengths: FnvHashMap<&[u8], usize> = gfa
+            .segments
+            .iter()
+            .map(|seg| (seg.name.as_slice(), seg.sequence.len()))
+            .collect();
+
+        let paths = gfa
+            .paths
+            .iter()
+            .map(|path| {
+                let mut offset = 0;
+                let steps = path
+                    .iter()
+                    .map(|(seg, _)| {
+                        let seg: &[u8] = seg.as_ref();
+                        let start = offset;
+                        offset += lengths.get(seg).copied().unwrap_or(0);
+                        (start, offset, seg)
+                    })
+                    .collect();
+                (path.path_name.as_slice(), steps)
+            })
+            .collect();
+
+        Self { paths }
+    }
 
-    let segments =
-        filtered!(gfa.segments, |s| segment_names.contains(s.name.as_slice()));
+    /// The segments overlapping the interval of the path, like
+    /// `path_range_segments`
+    pub fn segments(
+        &self,
+        path_name: &[u8],
+        start: usize,
+        end: usize,
+    ) -> Option<Vec<Vec<u8>>> {
+        let steps = self.paths.get(path_name)?;
+
+        // The first step that ends after the interval starts
+        let first = steps
+            .binary_search_by(|&(_, step_end, _)| {
+                if step_end <= start {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            })
+            .unwrap_or_else(|ix| ix);
+
+        let mut seen: HashSet<&[u8]> = HashSet::new();
+        let mut names = Vec::new();
+        for &(step_start, _, seg) in steps[first..].iter() {
+            if step_start >= end {
+                break;
+            }
+            if seen.insert(seg) {
+                names.push(seg.to_vec());
+            }
+        }
+        Some(names)
+    }
+}
+
+/// The segments covered by the aligned part of the path of each GAF
+/// record, in the order they're first seen, and the number of records
+/// whose path couldn't be resolved to segments of the graph, e.g.
+/// because they refer to a segment that isn't in it, or to a whole
+/// stable sequence. The bases that consecutive segments share through
+/// link overlaps are only counted once in the path coordinates.
+pub fn gaf_segments<T, I>(
+    index: &SegmentIndex<'_, T>,
+    records: I,
+) -> (Vec<Vec<u8>>, usize)
+where
+    T: OptFields,
+    I: IntoIterator<Item = GAF>,
+{
+    let mut seen: HashSet<Vec<u8>> = HashSet::new();
+    let mut names = Vec::new();
+    let mut unresolved = 0;
+
+    for gaf in records {
+        let steps = match &gaf.path {
+            GAFPath::OrientIntv(steps) => steps,
+            GAFPath::StableId(_) => {
+                unresolved += 1;
+                continue;
+            }
+        };
+
+        let mut segments = Vec::new();
+        for step in steps.iter() {
+            match index.resolve_step(step) {
+                Ok(resolved) => segments.extend(resolved),
+                Err(_) => {
+                    segments.clear();
+                    unresolved += 1;
+                    break;
+                }
+            }
+        }
+
+        // Each segment starts where the previous one ends, minus the
+        // bases they share through the overlap of the link between them
+        let overlaps = index.step_overlaps(&segments);
+        let (path_start, path_end) = gaf.path_range;
+        let mut seg_end = 0;
+        for ((_, seg), overlap) in segments.into_iter().zip(overlaps) {
+            let seg_start = seg_end - overlap;
+            seg_end = seg_start + seg.sequence.len();
+            if seg_start.max(path_start) < seg_end.min(path_end)
+                && !seen.contains(seg.name.as_slice())
+            {
+                seen.insert(seg.name.clone());
+                names.push(seg.name.clone());
+            }
+        }
+    }
+
+    (names, unresolved)
+}
+
+/// Read a batch of subgraph selections, one per line, each with an
+/// output name and a comma-separated list of names, separated by a
+/// tab. Empty lines and comments are skipped.
+pub fn read_batch<R: Read>(
+    reader: R,
+) -> io::Result<Vec<(String, Vec<Vec<u8>>)>> {
+    let mut batch = Vec::new();
+
+    for (i, line) in BufReader::new(reader).byte_lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with(b"#") {
+            continue;
+        }
+
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid batch line {}: {}", i + 1, line.as_bstr()),
+            )
+        };
+
+        let mut fields = line.split_str(b"\t");
+        let out_name = fields.next().ok_or_else(invalid)?;
+        let out_name = out_name.to_str().map_err(|_| invalid())?;
+        let names: Vec<Vec<u8>> = fields
+            .next()
+            .ok_or_else(invalid)?
+            .split_str(b",")
+            .filter(|name| !name.is_empty())
+            .map(|name| name.to_vec())
+            .collect();
+
+        batch.push((out_name.to_string(), names));
+    }
+
+    Ok(batch)
+}
+
+/// Read the intervals of a BED file, whose chromosome names are path
+/// names. Empty lines, comments, and track and browser lines are
+/// skipped.
+pub fn read_bed_intervals<R: Read>(reader: R) -> io::Result<Vec<PathInterval>> {
+    let mut intervals = Vec::new();
+    for (i, line) in BufReader::new(reader).byte_lines().enumerate() {
+        let line = line?;
+        if line.is_empty()
+            || line.starts_with(b"#")
+            || line.starts_with(b"track")
+            || line.starts_with(b"browser")
+        {
+            continue;
+        }
+        let fields: Vec<&[u8]> = line.split_str(b"\t").take(3).collect();
+        let coord = |ix: usize| -> Option<usize> {
+            fields.get(ix)?.to_str().ok()?.trim().parse().ok()
+        };
+        match (coord(1), coord(2)) {
+            (Some(start), Some(end)) if start <= end => {
+                intervals.push(PathInterval {
+                    path: fields[0].to_vec(),
+                    start,
+                    end,
+                });
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "line {} of the BED file is not a valid interval",
+                        i + 1
+                    ),
+                ))
+            }
+        }
+    }
+    Ok(intervals)
+}
+
+/// Returns a subgraph GFA that only contains elements with the
+/// provided segment names. Paths that pass through segments outside
+/// the subgraph are split into the fragments inside it, named
+/// `path:1`, `path:2`, and so on.
+pub fn segments_subgraph<N: SegmentName, T: OptFields + Clone>(
+    gfa: &GFA<N, T>,
+    segment_names: &[N],
+) -> GFA<N, T> {
+    let segment_names: HashSet<&N> = segment_names.iter().collect();
+
+    let segments = filtered!(gfa.segments, |s| segment_names.contains(&s.name));
 
     let links = filtered!(&gfa.links, |l| {
-        segment_names.contains(l.from_segment.as_slice())
-            && segment_names.contains(l.to_segment.as_slice())
+        segment_names.contains(&l.from_segment)
+            && segment_names.contains(&l.to_segment)
     });
 
     let containments = filtered!(&gfa.containments, |l| {
-        segment_names.contains(l.container_name.as_slice())
-            && segment_names.contains(l.contained_name.as_slice())
+        segment_names.contains(&l.container_name)
+            && segment_names.contains(&l.contained_name)
     });
 
-    let paths: Vec<_> = filtered!(gfa.paths, |p| p
+    // Paths are cut where they leave the subgraph, so that they only
+    // refer to segments that are in it
+    let paths: Vec<_> = gfa
+        .paths
         .iter()
-        .any(|(s, _)| segment_names.contains(s.as_ref())));
+        .flat_map(|path| split_path(path, |seg| segment_names.contains(seg)))
+        .collect();
 
     GFA {
         header: gfa.header.clone(),
@@ -86,3 +1164,169 @@ pub fn segments_subgraph<T: OptFields + Clone>(
         containments,
     }
 }
+
+/// The segment name of a step of a P-line, without its orientation
+fn step_segment(step: &[u8]) -> &[u8] {
+    match step.last() {
+        Some(b'+') | Some(b'-') => &step[..step.len() - 1],
+        _ => step,
+    }
+}
+
+/// The names of the segments in the given paths, read from the P-lines
+/// of a GFA without loading the rest of it
+pub fn stream_path_segments<R: Read>(
+    reader: R,
+    paths: &[Vec<u8>],
+) -> io::Result<Vec<Vec<u8>>> {
+    let path_names: HashSet<&[u8]> = paths.iter().map(|p| p.as_ref()).collect();
+
+    let mut seen: HashSet<Vec<u8>> = HashSet::new();
+    let mut names = Vec::new();
+    for line in BufReader::new(reader).byte_lines() {
+        let line = line?;
+        let fields: Vec<&[u8]> = line.split_str(b"\t").take(3).collect();
+        if fields.len() < 3
+            || fields[0] != b"P"
+            || !path_names.contains(fields[1])
+        {
+            continue;
+        }
+        for step in fields[2].split_str(b",") {
+            let seg = step_segment(step);
+            if !seen.contains(seg) {
+                seen.insert(seg.to_vec());
+                names.push(seg.to_vec());
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Copy the lines of a GFA that belong to the subgraph with the given
+/// segments from `reader` to `out`, one line at a time, so that the
+/// GFA is never loaded. The lines are written in the order of the
+/// input, and the header is kept. If `paths` is given, only those
+/// paths are included, like `paths_segments_subgraph`; otherwise
+/// every path is split into the fragments that stay inside the
+/// subgraph, like `segments_subgraph`. If `program` is given, it's
+/// recorded in the PG tag of the first header line, or of a new one
+/// if the GFA doesn't start with a header. Returns the number of
+/// lines written.
+pub fn stream_subgraph<R: Read, W: Write>(
+    reader: R,
+    out: &mut W,
+    segment_names: &[Vec<u8>],
+    paths: Option<&[Vec<u8>]>,
+    program: Option<&[u8]>,
+) -> io::Result<usize> {
+    let segment_names: HashSet<&[u8]> =
+        segment_names.iter().map(|s| s.as_ref()).collect();
+    let path_names: Option<HashSet<&[u8]>> =
+        paths.map(|paths| paths.iter().map(|p| p.as_ref()).collect());
+
+    let mut program = program;
+    let mut written = 0;
+    for line in BufReader::new(reader).byte_lines() {
+        let line = line?;
+        let fields: Vec<&[u8]> = line.split_str(b"\t").collect();
+
+        if let Some(record) = program.take() {
+            if fields[0] == b"H" {
+                out.write_all(&add_program_field(&line, record))?;
+                out.write_all(b"\n")?;
+                written += 1;
+                continue;
+            }
+            out.write_all(&add_program_field(b"H", record))?;
+            out.write_all(b"\n")?;
+            written += 1;
+        }
+
+        let keep = match fields[0] {
+            b"H" => true,
+            b"S" => fields.len() > 1 && segment_names.contains(fields[1]),
+            b"L" | b"C" => {
+                fields.len() > 3
+                    && segment_names.contains(fields[1])
+                    && segment_names.contains(fields[3])
+            }
+            b"P" if fields.len() > 2 => match &path_names {
+                Some(path_names) => path_names.contains(fields[1]),
+                None => {
+                    written +=
+                        write_path_fragments(out, &fields, &segment_names)?;
+                    false
+                }
+            },
+            _ => false,
+        };
+
+        if keep {
+            out.write_all(&line)?;
+            out.write_all(b"\n")?;
+            written += 1;
+        }
+    }
+    Ok(written)
+}
+
+/// Write the fragments of a P-line, given by its fields, that only
+/// use the given segments, named like `split_path` names them.
+/// Returns the number of fragments.
+fn write_path_fragments<W: Write>(
+    out: &mut W,
+    fields: &[&[u8]],
+    segment_names: &HashSet<&[u8]>,
+) -> io::Result<usize> {
+    let steps: Vec<&[u8]> = fields[2].split_str(b",").collect();
+    let overlaps: Vec<&[u8]> = match fields.get(3) {
+        Some(overlaps) if *overlaps != b"*" => {
+            overlaps.split_str(b",").collect()
+        }
+        _ => Vec::new(),
+    };
+    let overlaps_match = overlaps.len() + 1 == steps.len();
+    let tags = fields.get(4..).unwrap_or(&[]);
+
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    let mut run_start = None;
+    for (ix, step) in steps.iter().enumerate() {
+        match (segment_names.contains(step_segment(step)), run_start) {
+            (true, None) => run_start = Some(ix),
+            (false, Some(start)) => {
+                runs.push((start, ix));
+                run_start = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some(start) = run_start {
+        runs.push((start, steps.len()));
+    }
+
+    let whole_path = runs.len() == 1 && runs[0] == (0, steps.len());
+
+    for (frag_ix, &(start, end)) in runs.iter().enumerate() {
+        out.write_all(b"P\t")?;
+        out.write_all(fields[1])?;
+        if !whole_path {
+            write!(out, ":{}", frag_ix + 1)?;
+        }
+        out.write_all(b"\t")?;
+        out.write_all(&steps[start..end].join(&b","[..]))?;
+        out.write_all(b"\t")?;
+        if overlaps_match && end - start > 1 {
+            out.write_all(&overlaps[start..end - 1].join(&b","[..]))?;
+        } else {
+            out.write_all(b"*")?;
+        }
+        for tag in tags {
+            out.write_all(b"\t")?;
+            out.write_all(tag)?;
+        }
+        out.write_all(b"\n")?;
+    }
+
+    Ok(runs.len())
+}
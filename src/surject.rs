@@ -0,0 +1,761 @@
+//! Projection of GAF alignments onto the linear coordinates of a
+//! reference path, for output as SAM or BED, or as a depth track.
+
+use bstr::{BString, ByteSlice};
+use fnv::FnvHashMap;
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+use gfa::{
+    cigar::CIGAROp,
+    gafpaf::{GAFPath, GAFStep},
+    gfa::Orientation,
+    optfields::OptionalFields,
+};
+
+use crate::{gaf_convert::get_cigar, variants::PathData};
+
+type GAF = gfa::gafpaf::GAF<OptionalFields>;
+
+/// The position of every node on a reference path, so that graph
+/// coordinates can be translated to path coordinates. Nodes that
+/// occur more than once on the path are indexed at their first
+/// occurrence.
+#[derive(Debug, Clone)]
+pub struct PathPositionIndex {
+    pub name: BString,
+    pub length: usize,
+    nodes: FnvHashMap<usize, (usize, usize, Orientation)>,
+    pub repeated_nodes: usize,
+}
+
+impl PathPositionIndex {
+    pub fn new(path_data: &PathData, path_ix: usize) -> Self {
+        let mut nodes = FnvHashMap::default();
+        let mut repeated_nodes = 0;
+
+        for &(node, offset, orient) in path_data.paths[path_ix].iter() {
+            let node_len =
+                path_data.segment_map.get(&node).map_or(0, |s| s.len());
+            if nodes.contains_key(&node) {
+                repeated_nodes += 1;
+            } else {
+                nodes.insert(node, (offset - 1, node_len, orient));
+            }
+        }
+
+        Self {
+            name: path_data.path_names[path_ix].clone(),
+            length: path_data.path_length(path_ix),
+            nodes,
+            repeated_nodes,
+        }
+    }
+
+    /// The 0-based reference position of a base in a node traversed
+    /// with orientation `orient`, where `offset` is the offset into
+    /// the oriented node sequence, along with whether the node is
+    /// traversed in the opposite orientation of the reference path.
+    fn project(
+        &self,
+        node: usize,
+        orient: Orientation,
+        offset: usize,
+    ) -> Option<(usize, bool)> {
+        let &(start, len, ref_orient) = self.nodes.get(&node)?;
+        if offset >= len {
+            return None;
+        }
+        let reversed = orient != ref_orient;
+        let pos = if reversed {
+            start + len - 1 - offset
+        } else {
+            start + offset
+        };
+        Some((pos, reversed))
+    }
+}
+
+/// A GAF alignment projected onto a reference path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SamRecord {
+    pub name: BString,
+    pub flag: u16,
+    pub ref_name: Option<BString>,
+    /// 0-based position of the first aligned base
+    pub pos: usize,
+    pub mapq: u8,
+    pub cigar: Vec<(usize, char)>,
+}
+
+impl SamRecord {
+    fn unmapped(name: BString) -> Self {
+        Self {
+            name,
+            flag: 4,
+            ref_name: None,
+            pos: 0,
+            mapq: 0,
+            cigar: Vec::new(),
+        }
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        self.flag & 4 == 0
+    }
+}
+
+impl fmt::Display for SamRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cigar = if self.cigar.is_empty() {
+            "*".to_string()
+        } else {
+            self.cigar
+                .iter()
+                .map(|(len, op)| format!("{}{}", len, op))
+                .collect()
+        };
+        let (ref_name, pos) = match &self.ref_name {
+            Some(name) => (name.to_string(), self.pos + 1),
+            None => ("*".to_string(), 0),
+        };
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}\t{}\t*\t0\t0\t*\t*",
+            self.name, self.flag, ref_name, pos, self.mapq, cigar
+        )
+    }
+}
+
+/// Append an operation to a CIGAR, merging it with the last one if
+/// they're the same
+fn push_op(cigar: &mut Vec<(usize, char)>, len: usize, op: char) {
+    if len == 0 {
+        return;
+    }
+    match cigar.last_mut() {
+        Some((last_len, last_op)) if *last_op == op => *last_len += len,
+        _ => cigar.push((len, op)),
+    }
+}
+
+/// Replace leading and trailing insertions with soft clips, and drop
+/// leading and trailing deletions, as neither is allowed in SAM
+fn clip_ends(cigar: Vec<(usize, char)>) -> Vec<(usize, char)> {
+    let first = cigar.iter().position(|&(_, op)| op == 'M');
+    let last = cigar.iter().rposition(|&(_, op)| op == 'M');
+    let (first, last) = match (first, last) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Vec::new(),
+    };
+
+    let clipped = |ops: &[(usize, char)]| -> usize {
+        ops.iter()
+            .filter(|&&(_, op)| op == 'S' || op == 'I')
+            .map(|&(len, _)| len)
+            .sum()
+    };
+
+    let mut result = Vec::new();
+    push_op(&mut result, clipped(&cigar[..first]), 'S');
+    for &(len, op) in cigar[first..=last].iter() {
+        push_op(&mut result, len, op);
+    }
+    push_op(&mut result, clipped(&cigar[last + 1..]), 'S');
+    result
+}
+
+/// The reference position of each base in the aligned part of a GAF
+/// path given as oriented steps, and whether it's on the reverse
+/// strand of the reference, or `None` if it's not on the reference.
+/// The bases of a stable interval are on the reference only if its
+/// stable sequence is the reference path. Returns `None` if the path
+/// is a stable ID, if a segment step isn't an integer node ID, or if
+/// an interval or the path range is out of bounds.
+fn aligned_path_bases(
+    index: &PathPositionIndex,
+    segment_map: &FnvHashMap<usize, BString>,
+    gaf: &GAF,
+) -> Option<Vec<Option<(usize, bool)>>> {
+    let steps = match &gaf.path {
+        GAFPath::StableId(_) => return None,
+        GAFPath::OrientIntv(steps) => steps,
+    };
+
+    let mut path_bases = Vec::new();
+    for step in steps.iter() {
+        match step {
+            GAFStep::SegId(orient, id) => {
+                let node = id.to_str().ok()?.parse::<usize>().ok()?;
+                let len = segment_map.get(&node).map_or(0, |s| s.len());
+                path_bases.extend(
+                    (0..len).map(|offset| index.project(node, *orient, offset)),
+                );
+            }
+            GAFStep::StableIntv(orient, name, from, to) => {
+                let (from, to) = (*from, *to);
+                if from > to {
+                    return None;
+                }
+                if *name != index.name {
+                    path_bases.extend(std::iter::repeat(None).take(to - from));
+                } else if to > index.length {
+                    return None;
+                } else if orient.is_reverse() {
+                    path_bases
+                        .extend((from..to).rev().map(|p| Some((p, true))));
+                } else {
+                    path_bases.extend((from..to).map(|p| Some((p, false))));
+                }
+            }
+        }
+    }
+
+    let (path_start, path_end) = gaf.path_range;
+    if path_end > path_bases.len() || path_start > path_end {
+        return None;
+    }
+    path_bases.truncate(path_end);
+    path_bases.drain(..path_start);
+    Some(path_bases)
+}
+
+/// Project a GAF record onto the reference path. Bases aligned to
+/// nodes that aren't on the reference, or that would move the
+/// alignment backwards along the reference, become insertions; jumps
+/// forward along the reference become deletions. Records that can't
+/// be placed on the reference are returned as unmapped.
+pub fn surject_gaf(
+    index: &PathPositionIndex,
+    segment_map: &FnvHashMap<usize, BString>,
+    gaf: &GAF,
+) -> SamRecord {
+    let name = gaf.seq_name.clone();
+
+    match &gaf.path {
+        GAFPath::StableId(id) if *id == index.name => {
+            return surject_stable(index, gaf);
+        }
+        GAFPath::StableId(_) => return SamRecord::unmapped(name),
+        _ => (),
+    }
+
+    let path_bases = match aligned_path_bases(index, segment_map, gaf) {
+        Some(bases) => bases,
+        None => return SamRecord::unmapped(name),
+    };
+
+    let query_len = gaf.seq_range.1.saturating_sub(gaf.seq_range.0);
+    let ops: Vec<CIGAROp> = match get_cigar(&gaf.optional) {
+        Some(cigar) => cigar.iter_single().collect(),
+        None if query_len == path_bases.len() => {
+            vec![CIGAROp::M; query_len]
+        }
+        None => return SamRecord::unmapped(name),
+    };
+
+    // The direction along the reference is set by the first base that
+    // can be projected
+    let reversed = match path_bases.iter().find_map(|&base| base) {
+        Some((_, reversed)) => reversed,
+        None => return SamRecord::unmapped(name),
+    };
+
+    let (lead_clip, trail_clip) = match gaf.strand {
+        Orientation::Forward => {
+            (gaf.seq_range.0, gaf.seq_len - gaf.seq_range.1)
+        }
+        Orientation::Backward => {
+            (gaf.seq_len - gaf.seq_range.1, gaf.seq_range.0)
+        }
+    };
+
+    let mut cigar = Vec::new();
+    push_op(&mut cigar, lead_clip, 'S');
+
+    let mut path_ix = 0;
+    // The last projected base, aligned or deleted, which the next one
+    // must come after, and the first and last aligned bases, which
+    // are where the alignment starts and ends once the deletions at
+    // the ends are dropped
+    let mut last_ref: Option<usize> = None;
+    let mut first_match: Option<usize> = None;
+    let mut last_match: Option<usize> = None;
+
+    for op in ops {
+        use CIGAROp::*;
+        let (query, path) = match op {
+            _ if op.is_match_or_mismatch() => (true, true),
+            I | S => (true, false),
+            D | N => (false, true),
+            _ => (false, false),
+        };
+
+        if !path {
+            if query {
+                push_op(&mut cigar, 1, 'I');
+            }
+            continue;
+        }
+
+        let projected = path_bases.get(path_ix).and_then(|&base| {
+            let (pos, rev) = base?;
+            if rev != reversed {
+                return None;
+            }
+            match last_ref {
+                None => Some(pos),
+                Some(last) if !reversed && pos > last => Some(pos),
+                Some(last) if reversed && pos < last => Some(pos),
+                _ => None,
+            }
+        });
+        path_ix += 1;
+
+        match projected {
+            Some(pos) => {
+                if let Some(last) = last_ref {
+                    let gap = if reversed { last - pos } else { pos - last };
+                    push_op(&mut cigar, gap - 1, 'D');
+                }
+                push_op(&mut cigar, 1, if query { 'M' } else { 'D' });
+                last_ref = Some(pos);
+                if query {
+                    first_match.get_or_insert(pos);
+                    last_match = Some(pos);
+                }
+            }
+            None => {
+                if query {
+                    push_op(&mut cigar, 1, 'I');
+                }
+            }
+        }
+    }
+
+    push_op(&mut cigar, trail_clip, 'S');
+
+    let (first_match, last_match) = match (first_match, last_match) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return SamRecord::unmapped(name),
+    };
+
+    if reversed {
+        cigar.reverse();
+    }
+    let cigar = clip_ends(cigar);
+    if cigar.is_empty() {
+        return SamRecord::unmapped(name);
+    }
+
+    let reverse_strand = reversed != gaf.strand.is_reverse();
+
+    SamRecord {
+        name,
+        flag: if reverse_strand { 16 } else { 0 },
+        ref_name: Some(index.name.clone()),
+        pos: first_match.min(last_match),
+        mapq: gaf.quality.min(255) as u8,
+        cigar,
+    }
+}
+
+/// A GAF record that's already in the stable coordinates of the
+/// reference path only needs its CIGAR and clipping converted
+fn surject_stable(index: &PathPositionIndex, gaf: &GAF) -> SamRecord {
+    let name = gaf.seq_name.clone();
+
+    let (lead_clip, trail_clip) = match gaf.strand {
+        Orientation::Forward => {
+            (gaf.seq_range.0, gaf.seq_len - gaf.seq_range.1)
+        }
+        Orientation::Backward => {
+            (gaf.seq_len - gaf.seq_range.1, gaf.seq_range.0)
+        }
+    };
+
+    let mut cigar = Vec::new();
+    push_op(&mut cigar, lead_clip, 'S');
+    match get_cigar(&gaf.optional) {
+        Some(gaf_cigar) => {
+            for op in gaf_cigar.iter_single() {
+                use CIGAROp::*;
+                let op = match op {
+                    _ if op.is_match_or_mismatch() => 'M',
+                    I | S => 'I',
+                    D | N => 'D',
+                    _ => continue,
+                };
+                push_op(&mut cigar, 1, op);
+            }
+        }
+        None => {
+            let len = gaf.path_range.1.saturating_sub(gaf.path_range.0);
+            push_op(&mut cigar, len, 'M');
+        }
+    }
+    push_op(&mut cigar, trail_clip, 'S');
+
+    let cigar = clip_ends(cigar);
+    if cigar.is_empty() {
+        return SamRecord::unmapped(name);
+    }
+
+    SamRecord {
+        name,
+        flag: if gaf.strand.is_reverse() { 16 } else { 0 },
+        ref_name: Some(index.name.clone()),
+        pos: gaf.path_range.0,
+        mapq: gaf.quality.min(255) as u8,
+        cigar,
+    }
+}
+
+/// A BED interval on the reference path covered by a GAF alignment
+#[derive(Debug, Clone, PartialEq)]
+pub struct BedInterval {
+    pub ref_name: BString,
+    /// 0-based, half-open
+    pub start: usize,
+    pub end: usize,
+    pub name: BString,
+    /// The residue matches divided by the alignment block length
+    pub identity: f64,
+    pub reverse: bool,
+}
+
+impl fmt::Display for BedInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{:.4}\t{}",
+            self.ref_name,
+            self.start,
+            self.end,
+            self.name,
+            self.identity,
+            if self.reverse { '-' } else { '+' }
+        )
+    }
+}
+
+/// Intersect the path of a GAF record with the reference path, and
+/// return the reference intervals its aligned path bases fall on,
+/// sorted by start. Bases on consecutive reference positions are
+/// merged into one interval, so an alignment that leaves the
+/// reference, e.g. through an insertion, or skips part of it, is
+/// split into several intervals. The strand is the read's strand
+/// relative to the reference, as in `surject_gaf`.
+pub fn project_gaf_intervals(
+    index: &PathPositionIndex,
+    segment_map: &FnvHashMap<usize, BString>,
+    gaf: &GAF,
+) -> Vec<BedInterval> {
+    let identity = if gaf.block_length == 0 {
+        0.0
+    } else {
+        gaf.residue_matches as f64 / gaf.block_length as f64
+    };
+
+    let interval = |start: usize, end: usize, reverse: bool| BedInterval {
+        ref_name: index.name.clone(),
+        start,
+        end,
+        name: gaf.seq_name.clone(),
+        identity,
+        reverse,
+    };
+
+    if let GAFPath::StableId(id) = &gaf.path {
+        let (start, end) = gaf.path_range;
+        if *id != index.name || start >= end || end > index.length {
+            return Vec::new();
+        }
+        return vec![interval(start, end, gaf.strand.is_reverse())];
+    }
+
+    let path_bases = match aligned_path_bases(index, segment_map, gaf) {
+        Some(bases) => bases,
+        None => return Vec::new(),
+    };
+
+    let mut reversed = None;
+    let mut positions: Vec<usize> = path_bases
+        .iter()
+        .filter_map(|&base| {
+            let (pos, rev) = base?;
+            reversed.get_or_insert(rev);
+            Some(pos)
+        })
+        .collect();
+    positions.sort_unstable();
+    positions.dedup();
+
+    let reverse = match reversed {
+        Some(reversed) => reversed != gaf.strand.is_reverse(),
+        None => return Vec::new(),
+    };
+
+    let mut intervals: Vec<BedInterval> = Vec::new();
+    for pos in positions {
+        match intervals.last_mut() {
+            Some(last) if last.end == pos => last.end += 1,
+            _ => intervals.push(interval(pos, pos + 1, reverse)),
+        }
+    }
+    intervals
+}
+
+/// The per-base depth of alignments along a reference path, built up
+/// one alignment at a time from their projected intervals
+#[derive(Debug, Clone)]
+pub struct PathDepth {
+    name: BString,
+    /// The change in depth at each position of the path, and at its
+    /// end
+    diffs: Vec<i32>,
+}
+
+impl PathDepth {
+    pub fn new(index: &PathPositionIndex) -> Self {
+        Self {
+            name: index.name.clone(),
+            diffs: vec![0; index.length + 1],
+        }
+    }
+
+    pub fn length(&self) -> usize {
+        self.diffs.len() - 1
+    }
+
+    /// Add one to the depth of each base in the interval
+    pub fn add(&mut self, interval: &BedInterval) {
+        let end = interval.end.min(self.length());
+        if interval.start < end {
+            self.diffs[interval.start] += 1;
+            self.diffs[end] -= 1;
+        }
+    }
+
+    /// The runs of bases with the same depth, as (start, end, depth),
+    /// covering the whole path
+    pub fn runs(&self) -> Vec<(usize, usize, usize)> {
+        let mut runs: Vec<(usize, usize, usize)> = Vec::new();
+        let mut depth: i64 = 0;
+        for (pos, diff) in self.diffs[..self.length()].iter().enumerate() {
+            depth += *diff as i64;
+            let depth = depth as usize;
+            match runs.last_mut() {
+                Some(last) if last.2 == depth => last.1 += 1,
+                _ => runs.push((pos, pos + 1, depth)),
+            }
+        }
+        runs
+    }
+
+    /// Write the depth as a bedGraph, leaving out the runs with zero
+    /// depth
+    pub fn write_bedgraph<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for (start, end, depth) in self.runs() {
+            if depth > 0 {
+                writeln!(out, "{}\t{}\t{}\t{}", self.name, start, end, depth)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the depth of every base as a fixedStep wiggle track
+    pub fn write_wig<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "fixedStep chrom={} start=1 step=1", self.name)?;
+        for (start, end, depth) in self.runs() {
+            for _ in start..end {
+                writeln!(out, "{}", depth)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The SAM header, with the reference path as the only sequence
+pub fn sam_header(index: &PathPositionIndex) -> String {
+    format!(
+        "@HD\tVN:1.6\tSO:unknown\n\
+         @SQ\tSN:{}\tLN:{}\n\
+         @PG\tID:gfautil\tPN:gfautil\tVN:{}",
+        index.name,
+        index.length,
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gfa::gafpaf::parse_gaf;
+    use Orientation::*;
+
+    fn path_data() -> PathData {
+        let segment_map: FnvHashMap<usize, BString> = vec![
+            (1, "ACGT".into()),
+            (2, "GG".into()),
+            (3, "TTA".into()),
+            (4, "C".into()),
+        ]
+        .into_iter()
+        .collect();
+
+        PathData {
+            segment_map,
+            path_names: vec!["ref".into()],
+            paths: vec![vec![
+                (1, 1, Forward),
+                (2, 5, Forward),
+                (3, 7, Forward),
+            ]],
+        }
+    }
+
+    fn gaf(line: &str) -> GAF {
+        parse_gaf(line.as_bytes().split_str("\t")).unwrap()
+    }
+
+    #[test]
+    fn surject_through_off_reference_node() {
+        let path_data = path_data();
+        let index = PathPositionIndex::new(&path_data, 0);
+        assert_eq!(index.length, 9);
+
+        // >1>4>3 replaces node 2 with node 4: GG -> C
+        let record =
+            gaf("read\t8\t0\t8\t+\t>1>4>3\t8\t0\t8\t7\t8\t60\tcg:Z:8M");
+        let sam = surject_gaf(&index, &path_data.segment_map, &record);
+        assert_eq!(sam.pos, 0);
+        assert_eq!(sam.flag, 0);
+        assert_eq!(sam.cigar, vec![(4, 'M'), (1, 'I'), (2, 'D'), (3, 'M')]);
+
+        // The same read aligned to the reverse of the reference
+        let record = gaf("read\t7\t1\t6\t+\t<3<2\t5\t0\t5\t5\t5\t60\tcg:Z:5M");
+        let sam = surject_gaf(&index, &path_data.segment_map, &record);
+        assert_eq!(sam.pos, 4);
+        assert_eq!(sam.flag, 16);
+        assert_eq!(sam.cigar, vec![(1, 'S'), (5, 'M'), (1, 'S')]);
+        assert_eq!(
+            sam_header(&index).lines().nth(1),
+            Some("@SQ\tSN:ref\tLN:9")
+        );
+
+        let record = gaf("read\t1\t0\t1\t+\t>4\t1\t0\t1\t1\t1\t60\tcg:Z:1M");
+        assert!(
+            !surject_gaf(&index, &path_data.segment_map, &record).is_mapped()
+        );
+    }
+
+    #[test]
+    fn surject_starts_at_first_aligned_base() {
+        let path_data = path_data();
+        let index = PathPositionIndex::new(&path_data, 0);
+        let segment_map = &path_data.segment_map;
+
+        // The deletions at the ends are dropped, so the record starts
+        // at the first M, on either strand of the reference
+        let record =
+            gaf("read\t4\t0\t4\t+\t>1>2\t6\t0\t6\t4\t6\t60\tcg:Z:2D4M");
+        let sam = surject_gaf(&index, segment_map, &record);
+        assert_eq!(sam.pos, 2);
+        assert_eq!(sam.cigar, vec![(4, 'M')]);
+
+        let record =
+            gaf("read\t4\t0\t4\t+\t<2<1\t6\t0\t6\t4\t6\t60\tcg:Z:4M2D");
+        let sam = surject_gaf(&index, segment_map, &record);
+        assert_eq!(sam.pos, 2);
+        assert_eq!(sam.flag, 16);
+        assert_eq!(sam.cigar, vec![(4, 'M')]);
+    }
+
+    #[test]
+    fn surject_stable_intervals() {
+        let path_data = path_data();
+        let index = PathPositionIndex::new(&path_data, 0);
+        let segment_map = &path_data.segment_map;
+
+        let record =
+            gaf("read\t5\t0\t5\t+\t>ref:2-7\t5\t0\t5\t5\t5\t60\tcg:Z:5M");
+        let sam = surject_gaf(&index, segment_map, &record);
+        assert_eq!(sam.pos, 2);
+        assert_eq!(sam.flag, 0);
+        assert_eq!(sam.cigar, vec![(5, 'M')]);
+
+        // The bases of other stable sequences aren't on the reference
+        let record = gaf("read\t11\t0\t11\t+\t>ref:0-4>alt:0-2>ref:4-9\t\
+                          11\t0\t11\t11\t11\t60\tcg:Z:11M");
+        let sam = surject_gaf(&index, segment_map, &record);
+        assert_eq!(sam.pos, 0);
+        assert_eq!(sam.cigar, vec![(4, 'M'), (2, 'I'), (5, 'M')]);
+    }
+
+    #[test]
+    fn bed_intervals_split_at_off_reference_nodes() {
+        let path_data = path_data();
+        let index = PathPositionIndex::new(&path_data, 0);
+        let segment_map = &path_data.segment_map;
+
+        let record =
+            gaf("read\t8\t0\t8\t+\t>1>4>3\t8\t1\t7\t5\t6\t60\tcg:Z:6M");
+        let intervals = project_gaf_intervals(&index, segment_map, &record);
+        let lines: Vec<String> =
+            intervals.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec!["ref\t1\t4\tread\t0.8333\t+", "ref\t6\t8\tread\t0.8333\t+"]
+        );
+
+        let record = gaf("read\t5\t0\t5\t-\t<3<2\t5\t0\t5\t5\t5\t60");
+        let intervals = project_gaf_intervals(&index, segment_map, &record);
+        assert_eq!(intervals.len(), 1);
+        assert_eq!((intervals[0].start, intervals[0].end), (4, 9));
+        assert!(!intervals[0].reverse);
+
+        let record = gaf("read\t1\t0\t1\t+\t>4\t1\t0\t1\t1\t1\t60");
+        assert!(project_gaf_intervals(&index, segment_map, &record).is_empty());
+    }
+
+    #[test]
+    fn depth_tracks_from_projected_intervals() {
+        let path_data = path_data();
+        let index = PathPositionIndex::new(&path_data, 0);
+        let segment_map = &path_data.segment_map;
+
+        let mut depth = PathDepth::new(&index);
+        for line in &[
+            "read1\t8\t0\t8\t+\t>1>4>3\t8\t1\t7\t5\t6\t60",
+            "read2\t5\t0\t5\t-\t<3<2\t5\t0\t5\t5\t5\t60",
+        ] {
+            for interval in
+                project_gaf_intervals(&index, segment_map, &gaf(line))
+            {
+                depth.add(&interval);
+            }
+        }
+
+        assert_eq!(
+            depth.runs(),
+            vec![(0, 1, 0), (1, 6, 1), (6, 8, 2), (8, 9, 1)]
+        );
+
+        let mut bedgraph = Vec::new();
+        depth.write_bedgraph(&mut bedgraph).unwrap();
+        assert_eq!(
+            bedgraph.to_str().unwrap(),
+            "ref\t1\t6\t1\nref\t6\t8\t2\nref\t8\t9\t1\n"
+        );
+
+        let mut wig = Vec::new();
+        depth.write_wig(&mut wig).unwrap();
+        assert_eq!(
+            wig.to_str().unwrap(),
+            "fixedStep chrom=ref start=1 step=1\n0\n1\n1\n1\n1\n1\n2\n2\n1\n"
+        );
+    }
+}
@@ -1,5 +1,24 @@
 use indicatif::{ProgressBar, ProgressStyle};
 
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use bstr::{BString, ByteSlice};
+use fnv::{FnvHashMap, FnvHashSet};
+use gfa::{
+    gfa::{Header, Orientation, Path, SegmentId, GFA},
+    optfields::{OptField, OptFieldVal, OptFields, OptionalFields},
+};
+
 pub(crate) fn progress_bar(len: usize, steady: bool) -> ProgressBar {
     let p_bar = ProgressBar::new(len as u64);
     p_bar.set_style(
@@ -12,3 +31,697 @@ pub(crate) fn progress_bar(len: usize, steady: bool) -> ProgressBar {
     }
     p_bar
 }
+
+/// Logs the progress of a long-running job at a fixed interval, from
+/// a background thread that runs until the `Heartbeat` is dropped.
+/// Progress is read from the shared `processed` counter.
+pub(crate) struct Heartbeat {
+    stop: Option<mpsc::Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Heartbeat {
+    pub(crate) fn start(
+        label: &'static str,
+        interval: Duration,
+        total: usize,
+        processed: Arc<AtomicUsize>,
+    ) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let start = Instant::now();
+
+        let handle = std::thread::spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) =
+                stopped.recv_timeout(interval)
+            {
+                let done = processed.load(Ordering::Relaxed);
+                log::info!(
+                    "Heartbeat: {} {} processed, {} remaining, {:.1} min elapsed",
+                    done,
+                    label,
+                    total.saturating_sub(done),
+                    start.elapsed().as_secs_f64() / 60.0
+                );
+            }
+        });
+
+        Self {
+            stop: Some(stop),
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// The sample of a path name that follows the PanSN convention,
+/// `sample#haplotype#contig`, i.e. the part before the first `#`, or
+/// `None` if the name has no `#`
+pub fn pansn_sample(path_name: &[u8]) -> Option<&[u8]> {
+    let end = path_name.find_byte(b'#')?;
+    Some(&path_name[..end])
+}
+
+/// Build the comma-separated, oriented segment list of a GFA path
+/// line, e.g. `s1+,s2-`
+pub fn path_segment_names<'a, I>(steps: I) -> BString
+where
+    I: IntoIterator<Item = (&'a [u8], Orientation)>,
+{
+    let mut names: Vec<u8> = Vec::new();
+    for (ix, (name, orient)) in steps.into_iter().enumerate() {
+        if ix > 0 {
+            names.push(b',');
+        }
+        names.extend_from_slice(name);
+        names.push(if orient.is_reverse() { b'-' } else { b'+' });
+    }
+    names.into()
+}
+
+/// Segment names that paths can be taken apart and rebuilt with,
+/// so that the same code works on graphs with byte string names and
+/// on graphs with integer IDs, without converting between them
+pub trait SegmentName: SegmentId + Clone + Eq + std::hash::Hash {
+    /// The segments of the steps of a path, with their orientations
+    fn path_steps<T: OptFields>(
+        path: &Path<Self, T>,
+    ) -> Vec<(Self, Orientation)>;
+
+    /// The name as it's written in a GFA
+    fn name_bytes(&self) -> Cow<'_, [u8]>;
+
+    /// The name as an integer ID, if it is one
+    fn numeric_id(&self) -> Option<usize>;
+}
+
+impl SegmentName for Vec<u8> {
+    fn path_steps<T: OptFields>(
+        path: &Path<Self, T>,
+    ) -> Vec<(Self, Orientation)> {
+        path.iter()
+            .map(|(seg, orient)| {
+                let seg: &[u8] = seg.as_ref();
+                (seg.to_vec(), orient)
+            })
+            .collect()
+    }
+
+    fn name_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.as_slice())
+    }
+
+    fn numeric_id(&self) -> Option<usize> {
+        self.to_str().ok()?.parse().ok()
+    }
+}
+
+impl SegmentName for usize {
+    fn path_steps<T: OptFields>(
+        path: &Path<Self, T>,
+    ) -> Vec<(Self, Orientation)> {
+        path.iter().collect()
+    }
+
+    fn name_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(self.to_string().into_bytes())
+    }
+
+    fn numeric_id(&self) -> Option<usize> {
+        Some(*self)
+    }
+}
+
+/// Split a path into the maximal runs of consecutive steps whose
+/// segments satisfy `keep`. If the entire path is kept, it's returned
+/// unchanged; otherwise the fragments are named `path:1`, `path:2`,
+/// and so on.
+pub fn split_path<N, T, F>(path: &Path<N, T>, keep: F) -> Vec<Path<N, T>>
+where
+    N: SegmentName,
+    T: OptFields + Clone,
+    F: Fn(&N) -> bool,
+{
+    let steps = N::path_steps(path);
+
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    let mut run_start = None;
+    for (ix, (seg, _)) in steps.iter().enumerate() {
+        match (keep(seg), run_start) {
+            (true, None) => run_start = Some(ix),
+            (false, Some(start)) => {
+                runs.push((start, ix));
+                run_start = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some(start) = run_start {
+        runs.push((start, steps.len()));
+    }
+
+    if runs.len() == 1 && runs[0] == (0, steps.len()) {
+        return vec![path.clone()];
+    }
+
+    let overlaps_match = path.overlaps.len() + 1 == steps.len();
+
+    runs.iter()
+        .enumerate()
+        .map(|(frag_ix, &(start, end))| {
+            let mut name: Vec<u8> = path.path_name.clone().into();
+            name.extend_from_slice(format!(":{}", frag_ix + 1).as_bytes());
+
+            let overlaps = if overlaps_match {
+                path.overlaps[start..end - 1].to_vec()
+            } else {
+                Vec::new()
+            };
+
+            let mut segment_names: Vec<u8> = Vec::new();
+            for (ix, (seg, orient)) in steps[start..end].iter().enumerate() {
+                if ix > 0 {
+                    segment_names.push(b',');
+                }
+                segment_names.extend_from_slice(&seg.name_bytes());
+                segment_names.push(if orient.is_reverse() {
+                    b'-'
+                } else {
+                    b'+'
+                });
+            }
+
+            Path::new(
+                name.into(),
+                segment_names.into(),
+                overlaps,
+                path.optional.clone(),
+            )
+        })
+        .collect()
+}
+
+/// The largest segment ID that can be used in bubble detection, as
+/// the biedged graph stores two vertices, `2 * id` and `2 * id + 1`,
+/// for each segment
+pub const MAX_BUBBLE_SEGMENT_ID: u64 = (u64::MAX - 1) / 2;
+
+/// Reasons a segment name can't be used as a numeric segment ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentIdError {
+    NotNumeric,
+    ExceedsU64,
+    ExceedsUsize,
+    ExceedsMax(u64),
+}
+
+impl std::fmt::Display for SegmentIdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SegmentIdError::NotNumeric => write!(
+                f,
+                "not a non-negative integer, \
+                 convert the names with `id-convert --to-int` first"
+            ),
+            SegmentIdError::ExceedsU64 => {
+                write!(f, "larger than the maximum of {}", u64::MAX)
+            }
+            SegmentIdError::ExceedsUsize => write!(
+                f,
+                "larger than the maximum of {} on this platform; \
+                 renumber the segments with `id-convert --to-int`",
+                usize::MAX
+            ),
+            SegmentIdError::ExceedsMax(max) => write!(
+                f,
+                "larger than {}, which is the largest ID supported by \
+                 bubble detection; renumber the segments with \
+                 `id-convert --to-int`",
+                max
+            ),
+        }
+    }
+}
+
+/// Parse a segment name as a decimal u64 ID, and check that it's no
+/// larger than `max_id`, and that it fits in a `usize`
+pub fn parse_segment_id(
+    name: &[u8],
+    max_id: u64,
+) -> Result<u64, SegmentIdError> {
+    if name.is_empty() || !name.iter().all(|b| b.is_ascii_digit()) {
+        return Err(SegmentIdError::NotNumeric);
+    }
+
+    let id = name.iter().try_fold(0u64, |acc, &b| {
+        acc.checked_mul(10)?.checked_add(u64::from(b - b'0'))
+    });
+
+    match id {
+        None => Err(SegmentIdError::ExceedsU64),
+        Some(id) if id > usize::MAX as u64 => Err(SegmentIdError::ExceedsUsize),
+        Some(id) if id > max_id => Err(SegmentIdError::ExceedsMax(max_id)),
+        Some(id) => Ok(id),
+    }
+}
+
+/// Intervals from a BED file, by chromosome, using BED's 0-based,
+/// half-open coordinates
+#[derive(Debug, Default, Clone)]
+pub struct BedRegions {
+    regions: FnvHashMap<BString, Vec<(usize, usize)>>,
+}
+
+impl BedRegions {
+    pub fn from_reader<R: std::io::BufRead>(
+        reader: R,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        use bstr::io::BufReadExt;
+
+        let mut regions: FnvHashMap<BString, Vec<(usize, usize)>> =
+            FnvHashMap::default();
+
+        for line in reader.byte_lines() {
+            let line = line?;
+            if line.is_empty()
+                || line.starts_with(b"#")
+                || line.starts_with(b"track")
+                || line.starts_with(b"browser")
+            {
+                continue;
+            }
+
+            let fields: Vec<&[u8]> = line.split_str("\t").collect();
+            if fields.len() < 3 {
+                return Err(
+                    format!("Invalid BED line: {}", line.as_bstr()).into()
+                );
+            }
+
+            let start: usize = fields[1].to_str()?.trim().parse()?;
+            let end: usize = fields[2].to_str()?.trim().parse()?;
+
+            regions
+                .entry(fields[0].into())
+                .or_default()
+                .push((start, end));
+        }
+
+        for intervals in regions.values_mut() {
+            intervals.sort();
+        }
+
+        Ok(Self { regions })
+    }
+
+    pub fn from_file<P: AsRef<std::path::Path>>(
+        path: P,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = std::fs::File::open(path)?;
+        Self::from_reader(std::io::BufReader::new(file))
+    }
+
+    /// Whether the half-open interval `start..end` on `chrom` overlaps
+    /// any of the regions
+    pub fn overlaps(&self, chrom: &[u8], start: usize, end: usize) -> bool {
+        self.regions
+            .get(chrom.as_bstr())
+            .map(|intervals| {
+                intervals
+                    .iter()
+                    .take_while(|&&(r_start, _)| r_start < end)
+                    .any(|&(_, r_end)| r_end > start)
+            })
+            .unwrap_or(false)
+    }
+}
+
+/// A set of node IDs. When the IDs are dense, e.g. after compacting
+/// them with `id-convert`, they're stored in a bitset indexed by ID,
+/// which is much smaller and faster to query than a hash set; sparse
+/// IDs fall back to a hash set.
+#[derive(Debug, Clone)]
+pub enum NodeSet {
+    Dense { bits: Vec<u64>, len: usize },
+    Sparse(FnvHashSet<u64>),
+}
+
+impl NodeSet {
+    pub fn contains(&self, node: u64) -> bool {
+        match self {
+            NodeSet::Dense { bits, .. } => bits
+                .get((node / 64) as usize)
+                .map_or(false, |word| word & (1u64 << (node % 64)) != 0),
+            NodeSet::Sparse(set) => set.contains(&node),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            NodeSet::Dense { len, .. } => *len,
+            NodeSet::Sparse(set) => set.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The IDs in the set, sorted if the set is dense
+    pub fn to_vec(&self) -> Vec<u64> {
+        match self {
+            NodeSet::Dense { bits, .. } => bits
+                .iter()
+                .enumerate()
+                .flat_map(|(ix, &word)| {
+                    (0..64u64)
+                        .filter(move |bit| word & (1u64 << bit) != 0)
+                        .map(move |bit| (ix as u64) * 64 + bit)
+                })
+                .collect(),
+            NodeSet::Sparse(set) => set.iter().copied().collect(),
+        }
+    }
+}
+
+impl std::iter::FromIterator<u64> for NodeSet {
+    fn from_iter<I: IntoIterator<Item = u64>>(iter: I) -> Self {
+        let nodes: Vec<u64> = iter.into_iter().collect();
+        let max_id = nodes.iter().copied().max().unwrap_or(0);
+
+        // A bitset word per four nodes is about as large as a hash
+        // set entry; small ID ranges always use a bitset
+        let words = max_id / 64 + 1;
+        if words > (nodes.len() as u64 * 4).max(1 << 16) {
+            return NodeSet::Sparse(nodes.into_iter().collect());
+        }
+
+        let mut bits = vec![0u64; words as usize];
+        let mut len = 0;
+        for node in nodes {
+            let word = &mut bits[(node / 64) as usize];
+            let mask = 1u64 << (node % 64);
+            if *word & mask == 0 {
+                *word |= mask;
+                len += 1;
+            }
+        }
+        NodeSet::Dense { bits, len }
+    }
+}
+
+/// Windows of `window` bp every `step` bp along a sequence of
+/// `length` bp, as end-exclusive ranges. The last window ends at the
+/// end of the sequence, and may be shorter than the others.
+pub fn window_ranges(
+    length: usize,
+    window: usize,
+    step: usize,
+) -> Vec<(usize, usize)> {
+    let count = match length {
+        0 => 0,
+        _ if length <= window => 1,
+        _ => (length - window + step - 1) / step + 1,
+    };
+    (0..count)
+        .map(|ix| {
+            let start = ix * step;
+            (start, (start + window).min(length))
+        })
+        .collect()
+}
+
+/// Convert a command line argument to bytes without going through
+/// `str`, so that names that aren't valid UTF-8 can be given on the
+/// command line. On platforms where an `OsStr` isn't a byte string,
+/// invalid UTF-8 is replaced lossily.
+pub fn bytes_from_os_str(s: &OsStr) -> BString {
+    match <[u8]>::from_os_str(s) {
+        Some(bytes) => bytes.into(),
+        None => s.to_string_lossy().as_bytes().into(),
+    }
+}
+
+/// The header tag that records the commands that wrote a GFA, like
+/// the @PG lines of a SAM header
+pub const PROGRAM_TAG: &[u8; 2] = b"PG";
+
+/// A record of this run of gfautil, as `gfautil <version> <args>`,
+/// where `args` are the command line arguments, starting with the
+/// program name, which is skipped. Tabs and line breaks, which can't
+/// be part of a tag, are replaced with spaces.
+pub fn program_record<I: IntoIterator<Item = OsString>>(args: I) -> Vec<u8> {
+    let mut record =
+        format!("gfautil {}", env!("CARGO_PKG_VERSION")).into_bytes();
+    for arg in args.into_iter().skip(1) {
+        record.push(b' ');
+        record.extend_from_slice(&bytes_from_os_str(&arg));
+    }
+    for b in record.iter_mut() {
+        if matches!(*b, b'\t' | b'\n' | b'\r') {
+            *b = b' ';
+        }
+    }
+    record
+}
+
+/// Record a program in the PG tag of a header. If the header already
+/// has one, e.g. from an earlier run, the record is appended to it
+/// after a `;`, so the history of the graph is kept.
+pub fn add_program_tag(header: &mut Header<OptionalFields>, record: &[u8]) {
+    let existing = header
+        .optional
+        .iter_mut()
+        .find(|field| &field.tag == PROGRAM_TAG);
+    match existing {
+        Some(OptField {
+            value: OptFieldVal::Z(value),
+            ..
+        }) => {
+            value.push(b';');
+            value.extend_from_slice(record);
+        }
+        Some(field) => field.value = OptFieldVal::Z(record.to_vec().into()),
+        None => header.optional.push(OptField {
+            tag: *PROGRAM_TAG,
+            value: OptFieldVal::Z(record.to_vec().into()),
+        }),
+    }
+}
+
+/// Record a program in a header line that's copied as it is, like
+/// `add_program_tag`
+pub fn add_program_field(line: &[u8], record: &[u8]) -> Vec<u8> {
+    let mut fields: Vec<Vec<u8>> =
+        line.split_str(b"\t").map(|field| field.to_vec()).collect();
+    let existing = fields.iter_mut().skip(1).find(|f| f.starts_with(b"PG:"));
+    match existing {
+        Some(field) if field.starts_with(b"PG:Z:") => {
+            field.push(b';');
+            field.extend_from_slice(record);
+        }
+        Some(field) => *field = [&b"PG:Z:"[..], record].concat(),
+        None => fields.push([&b"PG:Z:"[..], record].concat()),
+    }
+    fields.join(&b"\t"[..])
+}
+
+fn write_opt_fields<W: Write, T: OptFields>(
+    out: &mut W,
+    optional: &T,
+) -> io::Result<()> {
+    for field in optional.fields() {
+        write!(out, "\t{}", field)?;
+    }
+    writeln!(out)
+}
+
+/// Write a GFA with the segment and path names, and the sequences,
+/// copied byte for byte. Unlike `gfa::writer::gfa_string`, which
+/// formats the names through `Display`, this keeps names that aren't
+/// valid UTF-8 intact. The header is written if it has a version or
+/// any other fields.
+pub fn write_gfa_bytes<W: Write, N: SegmentName, T: OptFields>(
+    gfa: &GFA<N, T>,
+    out: &mut W,
+) -> io::Result<()> {
+    if let Some(version) = &gfa.header.version {
+        out.write_all(b"H\tVN:Z:")?;
+        out.write_all(version)?;
+        write_opt_fields(out, &gfa.header.optional)?;
+    } else if !gfa.header.optional.fields().is_empty() {
+        out.write_all(b"H")?;
+        write_opt_fields(out, &gfa.header.optional)?;
+    }
+
+    for seg in gfa.segments.iter() {
+        out.write_all(b"S\t")?;
+        out.write_all(&seg.name.name_bytes())?;
+        out.write_all(b"\t")?;
+        out.write_all(&seg.sequence)?;
+        write_opt_fields(out, &seg.optional)?;
+    }
+
+    for link in gfa.links.iter() {
+        out.write_all(b"L\t")?;
+        out.write_all(&link.from_segment.name_bytes())?;
+        write!(out, "\t{}\t", link.from_orient)?;
+        out.write_all(&link.to_segment.name_bytes())?;
+        write!(out, "\t{}\t", link.to_orient)?;
+        out.write_all(&link.overlap)?;
+        write_opt_fields(out, &link.optional)?;
+    }
+
+    for cont in gfa.containments.iter() {
+        out.write_all(b"C\t")?;
+        out.write_all(&cont.container_name.name_bytes())?;
+        write!(out, "\t{}\t", cont.container_orient)?;
+        out.write_all(&cont.contained_name.name_bytes())?;
+        write!(out, "\t{}\t{}\t", cont.contained_orient, cont.pos)?;
+        out.write_all(&cont.overlap)?;
+        write_opt_fields(out, &cont.optional)?;
+    }
+
+    for path in gfa.paths.iter() {
+        out.write_all(b"P\t")?;
+        out.write_all(&path.path_name)?;
+        out.write_all(b"\t")?;
+        out.write_all(&path.segment_names)?;
+        out.write_all(b"\t")?;
+        if path.overlaps.is_empty() {
+            out.write_all(b"*")?;
+        }
+        for (i, overlap) in path.overlaps.iter().enumerate() {
+            if i > 0 {
+                out.write_all(b",")?;
+            }
+            match overlap {
+                Some(cigar) => write!(out, "{}", cigar)?,
+                None => out.write_all(b"*")?,
+            }
+        }
+        write_opt_fields(out, &path.optional)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn non_utf8_names_are_written_unchanged() {
+        let mut gfa: GFA<Vec<u8>, ()> = Default::default();
+        let name = b"seg\xff\xfe".to_vec();
+        gfa.segments.push(gfa::gfa::Segment {
+            name: name.clone(),
+            sequence: "ACGT".into(),
+            optional: (),
+        });
+
+        let mut out = Vec::new();
+        write_gfa_bytes(&gfa, &mut out).unwrap();
+        assert_eq!(out, b"S\tseg\xff\xfe\tACGT\n".to_vec());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let arg = OsStr::from_bytes(&name);
+            assert_eq!(bytes_from_os_str(arg), BString::from(name));
+        }
+    }
+
+    #[test]
+    fn program_records_are_kept() {
+        let args = vec!["gfautil", "-i", "a.gfa", "subgraph", "--tag", "x\ty"];
+        let record = program_record(args.into_iter().map(OsString::from));
+        let expected = format!(
+            "gfautil {} -i a.gfa subgraph --tag x y",
+            env!("CARGO_PKG_VERSION")
+        );
+        assert_eq!(record, expected.as_bytes());
+
+        let mut gfa: GFA<usize, OptionalFields> = Default::default();
+        add_program_tag(&mut gfa.header, b"gfautil subgraph");
+        let mut out = Vec::new();
+        write_gfa_bytes(&gfa, &mut out).unwrap();
+        assert_eq!(out, b"H\tPG:Z:gfautil subgraph\n".to_vec());
+
+        add_program_tag(&mut gfa.header, b"gfautil clean");
+        gfa.header.version = Some("1.0".into());
+        let mut out = Vec::new();
+        write_gfa_bytes(&gfa, &mut out).unwrap();
+        assert_eq!(
+            out,
+            b"H\tVN:Z:1.0\tPG:Z:gfautil subgraph;gfautil clean\n".to_vec()
+        );
+
+        assert_eq!(
+            add_program_field(b"H\tVN:Z:1.0", b"gfautil subgraph"),
+            b"H\tVN:Z:1.0\tPG:Z:gfautil subgraph".to_vec()
+        );
+        assert_eq!(
+            add_program_field(b"H\tPG:Z:a\tVN:Z:1.0", b"b"),
+            b"H\tPG:Z:a;b\tVN:Z:1.0".to_vec()
+        );
+    }
+
+    #[test]
+    fn segment_id_checks() {
+        assert_eq!(parse_segment_id(b"123", u64::MAX), Ok(123));
+        assert_eq!(
+            parse_segment_id(b"12a", u64::MAX),
+            Err(SegmentIdError::NotNumeric)
+        );
+        assert_eq!(
+            parse_segment_id(b"-1", u64::MAX),
+            Err(SegmentIdError::NotNumeric)
+        );
+        assert_eq!(
+            parse_segment_id(b"18446744073709551616", u64::MAX),
+            Err(SegmentIdError::ExceedsU64)
+        );
+        assert_eq!(
+            parse_segment_id(b"100", 99),
+            Err(SegmentIdError::ExceedsMax(99))
+        );
+    }
+
+    #[test]
+    fn dense_and_sparse_node_sets() {
+        let dense: NodeSet = vec![3, 64, 1, 3, 200].into_iter().collect();
+        assert!(matches!(dense, NodeSet::Dense { .. }));
+        assert_eq!(dense.len(), 4);
+        assert!(dense.contains(64));
+        assert!(!dense.contains(65));
+        assert!(!dense.contains(1 << 40));
+        assert_eq!(dense.to_vec(), vec![1, 3, 64, 200]);
+
+        let sparse: NodeSet = vec![1, 1 << 40].into_iter().collect();
+        assert!(matches!(sparse, NodeSet::Sparse(_)));
+        assert_eq!(sparse.len(), 2);
+        assert!(sparse.contains(1 << 40));
+        assert!(!sparse.contains(2));
+    }
+
+    #[test]
+    fn bed_region_overlaps() {
+        let bed = "track name=test\nchr1\t10\t20\nchr1\t50\t60\tname\n";
+        let regions = BedRegions::from_reader(bed.as_bytes()).unwrap();
+        assert!(regions.overlaps(b"chr1", 15, 16));
+        assert!(regions.overlaps(b"chr1", 0, 11));
+        assert!(!regions.overlaps(b"chr1", 20, 50));
+        assert!(regions.overlaps(b"chr1", 59, 100));
+        assert!(!regions.overlaps(b"chr2", 15, 16));
+    }
+}
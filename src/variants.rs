@@ -1,15 +1,24 @@
+pub mod binary;
+pub mod compact;
+pub mod compare;
+pub mod density;
+pub mod dotplot;
+pub mod traversal;
+pub mod truth;
+pub mod validate;
 pub mod vcf;
 
 use vcf::VCFRecord;
 
 use bstr::{BStr, BString, ByteSlice};
+use clap::arg_enum;
 use fnv::{FnvHashMap, FnvHashSet};
 use indicatif::ParallelProgressIterator;
 use rayon::prelude::*;
 
 use gfa::gfa::{Orientation, GFA};
 
-use crate::util::progress_bar;
+use crate::util::{progress_bar, NodeSet};
 
 use gfa::gfa::Orientation::Forward;
 #[allow(unused_imports)]
@@ -45,6 +54,16 @@ impl PathData {
 
         Some(state.finish())
     }
+
+    /// The length of the sequence spelled by a path
+    pub fn path_length(&self, path: usize) -> usize {
+        match self.paths[path].last() {
+            Some(&(node, offset, _)) => {
+                offset - 1 + self.segment_map.get(&node).map_or(0, |s| s.len())
+            }
+            None => 0,
+        }
+    }
 }
 
 pub fn gfa_path_data(mut gfa: GFA<usize, ()>) -> PathData {
@@ -90,7 +109,7 @@ pub fn gfa_path_data(mut gfa: GFA<usize, ()>) -> PathData {
 
 pub fn bubble_path_indices(
     paths: &[Vec<(usize, usize, Orientation)>],
-    vertices: &FnvHashSet<u64>,
+    vertices: &NodeSet,
 ) -> FnvHashMap<u64, FnvHashMap<usize, usize>> {
     let mut transposed: FnvHashMap<usize, FnvHashMap<u64, usize>> =
         FnvHashMap::default();
@@ -106,7 +125,7 @@ pub fn bubble_path_indices(
                         .enumerate()
                         .filter_map(|(ix, &(step, _, _))| {
                             let step = step as u64;
-                            if vertices.contains(&step) {
+                            if vertices.contains(step) {
                                 Some((step, ix))
                             } else {
                                 None
@@ -124,9 +143,10 @@ pub fn bubble_path_indices(
     let p_bar = progress_bar(vertices.len(), true);
 
     let path_map: FnvHashMap<u64, FnvHashMap<usize, usize>> = vertices
-        .par_iter()
+        .to_vec()
+        .into_par_iter()
         .progress_with(p_bar)
-        .map(|&node| {
+        .map(|node| {
             let inner = transposed
                 .iter()
                 .filter_map(|(path_ix, step_map)| {
@@ -169,8 +189,66 @@ impl std::fmt::Display for Variant {
     }
 }
 
+/// A query path that contains an alternate allele, and the position
+/// of the allele in that path
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct QueryPos {
+    pub name: BString,
+    pub pos: usize,
+}
+
+/// The alternate alleles found at a variant site, each with the
+/// query paths that support it
+pub type VariantSupport = FnvHashMap<Variant, FnvHashSet<QueryPos>>;
+
+/// The sequence of a path step, in the orientation of the step, so
+/// that offsets into it are offsets along the path
+fn step_sequence(
+    segment_sequences: &FnvHashMap<usize, BString>,
+    step: PathStep,
+) -> BString {
+    let (node, _, orient) = step;
+    let seq = segment_sequences.get(&node).unwrap();
+    if orient.is_reverse() {
+        handlegraph::util::dna::rev_comp_iter(seq.as_slice()).collect()
+    } else {
+        seq.clone()
+    }
+}
+
+/// The reference base an indel is anchored on. As in VCF, that's the
+/// last base before the indel, unless the indel is at the start of
+/// the reference path, in which case it's the first base after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IndelAnchor {
+    Before(u8),
+    After(u8),
+}
+
+/// The anchor of an indel at `ref_ix`, where `next_ix` is the first
+/// reference step after the indel, i.e. `ref_ix + 1` for a deletion
+/// of the step at `ref_ix`, and `ref_ix` for an insertion before it.
+/// Returns `None` if there's no reference base on either side.
+fn indel_anchor(
+    segment_sequences: &FnvHashMap<usize, BString>,
+    ref_path: &[PathStep],
+    ref_ix: usize,
+    next_ix: usize,
+) -> Option<IndelAnchor> {
+    if ref_ix > 0 {
+        let prev = step_sequence(segment_sequences, ref_path[ref_ix - 1]);
+        Some(IndelAnchor::Before(*prev.last()?))
+    } else {
+        let next = step_sequence(segment_sequences, *ref_path.get(next_ix)?);
+        Some(IndelAnchor::After(*next.first()?))
+    }
+}
+
 /// Abstraction to handle the different cases in
-/// `detect_variants_against_ref_with`
+/// `detect_variants_against_ref_with`. The `ref_seq_ix` and
+/// `query_seq_ix` arguments are the 1-based positions of the first
+/// base of the current steps; positions inside the nodes are found
+/// by adding offsets into the oriented step sequences.
 trait VariantHandler {
     fn deletion(
         &mut self,
@@ -205,6 +283,9 @@ trait VariantHandler {
     );
 }
 
+/// Returns true if the ref and query ranges were walked to their
+/// ends; if not, some of the variation between them was not passed
+/// to the handler.
 fn detect_variants_against_ref_ranges<H: VariantHandler>(
     segment_sequences: &FnvHashMap<usize, BString>,
     ref_path: &[(usize, usize, Orientation)],
@@ -212,7 +293,7 @@ fn detect_variants_against_ref_ranges<H: VariantHandler>(
     ref_range: (usize, usize),
     query_range: (usize, usize),
     handler: &mut H,
-) {
+) -> bool {
     let (ref_start, ref_end) = ref_range;
     let (query_start, query_end) = query_range;
 
@@ -228,40 +309,54 @@ fn detect_variants_against_ref_ranges<H: VariantHandler>(
         }
 
         let (ref_node, ref_offset, _) = ref_path[ref_ix];
-        let ref_seq = segment_sequences.get(&ref_node).unwrap();
-
         ref_seq_ix = ref_offset;
 
         let (query_node, query_offset, _) = query_path[query_ix];
-        let query_seq = segment_sequences.get(&query_node).unwrap();
-
         query_seq_ix = query_offset;
 
         if ref_node == query_node {
             ref_ix += 1;
             query_ix += 1;
         } else {
-            if ref_ix + 1 > ref_end || query_ix + 1 > query_end {
+            let next_ref_node = if ref_ix < ref_end {
+                Some(ref_path[ref_ix + 1].0)
+            } else {
+                None
+            };
+            let next_query_node = if query_ix < query_end {
+                Some(query_path[query_ix + 1].0)
+            } else {
+                None
+            };
+
+            // If only one of the paths is at its last step, and the
+            // other doesn't rejoin it, the rest can't be walked
+            if next_ref_node.is_some() != next_query_node.is_some()
+                && next_ref_node != Some(query_node)
+                && next_query_node != Some(ref_node)
+            {
                 trace!("At end of ref or query");
-                break;
+                return false;
             }
-            let (next_ref_node, _next_ref_offset, _) = ref_path[ref_ix + 1];
-            let (next_query_node, _next_query_offset, _) =
-                query_path[query_ix + 1];
 
-            if next_ref_node == query_node {
+            if next_ref_node == Some(query_node) {
                 trace!("Deletion at ref {}\t query {}", ref_ix, query_ix);
                 // Deletion
                 handler.deletion(ref_ix, query_ix, ref_seq_ix, query_seq_ix);
 
                 ref_ix += 1;
-            } else if next_query_node == ref_node {
+            } else if next_query_node == Some(ref_node) {
                 trace!("Insertion at ref {}\t query {}", ref_ix, query_ix);
                 // Insertion
                 handler.insertion(ref_ix, query_ix, ref_seq_ix, query_seq_ix);
 
                 query_ix += 1;
             } else {
+                let ref_seq =
+                    step_sequence(segment_sequences, ref_path[ref_ix]);
+                let query_seq =
+                    step_sequence(segment_sequences, query_path[query_ix]);
+
                 if ref_seq != query_seq {
                     handler.mismatch(
                         ref_ix,
@@ -278,6 +373,37 @@ fn detect_variants_against_ref_ranges<H: VariantHandler>(
             }
         }
     }
+
+    if ref_start > ref_end || query_start > query_end {
+        return ref_ix > ref_end && query_ix > query_end;
+    }
+
+    // One of the paths ended before the other, e.g. when a path
+    // stops short of, or continues past, the end of the reference
+    // path; the remaining steps are deleted from, or inserted after
+    // the end of, the other path
+    let step_end = |path: &[PathStep], ix: usize| {
+        let (node, offset, _) = path[ix];
+        offset + segment_sequences.get(&node).map_or(0, |s| s.len())
+    };
+
+    while ref_ix <= ref_end {
+        trace!("Deletion at end of query, at ref {}", ref_ix);
+        let ref_seq_ix = ref_path[ref_ix].1;
+        let query_seq_ix = step_end(query_path, query_end);
+        handler.deletion(ref_ix, query_ix, ref_seq_ix, query_seq_ix);
+        ref_ix += 1;
+    }
+
+    while query_ix <= query_end {
+        trace!("Insertion at end of ref, at query {}", query_ix);
+        let ref_seq_ix = step_end(ref_path, ref_end);
+        let query_seq_ix = query_path[query_ix].1;
+        handler.insertion(ref_ix, query_ix, ref_seq_ix, query_seq_ix);
+        query_ix += 1;
+    }
+
+    true
 }
 
 fn detect_variants_against_ref_with<H: VariantHandler>(
@@ -286,97 +412,67 @@ fn detect_variants_against_ref_with<H: VariantHandler>(
     query_path: &[(usize, usize, Orientation)],
     handler: &mut H,
 ) {
-    let mut ref_ix = 0;
-    let mut query_ix = 0;
-
-    let mut ref_seq_ix;
-    let mut query_seq_ix;
-
-    loop {
-        if ref_ix >= ref_path.len() || query_ix >= query_path.len() {
-            break;
-        }
-
-        let (ref_node, ref_offset, _) = ref_path[ref_ix];
-        let ref_seq = segment_sequences.get(&ref_node).unwrap();
-
-        ref_seq_ix = ref_offset;
-
-        let (query_node, query_offset, _) = query_path[query_ix];
-        let query_seq = segment_sequences.get(&query_node).unwrap();
-
-        query_seq_ix = query_offset;
-
-        if ref_node == query_node {
-            ref_ix += 1;
-            query_ix += 1;
-        } else {
-            if ref_ix + 1 >= ref_path.len() || query_ix + 1 >= query_path.len()
-            {
-                trace!("At end of ref or query");
-                break;
-            }
-            let (next_ref_node, _next_ref_offset, _) = ref_path[ref_ix + 1];
-            let (next_query_node, _next_query_offset, _) =
-                query_path[query_ix + 1];
-
-            if next_ref_node == query_node {
-                trace!("Deletion at ref {}\t query {}", ref_ix, query_ix);
-                // Deletion
-                handler.deletion(ref_ix, query_ix, ref_seq_ix, query_seq_ix);
-
-                ref_ix += 1;
-            } else if next_query_node == ref_node {
-                trace!("Insertion at ref {}\t query {}", ref_ix, query_ix);
-                // Insertion
-                handler.insertion(ref_ix, query_ix, ref_seq_ix, query_seq_ix);
-
-                query_ix += 1;
-            } else {
-                if ref_seq != query_seq {
-                    handler.mismatch(
-                        ref_ix,
-                        query_ix,
-                        ref_seq_ix,
-                        query_seq_ix,
-                    );
-                } else {
-                    handler.match_(ref_ix, query_ix, ref_seq_ix, query_seq_ix);
-                }
-
-                ref_ix += 1;
-                query_ix += 1;
-            }
-        }
+    if ref_path.is_empty() || query_path.is_empty() {
+        return;
     }
+    detect_variants_against_ref_ranges(
+        segment_sequences,
+        ref_path,
+        query_path,
+        (0, ref_path.len() - 1),
+        (0, query_path.len() - 1),
+        handler,
+    );
 }
 
 /// Implementation of `VariantHandler` that fills a hashmap of
 /// variants, same as the original `detect_variants_against_ref`
 #[derive(Debug, Clone)]
 struct VCFVariantHandler<'a> {
+    config: VariantConfig,
     segment_sequences: &'a FnvHashMap<usize, BString>,
     ref_name: &'a [u8],
+    query_names: &'a [&'a [u8]],
     ref_path: &'a [(usize, usize, Orientation)],
     query_path: &'a [(usize, usize, Orientation)],
-    variants: FnvHashMap<VariantKey, FnvHashSet<Variant>>,
+    variants: FnvHashMap<VariantKey, VariantSupport>,
 }
 
 impl<'a> VCFVariantHandler<'a> {
     fn new(
+        config: VariantConfig,
         segment_sequences: &'a FnvHashMap<usize, BString>,
         ref_name: &'a [u8],
+        query_names: &'a [&'a [u8]],
         ref_path: &'a [(usize, usize, Orientation)],
         query_path: &'a [(usize, usize, Orientation)],
     ) -> Self {
         Self {
+            config,
             segment_sequences,
             ref_name,
+            query_names,
             ref_path,
             query_path,
             variants: FnvHashMap::default(),
         }
     }
+
+    fn add_variant(
+        &mut self,
+        var_key: VariantKey,
+        variant: Variant,
+        query_pos: usize,
+    ) {
+        let entry = self.variants.entry(var_key).or_default();
+        let queries = entry.entry(variant).or_default();
+        for &name in self.query_names {
+            queries.insert(QueryPos {
+                name: name.into(),
+                pos: query_pos,
+            });
+        }
+    }
 }
 
 impl<'a> VariantHandler for VCFVariantHandler<'a> {
@@ -385,36 +481,55 @@ impl<'a> VariantHandler for VCFVariantHandler<'a> {
         ref_ix: usize,
         _query_ix: usize,
         ref_seq_ix: usize,
-        _query_seq_ix: usize,
+        query_seq_ix: usize,
     ) {
-        let (ref_node, _ref_offset, _) = self.ref_path[ref_ix];
-        let ref_seq = self.segment_sequences.get(&ref_node).unwrap();
-
-        // Deletion
-        let (prev_ref_node, _prev_ref_offset, _) = if ref_ix == 0 {
-            self.ref_path[ref_ix]
-        } else {
-            self.ref_path[ref_ix - 1]
+        let ref_seq =
+            step_sequence(self.segment_sequences, self.ref_path[ref_ix]);
+
+        let anchor = match indel_anchor(
+            self.segment_sequences,
+            self.ref_path,
+            ref_ix,
+            ref_ix + 1,
+        ) {
+            Some(anchor) => anchor,
+            None => {
+                debug!("Skipping unanchored deletion at ref {}", ref_ix);
+                return;
+            }
         };
 
-        let prev_ref_seq = self.segment_sequences.get(&prev_ref_node).unwrap();
-
-        let last_prev_seq: u8 = *prev_ref_seq.last().unwrap();
-
-        let key_ref_seq: BString = std::iter::once(last_prev_seq)
-            .chain(ref_seq.iter().copied())
-            .collect();
+        let (base, key_ref_seq, pos, query_pos): (u8, BString, _, _) =
+            match anchor {
+                IndelAnchor::Before(base) => (
+                    base,
+                    std::iter::once(base)
+                        .chain(ref_seq.iter().copied())
+                        .collect(),
+                    ref_seq_ix - 1,
+                    query_seq_ix - 1,
+                ),
+                IndelAnchor::After(base) => (
+                    base,
+                    ref_seq
+                        .iter()
+                        .copied()
+                        .chain(std::iter::once(base))
+                        .collect(),
+                    ref_seq_ix,
+                    query_seq_ix,
+                ),
+            };
 
         let var_key = VariantKey {
             ref_name: self.ref_name.into(),
-            pos: ref_seq_ix - 1,
+            pos,
             sequence: key_ref_seq,
         };
 
-        let variant = Variant::Del(BString::from(&[last_prev_seq][..]));
+        let variant = Variant::Del(BString::from(&[base][..]));
 
-        let entry = self.variants.entry(var_key).or_default();
-        entry.insert(variant);
+        self.add_variant(var_key, variant, query_pos);
     }
 
     fn insertion(
@@ -422,35 +537,53 @@ impl<'a> VariantHandler for VCFVariantHandler<'a> {
         ref_ix: usize,
         query_ix: usize,
         ref_seq_ix: usize,
-        _query_seq_ix: usize,
+        query_seq_ix: usize,
     ) {
-        let (query_node, _query_offset, _) = self.query_path[query_ix];
-        let query_seq = self.segment_sequences.get(&query_node).unwrap();
-
-        let (prev_ref_node, _prev_ref_offset, _) = if ref_ix == 0 {
-            self.ref_path[ref_ix]
-        } else {
-            self.ref_path[ref_ix - 1]
+        let query_seq =
+            step_sequence(self.segment_sequences, self.query_path[query_ix]);
+
+        let anchor = match indel_anchor(
+            self.segment_sequences,
+            self.ref_path,
+            ref_ix,
+            ref_ix,
+        ) {
+            Some(anchor) => anchor,
+            None => {
+                debug!("Skipping unanchored insertion at ref {}", ref_ix);
+                return;
+            }
         };
-        let prev_ref_seq = self.segment_sequences.get(&prev_ref_node).unwrap();
-
-        let last_prev_seq: u8 = *prev_ref_seq.last().unwrap();
 
-        let key_ref_seq: BString = std::iter::once(last_prev_seq).collect();
+        let (base, var_seq, pos, query_pos): (u8, BString, _, _) = match anchor
+        {
+            IndelAnchor::Before(base) => (
+                base,
+                std::iter::once(base)
+                    .chain(query_seq.iter().copied())
+                    .collect(),
+                ref_seq_ix - 1,
+                query_seq_ix - 1,
+            ),
+            IndelAnchor::After(base) => (
+                base,
+                query_seq
+                    .iter()
+                    .copied()
+                    .chain(std::iter::once(base))
+                    .collect(),
+                ref_seq_ix,
+                query_seq_ix,
+            ),
+        };
 
         let var_key = VariantKey {
             ref_name: self.ref_name.into(),
-            pos: ref_seq_ix - 1,
-            sequence: key_ref_seq,
+            pos,
+            sequence: std::iter::once(base).collect(),
         };
 
-        let var_seq: BString = std::iter::once(last_prev_seq)
-            .chain(query_seq.iter().copied())
-            .collect();
-        let variant = Variant::Ins(var_seq);
-
-        let entry = self.variants.entry(var_key).or_default();
-        entry.insert(variant);
+        self.add_variant(var_key, Variant::Ins(var_seq), query_pos);
     }
 
     fn mismatch(
@@ -458,31 +591,81 @@ impl<'a> VariantHandler for VCFVariantHandler<'a> {
         ref_ix: usize,
         query_ix: usize,
         ref_seq_ix: usize,
-        _query_seq_ix: usize,
+        query_seq_ix: usize,
     ) {
-        let (ref_node, _ref_offset, _) = self.ref_path[ref_ix];
-        let ref_seq = self.segment_sequences.get(&ref_node).unwrap();
+        let ref_seq =
+            step_sequence(self.segment_sequences, self.ref_path[ref_ix]);
+        let query_seq =
+            step_sequence(self.segment_sequences, self.query_path[query_ix]);
+
+        if self.config.decompose_mnp
+            && ref_seq.len() > 1
+            && ref_seq.len() == query_seq.len()
+        {
+            trace!("Decomposing MNP at ref {}\t query {}", ref_ix, query_ix);
+            for (offset, (&ref_base, &query_base)) in
+                ref_seq.iter().zip(query_seq.iter()).enumerate()
+            {
+                if ref_base != query_base {
+                    let var_key = VariantKey {
+                        ref_name: self.ref_name.into(),
+                        pos: ref_seq_ix + offset,
+                        sequence: std::iter::once(ref_base).collect(),
+                    };
+                    self.add_variant(
+                        var_key,
+                        Variant::Snv(query_base),
+                        query_seq_ix + offset,
+                    );
+                }
+            }
+            return;
+        }
+
+        // Equal-length alleles are trimmed to the span between the
+        // first and last mismatching bases, so that the position
+        // points at the actual variation inside the node
+        let (start, end) = if ref_seq.len() == query_seq.len() {
+            let mismatches = ref_seq
+                .iter()
+                .zip(query_seq.iter())
+                .enumerate()
+                .filter(|(_, (r, q))| r != q)
+                .map(|(ix, _)| ix);
+            let first = mismatches.clone().next().unwrap_or(0);
+            let last = mismatches.last().unwrap_or(ref_seq.len() - 1);
+            (first, last + 1)
+        } else {
+            (0, ref_seq.len())
+        };
 
-        let (query_node, _query_offset, _) = self.query_path[query_ix];
-        let query_seq = self.segment_sequences.get(&query_node).unwrap();
+        let ref_allele = &ref_seq[start..end];
 
         let var_key = VariantKey {
             ref_name: self.ref_name.into(),
-            pos: ref_seq_ix,
-            sequence: ref_seq.as_bstr().to_owned(),
+            pos: ref_seq_ix + start,
+            sequence: ref_allele.as_bstr().to_owned(),
         };
 
-        let variant = if ref_seq.len() == 1 {
+        let variant = if ref_allele.len() == 1 {
             trace!("SNV at ref {}\t query {}", ref_ix, query_ix);
-            let last_query_seq: u8 = *query_seq.last().unwrap();
-            Variant::Snv(last_query_seq)
+            let query_end = if ref_seq.len() == query_seq.len() {
+                end
+            } else {
+                query_seq.len()
+            };
+            Variant::Snv(query_seq[query_end - 1])
         } else {
             trace!("MNP at ref {}\t query {}", ref_ix, query_ix);
-            Variant::Mnp(query_seq.as_bstr().to_owned())
+            let query_allele = if ref_seq.len() == query_seq.len() {
+                &query_seq[start..end]
+            } else {
+                query_seq.as_slice()
+            };
+            Variant::Mnp(query_allele.as_bstr().to_owned())
         };
 
-        let entry = self.variants.entry(var_key).or_default();
-        entry.insert(variant);
+        self.add_variant(var_key, variant, query_seq_ix + start);
     }
 
     fn match_(
@@ -503,12 +686,57 @@ pub struct SNPRow {
     pub query_base: u8,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum VariantKind {
+    Snv,
+    Ins,
+    Del,
+}
+
+impl std::fmt::Display for VariantKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VariantKind::Snv => write!(f, "snv"),
+            VariantKind::Ins => write!(f, "ins"),
+            VariantKind::Del => write!(f, "del"),
+        }
+    }
+}
+
+/// A SNP, insertion, or deletion in a query path compared to the
+/// reference. Indels are anchored on the reference base preceding
+/// them, as in VCF, so both sequences start with that base; indels
+/// at the start of the reference are anchored on the base following
+/// them instead, so both sequences end with it.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct VariantRow {
+    pub ref_pos: usize,
+    pub query_pos: usize,
+    pub kind: VariantKind,
+    pub ref_seq: BString,
+    pub query_seq: BString,
+}
+
+impl From<SNPRow> for VariantRow {
+    fn from(snp: SNPRow) -> Self {
+        Self {
+            ref_pos: snp.ref_pos,
+            query_pos: snp.query_pos,
+            kind: VariantKind::Snv,
+            ref_seq: std::iter::once(snp.ref_base).collect(),
+            query_seq: std::iter::once(snp.query_base).collect(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct SNPVariantHandler<'a> {
     segment_sequences: &'a FnvHashMap<usize, BString>,
     ref_path: &'a [(usize, usize, Orientation)],
     query_path: &'a [(usize, usize, Orientation)],
     snp_rows: Vec<SNPRow>,
+    include_indels: bool,
+    indel_rows: Vec<VariantRow>,
 }
 
 impl<'a> SNPVariantHandler<'a> {
@@ -522,53 +750,134 @@ impl<'a> SNPVariantHandler<'a> {
             ref_path,
             query_path,
             snp_rows: Vec::new(),
+            include_indels: false,
+            indel_rows: Vec::new(),
         }
     }
+
+    /// Also collect insertions and deletions, in `indel_rows`
+    fn with_indels(mut self, include_indels: bool) -> Self {
+        self.include_indels = include_indels;
+        self
+    }
+
+    fn anchor(&self, ref_ix: usize, next_ix: usize) -> Option<IndelAnchor> {
+        indel_anchor(self.segment_sequences, self.ref_path, ref_ix, next_ix)
+    }
 }
 
 impl<'a> VariantHandler for SNPVariantHandler<'a> {
-    fn deletion(&mut self, _: usize, _: usize, _: usize, _: usize) {}
-    fn insertion(&mut self, _: usize, _: usize, _: usize, _: usize) {}
-
-    fn mismatch(
+    fn deletion(
         &mut self,
         ref_ix: usize,
-        query_ix: usize,
+        _query_ix: usize,
         ref_seq_ix: usize,
         query_seq_ix: usize,
     ) {
-        let (ref_node, _ref_offset, _) = self.ref_path[ref_ix];
-        let ref_seq = self.segment_sequences.get(&ref_node).unwrap();
-
-        let (query_node, _query_offset, _) = self.query_path[query_ix];
-        let query_seq = self.segment_sequences.get(&query_node).unwrap();
-
-        if ref_seq.len() == 1 && query_seq.len() == 1 {
-            let ref_base = ref_seq[0];
-            let query_base = query_seq[0];
+        if !self.include_indels {
+            return;
+        }
 
-            let snp_row = SNPRow {
+        let ref_seq =
+            step_sequence(self.segment_sequences, self.ref_path[ref_ix]);
+
+        let row = match self.anchor(ref_ix, ref_ix + 1) {
+            Some(IndelAnchor::Before(anchor)) => VariantRow {
+                ref_pos: ref_seq_ix - 1,
+                query_pos: query_seq_ix - 1,
+                kind: VariantKind::Del,
+                ref_seq: std::iter::once(anchor)
+                    .chain(ref_seq.iter().copied())
+                    .collect(),
+                query_seq: std::iter::once(anchor).collect(),
+            },
+            Some(IndelAnchor::After(anchor)) => VariantRow {
                 ref_pos: ref_seq_ix,
                 query_pos: query_seq_ix,
-                ref_base,
-                query_base,
-            };
-            self.snp_rows.push(snp_row);
-        } else {
-            debug!("TODO: SNPVariantHandler ignoring mismatch with ref and/or query nodes not being length 1");
+                kind: VariantKind::Del,
+                ref_seq: ref_seq
+                    .iter()
+                    .copied()
+                    .chain(std::iter::once(anchor))
+                    .collect(),
+                query_seq: std::iter::once(anchor).collect(),
+            },
+            None => return,
+        };
+        self.indel_rows.push(row);
+    }
 
-            /*
-            let ref_base = ref_seq[0];
-            let query_base = query_seq[0];
+    fn insertion(
+        &mut self,
+        ref_ix: usize,
+        query_ix: usize,
+        ref_seq_ix: usize,
+        query_seq_ix: usize,
+    ) {
+        if !self.include_indels {
+            return;
+        }
 
-            let snp_row = SNPRow {
+        let query_seq =
+            step_sequence(self.segment_sequences, self.query_path[query_ix]);
+
+        let row = match self.anchor(ref_ix, ref_ix) {
+            Some(IndelAnchor::Before(anchor)) => VariantRow {
+                ref_pos: ref_seq_ix - 1,
+                query_pos: query_seq_ix - 1,
+                kind: VariantKind::Ins,
+                ref_seq: std::iter::once(anchor).collect(),
+                query_seq: std::iter::once(anchor)
+                    .chain(query_seq.iter().copied())
+                    .collect(),
+            },
+            Some(IndelAnchor::After(anchor)) => VariantRow {
                 ref_pos: ref_seq_ix,
                 query_pos: query_seq_ix,
-                ref_base,
-                query_base,
-            };
-            self.snp_rows.push(snp_row);
-            */
+                kind: VariantKind::Ins,
+                ref_seq: std::iter::once(anchor).collect(),
+                query_seq: query_seq
+                    .iter()
+                    .copied()
+                    .chain(std::iter::once(anchor))
+                    .collect(),
+            },
+            None => return,
+        };
+        self.indel_rows.push(row);
+    }
+
+    fn mismatch(
+        &mut self,
+        ref_ix: usize,
+        query_ix: usize,
+        ref_seq_ix: usize,
+        query_seq_ix: usize,
+    ) {
+        let ref_seq =
+            step_sequence(self.segment_sequences, self.ref_path[ref_ix]);
+        let query_seq =
+            step_sequence(self.segment_sequences, self.query_path[query_ix]);
+
+        if ref_seq.len() == query_seq.len() {
+            for (offset, (&ref_base, &query_base)) in
+                ref_seq.iter().zip(query_seq.iter()).enumerate()
+            {
+                if ref_base != query_base {
+                    self.snp_rows.push(SNPRow {
+                        ref_pos: ref_seq_ix + offset,
+                        query_pos: query_seq_ix + offset,
+                        ref_base,
+                        query_base,
+                    });
+                }
+            }
+        } else {
+            debug!(
+                "SNPVariantHandler ignoring mismatch between nodes of \
+                 different lengths, at ref {}\t query {}",
+                ref_ix, query_ix
+            );
         }
     }
 
@@ -583,9 +892,61 @@ fn sub_path_edge_orient(
     (from, to)
 }
 
+arg_enum! {
+    /// How to compute the QUAL column from the number of paths
+    /// traversing a bubble, and the number that support an
+    /// alternate allele at the variant site
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum QualityScore {
+        None,
+        Count,
+        Fraction,
+        Phred,
+    }
+}
+
+impl QualityScore {
+    const MAX_PHRED: i32 = 99;
+
+    pub fn score(&self, supporting: usize, total: usize) -> Option<i32> {
+        let fraction = if total == 0 {
+            0.0
+        } else {
+            supporting as f64 / total as f64
+        };
+
+        match self {
+            QualityScore::None => None,
+            QualityScore::Count => Some(supporting as i32),
+            QualityScore::Fraction => Some((fraction * 100.0).round() as i32),
+            QualityScore::Phred => {
+                let not_supporting = 1.0 - fraction;
+                if not_supporting <= 0.0 {
+                    Some(Self::MAX_PHRED)
+                } else {
+                    let phred = (-10.0 * not_supporting.log10()).round() as i32;
+                    Some(phred.min(Self::MAX_PHRED))
+                }
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct VariantConfig {
     pub ignore_inverted_paths: bool,
+    /// Split MNPs whose reference and alternate alleles are of equal
+    /// length into one SNV per differing base
+    pub decompose_mnp: bool,
+    /// Add the QNAME and QSTART INFO fields, listing the query paths
+    /// that contain each alternate allele, and the positions of the
+    /// allele in those paths
+    pub query_coords: bool,
+    /// Add the PATHS INFO field, listing the query paths that
+    /// contain any of the alternate alleles
+    pub path_names: bool,
+    /// Scoring function for the QUAL column
+    pub quality: QualityScore,
 }
 
 impl VariantConfig {
@@ -607,13 +968,17 @@ impl Default for VariantConfig {
     fn default() -> Self {
         Self {
             ignore_inverted_paths: true,
+            decompose_mnp: false,
+            query_coords: false,
+            path_names: false,
+            quality: QualityScore::None,
         }
     }
 }
 
 pub type PathIndices = FnvHashMap<u64, FnvHashMap<usize, usize>>;
 
-fn path_data_sub_path_ranges(
+pub(crate) fn path_data_sub_path_ranges(
     path_data: &PathData,
     path_indices: &PathIndices,
     from: u64,
@@ -655,8 +1020,8 @@ pub fn detect_variants_in_sub_paths(
     path_indices: &FnvHashMap<u64, FnvHashMap<usize, usize>>,
     from: u64,
     to: u64,
-) -> Option<FnvHashMap<BString, FnvHashMap<VariantKey, FnvHashSet<Variant>>>> {
-    let mut variants: FnvHashMap<BString, FnvHashMap<_, FnvHashSet<_>>> =
+) -> Option<FnvHashMap<BString, FnvHashMap<VariantKey, VariantSupport>>> {
+    let mut variants: FnvHashMap<BString, FnvHashMap<_, VariantSupport>> =
         FnvHashMap::default();
 
     let sub_path_ranges =
@@ -691,26 +1056,29 @@ pub fn detect_variants_in_sub_paths(
         xs.cmp(ys)
     });
 
-    query_path_ranges.dedup_by(
-        |&mut (x_ix, (x0, x1)), &mut (y_ix, (y0, y1))| {
-            let x = path_data.paths.get(x_ix).unwrap();
-            let y = path_data.paths.get(y_ix).unwrap();
-
-            // let xs = &x[x0..=x1];
-            // let ys = &y[y0..=y1];
-
-            let xa = x0.min(x1);
-            let xb = x0.max(x1);
-
-            let ya = y0.min(y1);
-            let yb = y0.max(y1);
-
-            let xs = &x[xa..=xb];
-            let ys = &y[ya..=yb];
-
-            xs == ys
-        },
-    );
+    // Query paths that traverse the bubble identically (including
+    // the step offsets) are grouped, so that the variants only have
+    // to be detected once, but are attributed to every path in the group
+    let mut query_groups: Vec<((usize, (usize, usize)), Vec<&[u8]>)> =
+        Vec::new();
+
+    for &(query_ix, query_range) in query_path_ranges.iter() {
+        let query_name = path_data.path_names[query_ix].as_slice();
+        let query_steps =
+            sub_path_slice(&path_data.paths[query_ix], query_range);
+
+        match query_groups.last_mut() {
+            Some(((rep_ix, rep_range), names))
+                if sub_path_slice(&path_data.paths[*rep_ix], *rep_range)
+                    == query_steps =>
+            {
+                names.push(query_name);
+            }
+            _ => {
+                query_groups.push(((query_ix, query_range), vec![query_name]));
+            }
+        }
+    }
 
     variants.extend(sub_path_ranges.iter().filter_map(
         |&(ref_ix, (ref_from, ref_to))| {
@@ -722,10 +1090,11 @@ pub fn detect_variants_in_sub_paths(
             let ref_path = path_data.paths.get(ref_ix).unwrap();
             let ref_orient = sub_path_edge_orient(ref_path);
 
-            let mut ref_map: FnvHashMap<VariantKey, FnvHashSet<_>> =
+            let mut ref_map: FnvHashMap<VariantKey, VariantSupport> =
                 FnvHashMap::default();
 
-            for &(query_ix, (query_from, query_to)) in query_path_ranges.iter()
+            for &((query_ix, (query_from, query_to)), ref query_names) in
+                query_groups.iter()
             {
                 let query_name = path_data.path_names.get(query_ix)?;
                 let query_path = path_data.paths.get(query_ix).unwrap();
@@ -736,8 +1105,10 @@ pub fn detect_variants_in_sub_paths(
                     && !variant_config.ignore_path(ref_orient, query_orient)
                 {
                     let mut handler = VCFVariantHandler::new(
+                        *variant_config,
                         &path_data.segment_map,
                         ref_name,
+                        query_names,
                         ref_path,
                         query_path,
                     );
@@ -751,8 +1122,11 @@ pub fn detect_variants_in_sub_paths(
                         &mut handler,
                     );
 
-                    for (var_key, var_set) in handler.variants {
-                        ref_map.entry(var_key).or_default().extend(var_set);
+                    for (var_key, var_support) in handler.variants {
+                        let entry = ref_map.entry(var_key).or_default();
+                        for (variant, queries) in var_support {
+                            entry.entry(variant).or_default().extend(queries);
+                        }
                     }
                 }
             }
@@ -765,6 +1139,14 @@ pub fn detect_variants_in_sub_paths(
     Some(variants)
 }
 
+pub(crate) fn sub_path_slice(
+    path: &[PathStep],
+    range: (usize, usize),
+) -> &[PathStep] {
+    let (from, to) = range;
+    &path[from.min(to)..=from.max(to)]
+}
+
 fn path_data_sub_paths<'a, 'b>(
     path_data: &'a PathData,
     path_indices: &'b PathIndices,
@@ -795,28 +1177,65 @@ fn path_data_sub_paths<'a, 'b>(
     Some(sub_paths)
 }
 
-pub fn find_snps_in_sub_paths(
+/// Return the number of distinct node traversals of each of the
+/// given bubbles, among the paths that pass through both of its
+/// boundary nodes. Bubbles that no path passes through have zero
+/// traversals.
+pub fn bubble_traversal_counts(
     path_data: &PathData,
+    path_indices: &PathIndices,
+    bubbles: &[(u64, u64)],
+) -> Vec<usize> {
+    bubbles
+        .par_iter()
+        .map(|&(from, to)| {
+            let sub_paths =
+                path_data_sub_paths(path_data, path_indices, from, to)
+                    .unwrap_or_default();
+
+            let traversals: FnvHashSet<Vec<(usize, bool)>> = sub_paths
+                .iter()
+                .map(|(_, steps)| {
+                    steps
+                        .iter()
+                        .map(|&(node, _, orient)| (node, orient.is_reverse()))
+                        .collect()
+                })
+                .collect();
+
+            traversals.len()
+        })
+        .collect()
+}
+
+/// Run the SNP handler on every path that traverses the bubble,
+/// against the reference path, calling `f` with each query path name
+/// and its finished handler
+fn for_each_sub_path_handler<'a, F>(
+    path_data: &'a PathData,
     ref_path_ix: usize,
     path_indices: &PathIndices,
     from: u64,
     to: u64,
-) -> Option<FnvHashMap<BString, Vec<SNPRow>>> {
-    let mut query_snp_map: FnvHashMap<BString, Vec<SNPRow>> =
-        FnvHashMap::default();
-
+    include_indels: bool,
+    mut f: F,
+) -> Option<()>
+where
+    F: FnMut(&'a BString, SNPVariantHandler<'a>),
+{
     let sub_paths = path_data_sub_paths(path_data, path_indices, from, to)?;
 
     let ref_sub_path = sub_paths.iter().find(|&(ix, _)| ix == &ref_path_ix)?;
     let ref_sub_path = ref_sub_path.1;
 
-    for (path_ix, query_path) in sub_paths.iter() {
-        if let Some(query_name) = path_data.path_names.get(*path_ix) {
+    for &(path_ix, query_path) in sub_paths.iter() {
+        if let Some(query_name) = path_data.path_names.get(path_ix) {
             let mut snp_handler = SNPVariantHandler::new(
                 &path_data.segment_map,
                 ref_sub_path,
                 query_path,
-            );
+            )
+            .with_indels(include_indels);
 
             detect_variants_against_ref_with(
                 &path_data.segment_map,
@@ -825,55 +1244,440 @@ pub fn find_snps_in_sub_paths(
                 &mut snp_handler,
             );
 
-            let snp_rows = snp_handler.snp_rows;
-
-            let query_name: BString = query_name.clone();
-            let entry = query_snp_map.entry(query_name).or_default();
-            entry.extend(snp_rows);
+            f(query_name, snp_handler);
         }
     }
 
+    Some(())
+}
+
+pub fn find_snps_in_sub_paths(
+    path_data: &PathData,
+    ref_path_ix: usize,
+    path_indices: &PathIndices,
+    from: u64,
+    to: u64,
+) -> Option<FnvHashMap<BString, Vec<SNPRow>>> {
+    let mut query_snp_map: FnvHashMap<BString, Vec<SNPRow>> =
+        FnvHashMap::default();
+
+    for_each_sub_path_handler(
+        path_data,
+        ref_path_ix,
+        path_indices,
+        from,
+        to,
+        false,
+        |query_name, snp_handler| {
+            let entry = query_snp_map.entry(query_name.clone()).or_default();
+            entry.extend(snp_handler.snp_rows);
+        },
+    )?;
+
     Some(query_snp_map)
 }
 
+/// Like `find_snps_in_sub_paths`, but returns `VariantRow`s, which
+/// also include the insertions and deletions if `include_indels` is
+/// true
+pub fn find_variant_rows_in_sub_paths(
+    path_data: &PathData,
+    ref_path_ix: usize,
+    path_indices: &PathIndices,
+    from: u64,
+    to: u64,
+    include_indels: bool,
+) -> Option<FnvHashMap<BString, Vec<VariantRow>>> {
+    let mut query_row_map: FnvHashMap<BString, Vec<VariantRow>> =
+        FnvHashMap::default();
+
+    for_each_sub_path_handler(
+        path_data,
+        ref_path_ix,
+        path_indices,
+        from,
+        to,
+        include_indels,
+        |query_name, snp_handler| {
+            let entry = query_row_map.entry(query_name.clone()).or_default();
+            entry
+                .extend(snp_handler.snp_rows.into_iter().map(VariantRow::from));
+            entry.extend(snp_handler.indel_rows);
+        },
+    )?;
+
+    Some(query_row_map)
+}
+
+/// The indices of the paths that traverse the bubble between `from`
+/// and `to`, i.e. that contain both nodes at different steps, sorted
+pub fn bubble_path_ixs(
+    path_indices: &PathIndices,
+    from: u64,
+    to: u64,
+) -> Vec<usize> {
+    let mut path_ixs: Vec<usize> =
+        match (path_indices.get(&from), path_indices.get(&to)) {
+            (Some(from_indices), Some(to_indices)) => from_indices
+                .iter()
+                .filter(|(path_ix, from_ix)| {
+                    to_indices
+                        .get(*path_ix)
+                        .map(|to_ix| to_ix != *from_ix)
+                        .unwrap_or(false)
+                })
+                .map(|(path_ix, _)| *path_ix)
+                .collect(),
+            _ => Vec::new(),
+        };
+    path_ixs.sort_unstable();
+    path_ixs
+}
+
+/// The number of paths that traverse the bubble between `from` and
+/// `to`, i.e. that contain both nodes at different steps
+pub fn bubble_path_count(
+    path_indices: &PathIndices,
+    from: u64,
+    to: u64,
+) -> usize {
+    bubble_path_ixs(path_indices, from, to).len()
+}
+
+/// The differences between two paths, summed over the bubbles they
+/// both traverse
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PairDifferences {
+    /// The number of bubbles both paths traverse
+    pub bubbles: usize,
+    /// The number of mismatching bases between nodes of equal length
+    pub snvs: usize,
+    /// The number of nodes inserted in or deleted from one path
+    /// relative to the other
+    pub indels: usize,
+    /// The number of mismatching nodes of different lengths
+    pub complex: usize,
+}
+
+impl PairDifferences {
+    pub fn total(&self) -> usize {
+        self.snvs + self.indels + self.complex
+    }
+
+    fn merge(&mut self, other: &PairDifferences) {
+        self.bubbles += other.bubbles;
+        self.snvs += other.snvs;
+        self.indels += other.indels;
+        self.complex += other.complex;
+    }
+}
+
+/// Implementation of `VariantHandler` that only counts the
+/// differences between the paths
+#[derive(Debug, Clone)]
+struct DifferenceHandler<'a> {
+    segment_sequences: &'a FnvHashMap<usize, BString>,
+    ref_path: &'a [PathStep],
+    query_path: &'a [PathStep],
+    differences: PairDifferences,
+}
+
+impl<'a> VariantHandler for DifferenceHandler<'a> {
+    fn deletion(&mut self, _: usize, _: usize, _: usize, _: usize) {
+        self.differences.indels += 1;
+    }
+
+    fn insertion(&mut self, _: usize, _: usize, _: usize, _: usize) {
+        self.differences.indels += 1;
+    }
+
+    fn mismatch(
+        &mut self,
+        ref_ix: usize,
+        query_ix: usize,
+        _ref_seq_ix: usize,
+        _query_seq_ix: usize,
+    ) {
+        let ref_seq =
+            step_sequence(self.segment_sequences, self.ref_path[ref_ix]);
+        let query_seq =
+            step_sequence(self.segment_sequences, self.query_path[query_ix]);
+
+        if ref_seq.len() == query_seq.len() {
+            self.differences.snvs += ref_seq
+                .iter()
+                .zip(query_seq.iter())
+                .filter(|(r, q)| r != q)
+                .count();
+        } else {
+            self.differences.complex += 1;
+        }
+    }
+
+    fn match_(&mut self, _: usize, _: usize, _: usize, _: usize) {}
+}
+
+/// Count the differences between every pair of paths that traverse
+/// the same bubbles, keyed by the pair of path indices, lowest first.
+///
+/// Paths that traverse a bubble through the same oriented nodes are
+/// grouped, so each pair of distinct traversals is only compared
+/// once. Pairs whose traversals run in opposite directions through a
+/// bubble aren't compared there. The bubbles are processed in
+/// parallel.
+pub fn pairwise_differences(
+    path_data: &PathData,
+    path_indices: &PathIndices,
+    bubbles: &[(u64, u64)],
+) -> FnvHashMap<(usize, usize), PairDifferences> {
+    type PairMap = FnvHashMap<(usize, usize), PairDifferences>;
+
+    let merge_maps = |mut a: PairMap, b: PairMap| {
+        for (pair, diffs) in b {
+            a.entry(pair).or_default().merge(&diffs);
+        }
+        a
+    };
+
+    let p_bar = progress_bar(bubbles.len(), false);
+
+    bubbles
+        .par_iter()
+        .progress_with(p_bar)
+        .map(|&(from, to)| {
+            let mut pairs = PairMap::default();
+
+            let sub_paths =
+                path_data_sub_paths(path_data, path_indices, from, to)
+                    .unwrap_or_default();
+
+            let mut groups: Vec<(&[PathStep], Vec<usize>)> = Vec::new();
+            let mut group_ixs: FnvHashMap<Vec<(usize, bool)>, usize> =
+                FnvHashMap::default();
+            for &(path_ix, steps) in sub_paths.iter() {
+                let key = steps
+                    .iter()
+                    .map(|&(node, _, orient)| (node, orient.is_reverse()))
+                    .collect();
+                let group_ix = *group_ixs.entry(key).or_insert_with(|| {
+                    groups.push((steps, Vec::new()));
+                    groups.len() - 1
+                });
+                groups[group_ix].1.push(path_ix);
+            }
+
+            for (ix, (ref_steps, ref_paths)) in groups.iter().enumerate() {
+                for (jx, (query_steps, query_paths)) in
+                    groups.iter().enumerate().skip(ix)
+                {
+                    // Identical traversals have no differences, and
+                    // traversals in opposite directions can't be
+                    // compared
+                    let mut differences = PairDifferences {
+                        bubbles: 1,
+                        ..PairDifferences::default()
+                    };
+                    if ix != jx {
+                        if ref_steps[0].0 != query_steps[0].0 {
+                            continue;
+                        }
+                        let mut handler = DifferenceHandler {
+                            segment_sequences: &path_data.segment_map,
+                            ref_path: ref_steps,
+                            query_path: query_steps,
+                            differences,
+                        };
+                        detect_variants_against_ref_with(
+                            &path_data.segment_map,
+                            ref_steps,
+                            query_steps,
+                            &mut handler,
+                        );
+                        differences = handler.differences;
+                    }
+
+                    for &a in ref_paths.iter() {
+                        for &b in query_paths.iter() {
+                            if a < b || (ix != jx && a > b) {
+                                let pair = (a.min(b), a.max(b));
+                                pairs
+                                    .entry(pair)
+                                    .or_default()
+                                    .merge(&differences);
+                            }
+                        }
+                    }
+                }
+            }
+
+            pairs
+        })
+        .reduce(PairMap::default, merge_maps)
+}
+
+/// The paths of the graph as haploid samples, one sample per path,
+/// in the same order as the paths in the `PathData`
+#[derive(Debug, Clone)]
+pub struct HaploidSamples {
+    pub names: Vec<BString>,
+    sample_ixs: FnvHashMap<BString, usize>,
+}
+
+impl HaploidSamples {
+    pub fn new(path_data: &PathData) -> Self {
+        let names = path_data.path_names.clone();
+        let sample_ixs = names
+            .iter()
+            .enumerate()
+            .map(|(ix, name)| (name.clone(), ix))
+            .collect();
+        Self { names, sample_ixs }
+    }
+
+    /// The haploid GT column of every sample, tab-separated, and the
+    /// AC and AN INFO fields. Samples that traverse the bubble have
+    /// the index of their ALT allele, or 0 if they don't support
+    /// any; the others are missing (`.`) and don't count toward AN.
+    fn genotypes(
+        &self,
+        alleles: &[(&Variant, &FnvHashSet<QueryPos>)],
+        traversing: &[usize],
+    ) -> (BString, BString) {
+        let mut calls: Vec<Option<usize>> = vec![None; self.names.len()];
+        for &sample_ix in traversing {
+            calls[sample_ix] = Some(0);
+        }
+
+        let mut allele_counts = vec![0usize; alleles.len()];
+        for (allele_ix, (_, queries)) in alleles.iter().enumerate() {
+            for query in queries.iter() {
+                if let Some(&sample_ix) = self.sample_ixs.get(&query.name) {
+                    if let Some(0) | None = calls[sample_ix] {
+                        calls[sample_ix] = Some(allele_ix + 1);
+                        allele_counts[allele_ix] += 1;
+                    }
+                }
+            }
+        }
+
+        let gts = bstr::join(
+            "\t",
+            calls.iter().map(|call| match call {
+                Some(allele) => allele.to_string(),
+                None => ".".to_string(),
+            }),
+        );
+
+        let allele_number = calls.iter().filter(|call| call.is_some()).count();
+        let mut info: BString = ";AC=".into();
+        info.extend(bstr::join(
+            ",",
+            allele_counts.iter().map(|count| count.to_string()),
+        ));
+        info.extend_from_slice(format!(";AN={}", allele_number).as_bytes());
+
+        (gts.into(), info)
+    }
+}
+
+/// Build the VCF records for the variants found in a bubble. If
+/// `genotypes` is given, each record gets a haploid GT column for
+/// every sample, and AC and AN INFO fields; the slice holds the
+/// indices of the paths that traverse the bubble.
 pub fn variant_vcf_record(
-    variants: &FnvHashMap<BString, FnvHashMap<VariantKey, FnvHashSet<Variant>>>,
+    variant_config: &VariantConfig,
+    bubble_paths: usize,
+    variants: &FnvHashMap<BString, FnvHashMap<VariantKey, VariantSupport>>,
+    genotypes: Option<(&HaploidSamples, &[usize])>,
 ) -> Vec<VCFRecord> {
     let mut vcf_records = Vec::new();
 
     for (_, variant_map) in variants.iter() {
-        for (key, var_set) in variant_map.iter() {
-            let (alt_list, type_set): (Vec<BString>, Vec<BString>) = var_set
-                .iter()
-                .map(|var| match var {
-                    Variant::Del(seq) => (seq.clone(), "del".into()),
-                    Variant::Ins(seq) => (seq.clone(), "ins".into()),
-                    Variant::Snv(base) => {
-                        let base_seq =
-                            std::iter::once(*base).collect::<BString>();
-                        (base_seq, "snv".into())
-                    }
-                    Variant::Mnp(seq) => (seq.clone(), "mnp".into()),
-                    Variant::Clumped(seq) => (seq.clone(), "clumped".into()),
-                })
-                .unzip();
+        for (key, var_support) in variant_map.iter() {
+            let mut var_support: Vec<_> = var_support.iter().collect();
+            var_support.sort_by(|(v0, _), (v1, _)| v0.cmp(v1));
+
+            let (alt_list, type_set): (Vec<BString>, Vec<BString>) =
+                var_support
+                    .iter()
+                    .map(|(var, _)| match var {
+                        Variant::Del(seq) => (seq.clone(), "del".into()),
+                        Variant::Ins(seq) => (seq.clone(), "ins".into()),
+                        Variant::Snv(base) => {
+                            let base_seq =
+                                std::iter::once(*base).collect::<BString>();
+                            (base_seq, "snv".into())
+                        }
+                        Variant::Mnp(seq) => (seq.clone(), "mnp".into()),
+                        Variant::Clumped(seq) => {
+                            (seq.clone(), "clumped".into())
+                        }
+                    })
+                    .unzip();
 
             let alts = bstr::join(",", alt_list);
             let mut types: BString = "TYPE=".into();
             let types_temp = bstr::join(";TYPE=", type_set);
             types.extend(types_temp);
 
+            if variant_config.query_coords {
+                let mut queries: Vec<Vec<&QueryPos>> = var_support
+                    .iter()
+                    .map(|(_, queries)| queries.iter().collect())
+                    .collect();
+                queries.iter_mut().for_each(|qs| qs.sort());
+
+                let names = queries.iter().map(|qs| {
+                    bstr::join("|", qs.iter().map(|q| q.name.as_slice()))
+                });
+                let positions = queries.iter().map(|qs| {
+                    bstr::join("|", qs.iter().map(|q| q.pos.to_string()))
+                });
+
+                types.extend_from_slice(b";QNAME=");
+                types.extend(bstr::join(",", names));
+                types.extend_from_slice(b";QSTART=");
+                types.extend(bstr::join(",", positions));
+            }
+
+            let supporting_paths = var_support
+                .iter()
+                .flat_map(|(_, queries)| queries.iter().map(|q| &q.name))
+                .collect::<FnvHashSet<_>>();
+            let quality = variant_config
+                .quality
+                .score(supporting_paths.len(), bubble_paths);
+
+            let (format, sample_name) =
+                if let Some((samples, traversing)) = genotypes {
+                    let (gts, gt_info) =
+                        samples.genotypes(&var_support, traversing);
+                    types.extend(gt_info);
+                    (Some("GT".into()), Some(gts))
+                } else {
+                    (None, None)
+                };
+
+            // PATHS must be the last field, so that duplicate records
+            // can be merged by `vcf::merge_path_names`
+            if variant_config.path_names {
+                let mut names: Vec<_> = supporting_paths.into_iter().collect();
+                names.sort();
+                types.extend_from_slice(b";PATHS=");
+                types.extend(bstr::join(",", names));
+            }
+
             let vcf = VCFRecord {
                 chromosome: key.ref_name.clone(),
                 position: key.pos as i64,
                 id: None,
                 reference: key.sequence.clone(),
                 alternate: Some(alts.into()),
-                quality: None,
+                quality,
                 filter: None,
                 info: Some(types),
-                format: None,
-                sample_name: None,
+                format,
+                sample_name,
             };
 
             vcf_records.push(vcf);
@@ -882,3 +1686,570 @@ pub fn variant_vcf_record(
 
     vcf_records
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment_map(seqs: &[(usize, &str)]) -> FnvHashMap<usize, BString> {
+        seqs.iter()
+            .map(|&(id, seq)| (id, BString::from(seq)))
+            .collect()
+    }
+
+    fn path_steps(
+        segments: &FnvHashMap<usize, BString>,
+        nodes: &[usize],
+    ) -> Vec<PathStep> {
+        let mut offset = 1;
+        nodes
+            .iter()
+            .map(|&node| {
+                let step = (node, offset, Forward);
+                offset += segments[&node].len();
+                step
+            })
+            .collect()
+    }
+
+    fn oriented_path_steps(
+        segments: &FnvHashMap<usize, BString>,
+        steps: &[(usize, Orientation)],
+    ) -> Vec<PathStep> {
+        let mut offset = 1;
+        steps
+            .iter()
+            .map(|&(node, orient)| {
+                let step = (node, offset, orient);
+                offset += segments[&node].len();
+                step
+            })
+            .collect()
+    }
+
+    /// A `len` bp sequence that cycles through `ACGT`
+    fn cycled_seq(len: usize) -> String {
+        b"ACGT"
+            .iter()
+            .cycle()
+            .take(len)
+            .map(|&b| b as char)
+            .collect()
+    }
+
+    /// Replace the bases at the given offsets with `base`
+    fn with_bases(seq: &str, offsets: &[usize], base: u8) -> String {
+        let mut seq = seq.as_bytes().to_vec();
+        for &ix in offsets {
+            seq[ix] = base;
+        }
+        String::from_utf8(seq).unwrap()
+    }
+
+    fn vcf_variants(
+        config: VariantConfig,
+        segments: &FnvHashMap<usize, BString>,
+        ref_path: &[PathStep],
+        query_path: &[PathStep],
+    ) -> Vec<(usize, BString, Variant)> {
+        let query_names: &[&[u8]] = &[b"query"];
+        let mut handler = VCFVariantHandler::new(
+            config,
+            segments,
+            b"ref",
+            query_names,
+            ref_path,
+            query_path,
+        );
+        detect_variants_against_ref_with(
+            segments,
+            ref_path,
+            query_path,
+            &mut handler,
+        );
+
+        let mut vars: Vec<_> = handler
+            .variants
+            .into_iter()
+            .flat_map(|(key, support)| {
+                support
+                    .into_iter()
+                    .map(move |(var, _)| (key.pos, key.sequence.clone(), var))
+            })
+            .collect();
+        vars.sort_by_key(|(pos, _, _)| *pos);
+        vars
+    }
+
+    #[test]
+    fn snp_handler_reports_indels() {
+        let segments =
+            segment_map(&[(1, "AC"), (2, "GAT"), (3, "TT"), (4, "C")]);
+        let ref_path = path_steps(&segments, &[1, 2, 4]);
+        let query_path = path_steps(&segments, &[1, 3, 2]);
+
+        let mut handler =
+            SNPVariantHandler::new(&segments, &ref_path, &query_path)
+                .with_indels(true);
+        detect_variants_against_ref_with(
+            &segments,
+            &ref_path,
+            &query_path,
+            &mut handler,
+        );
+
+        // The query ends before node 4, which is a deletion at the
+        // end of the reference
+        assert_eq!(
+            handler.indel_rows,
+            vec![
+                VariantRow {
+                    ref_pos: 2,
+                    query_pos: 2,
+                    kind: VariantKind::Ins,
+                    ref_seq: "C".into(),
+                    query_seq: "CTT".into(),
+                },
+                VariantRow {
+                    ref_pos: 5,
+                    query_pos: 7,
+                    kind: VariantKind::Del,
+                    ref_seq: "TC".into(),
+                    query_seq: "T".into(),
+                },
+            ]
+        );
+        assert!(handler.snp_rows.is_empty());
+    }
+
+    #[test]
+    fn indels_at_reference_path_ends() {
+        let segments =
+            segment_map(&[(1, "GA"), (2, "CT"), (3, "TTG"), (4, "A")]);
+        let vars = |ref_nodes: &[usize], query_nodes: &[usize]| {
+            vcf_variants(
+                VariantConfig::default(),
+                &segments,
+                &path_steps(&segments, ref_nodes),
+                &path_steps(&segments, query_nodes),
+            )
+        };
+
+        // Deleting the first node: there's no base before it, so the
+        // deletion is anchored on the base after it, at position 1
+        assert_eq!(
+            vars(&[1, 2, 3], &[2, 3]),
+            vec![(1, BString::from("GAC"), Variant::Del("C".into()))]
+        );
+
+        // Likewise for an insertion before the first base
+        assert_eq!(
+            vars(&[2, 3], &[4, 2, 3]),
+            vec![(1, BString::from("C"), Variant::Ins("AC".into()))]
+        );
+
+        // Steps past the end of the reference are inserted after its
+        // last base, and missing steps at the end are deleted
+        assert_eq!(
+            vars(&[1, 2], &[1, 2, 3]),
+            vec![(4, BString::from("T"), Variant::Ins("TTTG".into()))]
+        );
+        assert_eq!(
+            vars(&[1, 2, 3], &[1, 2]),
+            vec![(4, BString::from("TTTG"), Variant::Del("T".into()))]
+        );
+
+        let ref_path = path_steps(&segments, &[1, 2]);
+        let query_path = path_steps(&segments, &[4, 1, 2]);
+        let mut handler =
+            SNPVariantHandler::new(&segments, &ref_path, &query_path)
+                .with_indels(true);
+        detect_variants_against_ref_with(
+            &segments,
+            &ref_path,
+            &query_path,
+            &mut handler,
+        );
+        assert_eq!(
+            handler.indel_rows,
+            vec![VariantRow {
+                ref_pos: 1,
+                query_pos: 1,
+                kind: VariantKind::Ins,
+                ref_seq: "G".into(),
+                query_seq: "AG".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn decomposed_positions_inside_long_nodes() {
+        let ref_mid = cycled_seq(5000);
+        let query_mid = with_bases(&ref_mid, &[3000, 4501], b'N');
+        let segments = segment_map(&[
+            (1, cycled_seq(2000).as_str()),
+            (2, ref_mid.as_str()),
+            (3, query_mid.as_str()),
+            (4, "T"),
+        ]);
+        let ref_path = path_steps(&segments, &[1, 2, 4]);
+        let query_path = path_steps(&segments, &[1, 3, 4]);
+
+        let config = VariantConfig {
+            decompose_mnp: true,
+            ..VariantConfig::default()
+        };
+        let vars = vcf_variants(config, &segments, &ref_path, &query_path);
+
+        assert_eq!(
+            vars,
+            vec![
+                (5001, BString::from("A"), Variant::Snv(b'N')),
+                (6502, BString::from("C"), Variant::Snv(b'N')),
+            ]
+        );
+    }
+
+    #[test]
+    fn mnp_trimmed_to_mismatching_span() {
+        let ref_mid = cycled_seq(4000);
+        let query_mid = with_bases(&ref_mid, &[1500, 1502], b'N');
+        let segments = segment_map(&[
+            (1, cycled_seq(3000).as_str()),
+            (2, ref_mid.as_str()),
+            (3, query_mid.as_str()),
+            (4, "T"),
+        ]);
+        let ref_path = path_steps(&segments, &[1, 2, 4]);
+        let query_path = path_steps(&segments, &[1, 3, 4]);
+
+        let vars = vcf_variants(
+            VariantConfig::default(),
+            &segments,
+            &ref_path,
+            &query_path,
+        );
+
+        assert_eq!(
+            vars,
+            vec![(
+                4501,
+                BString::from("ACG"),
+                Variant::Mnp(BString::from("NTN"))
+            )]
+        );
+    }
+
+    #[test]
+    fn positions_inside_reverse_steps() {
+        use gfa::gfa::Orientation::Backward;
+
+        let ref_mid = cycled_seq(5000);
+        // Offset 1000 on the forward strand is offset 3999 along the
+        // reverse complement
+        let query_mid = with_bases(&ref_mid, &[1000], b'N');
+        let segments = segment_map(&[
+            (1, cycled_seq(2000).as_str()),
+            (2, ref_mid.as_str()),
+            (3, query_mid.as_str()),
+            (4, "T"),
+        ]);
+        let ref_path = oriented_path_steps(
+            &segments,
+            &[(1, Forward), (2, Backward), (4, Forward)],
+        );
+        let query_path = oriented_path_steps(
+            &segments,
+            &[(1, Forward), (3, Backward), (4, Forward)],
+        );
+
+        let config = VariantConfig {
+            decompose_mnp: true,
+            ..VariantConfig::default()
+        };
+        let vars = vcf_variants(config, &segments, &ref_path, &query_path);
+
+        // The forward base at offset 1000 is A, so the reference
+        // path reads its complement
+        assert_eq!(vars, vec![(6000, BString::from("T"), Variant::Snv(b'N'))]);
+
+        let mut handler =
+            SNPVariantHandler::new(&segments, &ref_path, &query_path);
+        detect_variants_against_ref_with(
+            &segments,
+            &ref_path,
+            &query_path,
+            &mut handler,
+        );
+        assert_eq!(
+            handler.snp_rows,
+            vec![SNPRow {
+                ref_pos: 6000,
+                query_pos: 6000,
+                ref_base: b'T',
+                query_base: b'N',
+            }]
+        );
+    }
+
+    #[test]
+    fn deletion_anchored_on_reverse_step() {
+        use gfa::gfa::Orientation::Backward;
+
+        // Read in reverse, the last base of node 1 is the complement
+        // of its first base, i.e. T
+        let node_1 = format!("A{}C", cycled_seq(2998));
+        let segments = segment_map(&[
+            (1, node_1.as_str()),
+            (2, cycled_seq(1500).as_str()),
+            (3, "G"),
+        ]);
+        let ref_path = oriented_path_steps(
+            &segments,
+            &[(1, Backward), (2, Forward), (3, Forward)],
+        );
+        let query_path =
+            oriented_path_steps(&segments, &[(1, Backward), (3, Forward)]);
+
+        let vars = vcf_variants(
+            VariantConfig::default(),
+            &segments,
+            &ref_path,
+            &query_path,
+        );
+
+        assert_eq!(vars.len(), 1);
+        let (pos, ref_allele, variant) = &vars[0];
+        assert_eq!(*pos, 3000);
+        assert_eq!(ref_allele.len(), 1501);
+        assert_eq!(ref_allele[0], b'T');
+        assert_eq!(*variant, Variant::Del(BString::from("T")));
+    }
+
+    #[test]
+    fn snp_handler_splits_equal_length_mismatches() {
+        let segments =
+            segment_map(&[(1, "AC"), (2, "GATTA"), (3, "GCTTC"), (4, "T")]);
+        let ref_path = path_steps(&segments, &[1, 2, 4]);
+        let query_path = path_steps(&segments, &[1, 3, 4]);
+
+        let mut handler =
+            SNPVariantHandler::new(&segments, &ref_path, &query_path);
+        detect_variants_against_ref_with(
+            &segments,
+            &ref_path,
+            &query_path,
+            &mut handler,
+        );
+
+        assert_eq!(
+            handler.snp_rows,
+            vec![
+                SNPRow {
+                    ref_pos: 4,
+                    query_pos: 4,
+                    ref_base: b'A',
+                    query_base: b'C',
+                },
+                SNPRow {
+                    ref_pos: 7,
+                    query_pos: 7,
+                    ref_base: b'A',
+                    query_base: b'C',
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn haploid_genotypes() {
+        let path_data = PathData {
+            segment_map: FnvHashMap::default(),
+            path_names: vec!["ref".into(), "a".into(), "b".into(), "c".into()],
+            paths: vec![Vec::new(); 4],
+        };
+        let samples = HaploidSamples::new(&path_data);
+
+        let key = VariantKey {
+            ref_name: "ref".into(),
+            pos: 5,
+            sequence: "A".into(),
+        };
+        let mut support: VariantSupport = FnvHashMap::default();
+        support
+            .entry(Variant::Snv(b'G'))
+            .or_default()
+            .insert(QueryPos {
+                name: "b".into(),
+                pos: 7,
+            });
+        let mut ref_map = FnvHashMap::default();
+        ref_map.insert(key, support);
+        let mut variants = FnvHashMap::default();
+        variants.insert(BString::from("ref"), ref_map);
+
+        // "c" doesn't traverse the bubble
+        let traversing = [0, 1, 2];
+        let records = variant_vcf_record(
+            &VariantConfig::default(),
+            traversing.len(),
+            &variants,
+            Some((&samples, &traversing[..])),
+        );
+
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.format, Some("GT".into()));
+        assert_eq!(record.sample_name, Some("0\t0\t1\t.".into()));
+        assert_eq!(record.info, Some("TYPE=snv;AC=1;AN=3".into()));
+    }
+
+    #[test]
+    fn grouped_query_coords() {
+        let segments =
+            segment_map(&[(1, "AC"), (2, "G"), (3, "T"), (4, "A"), (5, "GG")]);
+        // "a" and "b" traverse the bubble identically and are grouped,
+        // "c" has the same alleles but at a different offset
+        let paths = vec![
+            path_steps(&segments, &[1, 2, 4]),
+            path_steps(&segments, &[1, 3, 4]),
+            path_steps(&segments, &[1, 3, 4]),
+            path_steps(&segments, &[5, 1, 3, 4]),
+        ];
+        let nodes: NodeSet = vec![1, 4].into_iter().collect();
+        let path_indices = bubble_path_indices(&paths, &nodes);
+        let path_data = PathData {
+            segment_map: segments,
+            path_names: vec!["ref".into(), "a".into(), "b".into(), "c".into()],
+            paths,
+        };
+
+        let config = VariantConfig {
+            query_coords: true,
+            ..VariantConfig::default()
+        };
+        let ref_names: FnvHashSet<BString> =
+            std::iter::once(BString::from("ref")).collect();
+        let variants = detect_variants_in_sub_paths(
+            &config,
+            &path_data,
+            Some(&ref_names),
+            &path_indices,
+            1,
+            4,
+        )
+        .unwrap();
+
+        let records = variant_vcf_record(&config, 4, &variants, None);
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.position, 3);
+        assert_eq!(record.reference, "G");
+        assert_eq!(record.alternate, Some("T".into()));
+        // QSTART is 1-based, like POS
+        assert_eq!(
+            record.info,
+            Some("TYPE=snv;QNAME=a|b|c;QSTART=3|3|5".into())
+        );
+    }
+
+    #[test]
+    fn quality_score_modes() {
+        assert_eq!(QualityScore::None.score(2, 4), None);
+        assert_eq!(QualityScore::Count.score(2, 4), Some(2));
+        assert_eq!(QualityScore::Fraction.score(2, 4), Some(50));
+        assert_eq!(QualityScore::Fraction.score(1, 3), Some(33));
+        assert_eq!(QualityScore::Phred.score(2, 4), Some(3));
+        assert_eq!(QualityScore::Phred.score(9, 10), Some(10));
+        assert_eq!(QualityScore::Phred.score(4, 4), Some(99));
+        assert_eq!(QualityScore::Phred.score(0, 0), Some(0));
+    }
+
+    #[test]
+    fn quality_in_records() {
+        let key = VariantKey {
+            ref_name: "ref".into(),
+            pos: 5,
+            sequence: "A".into(),
+        };
+        let mut support: VariantSupport = FnvHashMap::default();
+        support
+            .entry(Variant::Snv(b'G'))
+            .or_default()
+            .insert(QueryPos {
+                name: "b".into(),
+                pos: 7,
+            });
+        let mut ref_map = FnvHashMap::default();
+        ref_map.insert(key, support);
+        let mut variants = FnvHashMap::default();
+        variants.insert(BString::from("ref"), ref_map);
+
+        let record_with = |quality| {
+            let config = VariantConfig {
+                quality,
+                ..VariantConfig::default()
+            };
+            let records = variant_vcf_record(&config, 4, &variants, None);
+            assert_eq!(records.len(), 1);
+            records.into_iter().next().unwrap()
+        };
+
+        // QUAL is left empty unless --qual-score is given
+        let record = record_with(VariantConfig::default().quality);
+        assert_eq!(record.quality, None);
+        assert_eq!(record.to_string().split('\t').nth(5), Some("."));
+
+        assert_eq!(record_with(QualityScore::Count).quality, Some(1));
+        assert_eq!(record_with(QualityScore::Fraction).quality, Some(25));
+        let record = record_with(QualityScore::Phred);
+        assert_eq!(record.quality, Some(1));
+        assert_eq!(record.to_string().split('\t').nth(5), Some("1"));
+    }
+
+    #[test]
+    fn pairwise_path_differences() {
+        let segments = segment_map(&[
+            (1, "ACGTACGTAC"),
+            (2, "A"),
+            (3, "G"),
+            (4, "ACGT"),
+            (5, "TTTTT"),
+            (6, "CC"),
+            (7, "GA"),
+        ]);
+        let paths = vec![
+            path_steps(&segments, &[1, 2, 4, 6]),
+            path_steps(&segments, &[1, 3, 4, 5, 6]),
+            path_steps(&segments, &[1, 2, 4, 6]),
+            path_steps(&segments, &[1, 7, 4, 6]),
+        ];
+        let bubbles = [(1, 4), (4, 6)];
+        let nodes: NodeSet = vec![1, 4, 6].into_iter().collect();
+        let path_indices = bubble_path_indices(&paths, &nodes);
+        let path_data = PathData {
+            segment_map: segments,
+            path_names: vec!["a".into(), "b".into(), "c".into(), "d".into()],
+            paths,
+        };
+
+        let pairs = pairwise_differences(&path_data, &path_indices, &bubbles);
+        let diffs = |snvs, indels, complex| PairDifferences {
+            bubbles: 2,
+            snvs,
+            indels,
+            complex,
+        };
+
+        assert_eq!(pairs.len(), 6);
+        assert_eq!(pairs[&(0, 1)], diffs(1, 1, 0));
+        assert_eq!(pairs[&(0, 2)], diffs(0, 0, 0));
+        assert_eq!(pairs[&(0, 3)], diffs(0, 0, 1));
+        assert_eq!(pairs[&(1, 2)], diffs(1, 1, 0));
+        assert_eq!(pairs[&(1, 3)], diffs(0, 1, 1));
+        assert_eq!(pairs[&(2, 3)], diffs(0, 0, 1));
+        assert_eq!(pairs[&(1, 3)].total(), 2);
+
+        assert_eq!(path_data.path_length(0), 17);
+    }
+}
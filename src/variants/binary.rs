@@ -0,0 +1,277 @@
+//! A compact binary format for `PathData`, so that the GFA doesn't
+//! have to be parsed, and the path offsets recomputed, every time
+//! variants are called on the same graph.
+//!
+//! The file is gzip-compressed, and contains a magic string, the size
+//! and modification time of the GFA it was built from, so that it's
+//! not used for a different version of the graph, followed by the
+//! segments (ID, length, sequence), the paths (name, and node,
+//! offset, and orientation of each step), and, if they were found,
+//! the graph's ultrabubbles. All integers are little-endian u64s.
+
+use bstr::BString;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use fnv::FnvHashMap;
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+use gfa::gfa::Orientation;
+
+use super::PathData;
+
+const MAGIC: &[u8; 8] = b"GFAUPD02";
+
+/// The size and modification time, in nanoseconds since the Unix
+/// epoch, of the GFA a path data file was built from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GfaFingerprint {
+    pub size: u64,
+    pub modified: u64,
+}
+
+impl GfaFingerprint {
+    pub fn of_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or(0);
+        Ok(Self {
+            size: metadata.len(),
+            modified,
+        })
+    }
+}
+
+/// The contents of a path data file
+pub struct PathDataFile {
+    pub gfa: GfaFingerprint,
+    pub path_data: PathData,
+    pub ultrabubbles: Option<Vec<(u64, u64)>>,
+}
+
+fn write_u64<W: Write>(out: &mut W, val: usize) -> io::Result<()> {
+    out.write_all(&(val as u64).to_le_bytes())
+}
+
+fn write_bytes<W: Write>(out: &mut W, bytes: &[u8]) -> io::Result<()> {
+    write_u64(out, bytes.len())?;
+    out.write_all(bytes)
+}
+
+fn read_u64<R: Read>(input: &mut R) -> io::Result<usize> {
+    let mut buf = [0u8; 8];
+    input.read_exact(&mut buf)?;
+    let val = u64::from_le_bytes(buf);
+    if val > usize::MAX as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("value {} does not fit in usize", val),
+        ));
+    }
+    Ok(val as usize)
+}
+
+fn read_bytes<R: Read>(input: &mut R) -> io::Result<BString> {
+    let len = read_u64(input)?;
+    let mut bytes = Vec::new();
+    input.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes.into())
+}
+
+pub fn write_path_data<W: Write>(
+    gfa: GfaFingerprint,
+    path_data: &PathData,
+    ultrabubbles: Option<&[(u64, u64)]>,
+    out: W,
+) -> io::Result<()> {
+    let mut out = GzEncoder::new(out, Compression::default());
+
+    out.write_all(MAGIC)?;
+    out.write_all(&gfa.size.to_le_bytes())?;
+    out.write_all(&gfa.modified.to_le_bytes())?;
+
+    // Sorted by ID, so that the same graph always gives the same file
+    let mut segments: Vec<_> = path_data.segment_map.iter().collect();
+    segments.sort_by_key(|(id, _)| **id);
+
+    write_u64(&mut out, segments.len())?;
+    for (&id, seq) in segments {
+        write_u64(&mut out, id)?;
+        write_bytes(&mut out, seq)?;
+    }
+
+    write_u64(&mut out, path_data.paths.len())?;
+    for (name, steps) in path_data.path_names.iter().zip(&path_data.paths) {
+        write_bytes(&mut out, name)?;
+        write_u64(&mut out, steps.len())?;
+        for &(node, offset, orient) in steps.iter() {
+            write_u64(&mut out, node)?;
+            write_u64(&mut out, offset)?;
+            out.write_all(&[orient.is_reverse() as u8])?;
+        }
+    }
+
+    out.write_all(&[ultrabubbles.is_some() as u8])?;
+    if let Some(ultrabubbles) = ultrabubbles {
+        write_u64(&mut out, ultrabubbles.len())?;
+        for &(start, end) in ultrabubbles {
+            out.write_all(&start.to_le_bytes())?;
+            out.write_all(&end.to_le_bytes())?;
+        }
+    }
+
+    out.finish()?.flush()
+}
+
+fn read_raw_u64<R: Read>(input: &mut R) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    input.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+pub fn read_path_data<R: Read>(input: R) -> io::Result<PathDataFile> {
+    let mut input = GzDecoder::new(input);
+
+    let mut magic = [0u8; 8];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a gfautil path data file, or one written by an older \
+             version of gfautil",
+        ));
+    }
+
+    let gfa = GfaFingerprint {
+        size: read_raw_u64(&mut input)?,
+        modified: read_raw_u64(&mut input)?,
+    };
+
+    let segment_count = read_u64(&mut input)?;
+    let mut segment_map = FnvHashMap::default();
+    for _ in 0..segment_count {
+        let id = read_u64(&mut input)?;
+        let seq = read_bytes(&mut input)?;
+        segment_map.insert(id, seq);
+    }
+
+    let path_count = read_u64(&mut input)?;
+    let mut path_names = Vec::with_capacity(path_count);
+    let mut paths = Vec::with_capacity(path_count);
+    for _ in 0..path_count {
+        path_names.push(read_bytes(&mut input)?);
+
+        let step_count = read_u64(&mut input)?;
+        let mut steps = Vec::with_capacity(step_count);
+        for _ in 0..step_count {
+            let node = read_u64(&mut input)?;
+            let offset = read_u64(&mut input)?;
+            let mut orient = [0u8; 1];
+            input.read_exact(&mut orient)?;
+            let orient = if orient[0] == 0 {
+                Orientation::Forward
+            } else {
+                Orientation::Backward
+            };
+            steps.push((node, offset, orient));
+        }
+        paths.push(steps);
+    }
+
+    let mut has_ultrabubbles = [0u8; 1];
+    input.read_exact(&mut has_ultrabubbles)?;
+    let ultrabubbles = if has_ultrabubbles[0] == 0 {
+        None
+    } else {
+        let count = read_u64(&mut input)?;
+        let mut ultrabubbles = Vec::with_capacity(count);
+        for _ in 0..count {
+            let start = read_raw_u64(&mut input)?;
+            let end = read_raw_u64(&mut input)?;
+            ultrabubbles.push((start, end));
+        }
+        Some(ultrabubbles)
+    };
+
+    Ok(PathDataFile {
+        gfa,
+        path_data: PathData {
+            segment_map,
+            path_names,
+            paths,
+        },
+        ultrabubbles,
+    })
+}
+
+pub fn save_path_data<P: AsRef<Path>>(
+    gfa: GfaFingerprint,
+    path_data: &PathData,
+    ultrabubbles: Option<&[(u64, u64)]>,
+    path: P,
+) -> io::Result<()> {
+    let file = File::create(path)?;
+    write_path_data(gfa, path_data, ultrabubbles, BufWriter::new(file))
+}
+
+pub fn load_path_data<P: AsRef<Path>>(path: P) -> io::Result<PathDataFile> {
+    let file = File::open(path)?;
+    read_path_data(BufReader::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_data_roundtrip() {
+        let segment_map: FnvHashMap<usize, BString> =
+            vec![(1, "ACGT".into()), (7, "GG".into())]
+                .into_iter()
+                .collect();
+
+        let path_data = PathData {
+            segment_map,
+            path_names: vec!["a".into(), "b".into()],
+            paths: vec![
+                vec![
+                    (1, 1, Orientation::Forward),
+                    (7, 5, Orientation::Backward),
+                ],
+                vec![(7, 1, Orientation::Forward)],
+            ],
+        };
+
+        let gfa = GfaFingerprint {
+            size: 1234,
+            modified: 5678,
+        };
+        let ultrabubbles = vec![(1, 7)];
+
+        let mut buf = Vec::new();
+        write_path_data(gfa, &path_data, Some(&ultrabubbles), &mut buf)
+            .unwrap();
+        let loaded = read_path_data(buf.as_slice()).unwrap();
+
+        assert_eq!(loaded.gfa, gfa);
+        assert_eq!(loaded.ultrabubbles, Some(ultrabubbles));
+        let loaded = loaded.path_data;
+        assert_eq!(loaded.segment_map, path_data.segment_map);
+        assert_eq!(loaded.path_names, path_data.path_names);
+        assert_eq!(loaded.paths, path_data.paths);
+
+        let mut buf = Vec::new();
+        write_path_data(gfa, &path_data, None, &mut buf).unwrap();
+        let loaded = read_path_data(buf.as_slice()).unwrap();
+        assert_eq!(loaded.ultrabubbles, None);
+    }
+}
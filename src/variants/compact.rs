@@ -0,0 +1,319 @@
+//! A compact in-memory representation of VCF records, for holding
+//! all the records of a run until they're sorted and written.
+//!
+//! Each record is a small fixed-size struct, with the chromosome
+//! interned as an ID, the position as a u64, and the text fields
+//! stored back to back in one shared byte buffer, so that there's no
+//! allocation per field. `VCFRecord`s are only rebuilt from them when
+//! the records are written.
+
+use bstr::{BString, ByteSlice};
+use fnv::FnvHashMap;
+use rayon::prelude::*;
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    convert::TryFrom,
+    io::{self, Write},
+};
+
+use super::vcf::VCFRecord;
+
+/// Marks an optional text field that's missing
+const MISSING: u32 = u32::MAX;
+
+/// The text fields of a record, in the order they're stored
+const ID: usize = 0;
+const REF: usize = 1;
+const ALT: usize = 2;
+const FILTER: usize = 3;
+const INFO: usize = 4;
+const FORMAT: usize = 5;
+const SAMPLE: usize = 6;
+const FIELDS: usize = 7;
+
+#[derive(Debug, Clone, Copy)]
+struct CompactRecord {
+    chromosome: u32,
+    position: u64,
+    quality: Option<i32>,
+    /// Offset of the first text field in the buffer
+    start: usize,
+    /// Length of each text field, or `MISSING`
+    lens: [u32; FIELDS],
+}
+
+/// VCF records, with their chromosome names and text fields shared
+/// between them
+#[derive(Debug, Default, Clone)]
+pub struct CompactRecords {
+    chromosomes: Vec<BString>,
+    chromosome_ids: FnvHashMap<Vec<u8>, u32>,
+    text: Vec<u8>,
+    records: Vec<CompactRecord>,
+}
+
+impl CompactRecords {
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    fn chromosome_id(&mut self, name: &[u8]) -> u32 {
+        if let Some(&id) = self.chromosome_ids.get(name) {
+            return id;
+        }
+        let id = self.chromosomes.len() as u32;
+        self.chromosomes.push(name.into());
+        self.chromosome_ids.insert(name.into(), id);
+        id
+    }
+
+    fn push_field<B: AsRef<[u8]>>(&mut self, field: Option<B>) -> u32 {
+        match field {
+            Some(field) => {
+                let field = field.as_ref();
+                self.text.extend_from_slice(field);
+                field.len() as u32
+            }
+            None => MISSING,
+        }
+    }
+
+    /// Add a record. VCF positions start at 1, so a negative position
+    /// is stored as 0.
+    pub fn push(&mut self, record: &VCFRecord) {
+        let position = u64::try_from(record.position).unwrap_or(0);
+        let chromosome = self.chromosome_id(&record.chromosome);
+        let start = self.text.len();
+
+        let mut lens = [MISSING; FIELDS];
+        lens[ID] = self.push_field(record.id.as_ref());
+        lens[REF] = self.push_field(Some(&record.reference));
+        lens[ALT] = self.push_field(record.alternate.as_ref());
+        lens[FILTER] = self.push_field(record.filter.as_ref());
+        lens[INFO] = self.push_field(record.info.as_ref());
+        lens[FORMAT] = self.push_field(record.format.as_ref());
+        lens[SAMPLE] = self.push_field(record.sample_name.as_ref());
+
+        self.records.push(CompactRecord {
+            chromosome,
+            position,
+            quality: record.quality,
+            start,
+            lens,
+        });
+    }
+
+    /// Move the records of `other` to the end of these
+    pub fn append(&mut self, mut other: CompactRecords) {
+        let chromosome_ids: Vec<u32> = other
+            .chromosomes
+            .iter()
+            .map(|name| self.chromosome_id(name))
+            .collect();
+        let offset = self.text.len();
+        self.text.append(&mut other.text);
+        self.records.extend(other.records.into_iter().map(|record| {
+            CompactRecord {
+                chromosome: chromosome_ids[record.chromosome as usize],
+                start: record.start + offset,
+                ..record
+            }
+        }));
+    }
+
+    fn field(&self, record: &CompactRecord, field: usize) -> Option<&[u8]> {
+        if record.lens[field] == MISSING {
+            return None;
+        }
+        let start = record.start
+            + record.lens[..field]
+                .iter()
+                .filter(|&&len| len != MISSING)
+                .map(|&len| len as usize)
+                .sum::<usize>();
+        Some(&self.text[start..start + record.lens[field] as usize])
+    }
+
+    /// Sort the records by chromosome name and position, and then by
+    /// reference and alternate allele, like `VCFRecord::vcf_cmp`
+    pub fn sort(&mut self) {
+        let mut by_name: Vec<usize> = (0..self.chromosomes.len()).collect();
+        by_name.sort_by(|&a, &b| self.chromosomes[a].cmp(&self.chromosomes[b]));
+        let mut ranks = vec![0; by_name.len()];
+        for (rank, ix) in by_name.into_iter().enumerate() {
+            ranks[ix] = rank;
+        }
+
+        let mut records = std::mem::take(&mut self.records);
+        records.par_sort_by(|r0, r1| {
+            ranks[r0.chromosome as usize]
+                .cmp(&ranks[r1.chromosome as usize])
+                .then(r0.position.cmp(&r1.position))
+                .then_with(|| self.cmp_field(r0, r1, REF))
+                .then_with(|| self.cmp_field(r0, r1, ALT))
+        });
+        self.records = records;
+    }
+
+    fn cmp_field(
+        &self,
+        r0: &CompactRecord,
+        r1: &CompactRecord,
+        field: usize,
+    ) -> Ordering {
+        self.field(r0, field).cmp(&self.field(r1, field))
+    }
+
+    /// Rebuild the record at index `ix`
+    pub fn get(&self, ix: usize) -> VCFRecord {
+        let record = &self.records[ix];
+        let text = |field| self.field(record, field).map(BString::from);
+        VCFRecord {
+            chromosome: self.chromosomes[record.chromosome as usize].clone(),
+            position: record.position as i64,
+            id: text(ID),
+            reference: text(REF).unwrap_or_default(),
+            alternate: text(ALT),
+            quality: record.quality,
+            filter: text(FILTER),
+            info: text(INFO),
+            format: text(FORMAT),
+            sample_name: text(SAMPLE),
+        }
+    }
+
+    /// Rebuild the records, in their current order
+    pub fn iter(&self) -> impl Iterator<Item = VCFRecord> + '_ {
+        (0..self.records.len()).map(move |ix| self.get(ix))
+    }
+}
+
+impl Extend<VCFRecord> for CompactRecords {
+    fn extend<I: IntoIterator<Item = VCFRecord>>(&mut self, iter: I) {
+        for record in iter {
+            self.push(&record);
+        }
+    }
+}
+
+/// The sort key of a VCF line, in the order of `CompactRecords::sort`:
+/// the chromosome, position, REF, and ALT
+fn line_key(line: &[u8]) -> (&[u8], u64, &[u8], &[u8]) {
+    let mut fields = line.split_str("\t");
+    let mut next = || fields.next().unwrap_or_default();
+    let chromosome = next();
+    let position = next().to_str().ok().and_then(|p| p.parse().ok());
+    let _id = next();
+    let reference = next();
+    let alternate = next();
+    (chromosome, position.unwrap_or(0), reference, alternate)
+}
+
+/// Merge runs of VCF lines that were each sorted with
+/// `CompactRecords::sort`, e.g. the blocks written by gfa2vcf with
+/// --flush-every, into a single sorted run. Each run is the text of
+/// its lines, each followed by a newline.
+pub fn merge_sorted_runs<W: Write>(
+    runs: &[&[u8]],
+    mut out: W,
+) -> io::Result<()> {
+    let mut lines: Vec<_> = runs.iter().map(|run| run.lines()).collect();
+
+    let mut heap = BinaryHeap::with_capacity(lines.len());
+    for (ix, run) in lines.iter_mut().enumerate() {
+        if let Some(line) = run.next() {
+            heap.push(Reverse((line_key(line), ix, line)));
+        }
+    }
+
+    while let Some(Reverse((_, ix, line))) = heap.pop() {
+        out.write_all(line)?;
+        out.write_all(b"\n")?;
+        if let Some(line) = lines[ix].next() {
+            heap.push(Reverse((line_key(line), ix, line)));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(chr: &str, pos: i64, alt: Option<&str>) -> VCFRecord {
+        VCFRecord {
+            chromosome: chr.into(),
+            position: pos,
+            id: None,
+            reference: "A".into(),
+            alternate: alt.map(BString::from),
+            quality: Some(30),
+            filter: None,
+            info: Some("TYPE=snv".into()),
+            format: Some("GT".into()),
+            sample_name: Some("1".into()),
+        }
+    }
+
+    #[test]
+    fn compact_records_roundtrip_sorted() {
+        let mut first = CompactRecords::default();
+        first.extend(vec![
+            record("chr2", 5, Some("T")),
+            record("chr1", 9, Some("G")),
+        ]);
+        let mut second = CompactRecords::default();
+        second.extend(vec![
+            record("chr1", 9, Some("C")),
+            record("chr2", 1, None),
+        ]);
+        first.append(second);
+        assert_eq!(first.len(), 4);
+
+        first.sort();
+
+        let mut expected = vec![
+            record("chr1", 9, Some("C")),
+            record("chr1", 9, Some("G")),
+            record("chr2", 1, None),
+            record("chr2", 5, Some("T")),
+        ];
+        assert_eq!(first.iter().collect::<Vec<_>>(), expected);
+
+        expected.sort_by(|v0, v1| {
+            v0.vcf_cmp(v1)
+                .then_with(|| v0.reference.cmp(&v1.reference))
+                .then_with(|| v0.alternate.cmp(&v1.alternate))
+        });
+        assert_eq!(first.iter().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn compact_records_past_u32() {
+        let pos = i64::from(u32::MAX) + 10;
+        let mut records = CompactRecords::default();
+        records.extend(vec![record("chr1", pos, Some("G"))]);
+        assert_eq!(records.get(0).position, pos);
+    }
+
+    #[test]
+    fn merge_sorted_runs_by_position() {
+        let first = b"chr1\t9\t.\tA\tG\nchr2\t5\t.\tA\tT\n";
+        let second = b"chr1\t10\t.\tA\tC\nchr2\t1\t.\tA\t.\n";
+        let mut merged = Vec::new();
+        merge_sorted_runs(&[&first[..], &second[..]], &mut merged).unwrap();
+        assert_eq!(
+            merged.to_str().unwrap(),
+            "chr1\t9\t.\tA\tG\n\
+             chr1\t10\t.\tA\tC\n\
+             chr2\t1\t.\tA\t.\n\
+             chr2\t5\t.\tA\tT\n"
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use bstr::{BString, ByteSlice};
+use fnv::FnvHashSet;
+use std::collections::BTreeMap;
+
+use super::vcf::VCFRecord;
+
+/// A single ALT allele of a VCF record
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Allele {
+    pub chromosome: BString,
+    pub position: i64,
+    pub reference: BString,
+    pub alternate: BString,
+}
+
+impl Allele {
+    /// The last reference position covered by the allele
+    fn ref_end(&self) -> i64 {
+        self.position + (self.reference.len().max(1) as i64) - 1
+    }
+}
+
+/// Split the records into one allele per ALT
+pub fn record_alleles<'a, I>(records: I) -> FnvHashSet<Allele>
+where
+    I: IntoIterator<Item = &'a VCFRecord>,
+{
+    records
+        .into_iter()
+        .flat_map(|record| {
+            let alts = record.alternate.clone().unwrap_or_default();
+            alts.split_str(",")
+                .map(|alt| Allele {
+                    chromosome: record.chromosome.clone(),
+                    position: record.position,
+                    reference: record.reference.to_ascii_uppercase().into(),
+                    alternate: alt.to_ascii_uppercase().into(),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// The result of comparing the alleles called from two graphs. Alleles
+/// that are only found in one of the graphs, but overlap an allele
+/// only found in the other, are counted as representation
+/// differences rather than as unique to either graph.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct VariantComparison {
+    pub shared: Vec<Allele>,
+    pub only_a: Vec<Allele>,
+    pub only_b: Vec<Allele>,
+    pub repr_a: Vec<Allele>,
+    pub repr_b: Vec<Allele>,
+}
+
+/// Return the alleles in `alleles` that overlap any allele in `other`
+/// on the reference
+fn overlapping(alleles: &[Allele], other: &[Allele]) -> FnvHashSet<Allele> {
+    let mut by_chrom: BTreeMap<&[u8], Vec<(i64, i64)>> = BTreeMap::new();
+    for allele in other {
+        by_chrom
+            .entry(allele.chromosome.as_slice())
+            .or_default()
+            .push((allele.position, allele.ref_end()));
+    }
+
+    alleles
+        .iter()
+        .filter(|allele| {
+            by_chrom
+                .get(allele.chromosome.as_slice())
+                .map(|intervals| {
+                    intervals.iter().any(|&(start, end)| {
+                        start <= allele.ref_end() && allele.position <= end
+                    })
+                })
+                .unwrap_or(false)
+        })
+        .cloned()
+        .collect()
+}
+
+pub fn compare_alleles(
+    a: &FnvHashSet<Allele>,
+    b: &FnvHashSet<Allele>,
+) -> VariantComparison {
+    let mut shared: Vec<Allele> = a.intersection(b).cloned().collect();
+    let mut unique_a: Vec<Allele> = a.difference(b).cloned().collect();
+    let mut unique_b: Vec<Allele> = b.difference(a).cloned().collect();
+    shared.sort();
+    unique_a.sort();
+    unique_b.sort();
+
+    let overlap_a = overlapping(&unique_a, &unique_b);
+    let overlap_b = overlapping(&unique_b, &unique_a);
+
+    let (repr_a, only_a) =
+        unique_a.into_iter().partition(|al| overlap_a.contains(al));
+    let (repr_b, only_b) =
+        unique_b.into_iter().partition(|al| overlap_b.contains(al));
+
+    VariantComparison {
+        shared,
+        only_a,
+        only_b,
+        repr_a,
+        repr_b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allele(pos: i64, reference: &str, alt: &str) -> Allele {
+        Allele {
+            chromosome: "ref".into(),
+            position: pos,
+            reference: reference.into(),
+            alternate: alt.into(),
+        }
+    }
+
+    #[test]
+    fn shared_unique_and_representation() {
+        let a: FnvHashSet<_> = vec![
+            allele(10, "A", "C"),
+            allele(20, "GAT", "G"),
+            allele(50, "T", "A"),
+        ]
+        .into_iter()
+        .collect();
+        let b: FnvHashSet<_> = vec![
+            allele(10, "A", "C"),
+            allele(21, "AT", "A"),
+            allele(80, "C", "G"),
+        ]
+        .into_iter()
+        .collect();
+
+        let cmp = compare_alleles(&a, &b);
+        assert_eq!(cmp.shared, vec![allele(10, "A", "C")]);
+        assert_eq!(cmp.repr_a, vec![allele(20, "GAT", "G")]);
+        assert_eq!(cmp.repr_b, vec![allele(21, "AT", "A")]);
+        assert_eq!(cmp.only_a, vec![allele(50, "T", "A")]);
+        assert_eq!(cmp.only_b, vec![allele(80, "C", "G")]);
+    }
+}
@@ -0,0 +1,225 @@
+use bstr::BString;
+use fnv::FnvHashMap;
+use std::fmt;
+
+use super::{vcf::VCFRecord, PathData};
+
+/// A window on a reference path, in 0-based, half-open BED
+/// coordinates, with the number of variant records starting in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct DensityWindow {
+    pub chromosome: BString,
+    pub start: usize,
+    pub end: usize,
+    pub count: usize,
+}
+
+impl DensityWindow {
+    /// The number of variants per kilobase in the window
+    pub fn per_kb(&self) -> f64 {
+        let len = self.end - self.start;
+        if len == 0 {
+            0.0
+        } else {
+            (self.count as f64 * 1000.0) / len as f64
+        }
+    }
+}
+
+impl fmt::Display for DensityWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{:.3}",
+            self.chromosome,
+            self.start,
+            self.end,
+            self.count,
+            self.per_kb()
+        )
+    }
+}
+
+/// Collects the positions of the emitted VCF records on each
+/// reference path, to summarize them in sliding windows
+#[derive(Debug, Default)]
+pub struct VariantDensity {
+    positions: FnvHashMap<BString, Vec<usize>>,
+}
+
+/// The index of the first position that's not less than `pos`, in
+/// a sorted slice
+fn lower_bound(positions: &[usize], pos: usize) -> usize {
+    positions
+        .binary_search_by(|&p| {
+            if p < pos {
+                std::cmp::Ordering::Less
+            } else {
+                std::cmp::Ordering::Greater
+            }
+        })
+        .unwrap_err()
+}
+
+impl VariantDensity {
+    /// Make sure `chromosome` gets windows even if it has no
+    /// variants
+    pub fn add_chromosome(&mut self, chromosome: &[u8]) {
+        self.positions.entry(chromosome.into()).or_default();
+    }
+
+    pub fn add(&mut self, record: &VCFRecord) {
+        let pos = (record.position.max(1) - 1) as usize;
+        self.positions
+            .entry(record.chromosome.clone())
+            .or_default()
+            .push(pos);
+    }
+
+    /// Count the variants in windows of `window` bp, starting every
+    /// `step` bp along each path, with the path lengths taken from
+    /// `path_lens`. The last window on each path is cut short at the
+    /// end of the path. The windows are sorted by path and start.
+    pub fn windows(
+        &mut self,
+        path_lens: &FnvHashMap<BString, usize>,
+        window: usize,
+        step: usize,
+    ) -> Vec<DensityWindow> {
+        let window = window.max(1);
+        let step = step.max(1);
+
+        let mut chromosomes: Vec<BString> =
+            self.positions.keys().cloned().collect();
+        chromosomes.sort();
+
+        let mut windows = Vec::new();
+
+        for chromosome in chromosomes {
+            let positions = self.positions.get_mut(&chromosome).unwrap();
+            positions.sort_unstable();
+
+            let path_len = path_lens
+                .get(&chromosome)
+                .copied()
+                .unwrap_or_else(|| positions.last().map_or(0, |&p| p + 1));
+
+            let mut start = 0;
+            while start < path_len {
+                let end = (start + window).min(path_len);
+                let count =
+                    lower_bound(positions, end) - lower_bound(positions, start);
+                windows.push(DensityWindow {
+                    chromosome: chromosome.clone(),
+                    start,
+                    end,
+                    count,
+                });
+                if end == path_len {
+                    break;
+                }
+                start += step;
+            }
+        }
+
+        windows
+    }
+
+    /// Merge the overlapping and adjacent windows with at least
+    /// `min_per_kb` variants per kilobase into hotspot intervals,
+    /// recounting the variants in each merged interval
+    pub fn hotspots(
+        &self,
+        windows: &[DensityWindow],
+        min_per_kb: f64,
+    ) -> Vec<DensityWindow> {
+        let mut hotspots: Vec<DensityWindow> = Vec::new();
+
+        for window in windows.iter().filter(|w| w.per_kb() >= min_per_kb) {
+            match hotspots.last_mut() {
+                Some(last)
+                    if last.chromosome == window.chromosome
+                        && window.start <= last.end =>
+                {
+                    last.end = last.end.max(window.end);
+                }
+                _ => hotspots.push(window.clone()),
+            }
+        }
+
+        for hotspot in hotspots.iter_mut() {
+            if let Some(positions) = self.positions.get(&hotspot.chromosome) {
+                hotspot.count = lower_bound(positions, hotspot.end)
+                    - lower_bound(positions, hotspot.start);
+            }
+        }
+
+        hotspots
+    }
+}
+
+/// The length of the sequence spelled by each path
+pub fn path_lengths(path_data: &PathData) -> FnvHashMap<BString, usize> {
+    path_data
+        .path_names
+        .iter()
+        .enumerate()
+        .map(|(ix, name)| (name.clone(), path_data.path_length(ix)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(pos: i64) -> VCFRecord {
+        VCFRecord {
+            chromosome: "ref".into(),
+            position: pos,
+            id: None,
+            reference: "A".into(),
+            alternate: Some("G".into()),
+            quality: None,
+            filter: None,
+            info: None,
+            format: None,
+            sample_name: None,
+        }
+    }
+
+    #[test]
+    fn sliding_windows_and_hotspots() {
+        let mut density = VariantDensity::default();
+        for &pos in &[1, 5, 10, 12, 31] {
+            density.add(&record(pos));
+        }
+        density.add_chromosome(b"other");
+
+        let mut path_lens = FnvHashMap::default();
+        path_lens.insert(BString::from("ref"), 35);
+        path_lens.insert(BString::from("other"), 8);
+
+        let windows = density.windows(&path_lens, 10, 5);
+        let summary: Vec<_> = windows
+            .iter()
+            .map(|w| (w.chromosome.to_string(), w.start, w.end, w.count))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("other".to_string(), 0, 8, 0),
+                ("ref".to_string(), 0, 10, 3),
+                ("ref".to_string(), 5, 15, 2),
+                ("ref".to_string(), 10, 20, 1),
+                ("ref".to_string(), 15, 25, 0),
+                ("ref".to_string(), 20, 30, 0),
+                ("ref".to_string(), 25, 35, 1),
+            ]
+        );
+
+        let hotspots = density.hotspots(&windows, 150.0);
+        let summary: Vec<_> =
+            hotspots.iter().map(|w| (w.start, w.end, w.count)).collect();
+        assert_eq!(summary, vec![(0, 15, 4)]);
+    }
+}
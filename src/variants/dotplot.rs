@@ -0,0 +1,299 @@
+//! Export of k-mer anchor matches between the reference and query
+//! alleles of complex bubbles, i.e. bubbles where the sub-paths can't
+//! be cleanly decomposed into SNVs, MNPs, insertions, and deletions,
+//! so that the loci can be inspected in a dotplot.
+
+use bstr::BString;
+use fnv::{FnvHashMap, FnvHashSet};
+use std::fmt;
+
+use super::{
+    detect_variants_against_ref_ranges, path_data_sub_path_ranges,
+    step_sequence, sub_path_slice, PathData, PathIndices, PathStep,
+    VariantHandler,
+};
+
+/// Ignore k-mers that occur more often than this in the reference
+/// allele, so that repeats don't blow up the number of matches
+const MAX_KMER_OCCURRENCES: usize = 16;
+
+/// Flags a ref/query pair as complex if two differing steps have
+/// sequences of different lengths, as those can't be represented as
+/// a single SNV, MNP, insertion, or deletion
+struct ComplexityHandler<'a> {
+    segment_sequences: &'a FnvHashMap<usize, BString>,
+    ref_path: &'a [PathStep],
+    query_path: &'a [PathStep],
+    complex: bool,
+}
+
+impl<'a> VariantHandler for ComplexityHandler<'a> {
+    fn deletion(&mut self, _: usize, _: usize, _: usize, _: usize) {}
+
+    fn insertion(&mut self, _: usize, _: usize, _: usize, _: usize) {}
+
+    fn mismatch(
+        &mut self,
+        ref_ix: usize,
+        query_ix: usize,
+        _ref_seq_ix: usize,
+        _query_seq_ix: usize,
+    ) {
+        let ref_seq =
+            step_sequence(self.segment_sequences, self.ref_path[ref_ix]);
+        let query_seq =
+            step_sequence(self.segment_sequences, self.query_path[query_ix]);
+        if ref_seq.len() != query_seq.len() {
+            self.complex = true;
+        }
+    }
+
+    fn match_(&mut self, _: usize, _: usize, _: usize, _: usize) {}
+}
+
+/// A run of consecutive matching k-mers on the same diagonal, in
+/// 0-based, half-open path coordinates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnchorMatch {
+    pub ref_start: usize,
+    pub ref_end: usize,
+    pub query_start: usize,
+    pub query_end: usize,
+}
+
+/// The reference and query alleles of a complex bubble, with their
+/// 0-based start positions on the respective paths
+#[derive(Debug, Clone)]
+pub struct ComplexAllele {
+    pub ref_name: BString,
+    pub ref_start: usize,
+    pub ref_seq: BString,
+    pub query_name: BString,
+    pub query_start: usize,
+    pub query_seq: BString,
+}
+
+/// A dotplot row, the anchors of one complex allele in one bubble
+#[derive(Debug, Clone)]
+pub struct DotplotRow {
+    pub ref_name: BString,
+    pub from: u64,
+    pub to: u64,
+    pub query_name: BString,
+    pub anchor: AnchorMatch,
+}
+
+impl fmt::Display for DotplotRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.ref_name,
+            self.from,
+            self.to,
+            self.query_name,
+            self.anchor.ref_start,
+            self.anchor.ref_end,
+            self.anchor.query_start,
+            self.anchor.query_end
+        )
+    }
+}
+
+fn steps_sequence(
+    segment_sequences: &FnvHashMap<usize, BString>,
+    steps: &[PathStep],
+) -> BString {
+    let mut seq = BString::from(Vec::new());
+    for &step in steps {
+        seq.extend_from_slice(&step_sequence(segment_sequences, step));
+    }
+    seq
+}
+
+/// Find the alleles of the query paths that can't be decomposed
+/// against the reference paths in the bubble between `from` and `to`.
+/// Every query path is compared to every reference path; query paths
+/// that traverse the same nodes as the reference are skipped.
+pub fn complex_bubble_alleles(
+    path_data: &PathData,
+    ref_path_names: Option<&FnvHashSet<BString>>,
+    path_indices: &PathIndices,
+    from: u64,
+    to: u64,
+) -> Vec<ComplexAllele> {
+    let sub_path_ranges =
+        match path_data_sub_path_ranges(path_data, path_indices, from, to) {
+            Some(ranges) => ranges,
+            None => return Vec::new(),
+        };
+
+    let nodes = |steps: &[PathStep]| -> Vec<usize> {
+        steps.iter().map(|&(node, _, _)| node).collect()
+    };
+
+    let mut alleles = Vec::new();
+
+    for &(ref_ix, ref_range) in sub_path_ranges.iter() {
+        let ref_name = &path_data.path_names[ref_ix];
+        if let Some(ref_paths) = ref_path_names {
+            if !ref_paths.contains(ref_name) {
+                continue;
+            }
+        }
+
+        let ref_path = &path_data.paths[ref_ix];
+        let ref_steps = sub_path_slice(ref_path, ref_range);
+
+        for &(query_ix, query_range) in sub_path_ranges.iter() {
+            if query_ix == ref_ix {
+                continue;
+            }
+
+            let query_path = &path_data.paths[query_ix];
+            let query_steps = sub_path_slice(query_path, query_range);
+
+            if nodes(ref_steps) == nodes(query_steps) {
+                continue;
+            }
+
+            let mut handler = ComplexityHandler {
+                segment_sequences: &path_data.segment_map,
+                ref_path,
+                query_path,
+                complex: false,
+            };
+
+            let complete = detect_variants_against_ref_ranges(
+                &path_data.segment_map,
+                ref_path,
+                query_path,
+                ref_range,
+                query_range,
+                &mut handler,
+            );
+
+            if complete && !handler.complex {
+                continue;
+            }
+
+            alleles.push(ComplexAllele {
+                ref_name: ref_name.clone(),
+                ref_start: ref_steps[0].1 - 1,
+                ref_seq: steps_sequence(&path_data.segment_map, ref_steps),
+                query_name: path_data.path_names[query_ix].clone(),
+                query_start: query_steps[0].1 - 1,
+                query_seq: steps_sequence(&path_data.segment_map, query_steps),
+            });
+        }
+    }
+
+    alleles
+}
+
+/// Find the exact k-mer matches between the reference and query
+/// allele sequences, merging matches on the same diagonal that
+/// overlap into a single anchor. The anchors are in path coordinates.
+pub fn kmer_anchors(allele: &ComplexAllele, k: usize) -> Vec<AnchorMatch> {
+    let ref_seq = allele.ref_seq.as_slice();
+    let query_seq = allele.query_seq.as_slice();
+
+    if k == 0 || ref_seq.len() < k || query_seq.len() < k {
+        return Vec::new();
+    }
+
+    let mut kmers: FnvHashMap<&[u8], Vec<usize>> = FnvHashMap::default();
+    for (pos, kmer) in ref_seq.windows(k).enumerate() {
+        kmers.entry(kmer).or_default().push(pos);
+    }
+
+    // Matches are keyed by diagonal, i.e. query pos - ref pos, and
+    // extended while consecutive k-mers on the diagonal match
+    let mut open: FnvHashMap<isize, AnchorMatch> = FnvHashMap::default();
+    let mut anchors = Vec::new();
+
+    for (query_pos, kmer) in query_seq.windows(k).enumerate() {
+        let ref_positions = match kmers.get(kmer) {
+            Some(positions) if positions.len() <= MAX_KMER_OCCURRENCES => {
+                positions
+            }
+            _ => continue,
+        };
+
+        for &ref_pos in ref_positions {
+            let diagonal = query_pos as isize - ref_pos as isize;
+            match open.get_mut(&diagonal) {
+                Some(anchor) if anchor.query_end == query_pos + k - 1 => {
+                    anchor.ref_end += 1;
+                    anchor.query_end += 1;
+                }
+                _ => {
+                    let anchor = AnchorMatch {
+                        ref_start: ref_pos,
+                        ref_end: ref_pos + k,
+                        query_start: query_pos,
+                        query_end: query_pos + k,
+                    };
+                    if let Some(prev) = open.insert(diagonal, anchor) {
+                        anchors.push(prev);
+                    }
+                }
+            }
+        }
+    }
+
+    anchors.extend(open.into_iter().map(|(_, anchor)| anchor));
+
+    for anchor in anchors.iter_mut() {
+        anchor.ref_start += allele.ref_start;
+        anchor.ref_end += allele.ref_start;
+        anchor.query_start += allele.query_start;
+        anchor.query_end += allele.query_start;
+    }
+
+    anchors.sort_by_key(|a| (a.ref_start, a.query_start));
+    anchors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allele(ref_seq: &str, query_seq: &str) -> ComplexAllele {
+        ComplexAllele {
+            ref_name: "ref".into(),
+            ref_start: 100,
+            ref_seq: ref_seq.into(),
+            query_name: "query".into(),
+            query_start: 10,
+            query_seq: query_seq.into(),
+        }
+    }
+
+    #[test]
+    fn anchors_are_merged_along_diagonals() {
+        // The query has an insertion of TT after the first 6 bases
+        let allele = allele("ACGTACCCGGA", "ACGTACTTCCGGA");
+        let anchors = kmer_anchors(&allele, 4);
+
+        assert_eq!(
+            anchors,
+            vec![
+                AnchorMatch {
+                    ref_start: 100,
+                    ref_end: 106,
+                    query_start: 10,
+                    query_end: 16,
+                },
+                AnchorMatch {
+                    ref_start: 106,
+                    ref_end: 111,
+                    query_start: 18,
+                    query_end: 23,
+                },
+            ]
+        );
+
+        assert!(kmer_anchors(&allele, 20).is_empty());
+    }
+}
@@ -0,0 +1,238 @@
+use bstr::{BString, ByteSlice};
+use std::fmt;
+
+use super::{
+    validate::RefSequences,
+    vcf::{info_value, VCFRecord},
+    PathStep,
+};
+
+/// The node traversal and sequence spelled by one path at the
+/// position of a VCF allele
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlleleTraversal {
+    pub chromosome: BString,
+    pub position: i64,
+    /// 0 for REF, and 1 and up for the ALT alleles
+    pub allele_ix: usize,
+    /// The allele, as written in the VCF
+    pub allele: BString,
+    /// The path the allele was looked up in, and the 1-based
+    /// position of the allele in that path. `None` for ALT alleles
+    /// of records without the QNAME and QSTART INFO fields.
+    pub path: Option<(BString, usize)>,
+    pub steps: Vec<PathStep>,
+    pub sequence: Option<BString>,
+}
+
+impl AlleleTraversal {
+    /// Whether the sequence of the path at the allele's position is
+    /// the same as the allele in the VCF
+    pub fn matches(&self) -> bool {
+        self.sequence
+            .as_ref()
+            .map_or(false, |seq| seq.eq_ignore_ascii_case(&self.allele))
+    }
+
+    /// The steps as a GAF-style walk, e.g. `>1<2>3`
+    pub fn walk(&self) -> String {
+        self.steps
+            .iter()
+            .map(|&(node, _, orient)| {
+                let dir = if orient.is_reverse() { '<' } else { '>' };
+                format!("{}{}", dir, node)
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for AlleleTraversal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (path, path_pos) = match &self.path {
+            Some((name, pos)) => (name.to_string(), pos.to_string()),
+            None => (".".to_string(), ".".to_string()),
+        };
+        let walk = if self.steps.is_empty() {
+            ".".to_string()
+        } else {
+            self.walk()
+        };
+        let sequence = self
+            .sequence
+            .as_ref()
+            .map_or_else(|| ".".to_string(), |seq| seq.to_string());
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.chromosome,
+            self.position,
+            self.allele_ix,
+            self.allele,
+            path,
+            path_pos,
+            walk,
+            sequence,
+            if self.matches() { "yes" } else { "no" }
+        )
+    }
+}
+
+/// Look up the `len` bases of the named path starting at the 1-based
+/// position `pos`
+fn path_interval(
+    ref_seqs: &RefSequences<'_>,
+    path: &[u8],
+    pos: usize,
+    len: usize,
+) -> (Vec<PathStep>, Option<BString>) {
+    match ref_seqs.path_index(path) {
+        Some(path_ix) => (
+            ref_seqs.steps(path_ix, pos, len).to_vec(),
+            ref_seqs.sequence(path_ix, pos, len),
+        ),
+        None => (Vec::new(), None),
+    }
+}
+
+/// Reconstruct the traversals of the alleles of a VCF record produced
+/// by gfa2vcf. The REF allele is looked up in the reference path at
+/// the record's position. The ALT alleles are looked up in each of
+/// the query paths listed in the QNAME and QSTART INFO fields, which
+/// gfa2vcf adds with --query-coords; without those fields, the ALT
+/// alleles are returned without a traversal.
+pub fn record_traversals(
+    ref_seqs: &RefSequences<'_>,
+    record: &VCFRecord,
+) -> Vec<AlleleTraversal> {
+    let traversal = |allele_ix: usize, allele: &[u8]| AlleleTraversal {
+        chromosome: record.chromosome.clone(),
+        position: record.position,
+        allele_ix,
+        allele: allele.into(),
+        path: None,
+        steps: Vec::new(),
+        sequence: None,
+    };
+
+    let mut traversals = Vec::new();
+
+    let ref_pos = record.position.max(0) as usize;
+    let (steps, sequence) = path_interval(
+        ref_seqs,
+        &record.chromosome,
+        ref_pos,
+        record.reference.len(),
+    );
+    traversals.push(AlleleTraversal {
+        path: Some((record.chromosome.clone(), ref_pos)),
+        steps,
+        sequence,
+        ..traversal(0, &record.reference)
+    });
+
+    let alts: Vec<&[u8]> = record
+        .alternate
+        .as_ref()
+        .map(|alts| alts.split_str(",").collect())
+        .unwrap_or_default();
+
+    let split_alleles = |key: &str| -> Vec<Vec<&[u8]>> {
+        info_value(record, key)
+            .map(|value| {
+                value
+                    .split_str(",")
+                    .map(|names| names.split_str("|").collect())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let query_names = split_alleles("QNAME");
+    let query_starts = split_alleles("QSTART");
+
+    for (ix, alt) in alts.into_iter().enumerate() {
+        let queries: Vec<(&[u8], usize)> =
+            match (query_names.get(ix), query_starts.get(ix)) {
+                (Some(names), Some(starts)) => names
+                    .iter()
+                    .zip(starts.iter())
+                    .filter_map(|(&name, start)| {
+                        let start = start.to_str().ok()?.parse().ok()?;
+                        Some((name, start))
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+
+        if queries.is_empty() {
+            traversals.push(traversal(ix + 1, alt));
+        }
+
+        for (name, start) in queries {
+            let (steps, sequence) =
+                path_interval(ref_seqs, name, start, alt.len());
+            traversals.push(AlleleTraversal {
+                path: Some((name.into(), start)),
+                steps,
+                sequence,
+                ..traversal(ix + 1, alt)
+            });
+        }
+    }
+
+    traversals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variants::{vcf::parse_record, PathData};
+    use gfa::gfa::Orientation::Forward;
+
+    fn path_data() -> PathData {
+        let segment_map = vec![(1, "ACGT"), (2, "A"), (3, "C"), (4, "GGT")]
+            .into_iter()
+            .map(|(id, seq)| (id, BString::from(seq)))
+            .collect();
+        let paths = vec![
+            vec![(1, 1, Forward), (2, 5, Forward), (4, 6, Forward)],
+            vec![(1, 1, Forward), (3, 5, Forward), (4, 6, Forward)],
+        ];
+        PathData {
+            segment_map,
+            path_names: vec!["ref".into(), "query".into()],
+            paths,
+        }
+    }
+
+    #[test]
+    fn snv_traversals() {
+        let path_data = path_data();
+        let ref_seqs = RefSequences::new(&path_data);
+
+        let record = parse_record(
+            b"ref\t5\t.\tA\tC\t.\t.\tTYPE=snv;QNAME=query;QSTART=5",
+        )
+        .unwrap();
+        let traversals = record_traversals(&ref_seqs, &record);
+        assert_eq!(traversals.len(), 2);
+
+        assert_eq!(traversals[0].allele_ix, 0);
+        assert_eq!(traversals[0].walk(), ">2");
+        assert_eq!(traversals[0].sequence, Some("A".into()));
+        assert!(traversals[0].matches());
+
+        assert_eq!(traversals[1].allele_ix, 1);
+        assert_eq!(traversals[1].path, Some(("query".into(), 5)));
+        assert_eq!(traversals[1].walk(), ">3");
+        assert!(traversals[1].matches());
+
+        let record =
+            parse_record(b"ref\t4\t.\tTAG\tTCG\t.\t.\tTYPE=mnp").unwrap();
+        let traversals = record_traversals(&ref_seqs, &record);
+        assert_eq!(traversals[0].walk(), ">1>2>4");
+        assert!(traversals[0].matches());
+        assert_eq!(traversals[1].path, None);
+        assert!(!traversals[1].matches());
+        assert_eq!(traversals[1].to_string(), "ref\t4\t1\tTCG\t.\t.\t.\t.\tno");
+    }
+}
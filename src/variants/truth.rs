@@ -0,0 +1,199 @@
+use bstr::{io::*, BString, ByteSlice};
+use fnv::{FnvHashMap, FnvHashSet};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use super::vcf::VCFRecord;
+
+/// An allele is identified by the contig, position, REF, and ALT.
+/// Sequences are uppercased before comparison.
+type AlleleKey = (BString, i64, BString, BString);
+
+/// True positive, false positive, and false negative allele counts
+/// for one reference
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TruthCounts {
+    pub true_pos: usize,
+    pub false_pos: usize,
+    pub false_neg: usize,
+}
+
+/// Compares emitted VCF records against a known callset, one ALT
+/// allele at a time
+#[derive(Debug, Default)]
+pub struct TruthSet {
+    alleles: FnvHashSet<AlleleKey>,
+    found: FnvHashSet<AlleleKey>,
+    counts: FnvHashMap<BString, TruthCounts>,
+}
+
+fn allele_key(
+    chrom: &[u8],
+    pos: i64,
+    reference: &[u8],
+    alt: &[u8],
+) -> AlleleKey {
+    (
+        chrom.into(),
+        pos,
+        reference.to_ascii_uppercase().into(),
+        alt.to_ascii_uppercase().into(),
+    )
+}
+
+impl TruthSet {
+    pub fn from_reader<R: BufRead>(reader: R) -> std::io::Result<Self> {
+        let mut truth = TruthSet::default();
+
+        for line in reader.byte_lines() {
+            let line = line?;
+            if line.is_empty() || line.starts_with(b"#") {
+                continue;
+            }
+
+            let fields: Vec<&[u8]> = line.split_str("\t").collect();
+            if fields.len() < 5 {
+                continue;
+            }
+
+            let pos = match fields[1].to_str().ok().and_then(|p| p.parse().ok())
+            {
+                Some(pos) => pos,
+                None => continue,
+            };
+
+            for alt in fields[4].split_str(",") {
+                if alt != b"." && alt != b"*" {
+                    truth
+                        .alleles
+                        .insert(allele_key(fields[0], pos, fields[3], alt));
+                }
+            }
+        }
+
+        Ok(truth)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let file = File::open(path)?;
+        Self::from_reader(BufReader::new(file))
+    }
+
+    /// Classify each ALT allele of the record as TP or FP, and add
+    /// the classification to its INFO column as `TRUTH`
+    pub fn annotate(&mut self, record: &mut VCFRecord) {
+        let alts = match &record.alternate {
+            Some(alts) => alts.clone(),
+            None => return,
+        };
+
+        let TruthSet {
+            alleles,
+            found,
+            counts,
+        } = self;
+        let counts = counts.entry(record.chromosome.clone()).or_default();
+
+        let labels: Vec<&str> = alts
+            .split_str(",")
+            .map(|alt| {
+                let key = allele_key(
+                    &record.chromosome,
+                    record.position,
+                    &record.reference,
+                    alt,
+                );
+                if alleles.contains(&key) {
+                    if found.insert(key) {
+                        counts.true_pos += 1;
+                    }
+                    "TP"
+                } else {
+                    counts.false_pos += 1;
+                    "FP"
+                }
+            })
+            .collect();
+
+        let truth_info = format!("TRUTH={}", labels.join(","));
+        record.info = Some(match record.info.take() {
+            Some(info) => format!("{};{}", info, truth_info).into(),
+            None => truth_info.into(),
+        });
+    }
+
+    /// Finish the comparison, counting the truth alleles that were
+    /// never emitted as false negatives. The counts are sorted by
+    /// reference name.
+    pub fn summary(mut self) -> Vec<(BString, TruthCounts)> {
+        for key in self.alleles.iter() {
+            if !self.found.contains(key) {
+                self.counts.entry(key.0.clone()).or_default().false_neg += 1;
+            }
+        }
+
+        let mut summary: Vec<_> = self.counts.into_iter().collect();
+        summary.sort_by(|(a, _), (b, _)| a.cmp(b));
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(pos: i64, reference: &str, alt: &str) -> VCFRecord {
+        VCFRecord {
+            chromosome: "ref".into(),
+            position: pos,
+            id: None,
+            reference: reference.into(),
+            alternate: Some(alt.into()),
+            quality: None,
+            filter: None,
+            info: Some("TYPE=snv".into()),
+            format: None,
+            sample_name: None,
+        }
+    }
+
+    #[test]
+    fn truth_annotation_and_counts() {
+        let truth_vcf = "##fileformat=VCFv4.2\n\
+                         #CHROM\tPOS\tID\tREF\tALT\n\
+                         ref\t10\t.\tA\tC,G\n\
+                         ref\t20\t.\tT\tA\n\
+                         other\t5\t.\tG\tT\n";
+        let mut truth = TruthSet::from_reader(truth_vcf.as_bytes()).unwrap();
+
+        let mut rec = record(10, "a", "g,T");
+        truth.annotate(&mut rec);
+        assert_eq!(rec.info, Some("TYPE=snv;TRUTH=TP,FP".into()));
+
+        let summary = truth.summary();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "other".into(),
+                    TruthCounts {
+                        true_pos: 0,
+                        false_pos: 0,
+                        false_neg: 1,
+                    }
+                ),
+                (
+                    "ref".into(),
+                    TruthCounts {
+                        true_pos: 1,
+                        false_pos: 1,
+                        false_neg: 2,
+                    }
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,286 @@
+use bstr::{BString, ByteSlice};
+use fnv::FnvHashMap;
+use std::fmt;
+
+use super::{vcf::VCFRecord, PathData, PathStep};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    UnknownContig,
+    PosOutOfBounds { pos: i64, len: usize },
+    RefMismatch { expected: BString, found: BString },
+    EmptyAlt,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::UnknownContig => {
+                write!(f, "contig is not a path in the graph")
+            }
+            ValidationError::PosOutOfBounds { pos, len } => write!(
+                f,
+                "position {} is outside the contig, which has length {}",
+                pos, len
+            ),
+            ValidationError::RefMismatch { expected, found } => write!(
+                f,
+                "REF is {}, but the reference path has {}",
+                found, expected
+            ),
+            ValidationError::EmptyAlt => write!(f, "ALT is empty"),
+        }
+    }
+}
+
+/// Look up the sequences of the reference paths in a `PathData`,
+/// without reconstructing the full path sequences
+pub struct RefSequences<'a> {
+    path_data: &'a PathData,
+    path_indices: FnvHashMap<&'a [u8], usize>,
+}
+
+impl<'a> RefSequences<'a> {
+    pub fn new(path_data: &'a PathData) -> Self {
+        let path_indices = path_data
+            .path_names
+            .iter()
+            .enumerate()
+            .map(|(ix, name)| (name.as_slice(), ix))
+            .collect();
+        Self {
+            path_data,
+            path_indices,
+        }
+    }
+
+    /// The index of the path with the given name
+    pub fn path_index(&self, name: &[u8]) -> Option<usize> {
+        self.path_indices.get(name).copied()
+    }
+
+    /// The steps of the path that overlap the `len` bases starting at
+    /// the 1-based position `pos`
+    pub fn steps(&self, path_ix: usize, pos: usize, len: usize) -> &[PathStep] {
+        let steps = &self.path_data.paths[path_ix];
+        let first_step =
+            match steps.binary_search_by_key(&pos, |&(_, offset, _)| offset) {
+                Ok(ix) => ix,
+                Err(ix) => ix.saturating_sub(1),
+            };
+        let end = pos + len.max(1);
+        let last_step = steps[first_step..]
+            .iter()
+            .position(|&(_, offset, _)| offset >= end)
+            .map_or(steps.len(), |ix| first_step + ix);
+        &steps[first_step..last_step]
+    }
+
+    /// Return `len` bases of the path sequence, starting at the
+    /// 1-based position `pos`, taking step orientations into account
+    pub fn sequence(
+        &self,
+        path_ix: usize,
+        pos: usize,
+        len: usize,
+    ) -> Option<BString> {
+        let steps = &self.path_data.paths[path_ix];
+
+        let first_step =
+            match steps.binary_search_by_key(&pos, |&(_, offset, _)| offset) {
+                Ok(ix) => ix,
+                Err(ix) => ix.checked_sub(1)?,
+            };
+
+        let mut seq: Vec<u8> = Vec::with_capacity(len);
+
+        for &(node, offset, orient) in steps[first_step..].iter() {
+            let node_seq = self.path_data.segment_map.get(&node)?;
+            let skip = pos.saturating_sub(offset);
+            let remaining = len - seq.len();
+            if orient.is_reverse() {
+                seq.extend(
+                    handlegraph::util::dna::rev_comp_iter(node_seq.as_slice())
+                        .skip(skip)
+                        .take(remaining),
+                );
+            } else {
+                seq.extend(node_seq.iter().skip(skip).take(remaining));
+            }
+            if seq.len() >= len {
+                break;
+            }
+        }
+
+        if seq.len() == len {
+            Some(seq.into())
+        } else {
+            None
+        }
+    }
+
+    /// Check that the record's position lies within its contig, that
+    /// its REF matches the reference path, and that it has an ALT
+    pub fn validate_record(
+        &self,
+        record: &VCFRecord,
+    ) -> Result<(), ValidationError> {
+        let path_ix = *self
+            .path_indices
+            .get(record.chromosome.as_slice())
+            .ok_or(ValidationError::UnknownContig)?;
+
+        let len = self.path_data.path_length(path_ix);
+        let ref_len = record.reference.len();
+
+        if record.position < 1
+            || record.position as usize + ref_len.max(1) - 1 > len
+        {
+            return Err(ValidationError::PosOutOfBounds {
+                pos: record.position,
+                len,
+            });
+        }
+
+        let expected = self
+            .sequence(path_ix, record.position as usize, ref_len)
+            .unwrap_or_default();
+        if !expected.eq_ignore_ascii_case(&record.reference) {
+            return Err(ValidationError::RefMismatch {
+                expected,
+                found: record.reference.clone(),
+            });
+        }
+
+        match &record.alternate {
+            Some(alt) if !alt.is_empty() => Ok(()),
+            _ => Err(ValidationError::EmptyAlt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gfa::gfa::Orientation::*;
+
+    /// ref: ACGT, then GAC reversed (GTC), then TTA
+    fn path_data() -> PathData {
+        let segment_map = vec![(1, "ACGT"), (2, "GAC"), (3, "TTA")]
+            .into_iter()
+            .map(|(id, seq)| (id, BString::from(seq)))
+            .collect();
+        PathData {
+            segment_map,
+            path_names: vec!["ref".into()],
+            paths: vec![vec![
+                (1, 1, Forward),
+                (2, 5, Backward),
+                (3, 8, Forward),
+            ]],
+        }
+    }
+
+    fn record(pos: i64, reference: &str, alt: Option<&str>) -> VCFRecord {
+        VCFRecord {
+            chromosome: "ref".into(),
+            position: pos,
+            id: None,
+            reference: reference.into(),
+            alternate: alt.map(BString::from),
+            quality: None,
+            filter: None,
+            info: None,
+            format: None,
+            sample_name: None,
+        }
+    }
+
+    #[test]
+    fn validate_position() {
+        let path_data = path_data();
+        let ref_seqs = RefSequences::new(&path_data);
+
+        assert!(ref_seqs
+            .validate_record(&record(10, "A", Some("G")))
+            .is_ok());
+        assert_eq!(
+            ref_seqs.validate_record(&record(11, "A", Some("G"))),
+            Err(ValidationError::PosOutOfBounds { pos: 11, len: 10 })
+        );
+        // The REF runs past the end of the path
+        assert_eq!(
+            ref_seqs.validate_record(&record(10, "AT", Some("G"))),
+            Err(ValidationError::PosOutOfBounds { pos: 10, len: 10 })
+        );
+        assert_eq!(
+            ref_seqs.validate_record(&record(0, "A", Some("G"))),
+            Err(ValidationError::PosOutOfBounds { pos: 0, len: 10 })
+        );
+
+        let mut unknown = record(1, "A", Some("G"));
+        unknown.chromosome = "chr1".into();
+        assert_eq!(
+            ref_seqs.validate_record(&unknown),
+            Err(ValidationError::UnknownContig)
+        );
+    }
+
+    #[test]
+    fn validate_reverse_step() {
+        let path_data = path_data();
+        let ref_seqs = RefSequences::new(&path_data);
+
+        assert_eq!(ref_seqs.sequence(0, 5, 3), Some("GTC".into()));
+        assert!(ref_seqs
+            .validate_record(&record(5, "GTC", Some("A")))
+            .is_ok());
+        assert!(ref_seqs
+            .validate_record(&record(6, "tc", Some("A")))
+            .is_ok());
+        // The segment's own sequence, not the one the path spells
+        assert_eq!(
+            ref_seqs.validate_record(&record(5, "GAC", Some("A"))),
+            Err(ValidationError::RefMismatch {
+                expected: "GTC".into(),
+                found: "GAC".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn validate_ref_across_steps() {
+        let path_data = path_data();
+        let ref_seqs = RefSequences::new(&path_data);
+
+        assert_eq!(ref_seqs.sequence(0, 4, 5), Some("TGTCT".into()));
+        assert_eq!(ref_seqs.steps(0, 4, 5).len(), 3);
+        assert_eq!(ref_seqs.steps(0, 5, 3), &[(2, 5, Backward)][..]);
+
+        assert!(ref_seqs
+            .validate_record(&record(4, "TGTCT", Some("T")))
+            .is_ok());
+        assert_eq!(
+            ref_seqs.validate_record(&record(4, "TGACT", Some("T"))),
+            Err(ValidationError::RefMismatch {
+                expected: "TGTCT".into(),
+                found: "TGACT".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn validate_empty_alt() {
+        let path_data = path_data();
+        let ref_seqs = RefSequences::new(&path_data);
+
+        assert_eq!(
+            ref_seqs.validate_record(&record(1, "A", None)),
+            Err(ValidationError::EmptyAlt)
+        );
+        assert_eq!(
+            ref_seqs.validate_record(&record(1, "A", Some(""))),
+            Err(ValidationError::EmptyAlt)
+        );
+    }
+}
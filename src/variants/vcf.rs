@@ -1,4 +1,4 @@
-use bstr::BString;
+use bstr::{BString, ByteSlice};
 use std::{
     fmt,
     fmt::{Display, Formatter},
@@ -34,6 +34,118 @@ impl VCFRecord {
     }
 }
 
+/// Split an INFO column into the fields before the PATHS field, and
+/// the PATHS value, if any.
+fn split_path_names(info: &[u8]) -> (&[u8], Option<&[u8]>) {
+    match info.find(b";PATHS=") {
+        Some(ix) => (&info[..ix], Some(&info[ix + 7..])),
+        None => (info, None),
+    }
+}
+
+/// If `record` and `kept` are identical except for the query path
+/// names in their PATHS INFO fields, add the path names of `record`
+/// to `kept` and return true, so that this can be used with
+/// `Vec::dedup_by` to deduplicate records without losing provenance.
+pub fn merge_path_names(record: &mut VCFRecord, kept: &mut VCFRecord) -> bool {
+    let same_site = record.chromosome == kept.chromosome
+        && record.position == kept.position
+        && record.id == kept.id
+        && record.reference == kept.reference
+        && record.alternate == kept.alternate
+        && record.quality == kept.quality
+        && record.filter == kept.filter
+        && record.format == kept.format
+        && record.sample_name == kept.sample_name;
+
+    if !same_site {
+        return false;
+    }
+
+    let (record_info, kept_info) = match (&record.info, &kept.info) {
+        (Some(record_info), Some(kept_info)) => (record_info, kept_info),
+        (None, None) => return true,
+        _ => return false,
+    };
+
+    let (record_fields, record_paths) = split_path_names(record_info);
+    let (kept_fields, kept_paths) = split_path_names(kept_info);
+
+    if record_fields != kept_fields {
+        return false;
+    }
+
+    let (record_paths, kept_paths) = match (record_paths, kept_paths) {
+        (Some(record_paths), Some(kept_paths)) => (record_paths, kept_paths),
+        (None, None) => return true,
+        _ => return false,
+    };
+
+    let mut names: Vec<&[u8]> = kept_paths
+        .split_str(",")
+        .chain(record_paths.split_str(","))
+        .collect();
+    names.sort();
+    names.dedup();
+
+    let mut info: BString = kept_fields.into();
+    info.extend_from_slice(b";PATHS=");
+    info.extend(bstr::join(",", names));
+    kept.info = Some(info);
+
+    true
+}
+
+/// Parse a VCF data line. The FORMAT and sample columns, if any, are
+/// kept as they are, with the sample columns joined by tabs.
+pub fn parse_record(line: &[u8]) -> Option<VCFRecord> {
+    fn optional(field: &[u8]) -> Option<BString> {
+        if field == b"." {
+            None
+        } else {
+            Some(field.into())
+        }
+    }
+
+    let mut fields = line.splitn_str(10, "\t");
+    let chromosome = fields.next()?.into();
+    let position = fields.next()?.to_str().ok()?.parse().ok()?;
+    let id = optional(fields.next()?);
+    let reference = fields.next()?.into();
+    let alternate = optional(fields.next()?);
+    let quality = fields.next()?.to_str().ok()?.parse().ok();
+    let filter = optional(fields.next()?);
+    let info = optional(fields.next()?);
+    let format = fields.next().map(BString::from);
+    let sample_name = fields.next().map(BString::from);
+
+    Some(VCFRecord {
+        chromosome,
+        position,
+        id,
+        reference,
+        alternate,
+        quality,
+        filter,
+        info,
+        format,
+        sample_name,
+    })
+}
+
+/// The value of the INFO field `key`, if the record has it
+pub fn info_value<'a>(record: &'a VCFRecord, key: &str) -> Option<&'a [u8]> {
+    let info = record.info.as_ref()?;
+    info.split_str(";").find_map(|field| {
+        let (name, value) = field.split_at(field.find_byte(b'=')?);
+        if name == key.as_bytes() {
+            Some(&value[1..])
+        } else {
+            None
+        }
+    })
+}
+
 impl Display for VCFRecord {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         fn display_field<T: Display>(field: Option<T>) -> String {
@@ -64,12 +176,54 @@ impl Display for VCFRecord {
 
 pub struct VCFHeader {
     reference: PathBuf,
+    info_fields: Vec<String>,
+    format_fields: Vec<String>,
+    samples: Vec<BString>,
 }
 
 impl VCFHeader {
     pub fn new<T: AsRef<Path>>(path: T) -> Self {
         let reference = path.as_ref().to_owned();
-        Self { reference }
+        Self {
+            reference,
+            info_fields: Vec::new(),
+            format_fields: Vec::new(),
+            samples: Vec::new(),
+        }
+    }
+
+    /// Declare an additional INFO field, besides TYPE
+    pub fn add_info(
+        &mut self,
+        id: &str,
+        number: &str,
+        field_type: &str,
+        description: &str,
+    ) {
+        self.info_fields.push(format!(
+            r#"##INFO=<ID={},Number={},Type={},Description="{}">"#,
+            id, number, field_type, description
+        ));
+    }
+
+    /// Declare a FORMAT field
+    pub fn add_format(
+        &mut self,
+        id: &str,
+        number: &str,
+        field_type: &str,
+        description: &str,
+    ) {
+        self.format_fields.push(format!(
+            r#"##FORMAT=<ID={},Number={},Type={},Description="{}">"#,
+            id, number, field_type, description
+        ));
+    }
+
+    /// Set the sample columns. If there are any, the header line
+    /// includes the FORMAT column followed by the sample names.
+    pub fn set_samples(&mut self, samples: Vec<BString>) {
+        self.samples = samples;
     }
 }
 
@@ -86,6 +240,14 @@ impl Display for VCFHeader {
             r#"##INFO=<ID=TYPE,Number=A,Type=String,Description="Type of each allele (snv, ins, del, mnp, clumped)">"#
         )?;
 
+        for info in self.info_fields.iter() {
+            writeln!(f, "{}", info)?;
+        }
+
+        for format in self.format_fields.iter() {
+            writeln!(f, "{}", format)?;
+        }
+
         // writeln!(
         //     f,
         //     r#"##FORMAT=<ID=GT,Number=1,Type=String,Description="Genotype">"#
@@ -103,6 +265,73 @@ impl Display for VCFHeader {
         )
         .into();
 
-        write!(f, "{}", header_line)
+        write!(f, "{}", header_line)?;
+
+        if !self.samples.is_empty() {
+            write!(f, "\tFORMAT")?;
+            for sample in self.samples.iter() {
+                write!(f, "\t{}", sample)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(alt: &str, info: &str) -> VCFRecord {
+        VCFRecord {
+            chromosome: "ref".into(),
+            position: 10,
+            id: None,
+            reference: "A".into(),
+            alternate: Some(alt.into()),
+            quality: Some(50),
+            filter: None,
+            info: Some(info.into()),
+            format: None,
+            sample_name: None,
+        }
+    }
+
+    #[test]
+    fn parsing_records() {
+        let line = b"ref\t10\t.\tA\tG,T\t50\t.\tTYPE=snv;QSTART=3,7\tGT\t0\t1";
+        let vcf = parse_record(line).unwrap();
+        assert_eq!(vcf.chromosome, "ref");
+        assert_eq!(vcf.position, 10);
+        assert_eq!(vcf.id, None);
+        assert_eq!(vcf.alternate, Some("G,T".into()));
+        assert_eq!(vcf.quality, Some(50));
+        assert_eq!(vcf.format, Some("GT".into()));
+        assert_eq!(vcf.sample_name, Some("0\t1".into()));
+        assert_eq!(info_value(&vcf, "QSTART"), Some(&b"3,7"[..]));
+        assert_eq!(info_value(&vcf, "QNAME"), None);
+
+        let line = b"chr\xff1\t10\t.\tA\tG\t.\t.\t.";
+        let vcf = parse_record(line).unwrap();
+        assert_eq!(vcf.chromosome, BString::from(&b"chr\xff1"[..]));
+        assert_eq!(vcf.to_string().as_bytes(), &line[..]);
+    }
+
+    #[test]
+    fn merging_duplicates_keeps_path_names() {
+        let mut kept = record("G", "TYPE=snv;PATHS=b,c");
+        let mut dup = record("G", "TYPE=snv;PATHS=a,c");
+        assert!(merge_path_names(&mut dup, &mut kept));
+        assert_eq!(kept.info, Some("TYPE=snv;PATHS=a,b,c".into()));
+
+        let mut other_alt = record("T", "TYPE=snv;PATHS=a");
+        assert!(!merge_path_names(&mut other_alt, &mut kept));
+
+        let mut other_info = record("G", "TYPE=mnp;PATHS=a");
+        assert!(!merge_path_names(&mut other_info, &mut kept));
+
+        let mut exact = record("G", "TYPE=snv");
+        let mut exact_kept = record("G", "TYPE=snv");
+        assert!(merge_path_names(&mut exact, &mut exact_kept));
     }
 }
@@ -0,0 +1,174 @@
+use std::path::PathBuf;
+
+use gfa::{gfa::GFA, optfields::OptionalFields, parser::GFAParser};
+
+use gfautil::clean::{
+    add_missing_links, clean_gfa, hard_mask_ambiguous, missing_path_links,
+    remove_uncovered, split_ambiguous, uncovered_by_paths, Uncovered,
+};
+
+fn load_gfa(gfa_path: &str) -> GFA<Vec<u8>, OptionalFields> {
+    let gfa_path = PathBuf::from(gfa_path);
+    let parser = GFAParser::new();
+    parser.parse_file(gfa_path).unwrap()
+}
+
+fn path_steps(gfa: &GFA<Vec<u8>, OptionalFields>) -> Vec<(String, String)> {
+    gfa.paths
+        .iter()
+        .map(|p| {
+            let steps: Vec<String> = p
+                .iter()
+                .map(|(seg, _)| {
+                    let seg: &[u8] = seg.as_ref();
+                    String::from_utf8(seg.to_vec()).unwrap()
+                })
+                .collect();
+            let name = String::from_utf8(p.path_name.to_vec()).unwrap();
+            (name, steps.join(","))
+        })
+        .collect()
+}
+
+#[test]
+fn clean_missing_and_duplicates() {
+    let gfa = load_gfa("./tests/data/dirty.gfa");
+    let (cleaned, summary) = clean_gfa(&gfa);
+
+    assert_eq!(summary.zero_length_segments, 0);
+    assert_eq!(summary.missing_segment_links, 1);
+    assert_eq!(summary.duplicate_links, 2);
+    assert_eq!(summary.missing_segment_steps, 1);
+    assert_eq!(summary.split_paths, 1);
+    assert_eq!(summary.removed_paths, 0);
+
+    assert_eq!(cleaned.segments.len(), 3);
+    assert_eq!(cleaned.links.len(), 2);
+
+    let paths = path_steps(&cleaned);
+    assert_eq!(
+        paths,
+        vec![
+            ("p1".to_string(), "1,2,3".to_string()),
+            ("p2:1".to_string(), "1".to_string()),
+            ("p2:2".to_string(), "3".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn path_steps_without_links() {
+    let mut gfa = load_gfa("./tests/data/dirty.gfa");
+
+    let missing: Vec<(String, usize)> = missing_path_links(&gfa)
+        .into_iter()
+        .map(|m| (m.path_name.to_string(), m.step))
+        .collect();
+
+    // 4+ -> 3+ isn't backed by the 3+ -> 4+ link, as that's not its
+    // reverse complement
+    assert_eq!(missing, vec![("p2".to_string(), 0), ("p2".to_string(), 1)]);
+
+    let links_before = gfa.links.len();
+    assert_eq!(add_missing_links(&mut gfa), 2);
+    assert_eq!(gfa.links.len(), links_before + 2);
+    assert!(missing_path_links(&gfa).is_empty());
+}
+
+#[test]
+fn segments_and_links_without_paths() {
+    let gfa = load_gfa("./tests/data/components.gfa");
+
+    // Segment 6 isn't on any path, and p2 steps from 4+ to 5-, which
+    // the link 5- -> 4- doesn't connect
+    let uncovered = uncovered_by_paths(&gfa);
+    assert_eq!(
+        uncovered,
+        Uncovered {
+            segments: vec![5],
+            links: vec![2],
+        }
+    );
+
+    let covered = remove_uncovered(&gfa, &uncovered);
+    assert_eq!(covered.segments.len(), 5);
+    assert_eq!(covered.links.len(), 2);
+    assert_eq!(covered.paths.len(), 3);
+    assert_eq!(uncovered_by_paths(&covered).segments, Vec::<usize>::new());
+}
+
+#[test]
+fn mask_and_split_ambiguous_bases() {
+    let gfa = load_gfa("./tests/data/ambiguous.gfa");
+
+    let mut masked = gfa.clone();
+    assert_eq!(hard_mask_ambiguous(&mut masked), 1);
+    assert_eq!(masked.segments[2].sequence, "NTTA");
+
+    let (split, count) = split_ambiguous(&gfa).unwrap();
+    assert_eq!(count, 2);
+
+    let segments: Vec<(String, String)> = split
+        .segments
+        .iter()
+        .map(|s| {
+            let name = String::from_utf8(s.name.clone()).unwrap();
+            (name, s.sequence.to_string())
+        })
+        .collect();
+    let segment = |name: &str, seq: &str| (name.to_string(), seq.to_string());
+    assert_eq!(
+        segments,
+        vec![
+            segment("1", "ACGT"),
+            segment("2:1", "AC"),
+            segment("2:2", "NN"),
+            segment("2:3", "GT"),
+            segment("3:1", "R"),
+            segment("3:2", "TTA"),
+            segment("4", "GGG"),
+        ]
+    );
+
+    let links: Vec<(String, String)> = split
+        .links
+        .iter()
+        .map(|l| {
+            let from = String::from_utf8(l.from_segment.clone()).unwrap();
+            let to = String::from_utf8(l.to_segment.clone()).unwrap();
+            (from, to)
+        })
+        .collect();
+    let link = |from: &str, to: &str| (from.to_string(), to.to_string());
+    assert_eq!(
+        links,
+        vec![
+            link("2:1", "2:2"),
+            link("2:2", "2:3"),
+            link("3:1", "3:2"),
+            link("1", "2:1"),
+            link("2:3", "3:1"),
+            link("3:1", "4"),
+        ]
+    );
+
+    assert_eq!(
+        path_steps(&split),
+        vec![
+            ("p1".to_string(), "1,2:1,2:2,2:3,3:1,3:2".to_string()),
+            ("p2".to_string(), "4,3:1,3:2".to_string()),
+        ]
+    );
+    assert!(missing_path_links(&split).is_empty());
+
+    // The pieces keep the tags of the segment they came from
+    assert_eq!(split.segments[4].optional, gfa.segments[2].optional);
+    assert_eq!(split.segments[5].optional, gfa.segments[2].optional);
+
+    // A piece can't take the name of an existing segment
+    let mut taken = gfa.clone();
+    taken.segments[0].name = b"3:2".to_vec();
+    let err = split_ambiguous(&taken).unwrap_err();
+    assert_eq!(err.segment, "3");
+    assert_eq!(err.piece, "3:2");
+}
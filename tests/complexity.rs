@@ -0,0 +1,46 @@
+use gfa::{gfa::GFA, parser::GFAParser};
+
+use handlegraph::hashgraph::HashGraph;
+
+use gfautil::{complexity::complexity_track, edges, variants};
+
+fn load_gfa(gfa_path: &str) -> GFA<usize, ()> {
+    let parser = GFAParser::new();
+    parser.parse_file(gfa_path).unwrap()
+}
+
+#[test]
+fn complexity_windows() {
+    let gfa = load_gfa("./tests/data/complexity.gfa");
+    let hashgraph = HashGraph::from_gfa(&gfa);
+    let branching =
+        edges::branching_nodes(&edges::graph_edge_count(&hashgraph));
+    let path_data = variants::gfa_path_data(gfa);
+
+    let ultrabubbles = vec![(1, 4), (4, 6), (3, 8)];
+    let track =
+        complexity_track(&path_data, 0, &ultrabubbles, &branching, 10, 10);
+
+    let rows: Vec<_> = track
+        .windows
+        .iter()
+        .map(|w| (w.start, w.end, w.bubbles, w.branching_nodes, w.nonref_bp))
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            (0, 10, 0, 1, 1),
+            (10, 20, 1, 1, 0),
+            (20, 30, 1, 2, 8),
+            (30, 31, 0, 1, 0),
+        ]
+    );
+    assert_eq!(track.unplaced_bubbles, 1);
+    assert_eq!(track.unplaced_segments, 1);
+
+    let track =
+        complexity_track(&path_data, 0, &ultrabubbles, &branching, 20, 15);
+    let windows: Vec<_> =
+        track.windows.iter().map(|w| (w.start, w.end)).collect();
+    assert_eq!(windows, vec![(0, 20), (15, 31)]);
+}
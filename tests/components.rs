@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+
+use bstr::ByteSlice;
+use gfa::{gfa::GFA, optfields::OptionalFields, parser::GFAParser};
+
+use gfautil::components::{component_ids, split_components, total_length};
+use gfautil::stats::weakly_connected_components;
+
+fn load_gfa(gfa_path: &str) -> GFA<Vec<u8>, OptionalFields> {
+    let gfa_path = PathBuf::from(gfa_path);
+    let parser = GFAParser::new();
+    parser.parse_file(gfa_path).unwrap()
+}
+
+fn segment_names(gfa: &GFA<Vec<u8>, OptionalFields>) -> Vec<String> {
+    gfa.segments
+        .iter()
+        .map(|s| s.name.to_str().unwrap().to_string())
+        .collect()
+}
+
+fn path_names(gfa: &GFA<Vec<u8>, OptionalFields>) -> Vec<String> {
+    gfa.paths
+        .iter()
+        .map(|p| p.path_name.to_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn components_of_segments() {
+    let gfa = load_gfa("./tests/data/components.gfa");
+    let (components, count) = component_ids(&gfa);
+    assert_eq!(count, 3);
+    assert_eq!(components[&b"1"[..]], 0);
+    assert_eq!(components[&b"3"[..]], 0);
+    assert_eq!(components[&b"4"[..]], 1);
+    assert_eq!(components[&b"5"[..]], 1);
+    assert_eq!(components[&b"6"[..]], 2);
+}
+
+#[test]
+fn split_into_components() {
+    let gfa = load_gfa("./tests/data/components.gfa");
+    let graphs = split_components(&gfa);
+    assert_eq!(graphs.len(), 3);
+
+    // Sorted by length, and p3 steps between the first two
+    // components, so it's split
+    assert_eq!(segment_names(&graphs[0]), vec!["4", "5"]);
+    assert_eq!(total_length(&graphs[0]), 15);
+    assert_eq!(graphs[0].links.len(), 1);
+    assert_eq!(path_names(&graphs[0]), vec!["p2", "p3:1"]);
+
+    assert_eq!(segment_names(&graphs[1]), vec!["1", "2", "3"]);
+    assert_eq!(total_length(&graphs[1]), 13);
+    assert_eq!(graphs[1].links.len(), 2);
+    assert_eq!(path_names(&graphs[1]), vec!["p1", "p3:1"]);
+
+    assert_eq!(segment_names(&graphs[2]), vec!["6"]);
+    assert!(graphs[2].links.is_empty());
+    assert!(graphs[2].paths.is_empty());
+}
+
+#[test]
+fn component_sizes_for_stats() {
+    let parser: GFAParser<usize, ()> = GFAParser::new();
+    let gfa = parser.parse_file("./tests/data/components.gfa").unwrap();
+    let components = weakly_connected_components(&gfa);
+
+    let sizes: Vec<(Vec<usize>, usize)> = components
+        .into_iter()
+        .map(|c| (c.segments, c.total_bp))
+        .collect();
+    assert_eq!(
+        sizes,
+        vec![(vec![1, 2, 3], 13), (vec![4, 5], 15), (vec![6], 1)]
+    );
+}
@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+
+use gfa::{gfa::GFA, parser::GFAParser};
+
+use gfautil::cycles::{find_cycles, links_per_component, ComponentLinks};
+
+fn load_gfa(gfa_path: &str) -> GFA<Vec<u8>, ()> {
+    let gfa_path = PathBuf::from(gfa_path);
+    let parser = GFAParser::new();
+    parser.parse_file(gfa_path).unwrap()
+}
+
+#[test]
+fn cycles_self_loops_and_reversals() {
+    let gfa = load_gfa("./tests/data/cycles.gfa");
+    let report = find_cycles(&gfa, 10, 20);
+
+    assert!(!report.is_acyclic());
+    assert_eq!(report.self_loops, vec![4, 6]);
+    assert_eq!(report.reversals, vec![5, 6, 7]);
+    // Segments 1-3 form a cycle, and 4 has a self-loop, while the
+    // hairpin on 5 can't be left on the strand it was entered from
+    assert_eq!(report.cyclic_segments, 4);
+    // A cycle and its reverse complement are the same cycle
+    assert_eq!(
+        report.cycles,
+        vec![vec![(3, false)], vec![(0, false), (1, false), (2, false)]]
+    );
+
+    let report = find_cycles(&gfa, 1, 2);
+    assert_eq!(report.cycles, vec![vec![(3, false)]]);
+    assert_eq!(report.cyclic_segments, 4);
+}
+
+#[test]
+fn acyclic_graph() {
+    let gfa = load_gfa("./tests/data/subgraph.gfa");
+    let report = find_cycles(&gfa, 10, 20);
+    assert!(report.is_acyclic());
+    assert!(report.cycles.is_empty());
+    assert!(report.self_loops.is_empty());
+}
+
+#[test]
+fn loops_per_component() {
+    let gfa = load_gfa("./tests/data/loops.gfa");
+    let report = find_cycles(&gfa, 10, 20);
+    assert_eq!(report.self_loops, vec![1, 5]);
+    assert_eq!(report.reversals, vec![4, 5]);
+
+    // The component of 4 and 5 has neither, so it's skipped
+    let components = links_per_component(&gfa, &report);
+    assert_eq!(
+        components,
+        vec![
+            ComponentLinks {
+                component: 0,
+                first_segment: 0,
+                segments: 3,
+                self_loops: 1,
+                reversals: 0,
+            },
+            ComponentLinks {
+                component: 2,
+                first_segment: 5,
+                segments: 2,
+                self_loops: 1,
+                reversals: 2,
+            },
+        ]
+    );
+}
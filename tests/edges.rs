@@ -0,0 +1,25 @@
+use gfa::{gfa::GFA, parser::GFAParser};
+
+use gfautil::edges::gfa_edge_count;
+
+fn load_gfa(gfa_path: &str) -> GFA<Vec<u8>, ()> {
+    let parser = GFAParser::new();
+    parser.parse_file(gfa_path).unwrap()
+}
+
+#[test]
+fn edge_counts_from_links() {
+    let gfa = load_gfa("./tests/data/cycles.gfa");
+
+    assert_eq!(
+        gfa_edge_count(&gfa),
+        vec![
+            (1, 1, 2),
+            (1, 1, 2),
+            (1, 2, 3),
+            (2, 2, 4),
+            (1, 3, 4),
+            (1, 0, 1)
+        ]
+    );
+}
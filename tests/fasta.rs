@@ -0,0 +1,93 @@
+use std::{
+    fs::File,
+    io::{BufReader, Cursor},
+    path::PathBuf,
+};
+
+use gfa::{
+    gfa::{Orientation, GFA},
+    parser::GFAParser,
+};
+
+use gfautil::{
+    fasta::{first_mismatch, FastaReader, FastaRecord, SequenceChecksums},
+    node_seq,
+};
+
+fn load_gfa(gfa_path: &str) -> GFA<usize, ()> {
+    let gfa_path = PathBuf::from(gfa_path);
+    let parser = GFAParser::new();
+    parser.parse_file(gfa_path).unwrap()
+}
+
+fn read_fasta(path: &str) -> Vec<FastaRecord> {
+    let file = BufReader::new(File::open(path).unwrap());
+    FastaReader::new(file).collect::<Result<_, _>>().unwrap()
+}
+
+#[test]
+fn fasta_records() {
+    let records = read_fasta("./tests/data/paths.fa");
+    let names: Vec<&[u8]> =
+        records.iter().map(|rec| rec.name.as_slice()).collect();
+    assert_eq!(names, vec![&b"HG001#1#chr1"[..], b"HG002#1#chr1", b"chrM"]);
+    assert_eq!(records[0].sequence, b"CTGAAACGTGgctgtgaaaaa".to_vec());
+    assert_eq!(records[2].sequence, b"ACGT".to_vec());
+
+    let no_header = Cursor::new(b"ACGT\n>seq\nA\n".to_vec());
+    let mut reader = FastaReader::new(no_header);
+    assert!(reader.next().unwrap().is_err());
+}
+
+#[test]
+fn path_checksums_match_fasta() {
+    let mut gfa = load_gfa("./tests/data/subgraph.gfa");
+    let paths = std::mem::take(&mut gfa.paths);
+    let segment_map = node_seq::segment_sequences(gfa);
+    let records = read_fasta("./tests/data/paths.fa");
+
+    let path_checksums: Vec<SequenceChecksums> = paths
+        .iter()
+        .map(|path| {
+            let steps: Vec<(usize, Orientation)> = path.iter().collect();
+            let seq =
+                node_seq::traversal_sequence(&segment_map, &steps).unwrap();
+            SequenceChecksums::from_sequence(&seq)
+        })
+        .collect();
+
+    // Soft-masking and line breaks don't change the checksums
+    assert_eq!(
+        path_checksums[0],
+        SequenceChecksums::from_sequence(&records[0].sequence)
+    );
+    assert_ne!(
+        path_checksums[1],
+        SequenceChecksums::from_sequence(&records[1].sequence)
+    );
+
+    let checksums = SequenceChecksums::from_sequence(b"acgt");
+    assert_eq!(checksums.md5, "f1f8f4bf413b16ad135722aa4591043e");
+    assert_eq!(
+        checksums.sha256,
+        "1dff3e84fe7877e0673b69bbddcf40124e396e3f9943dd890c91b6a09adb9af0"
+    );
+}
+
+#[test]
+fn first_mismatches() {
+    assert_eq!(first_mismatch(b"ACGT", b"acgt"), None);
+    assert_eq!(first_mismatch(b"ACGT", b"ACTT"), Some(2));
+    assert_eq!(first_mismatch(b"ACGT", b"ACGTA"), Some(4));
+    assert_eq!(first_mismatch(b"", b""), None);
+
+    let mut gfa = load_gfa("./tests/data/subgraph.gfa");
+    let paths = std::mem::take(&mut gfa.paths);
+    let segment_map = node_seq::segment_sequences(gfa);
+    let records = read_fasta("./tests/data/paths.fa");
+
+    let steps: Vec<(usize, Orientation)> = paths[1].iter().collect();
+    let seq = node_seq::traversal_sequence(&segment_map, &steps).unwrap();
+    // The record has G where segment 6 starts with C
+    assert_eq!(first_mismatch(&seq, &records[1].sequence), Some(12));
+}
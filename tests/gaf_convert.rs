@@ -7,7 +7,13 @@ use gfa::{
     parser::GFAParser,
 };
 
-use gfautil::gaf_convert::gaf_to_paf;
+use gfautil::{
+    gaf_convert::{
+        gaf_line_to_paf_results, gaf_path_line, gaf_reader_to_pafs,
+        gaf_records, gaf_to_paf, GafConvertError, GafParseError, SegmentIndex,
+    },
+    gaf_reader::GafInput,
+};
 
 type PAF = gfa::gafpaf::PAF<OptionalFields>;
 
@@ -18,8 +24,26 @@ fn load_pafs(gfa_path: &str, gaf_path: &str) -> Vec<PAF> {
         parser.parse_file(gfa_path).unwrap();
 
     let gaf_path = PathBuf::from(gaf_path);
-    let pafs = gaf_to_paf(gfa, &gaf_path);
+    gaf_to_paf(gfa, &gaf_path).unwrap()
+}
 
+/// Convert GAF lines like gaf2paf does, `chunk_lines` lines at a time
+fn chunked_pafs(
+    index: &SegmentIndex<'_, OptionalFields>,
+    gaf: &[u8],
+    chunk_lines: usize,
+) -> Vec<Result<PAF, GafConvertError>> {
+    let mut pafs = Vec::new();
+    GafInput::from_reader(std::io::Cursor::new(gaf.to_vec()))
+        .for_each_line(
+            chunk_lines,
+            |i, line| gaf_line_to_paf_results(index, i, line, false),
+            |chunk| -> std::io::Result<()> {
+                pafs.extend(chunk);
+                Ok(())
+            },
+        )
+        .unwrap();
     pafs
 }
 
@@ -119,12 +143,12 @@ fn gafpaf_overlaps() {
 
     let paf = iter.next().unwrap();
     compare_paf_query(&paf, "read1", 6, (1, 5));
-    compare_paf_target(&paf, "3", 4, (0, 4));
+    compare_paf_target(&paf, "3", 4, (1, 4));
     compare_paf_rest(&paf, 3, 4, "1I3M");
 
     let paf = iter.next().unwrap();
     compare_paf_query(&paf, "read1", 6, (5, 6));
-    compare_paf_target(&paf, "4", 5, (0, 1));
+    compare_paf_target(&paf, "4", 5, (1, 2));
     compare_paf_rest(&paf, 1, 1, "1M");
 
     // read2
@@ -135,22 +159,66 @@ fn gafpaf_overlaps() {
 
     let paf = iter.next().unwrap();
     compare_paf_query(&paf, "read2", 7, (2, 6));
-    compare_paf_target(&paf, "5", 4, (0, 4));
+    compare_paf_target(&paf, "5", 4, (1, 4));
     compare_paf_rest(&paf, 3, 4, "2M1I1M");
 
     let paf = iter.next().unwrap();
     compare_paf_query(&paf, "read2", 7, (6, 7));
-    compare_paf_target(&paf, "6", 4, (0, 1));
+    compare_paf_target(&paf, "6", 4, (1, 2));
     compare_paf_rest(&paf, 1, 1, "1M");
 
     assert!(iter.next().is_none());
 }
 
+#[test]
+fn gafpaf_overlap_offsets() {
+    // The links in ov3.gfa overlap by two bases, which are only
+    // aligned as part of the first segment of each link
+    let pafs = load_pafs("./tests/data/ov3.gfa", "./tests/data/ov3.gaf");
+    let mut iter = pafs.iter();
+
+    // read1, with an insertion at the first segment boundary
+    let paf = iter.next().unwrap();
+    compare_paf_query(&paf, "read1", 9, (0, 2));
+    compare_paf_target(&paf, "1", 5, (3, 5));
+    compare_paf_rest(&paf, 2, 2, "2M");
+
+    let paf = iter.next().unwrap();
+    compare_paf_query(&paf, "read1", 9, (2, 6));
+    compare_paf_target(&paf, "2", 5, (2, 5));
+    compare_paf_rest(&paf, 3, 4, "1I3M");
+
+    let paf = iter.next().unwrap();
+    compare_paf_query(&paf, "read1", 9, (6, 9));
+    compare_paf_target(&paf, "3", 6, (2, 5));
+    compare_paf_rest(&paf, 3, 3, "3M");
+
+    // read2, in reverse and without a CIGAR, so the overlaps are
+    // found through the links in the other direction
+    let paf = iter.next().unwrap();
+    compare_paf_query(&paf, "read2", 6, (0, 2));
+    compare_paf_target(&paf, "3", 6, (4, 6));
+
+    let paf = iter.next().unwrap();
+    compare_paf_query(&paf, "read2", 6, (2, 5));
+    compare_paf_target(&paf, "2", 5, (2, 5));
+
+    let paf = iter.next().unwrap();
+    compare_paf_query(&paf, "read2", 6, (5, 6));
+    compare_paf_target(&paf, "1", 5, (2, 3));
+
+    assert!(iter.next().is_none());
+}
+
 #[test]
 fn gafpaf_dels() {
     let pafs = load_pafs("./tests/data/ov1.gfa", "./tests/data/dels.gaf");
     let mut iter = pafs.iter();
 
+    // A deletion takes up reference bases but no query bases, so the
+    // query range of each record is as long as the query bases of its
+    // CIGAR, e.g. 3 for 1M1D2M, and not as long as its target range
+
     // read1
     let paf = iter.next().unwrap();
     compare_paf_query(&paf, "read1", 5, (0, 1));
@@ -158,12 +226,12 @@ fn gafpaf_dels() {
     compare_paf_rest(&paf, 1, 1, "1M");
 
     let paf = iter.next().unwrap();
-    compare_paf_query(&paf, "read1", 5, (1, 5));
+    compare_paf_query(&paf, "read1", 5, (1, 4));
     compare_paf_target(&paf, "3", 4, (0, 4));
     compare_paf_rest(&paf, 3, 4, "1M1D2M");
 
     let paf = iter.next().unwrap();
-    compare_paf_query(&paf, "read1", 5, (5, 6));
+    compare_paf_query(&paf, "read1", 5, (4, 5));
     compare_paf_target(&paf, "4", 5, (0, 1));
     compare_paf_rest(&paf, 1, 1, "1M");
 
@@ -174,12 +242,191 @@ fn gafpaf_dels() {
     compare_paf_rest(&paf, 2, 2, "2M");
 
     let paf = iter.next().unwrap();
-    compare_paf_query(&paf, "read2", 6, (2, 6));
+    compare_paf_query(&paf, "read2", 6, (2, 5));
     compare_paf_target(&paf, "5", 4, (0, 4));
     compare_paf_rest(&paf, 3, 4, "1D3M");
 
     let paf = iter.next().unwrap();
-    compare_paf_query(&paf, "read2", 6, (6, 7));
+    compare_paf_query(&paf, "read2", 6, (5, 6));
     compare_paf_target(&paf, "6", 4, (0, 1));
     compare_paf_rest(&paf, 1, 1, "1M");
 }
+
+#[test]
+fn gafpaf_split_matches_and_block_length() {
+    let parser = GFAParser::new();
+    let gfa: GFA<Vec<u8>, OptionalFields> =
+        parser.parse_file("./tests/data/ov1.gfa").unwrap();
+    let index = SegmentIndex::new(&gfa);
+
+    // Like read1 in ov1.gaf, but with two mismatches on segment 3
+    let gaf =
+        "read3\t6\t0\t6\t+\t>2>3>4\t12\t2\t8\t4\t6\t255\tcg:Z:1=1X2=1X1=\n";
+    let pafs: Vec<PAF> = gaf_reader_to_pafs(&index, gaf.as_bytes(), true)
+        .map(|paf| paf.unwrap())
+        .collect();
+    assert_eq!(pafs.len(), 3);
+
+    compare_paf_target(&pafs[0], "2", 3, (2, 3));
+    compare_paf_rest(&pafs[0], 1, 1, "1=");
+    compare_paf_target(&pafs[1], "3", 4, (0, 4));
+    compare_paf_rest(&pafs[1], 2, 4, "1X2=1X");
+    compare_paf_target(&pafs[2], "4", 5, (0, 1));
+    compare_paf_rest(&pafs[2], 1, 1, "1=");
+
+    let matches: usize = pafs.iter().map(|paf| paf.residue_matches).sum();
+    assert_eq!(matches, 4);
+}
+
+#[test]
+fn gafpaf_missing_cigar() {
+    let pafs = load_pafs("./tests/data/ov1.gfa", "./tests/data/nocg.gaf");
+    let mut iter = pafs.iter();
+
+    let paf = iter.next().unwrap();
+    compare_paf_query(&paf, "read1", 6, (0, 1));
+    compare_paf_target(&paf, "2", 3, (2, 3));
+    assert!(get_cigar(&paf.optional).is_none());
+
+    let paf = iter.next().unwrap();
+    compare_paf_query(&paf, "read1", 6, (1, 5));
+    compare_paf_target(&paf, "3", 4, (0, 4));
+    assert_eq!(paf.residue_matches, 4);
+
+    let paf = iter.next().unwrap();
+    compare_paf_query(&paf, "read1", 6, (5, 6));
+    compare_paf_target(&paf, "4", 5, (0, 1));
+
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn gafpaf_stable_intervals() {
+    let pafs = load_pafs("./tests/data/stable.gfa", "./tests/data/stable.gaf");
+    let mut iter = pafs.iter();
+
+    // read1, same as in ov1.gaf, but with a stable interval path
+    let paf = iter.next().unwrap();
+    compare_paf_query(&paf, "read1", 6, (0, 1));
+    compare_paf_target(&paf, "2", 3, (2, 3));
+
+    let paf = iter.next().unwrap();
+    compare_paf_query(&paf, "read1", 6, (1, 5));
+    compare_paf_target(&paf, "3", 4, (0, 4));
+
+    let paf = iter.next().unwrap();
+    compare_paf_query(&paf, "read1", 6, (5, 6));
+    compare_paf_target(&paf, "4", 5, (0, 1));
+
+    // read2, on the reverse strand of chr1, i.e. <4<3
+    let paf = iter.next().unwrap();
+    compare_paf_query(&paf, "read2", 6, (0, 4));
+    compare_paf_target(&paf, "4", 5, (1, 5));
+    assert_eq!(paf.strand, gfa::gfa::Orientation::Backward);
+
+    let paf = iter.next().unwrap();
+    compare_paf_query(&paf, "read2", 6, (4, 6));
+    compare_paf_target(&paf, "3", 4, (0, 2));
+
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn gafpaf_errors() {
+    let parser = GFAParser::new();
+    let gfa: GFA<Vec<u8>, OptionalFields> =
+        parser.parse_file("./tests/data/ov1.gfa").unwrap();
+
+    let missing = PathBuf::from("./tests/data/missing.gaf");
+    assert!(matches!(
+        gaf_to_paf(gfa.clone(), &missing),
+        Err(GafConvertError::Read(_))
+    ));
+
+    // stable.gaf refers to chr1, which ov1.gfa has no SN tags for
+    let stable = PathBuf::from("./tests/data/stable.gaf");
+    assert!(matches!(
+        gaf_to_paf(gfa, &stable),
+        Err(GafConvertError::UnresolvedInterval(..))
+    ));
+}
+
+#[test]
+fn gafpaf_chunks_keep_order() {
+    let parser = GFAParser::new();
+    let gfa: GFA<Vec<u8>, OptionalFields> =
+        parser.parse_file("./tests/data/ov1.gfa").unwrap();
+    let index = SegmentIndex::new(&gfa);
+
+    let gaf = std::fs::read("./tests/data/ov1.gaf").unwrap();
+    let gaf = gaf.repeat(3);
+
+    let sequential: Vec<String> =
+        gaf_reader_to_pafs(&index, gaf.as_slice(), false)
+            .map(|paf| paf.unwrap().to_string())
+            .collect();
+
+    for &chunk_size in &[1, 2, 100] {
+        let chunked: Vec<String> = chunked_pafs(&index, &gaf, chunk_size)
+            .into_iter()
+            .map(|paf| paf.unwrap().to_string())
+            .collect();
+        assert_eq!(sequential, chunked);
+    }
+}
+
+#[test]
+fn gaf_records_as_path_lines() {
+    let parser = GFAParser::new();
+    let gfa: GFA<Vec<u8>, OptionalFields> =
+        parser.parse_file("./tests/data/stable.gfa").unwrap();
+    let index = SegmentIndex::new(&gfa);
+
+    let file = std::fs::File::open("./tests/data/stable.gaf").unwrap();
+    let gafs: Vec<_> = gaf_records(file).map(|gaf| gaf.unwrap()).collect();
+
+    let line = gaf_path_line(&index, &gafs[0], b"read1", false).unwrap();
+    assert_eq!(line, "P\tread1\t2+,3+,4+\t*");
+
+    let line = gaf_path_line(&index, &gafs[1], b"read2", false).unwrap();
+    assert_eq!(line, "P\tread2\t4-,3-\t*");
+
+    let line = gaf_path_line(&index, &gafs[1], b"read2", true).unwrap();
+    assert_eq!(line, "W\tread2\t0\tread2\t0\t6\t<4<3");
+}
+
+#[test]
+fn gafpaf_invalid_lines_are_diagnosed() {
+    let parser = GFAParser::new();
+    let gfa: GFA<Vec<u8>, OptionalFields> =
+        parser.parse_file("./tests/data/ov1.gfa").unwrap();
+    let index = SegmentIndex::new(&gfa);
+
+    let gaf = "read1\t6\t0\t6\t+\t>2>3>4\t12\t2\t8\t6\t6\t255\tcg:Z:6M\n\
+               read2\t7\tzero\t7\t+\t>2>5>6\t11\t1\t8\t7\t7\t255\n\
+               read3\t7\t0\t7\n";
+
+    let results: Vec<_> =
+        gaf_reader_to_pafs(&index, gaf.as_bytes(), false).collect();
+    assert_eq!(results.len(), 5);
+    assert!(results[..3].iter().all(|paf| paf.is_ok()));
+
+    let bad_start = GafParseError {
+        line: 2,
+        field: Some("query start"),
+        reason: "expected an integer, found \"zero\"".to_string(),
+    };
+    let errors: Vec<GafConvertError> =
+        results.into_iter().filter_map(|paf| paf.err()).collect();
+    assert_eq!(errors[0], GafConvertError::Parse(bad_start));
+    assert_eq!(
+        errors[1].to_string(),
+        "GAF line 3: expected at least 12 tab-separated columns, found 4"
+    );
+
+    let chunked: Vec<GafConvertError> = chunked_pafs(&index, gaf.as_bytes(), 2)
+        .into_iter()
+        .filter_map(|paf| paf.err())
+        .collect();
+    assert_eq!(chunked, errors);
+}
@@ -0,0 +1,110 @@
+use std::fs::File;
+
+use bstr::BString;
+use gfa::{gfa::GFA, optfields::OptionalFields, parser::GFAParser};
+
+use gfautil::{
+    gaf_convert::SegmentIndex,
+    gaf_stats::{GafStats, NodeCoverage},
+};
+
+#[test]
+fn gafstats_ov1() {
+    let parser = GFAParser::new();
+    let gfa: GFA<Vec<u8>, OptionalFields> =
+        parser.parse_file("./tests/data/ov1.gfa").unwrap();
+    let nodes: Vec<(BString, usize)> = gfa
+        .segments
+        .iter()
+        .map(|seg| (seg.name.clone().into(), seg.sequence.len()))
+        .collect();
+    let index = SegmentIndex::new(&gfa);
+
+    let mut stats = GafStats::default();
+    let gaf = File::open("./tests/data/ov1.gaf").unwrap();
+    stats.add_reader(&index, gaf).unwrap();
+
+    let extra = "read3\t5\t0\t5\t+\t>7\t5\t0\t5\t4\t5\t10\ttp:A:S\n\
+                 read4\t100\t*\t*\t*\t*\t*\t*\t*\t*\t*\t255\n";
+    stats.add_reader(&index, extra.as_bytes()).unwrap();
+
+    assert_eq!(stats.records, 3);
+    assert_eq!(stats.primary, 2);
+    assert_eq!(stats.secondary, 1);
+    assert_eq!(stats.unmapped_records, 1);
+    assert_eq!(stats.reads(), 4);
+    assert_eq!(stats.mapped_reads(), 3);
+    assert!((stats.mapping_rate() - 0.75).abs() < 1e-9);
+    assert!((stats.identity() - 17.0 / 18.0).abs() < 1e-9);
+
+    assert_eq!(stats.read_length_histogram(), vec![(6, 1), (7, 1)]);
+    assert_eq!(stats.identity_histogram(), vec![(80, 1), (100, 2)]);
+    assert_eq!(stats.mapq_histogram(), vec![(10, 1), (255, 2)]);
+
+    let coverage = |records, bases| NodeCoverage { records, bases };
+    assert_eq!(stats.node_coverage(b"1"), coverage(0, 0));
+    assert_eq!(stats.node_coverage(b"2"), coverage(2, 3));
+    assert_eq!(stats.node_coverage(b"3"), coverage(1, 4));
+    assert_eq!(stats.node_coverage(b"4"), coverage(1, 1));
+    assert_eq!(stats.node_coverage(b"5"), coverage(1, 4));
+    assert_eq!(stats.node_coverage(b"6"), coverage(1, 1));
+    assert_eq!(stats.node_coverage(b"7"), coverage(1, 5));
+
+    let tsv = stats.to_tsv(&nodes);
+    assert!(tsv.contains("SN\tmapping_rate\t0.7500\n"));
+    assert!(tsv.contains("NC\t3\t4\t1\t4\t1.0000\n"));
+
+    let json = serde_json::to_value(stats.report(&nodes)).unwrap();
+    assert_eq!(json["records"], 3);
+    assert_eq!(json["mapq"], serde_json::json!([[10, 1], [255, 2]]));
+    assert_eq!(
+        json["nodes"][6],
+        serde_json::json!({
+            "node": "7",
+            "length": 5,
+            "records": 1,
+            "bases": 5,
+            "depth": 1.0
+        })
+    );
+}
+
+#[test]
+fn gafstats_overlap_coverage() {
+    let parser = GFAParser::new();
+    let gfa: GFA<Vec<u8>, OptionalFields> =
+        parser.parse_file("./tests/data/ov3.gfa").unwrap();
+    let index = SegmentIndex::new(&gfa);
+
+    let mut stats = GafStats::default();
+    let gaf = File::open("./tests/data/ov3.gaf").unwrap();
+    stats.add_reader(&index, gaf).unwrap();
+
+    // The 2M overlaps shift each segment back by 2 bases on the path,
+    // so the path ranges end inside the last segment
+    let coverage = |records, bases| NodeCoverage { records, bases };
+    assert_eq!(stats.node_coverage(b"1"), coverage(2, 5));
+    assert_eq!(stats.node_coverage(b"2"), coverage(2, 10));
+    assert_eq!(stats.node_coverage(b"3"), coverage(2, 7));
+}
+
+#[test]
+fn gafstats_non_utf8_read_names() {
+    let parser = GFAParser::new();
+    let gfa: GFA<Vec<u8>, OptionalFields> =
+        parser.parse_file("./tests/data/ov1.gfa").unwrap();
+    let index = SegmentIndex::new(&gfa);
+
+    // Two names that only differ in bytes that aren't valid UTF-8,
+    // and would be merged if they were lossily converted first
+    let mut gaf: Vec<u8> = Vec::new();
+    for name in &[&b"read\xff"[..], &b"read\xfe"[..]] {
+        gaf.extend_from_slice(name);
+        gaf.extend_from_slice(b"\t5\t0\t5\t+\t>7\t5\t0\t5\t5\t5\t60\n");
+    }
+
+    let mut stats = GafStats::default();
+    stats.add_reader(&index, gaf.as_slice()).unwrap();
+    assert_eq!(stats.records, 2);
+    assert_eq!(stats.reads(), 2);
+}
@@ -0,0 +1,67 @@
+use std::path::PathBuf;
+
+use gfa::{gfa::GFA, parser::GFAParser};
+
+use gfautil::node_coverage::{
+    class_intervals, path_coverage, path_sample, SegmentClass, SegmentCoverage,
+};
+
+fn load_gfa(gfa_path: &str) -> GFA<Vec<u8>, ()> {
+    let gfa_path = PathBuf::from(gfa_path);
+    let parser = GFAParser::new();
+    parser.parse_file(gfa_path).unwrap()
+}
+
+#[test]
+fn segment_path_coverage() {
+    let gfa = load_gfa("./tests/data/coverage.gfa");
+    let coverage = path_coverage(&gfa);
+
+    assert_eq!(
+        coverage.samples,
+        vec![b"a".to_vec(), b"b".to_vec(), b"ref".to_vec()]
+    );
+
+    let cov = |paths, steps, samples: &[usize]| SegmentCoverage {
+        paths,
+        steps,
+        samples: samples.to_vec(),
+    };
+    assert_eq!(
+        coverage.segments,
+        vec![
+            cov(4, 5, &[0, 1, 2]),
+            cov(2, 2, &[0, 2]),
+            cov(4, 4, &[0, 1, 2]),
+            cov(0, 0, &[]),
+        ]
+    );
+
+    assert_eq!(path_sample(b"HG002#1#chr1"), b"HG002");
+    assert_eq!(path_sample(b"chr1"), b"chr1");
+}
+
+#[test]
+fn core_shell_and_cloud_segments() {
+    use SegmentClass::*;
+
+    let gfa = load_gfa("./tests/data/coverage.gfa");
+    let coverage = path_coverage(&gfa);
+
+    let classes = coverage.segment_classes(1.0, 1);
+    assert_eq!(classes, vec![Core, Shell, Core, Uncovered]);
+    assert_eq!(
+        coverage.segment_classes(0.6, 1),
+        vec![Core, Core, Core, Uncovered]
+    );
+    assert_eq!(
+        coverage.segment_classes(1.0, 2),
+        vec![Core, Cloud, Core, Uncovered]
+    );
+
+    let ref_path = gfa.paths.iter().find(|p| p.path_name == b"ref").unwrap();
+    assert_eq!(
+        class_intervals(&gfa, ref_path, &classes),
+        vec![(0, 4, Core), (4, 6, Shell), (6, 9, Core)]
+    );
+}
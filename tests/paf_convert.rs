@@ -0,0 +1,118 @@
+use std::path::PathBuf;
+
+use gfa::{
+    gafpaf::{parse_gaf, GAFPath},
+    gfa::{Orientation, GFA},
+    optfields::{OptFieldVal, OptFields, OptionalFields},
+    parser::GFAParser,
+};
+
+use bstr::{io::*, ByteSlice};
+
+use gfautil::{
+    gaf_convert::gaf_to_paf,
+    paf_convert::{lift_pafs, paf_to_gaf, PathLiftIndex},
+};
+
+type GAF = gfa::gafpaf::GAF<OptionalFields>;
+type PAF = gfa::gafpaf::PAF<OptionalFields>;
+
+fn load_gfa(gfa_path: &str) -> GFA<Vec<u8>, OptionalFields> {
+    let parser = GFAParser::new();
+    parser.parse_file(PathBuf::from(gfa_path)).unwrap()
+}
+
+fn load_gafs(gaf_path: &str) -> Vec<GAF> {
+    let file = std::fs::File::open(gaf_path).unwrap();
+    std::io::BufReader::new(file)
+        .byte_lines()
+        .map(|line| parse_gaf(line.unwrap().split_str(b"\t")).unwrap())
+        .collect()
+}
+
+fn cigar_string(gaf: &GAF) -> String {
+    let cg = gaf.optional.get_field(b"cg").unwrap();
+    if let OptFieldVal::Z(cg) = &cg.value {
+        cg.to_string()
+    } else {
+        panic!("cg tag is not a string");
+    }
+}
+
+fn paf_cigar(paf: &PAF) -> String {
+    let cg = paf.optional.get_field(b"cg").unwrap();
+    if let OptFieldVal::Z(cg) = &cg.value {
+        cg.to_string()
+    } else {
+        panic!("cg tag is not a string");
+    }
+}
+
+#[test]
+fn pafgaf_roundtrip() {
+    let gfa = load_gfa("./tests/data/ov1.gfa");
+    let orig_gafs = load_gafs("./tests/data/ov1.gaf");
+
+    let pafs = gaf_to_paf(gfa.clone(), &PathBuf::from("./tests/data/ov1.gaf"))
+        .unwrap();
+    let gafs = paf_to_gaf(&gfa, &pafs);
+
+    assert_eq!(gafs.len(), orig_gafs.len());
+
+    for (gaf, orig) in gafs.iter().zip(orig_gafs.iter()) {
+        assert_eq!(gaf.seq_name, orig.seq_name);
+        assert_eq!(gaf.seq_len, orig.seq_len);
+        assert_eq!(gaf.seq_range, orig.seq_range);
+        assert_eq!(gaf.path_len, orig.path_len);
+        assert_eq!(gaf.path_range, orig.path_range);
+        assert_eq!(gaf.residue_matches, orig.residue_matches);
+        assert_eq!(gaf.block_length, orig.block_length);
+        assert_eq!(cigar_string(gaf), cigar_string(orig));
+
+        match (&gaf.path, &orig.path) {
+            (GAFPath::OrientIntv(steps), GAFPath::OrientIntv(orig_steps)) => {
+                assert_eq!(steps, orig_steps);
+            }
+            _ => panic!("expected oriented segment paths"),
+        }
+    }
+}
+
+#[test]
+fn paf_lift_onto_reference_paths() {
+    let gfa = load_gfa("./tests/data/lift.gfa");
+    let pafs = gaf_to_paf(gfa.clone(), &PathBuf::from("./tests/data/ov1.gaf"))
+        .unwrap();
+
+    let index = PathLiftIndex::new(&gfa, b"ref").unwrap();
+    assert_eq!(index.length, 17);
+    let (lifted, skipped) = lift_pafs(&index, &pafs);
+    // read2 leaves the path after segment 2
+    assert_eq!(skipped, 2);
+    assert_eq!(lifted.len(), 2);
+
+    let read1 = &lifted[0];
+    assert_eq!(read1.target_seq_name, "ref");
+    assert_eq!(read1.target_seq_len, 17);
+    assert_eq!(read1.query_seq_range, (0, 6));
+    assert_eq!(read1.target_seq_range, (7, 13));
+    assert_eq!(read1.strand, Orientation::Forward);
+    assert_eq!((read1.residue_matches, read1.block_length), (6, 6));
+    assert_eq!(paf_cigar(read1), "6M");
+
+    let read2 = &lifted[1];
+    assert_eq!(read2.query_seq_range, (0, 2));
+    assert_eq!(read2.target_seq_range, (6, 8));
+
+    // The same alignment, against a path that traverses the
+    // segments in reverse
+    let index = PathLiftIndex::new(&gfa, b"rev").unwrap();
+    let (lifted, _) = lift_pafs(&index, &pafs);
+    let read1 = &lifted[0];
+    assert_eq!(read1.query_seq_range, (0, 6));
+    assert_eq!(read1.target_seq_range, (4, 10));
+    assert_eq!(read1.strand, Orientation::Backward);
+    assert_eq!(paf_cigar(read1), "6M");
+
+    assert!(PathLiftIndex::new(&gfa, b"missing").is_none());
+}
@@ -0,0 +1,30 @@
+use gfa::{gfa::GFA, parser::GFAParser};
+
+use gfautil::stats::path_summaries;
+
+fn load_gfa(gfa_path: &str) -> GFA<usize, ()> {
+    let parser = GFAParser::new();
+    parser.parse_file(gfa_path).unwrap()
+}
+
+#[test]
+fn path_report() {
+    let gfa = load_gfa("./tests/data/components.gfa");
+    let summaries = path_summaries(&gfa);
+
+    let rows: Vec<(String, usize, usize, Option<usize>, Option<usize>)> =
+        summaries
+            .iter()
+            .map(|p| (p.name.to_string(), p.steps, p.length, p.first, p.last))
+            .collect();
+    assert_eq!(
+        rows,
+        vec![
+            ("p1".to_string(), 3, 13, Some(1), Some(3)),
+            ("p2".to_string(), 2, 15, Some(4), Some(5)),
+            ("p3".to_string(), 2, 8, Some(3), Some(4)),
+        ]
+    );
+    assert_eq!(summaries[0].reverse_fraction(), 0.0);
+    assert_eq!(summaries[1].reverse_fraction(), 0.5);
+}
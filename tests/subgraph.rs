@@ -0,0 +1,586 @@
+use std::path::PathBuf;
+
+use bstr::ByteSlice;
+use gfa::{gfa::GFA, optfields::OptionalFields, parser::GFAParser};
+
+use gfautil::gaf_convert::{gaf_records, SegmentIndex};
+use gfautil::subgraph::{
+    add_stub_segments, gaf_segments, matching_paths, node_range_segments,
+    path_range_segments, path_segments, paths_new_subgraph,
+    paths_segments_subgraph, read_batch, read_bed_intervals, renumber_segments,
+    sample_paths, segments_subgraph, stream_path_segments, stream_subgraph,
+    tag_segments, write_fasta, Adjacency, BubbleEnds, NodeRange, PathInterval,
+    PathOffsets, SubgraphSummary, TagPredicate,
+};
+use gfautil::util::pansn_sample;
+
+fn load_gfa(gfa_path: &str) -> GFA<Vec<u8>, OptionalFields> {
+    let gfa_path = PathBuf::from(gfa_path);
+    let parser = GFAParser::new();
+    parser.parse_file(gfa_path).unwrap()
+}
+
+fn segment_names(gfa: &GFA<Vec<u8>, OptionalFields>) -> Vec<String> {
+    gfa.segments
+        .iter()
+        .map(|s| s.name.to_str().unwrap().to_string())
+        .collect()
+}
+
+fn link_names(gfa: &GFA<Vec<u8>, OptionalFields>) -> Vec<String> {
+    gfa.links
+        .iter()
+        .map(|l| {
+            format!(
+                "{}{}{}{}",
+                l.from_segment.as_bstr(),
+                l.from_orient,
+                l.to_segment.as_bstr(),
+                l.to_orient
+            )
+        })
+        .collect()
+}
+
+fn path_steps(gfa: &GFA<Vec<u8>, OptionalFields>) -> Vec<(String, String)> {
+    gfa.paths
+        .iter()
+        .map(|p| {
+            let steps: Vec<String> = p
+                .iter()
+                .map(|(seg, _)| {
+                    let seg: &[u8] = seg.as_ref();
+                    seg.to_str().unwrap().to_string()
+                })
+                .collect();
+            (p.path_name.to_str().unwrap().to_string(), steps.join(","))
+        })
+        .collect()
+}
+
+#[test]
+fn subgraph_by_node_range() {
+    let gfa = load_gfa("./tests/data/subgraph.gfa");
+
+    let range: NodeRange = "2..5".parse().unwrap();
+    assert_eq!(range, NodeRange { start: 2, end: 5 });
+    assert!("5..2".parse::<NodeRange>().is_err());
+    assert!("2-5".parse::<NodeRange>().is_err());
+
+    let (names, non_numeric) = node_range_segments(&gfa, range);
+    assert_eq!(non_numeric, 0);
+
+    let sub = segments_subgraph(&gfa, &names);
+    assert_eq!(segment_names(&sub), vec!["2", "3", "4", "5"]);
+    assert_eq!(link_names(&sub), vec!["2+3+", "3+4+", "2+5+"]);
+    assert_eq!(
+        path_steps(&sub),
+        vec![
+            ("HG001#1#chr1:1".to_string(), "2,3,4".to_string()),
+            ("HG002#1#chr1:1".to_string(), "2,5".to_string()),
+            ("HG002#1#chr1:2".to_string(), "4".to_string()),
+            ("HG002#2#chr1:1".to_string(), "4".to_string()),
+        ]
+    );
+
+    let all: Vec<Vec<u8>> = (1..=8)
+        .map(|id: usize| id.to_string().into_bytes())
+        .collect();
+    let whole = segments_subgraph(&gfa, &all);
+    assert_eq!(path_steps(&whole), path_steps(&gfa));
+}
+
+#[test]
+fn subgraph_with_context() {
+    let gfa = load_gfa("./tests/data/subgraph.gfa");
+    let adjacency = Adjacency::new(&gfa);
+
+    let names = vec![b"3".to_vec()];
+    let one_step = adjacency.expand(&names, 1);
+    assert_eq!(one_step, vec![b"3".to_vec(), b"2".to_vec(), b"4".to_vec()]);
+
+    let two_steps = segments_subgraph(&gfa, &adjacency.expand(&names, 2));
+    assert_eq!(
+        segment_names(&two_steps),
+        vec!["1", "2", "3", "4", "5", "6", "8"]
+    );
+
+    let paths = vec![b"HG001#1#chr1".to_vec()];
+    let segments = adjacency.expand(&path_segments(&gfa, &paths), 1);
+    let sub = paths_segments_subgraph(&gfa, &paths, &segments);
+    assert_eq!(
+        segment_names(&sub),
+        vec!["1", "2", "3", "4", "5", "6", "7", "8"]
+    );
+    assert_eq!(sub.paths.len(), 1);
+}
+
+#[test]
+fn subgraph_with_context_bases() {
+    let gfa = load_gfa("./tests/data/subgraph.gfa");
+    let adjacency = Adjacency::new(&gfa);
+    let names = vec![b"3".to_vec()];
+
+    assert_eq!(adjacency.expand_bp(&names, 0), names);
+
+    let one_base = adjacency.expand_bp(&names, 1);
+    assert_eq!(one_base, vec![b"3".to_vec(), b"2".to_vec(), b"4".to_vec()]);
+
+    // 2 is 3 bases long, so 1 and 5 start 3 bases away from 3, while
+    // 4 is 5 bases long, so 6 and 8 start too far away
+    let four_bases: Vec<String> = adjacency
+        .expand_bp(&names, 4)
+        .into_iter()
+        .map(|name| String::from_utf8(name).unwrap())
+        .collect();
+    assert_eq!(four_bases, vec!["3", "2", "4", "1", "5"]);
+}
+
+#[test]
+fn subgraph_by_path_interval() {
+    let gfa = load_gfa("./tests/data/subgraph.gfa");
+
+    let interval: PathInterval = "HG002#1#chr1:7-9".parse().unwrap();
+    assert_eq!(interval.path, b"HG002#1#chr1");
+    assert_eq!((interval.start, interval.end), (6, 9));
+    assert_eq!(interval.to_string(), "HG002#1#chr1:7-9");
+
+    let with_colon: PathInterval = "chr1:alt:1-1,000".parse().unwrap();
+    assert_eq!(with_colon.path, b"chr1:alt");
+    assert_eq!((with_colon.start, with_colon.end), (0, 1000));
+    assert!("chr1:0-5".parse::<PathInterval>().is_err());
+    assert!("chr1".parse::<PathInterval>().is_err());
+
+    // HG002#1#chr1 is 1 (0-5), 2 (5-8), 5 (8-12), 6 (12-16), 4 (16-21)
+    let names = path_range_segments(&gfa, &interval.path, 6, 9).unwrap();
+    assert_eq!(names, vec![b"2".to_vec(), b"5".to_vec()]);
+
+    let names = path_range_segments(&gfa, &interval.path, 5, 8).unwrap();
+    assert_eq!(names, vec![b"2".to_vec()]);
+
+    assert!(path_range_segments(&gfa, b"chr2", 0, 10).is_none());
+}
+
+#[test]
+fn subgraph_by_bed_intervals() {
+    let gfa = load_gfa("./tests/data/subgraph.gfa");
+    let bed = std::fs::File::open("./tests/data/subgraph.bed").unwrap();
+    let intervals = read_bed_intervals(bed).unwrap();
+    assert_eq!(intervals.len(), 3);
+    assert_eq!(intervals[0].to_string(), "HG002#1#chr1:7-9");
+
+    let offsets = PathOffsets::new(&gfa);
+    let segments: Vec<Option<Vec<Vec<u8>>>> = intervals
+        .iter()
+        .map(|i| offsets.segments(&i.path, i.start, i.end))
+        .collect();
+    assert_eq!(
+        segments,
+        vec![
+            Some(vec![b"2".to_vec(), b"5".to_vec()]),
+            Some(vec![b"8".to_vec()]),
+            None
+        ]
+    );
+
+    let invalid = read_bed_intervals("chr1\t10\t5\n".as_bytes());
+    assert!(invalid.is_err());
+}
+
+#[test]
+fn subgraph_by_bubble() {
+    let gfa = load_gfa("./tests/data/subgraph.gfa");
+    let adjacency = Adjacency::new(&gfa);
+
+    let to_strings = |names: Vec<Vec<u8>>| -> Vec<String> {
+        names
+            .into_iter()
+            .map(|n| String::from_utf8(n).unwrap())
+            .collect()
+    };
+
+    let bubble: BubbleEnds = "1,4".parse().unwrap();
+    assert_eq!(bubble.to_string(), "1,4");
+    let names = adjacency.bubble_segments(&bubble.from, &bubble.to, 100);
+    assert_eq!(
+        to_strings(names.unwrap()),
+        vec!["1", "2", "7", "3", "5", "6", "4"]
+    );
+
+    // 7 links into 6, but isn't reached from 2
+    let names = adjacency.bubble_segments(b"2", b"4", 100);
+    assert_eq!(to_strings(names.unwrap()), vec!["2", "3", "5", "4", "6"]);
+
+    // Found from the reverse side of 4
+    let names = adjacency.bubble_segments(b"4", b"1", 100);
+    assert_eq!(
+        to_strings(names.unwrap()),
+        vec!["4", "3", "6", "2", "5", "7", "1"]
+    );
+
+    assert!(adjacency.bubble_segments(b"1", b"4", 5).is_none());
+    assert!(adjacency.bubble_segments(b"2", b"7", 100).is_none());
+    assert!("1".parse::<BubbleEnds>().is_err());
+}
+
+#[test]
+fn streaming_subgraph() {
+    let gfa_bytes = std::fs::read("./tests/data/subgraph.gfa").unwrap();
+    let names = |names: &[&str]| -> Vec<Vec<u8>> {
+        names.iter().map(|n| n.as_bytes().to_vec()).collect()
+    };
+
+    let mut out = Vec::new();
+    let segments = names(&["1", "2", "3", "4"]);
+    let lines =
+        stream_subgraph(gfa_bytes.as_slice(), &mut out, &segments, None, None)
+            .unwrap();
+    assert_eq!(lines, 13);
+    assert_eq!(
+        out.to_str().unwrap(),
+        "\
+H\tVN:Z:1.0
+S\t1\tCTGAA
+S\t2\tACG
+S\t3\tTGGC
+S\t4\tTGTGA
+L\t1\t+\t2\t+\t0M
+L\t2\t+\t3\t+\t0M
+L\t3\t+\t4\t+\t0M
+P\tHG001#1#chr1:1\t1+,2+,3+,4+\t*
+P\tHG002#1#chr1:1\t1+,2+\t*
+P\tHG002#1#chr1:2\t4+\t*
+P\tHG002#2#chr1:1\t1+\t*
+P\tHG002#2#chr1:2\t4+\t*
+"
+    );
+
+    let paths = names(&["HG002#1#chr1"]);
+    let segments = stream_path_segments(gfa_bytes.as_slice(), &paths).unwrap();
+    assert_eq!(segments, names(&["1", "2", "5", "6", "4"]));
+
+    let mut out = Vec::new();
+    stream_subgraph(
+        gfa_bytes.as_slice(),
+        &mut out,
+        &segments,
+        Some(paths.as_slice()),
+        Some(&b"gfautil subgraph"[..]),
+    )
+    .unwrap();
+    let out = out.to_str().unwrap();
+    assert!(out.starts_with("H\tVN:Z:1.0\tPG:Z:gfautil subgraph\n"));
+    assert_eq!(out.lines().filter(|l| l.starts_with('S')).count(), 5);
+    assert_eq!(out.lines().filter(|l| l.starts_with('L')).count(), 4);
+    assert!(out.ends_with("P\tHG002#1#chr1\t1+,2+,5+,6+,4+\t*\n"));
+}
+
+#[test]
+fn subgraph_by_paths_regex() {
+    let gfa = load_gfa("./tests/data/subgraph.gfa");
+
+    let names = matching_paths(&gfa, "HG002#.*").unwrap();
+    assert_eq!(
+        names,
+        vec![b"HG002#1#chr1".to_vec(), b"HG002#2#chr1".to_vec()]
+    );
+
+    let names = matching_paths(&gfa, "HG00[12]#1#.*").unwrap();
+    assert_eq!(
+        names,
+        vec![b"HG001#1#chr1".to_vec(), b"HG002#1#chr1".to_vec()]
+    );
+
+    // The whole name must match
+    assert!(matching_paths(&gfa, "HG002").unwrap().is_empty());
+    assert!(matching_paths(&gfa, "HG002#(").is_err());
+}
+
+#[test]
+fn subgraph_by_gaf_alignments() {
+    let gfa = load_gfa("./tests/data/subgraph.gfa");
+    let index = SegmentIndex::new(&gfa);
+
+    // read2 only covers segment 7 of its path; read3 refers to a
+    // missing segment, and read4 to a stable sequence
+    let gaf = "\
+read1\t6\t0\t6\t+\t>2>5\t7\t1\t7\t6\t6\t60
+read2\t3\t0\t3\t+\t>1>7\t10\t5\t8\t3\t3\t60
+read3\t3\t0\t3\t+\t>4>9\t9\t0\t3\t3\t3\t60
+read4\t3\t0\t3\t+\tchr1\t100\t0\t3\t3\t3\t60
+read5\t4\t0\t4\t-\t<5<2\t7\t0\t4\t4\t4\t60
+";
+    let records = gaf_records(gaf.as_bytes()).map(|gaf| gaf.unwrap());
+    let (names, unresolved) = gaf_segments(&index, records);
+    assert_eq!(names, vec![b"2".to_vec(), b"5".to_vec(), b"7".to_vec()]);
+    assert_eq!(unresolved, 2);
+}
+
+#[test]
+fn subgraph_by_gaf_alignments_with_overlaps() {
+    let gfa = load_gfa("./tests/data/ov3.gfa");
+    let index = SegmentIndex::new(&gfa);
+
+    // Segments 1, 2, and 3 span 0-5, 3-8, and 6-12 of the path, as
+    // each shares 2 bases with the one before it
+    let gaf = "\
+read1\t4\t0\t4\t+\t>1>2>3\t12\t8\t12\t4\t4\t60
+read2\t2\t0\t2\t+\t>1>2>3\t12\t2\t4\t2\t2\t60
+";
+    let records = gaf_records(gaf.as_bytes()).map(|gaf| gaf.unwrap());
+    let (names, unresolved) = gaf_segments(&index, records);
+    assert_eq!(names, vec![b"3".to_vec(), b"1".to_vec(), b"2".to_vec()]);
+    assert_eq!(unresolved, 0);
+}
+
+#[test]
+fn batch_subgraphs() {
+    let batch = "# name\tsegments\nlocus1\t2,3\n\nlocus2\t5,6,\n";
+    let batch = read_batch(batch.as_bytes()).unwrap();
+    assert_eq!(
+        batch,
+        vec![
+            ("locus1".to_string(), vec![b"2".to_vec(), b"3".to_vec()]),
+            ("locus2".to_string(), vec![b"5".to_vec(), b"6".to_vec()]),
+        ]
+    );
+
+    assert!(read_batch("locus1\n".as_bytes()).is_err());
+}
+
+#[test]
+fn renumbered_subgraph() {
+    let gfa = load_gfa("./tests/data/subgraph.gfa");
+    let segments = vec![b"4".to_vec(), b"8".to_vec(), b"3".to_vec()];
+    let mut subgraph = segments_subgraph(&gfa, &segments);
+
+    let name_map = renumber_segments(&mut subgraph);
+    let ids: Vec<String> = ["3", "4", "8"]
+        .iter()
+        .map(|name| name_map.map_name(name.as_bytes()).unwrap().to_string())
+        .collect();
+    let id = |ix: usize| ids[ix].as_str();
+
+    assert_eq!(segment_names(&subgraph), ids);
+    assert_eq!(
+        link_names(&subgraph),
+        vec![
+            format!("{}+{}+", id(0), id(1)),
+            format!("{}+{}+", id(1), id(2)),
+        ]
+    );
+    assert_eq!(
+        path_steps(&subgraph),
+        vec![
+            (
+                "HG001#1#chr1:1".to_string(),
+                format!("{},{},{}", id(0), id(1), id(2))
+            ),
+            ("HG002#1#chr1:1".to_string(), id(1).to_string()),
+            ("HG002#2#chr1:1".to_string(), format!("{},{}", id(1), id(2))),
+        ]
+    );
+
+    // The IDs are dense, and the same as those of id-convert --to-int
+    let first = name_map.map_name(b"3").unwrap();
+    assert_eq!(name_map.map_name(b"8"), Some(first + 2));
+    let converted = name_map
+        .gfa_bytestring_to_usize(&segments_subgraph(&gfa, &segments), false)
+        .unwrap();
+    let converted_ids: Vec<String> = converted
+        .segments
+        .iter()
+        .map(|s| s.name.to_string())
+        .collect();
+    assert_eq!(converted_ids, ids);
+}
+
+#[test]
+fn subgraph_summary() {
+    let gfa = load_gfa("./tests/data/subgraph.gfa");
+    let segments: Vec<Vec<u8>> = ["2", "3", "4", "5", "6"]
+        .iter()
+        .map(|s| s.as_bytes().to_vec())
+        .collect();
+    let subgraph = segments_subgraph(&gfa, &segments);
+
+    let summary = SubgraphSummary::new(&gfa, &subgraph);
+    assert_eq!(summary.segments, (5, 8));
+    assert_eq!(summary.links, (5, 9));
+    assert_eq!(summary.paths, (3, 3));
+    assert_eq!(summary.path_fragments, 3);
+    assert_eq!(summary.bases, (20, 34));
+
+    let subgraph = segments_subgraph(&gfa, &[b"3".to_vec()]);
+    let summary = SubgraphSummary::new(&gfa, &subgraph);
+    assert_eq!(summary.paths, (1, 3));
+
+    let mut tsv = Vec::new();
+    summary.write_tsv(&mut tsv).unwrap();
+    assert_eq!(
+        tsv.to_str().unwrap(),
+        "records\tretained\tdropped
+segments\t1\t7
+links\t0\t9
+paths\t1\t2
+containments\t0\t0
+bp\t4\t30
+path fragments\t1\t-
+"
+    );
+}
+
+#[test]
+fn subgraph_with_stubs() {
+    let gfa = load_gfa("./tests/data/subgraph.gfa");
+
+    // 1 and 7 are one region before the subgraph, and 8 another after
+    let segments: Vec<Vec<u8>> = ["2", "3", "4", "5", "6"]
+        .iter()
+        .map(|s| s.as_bytes().to_vec())
+        .collect();
+    let mut subgraph = segments_subgraph(&gfa, &segments);
+    assert_eq!(add_stub_segments(&gfa, &mut subgraph), 2);
+
+    assert_eq!(
+        segment_names(&subgraph),
+        vec!["2", "3", "4", "5", "6", "stub1", "stub2"]
+    );
+    assert_eq!(subgraph.segments[5].sequence, "N");
+    assert_eq!(
+        link_names(&subgraph)[5..].to_vec(),
+        vec!["stub1+2+", "stub1+6+", "4+stub2+"]
+    );
+
+    // 2 and 6 are in the same region, connected through 5, and
+    // through 1 and 7
+    let segments = vec![b"3".to_vec(), b"4".to_vec()];
+    let mut subgraph = segments_subgraph(&gfa, &segments);
+    assert_eq!(add_stub_segments(&gfa, &mut subgraph), 2);
+    assert_eq!(
+        link_names(&subgraph),
+        vec!["3+4+", "stub1+3+", "stub1+4+", "4+stub2+"]
+    );
+}
+
+#[test]
+fn subgraph_fasta() {
+    let gfa = load_gfa("./tests/data/subgraph.gfa");
+    let segments: Vec<Vec<u8>> = ["2", "5", "6"]
+        .iter()
+        .map(|s| s.as_bytes().to_vec())
+        .collect();
+    let subgraph = segments_subgraph(&gfa, &segments);
+
+    let mut fasta = Vec::new();
+    write_fasta(&subgraph, false, &mut fasta).unwrap();
+    assert_eq!(fasta.to_str().unwrap(), ">2\nACG\n>5\nTTTC\n>6\nCTGA\n");
+
+    let mut fasta = Vec::new();
+    write_fasta(&subgraph, true, &mut fasta).unwrap();
+    assert!(fasta
+        .to_str()
+        .unwrap()
+        .ends_with(">HG002#1#chr1:1\nACGTTTCCTGA\n>HG002#2#chr1:1\nCTGA\n"));
+}
+
+#[test]
+fn subgraph_by_tags() {
+    let gfa = load_gfa("./tests/data/rgfa.gfa");
+    let select = |predicates: &[&str]| -> Vec<String> {
+        let predicates: Vec<TagPredicate> =
+            predicates.iter().map(|p| p.parse().unwrap()).collect();
+        tag_segments(&gfa, &predicates)
+            .into_iter()
+            .map(|n| String::from_utf8(n).unwrap())
+            .collect()
+    };
+
+    assert_eq!(select(&["SR:i<=1"]), vec!["1", "2", "3"]);
+    assert_eq!(select(&["SN:Z=chr1"]), vec!["1", "2"]);
+    assert_eq!(select(&["SR:i>0", "SN:Z!=alt2"]), vec!["3"]);
+    assert_eq!(select(&["dp:f>=2"]), vec!["1"]);
+    // Integer tags can be compared to floats, but not to strings
+    assert_eq!(select(&["SR:f<0.5"]), vec!["1", "2"]);
+    assert!(select(&["SR:Z=0"]).is_empty());
+
+    let predicate: TagPredicate = "SR:i<=1".parse().unwrap();
+    assert_eq!(predicate.to_string(), "SR:i<=1");
+    for invalid in &["SR:i~1", "SN:Z<chr1", "SR:x=1", "S:i=1", "SR:i=one"] {
+        assert!(invalid.parse::<TagPredicate>().is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn subgraph_of_integer_ids() {
+    let parser: GFAParser<usize, OptionalFields> = GFAParser::new();
+    let gfa = parser.parse_file("./tests/data/subgraph.gfa").unwrap();
+
+    let range: NodeRange = "2..5".parse().unwrap();
+    let (names, non_numeric) = node_range_segments(&gfa, range);
+    assert_eq!(non_numeric, 0);
+    assert_eq!(names, vec![2, 3, 4, 5]);
+
+    let sub = segments_subgraph(&gfa, &names);
+    let links: Vec<(usize, usize)> = sub
+        .links
+        .iter()
+        .map(|l| (l.from_segment, l.to_segment))
+        .collect();
+    assert_eq!(links, vec![(2, 3), (3, 4), (2, 5)]);
+    let paths: Vec<(String, Vec<usize>)> = sub
+        .paths
+        .iter()
+        .map(|p| {
+            let steps = p.iter().map(|(seg, _)| seg).collect();
+            (p.path_name.to_str().unwrap().to_string(), steps)
+        })
+        .collect();
+    assert_eq!(
+        paths,
+        vec![
+            ("HG001#1#chr1:1".to_string(), vec![2, 3, 4]),
+            ("HG002#1#chr1:1".to_string(), vec![2, 5]),
+            ("HG002#1#chr1:2".to_string(), vec![4]),
+            ("HG002#2#chr1:1".to_string(), vec![4]),
+        ]
+    );
+
+    // The summary is the same as with the segment names as bytes
+    let byte_gfa = load_gfa("./tests/data/subgraph.gfa");
+    let (byte_names, _) = node_range_segments(&byte_gfa, range);
+    let byte_sub = segments_subgraph(&byte_gfa, &byte_names);
+    assert_eq!(
+        SubgraphSummary::new(&gfa, &sub),
+        SubgraphSummary::new(&byte_gfa, &byte_sub)
+    );
+
+    let path = vec![b"HG002#1#chr1".to_vec()];
+    assert_eq!(path_segments(&gfa, &path), vec![1, 2, 5, 6, 4]);
+    let sub = paths_new_subgraph(&gfa, &path);
+    assert_eq!(sub.segments.len(), 5);
+    assert_eq!(sub.links.len(), 4);
+    assert_eq!(sub.paths.len(), 1);
+}
+
+#[test]
+fn subgraph_by_sample() {
+    let gfa = load_gfa("./tests/data/subgraph.gfa");
+
+    assert_eq!(pansn_sample(b"HG002#1#chr1"), Some(&b"HG002"[..]));
+    assert_eq!(pansn_sample(b"chr1"), None);
+
+    let paths = sample_paths(&gfa, &[b"HG002".to_vec()]);
+    assert_eq!(
+        paths,
+        vec![b"HG002#1#chr1".to_vec(), b"HG002#2#chr1".to_vec()]
+    );
+    assert!(sample_paths(&gfa, &[b"HG00".to_vec()]).is_empty());
+
+    let sub = paths_new_subgraph(&gfa, &paths);
+    assert_eq!(segment_names(&sub), vec!["1", "2", "4", "5", "6", "7", "8"]);
+    assert_eq!(sub.paths.len(), 2);
+
+    let both = sample_paths(&gfa, &[b"HG001".to_vec(), b"HG002".to_vec()]);
+    assert_eq!(both.len(), 3);
+}